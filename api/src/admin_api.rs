@@ -0,0 +1,46 @@
+use crate::{GameId, UserId};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenameUserRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteChatRequest {
+    pub user_id: UserId,
+    pub timestamp: i64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AdminAction {
+    DeleteGame {
+        game_id: GameId,
+    },
+    AbortGame {
+        game_id: GameId,
+    },
+    RenameUser {
+        user_id: UserId,
+        old_name: String,
+        new_name: String,
+    },
+    BanUser {
+        user_id: UserId,
+    },
+    DeleteChat {
+        user_id: UserId,
+        timestamp: i64,
+        message: String,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: i64,
+    pub user_id: UserId,
+    pub action: AdminAction,
+}
+
+pub type AuditLogResponse = Vec<AuditEntry>;
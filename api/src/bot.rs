@@ -11,6 +11,46 @@ pub enum BotStrategy {
     Random,
     Simulate,
     NeuralNet,
+    Ismcts,
+    IsmctsNeuralNet,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct BotOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_ms: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<u32>,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+impl BotOptions {
+    pub const MAX_TIME_MS: u32 = 30_000;
+    pub const MAX_ITERATIONS: u32 = 100_000;
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.time_ms
+            .is_none_or(|time| (1..=Self::MAX_TIME_MS).contains(&time))
+            && self
+                .iterations
+                .is_none_or(|iterations| (1..=Self::MAX_ITERATIONS).contains(&iterations))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -19,6 +59,8 @@ pub struct BotState {
     pub pre_pass_hand: Cards,
     pub post_pass_hand: Cards,
     pub void: VoidState,
+    // the void state before each play of the hand, restored if it's undone
+    voids: Vec<VoidState>,
 }
 
 impl BotState {
@@ -32,10 +74,21 @@ impl BotState {
             pre_pass_hand: hand,
             post_pass_hand: hand,
             void,
+            voids: Vec::new(),
         }
     }
 
     pub fn on_event(&mut self, state: &GameState, event: &GameEvent) {
+        match event {
+            GameEvent::Play { .. } => self.voids.push(self.void),
+            GameEvent::Undo { .. } => {
+                if let Some(void) = self.voids.pop() {
+                    self.void = void;
+                }
+            }
+            GameEvent::HandComplete { .. } => self.voids.clear(),
+            _ => {}
+        }
         self.void = self.void.on_event(state, event);
     }
 }
@@ -196,8 +249,8 @@ fn losers(suit: Suit, hand: Cards, state: &GameState) -> i8 {
 mod test {
     use super::*;
     use crate::{
-        Card, ChargeState, ChargingRules, ClaimState, DoneState, GamePhase, GameState, Suits,
-        Trick, WonState,
+        Card, ChargeState, ChargingRules, ClaimState, DoneState, GameLength, GamePhase, GameState,
+        RuleSet, Suits, Trick, UndoState, WonState,
     };
 
     #[test]
@@ -242,10 +295,43 @@ mod test {
         assert_eq!(1, case("Q", "K", false));
     }
 
+    #[test]
+    fn test_undo_void() {
+        let mut state = GameState::new();
+        state.phase = GamePhase::PlayLeft;
+        state.next_actor = Some(Seat::North);
+        let mut bot_state = BotState::new(Seat::South, Cards::NONE);
+        let lead = GameEvent::Play {
+            seat: Seat::North,
+            card: Card::TwoClubs,
+        };
+        bot_state.on_event(&state, &lead);
+        state.apply(&lead);
+        let before = state.clone();
+        let discard = GameEvent::Play {
+            seat: Seat::East,
+            card: Card::AceSpades,
+        };
+        bot_state.on_event(&state, &discard);
+        state.apply(&discard);
+        assert!(bot_state.void.is_void(Seat::East, Suit::Clubs));
+        let undo = GameEvent::Undo {
+            seat: Seat::East,
+            card: Card::AceSpades,
+            state: before,
+        };
+        bot_state.on_event(&state, &undo);
+        assert!(!bot_state.void.is_void(Seat::East, Suit::Clubs));
+    }
+
     #[test]
     fn test_can_claim() {
         let state = GameState {
             rules: ChargingRules::Classic,
+            rule_set: RuleSet::STANDARD,
+            length: GameLength::STANDARD,
+            hand: 0,
+            totals: [0; 4],
             phase: GamePhase::PassLeft,
             done: DoneState::new(),
             charge_count: 0,
@@ -253,6 +339,7 @@ mod test {
             next_actor: Some(Seat::East),
             played: Cards::NONE,
             claims: ClaimState::new(),
+            undo: UndoState::new(),
             won: WonState::new(),
             led_suits: Suits::NONE,
             current_trick: Trick::new(),
@@ -269,6 +356,10 @@ mod test {
     fn test_can_claim2() {
         let state = GameState {
             rules: ChargingRules::Classic,
+            rule_set: RuleSet::STANDARD,
+            length: GameLength::STANDARD,
+            hand: 0,
+            totals: [0; 4],
             phase: GamePhase::PassLeft,
             done: DoneState::new(),
             charge_count: 2,
@@ -278,6 +369,7 @@ mod test {
             next_actor: Some(Seat::West),
             played: "2K9C TS 584C TD  7D JC 68D  AKS".parse().unwrap(),
             claims: ClaimState::new(),
+            undo: UndoState::new(),
             won: WonState::new()
                 .win(Seat::South, "2K9C TS 584C TD".parse().unwrap())
                 .win(Seat::East, "7D JC 68D".parse().unwrap()),
@@ -296,6 +388,10 @@ mod test {
     fn test_can_claim3() {
         let state = GameState {
             rules: ChargingRules::Classic,
+            rule_set: RuleSet::STANDARD,
+            length: GameLength::STANDARD,
+            hand: 0,
+            totals: [0; 4],
             phase: GamePhase::PassAcross,
             done: DoneState::new(),
             charge_count: 0,
@@ -305,6 +401,7 @@ mod test {
                 .parse()
                 .unwrap(),
             claims: ClaimState::new(),
+            undo: UndoState::new(),
             won: WonState::new()
                 .win(Seat::West, "2745C".parse().unwrap())
                 .win(Seat::West, "K85TS".parse().unwrap())
@@ -332,6 +429,10 @@ mod test {
     fn test_can_claim4() {
         let state = GameState {
             rules: ChargingRules::Classic,
+            rule_set: RuleSet::STANDARD,
+            length: GameLength::STANDARD,
+            hand: 0,
+            totals: [0; 4],
             phase: GamePhase::PlayKeeper,
             done: DoneState::new(),
             charge_count: 0,
@@ -341,6 +442,7 @@ mod test {
                 .parse()
                 .unwrap(),
             claims: ClaimState::new(),
+            undo: UndoState::new(),
             won: WonState::new()
                 .win(Seat::South, "2QJAC".parse().unwrap())
                 .win(Seat::South, "7356D".parse().unwrap())
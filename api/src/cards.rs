@@ -411,7 +411,8 @@ impl Iterator for Powerset {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.index, Some(self.index))
+        let len = self.index.wrapping_add(1);
+        (len, Some(len))
     }
 }
 
@@ -574,6 +575,7 @@ mod test {
         assert_eq!(pset.next(), Some(c!(QS)));
         assert_eq!(pset.next(), Some(Cards::NONE));
         assert_eq!(pset.next(), None);
+        assert_eq!(c!(QS AH TC).powerset().collect::<Vec<_>>().len(), 8);
     }
 
     #[test]
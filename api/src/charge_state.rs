@@ -2,7 +2,7 @@ use crate::{Card, Cards, Seat};
 use serde::{Deserialize, Serialize};
 use std::{fmt, fmt::Formatter};
 
-#[derive(Copy, Clone, Deserialize, PartialEq, Serialize)]
+#[derive(Copy, Clone, Deserialize, Eq, PartialEq, Serialize)]
 pub struct ChargeState {
     charges: u16,
 }
@@ -1,6 +1,7 @@
 use crate::Seat;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct ClaimState {
     accepts: u16,
 }
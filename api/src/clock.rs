@@ -0,0 +1,103 @@
+use crate::{BotStrategy, Seat};
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub seconds: u32,
+    pub strategy: BotStrategy,
+}
+
+impl TimeControl {
+    pub const MAX_SECONDS: u32 = 3600;
+
+    pub fn is_valid(&self) -> bool {
+        (1..=Self::MAX_SECONDS).contains(&self.seconds)
+    }
+
+    pub fn millis(&self) -> i64 {
+        self.seconds as i64 * 1000
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Clock {
+    pub north: Option<i64>,
+    pub east: Option<i64>,
+    pub south: Option<i64>,
+    pub west: Option<i64>,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            north: None,
+            east: None,
+            south: None,
+            west: None,
+        }
+    }
+
+    pub fn deadline(&self, seat: Seat) -> Option<i64> {
+        match seat {
+            Seat::North => self.north,
+            Seat::East => self.east,
+            Seat::South => self.south,
+            Seat::West => self.west,
+        }
+    }
+
+    pub fn is_expired(&self, seat: Seat, now: i64) -> bool {
+        match self.deadline(seat) {
+            Some(deadline) => deadline <= now,
+            None => false,
+        }
+    }
+
+    #[must_use]
+    pub fn start(self, seat: Seat, deadline: i64) -> Self {
+        match self.deadline(seat) {
+            Some(_) => self,
+            None => self.set(seat, Some(deadline)),
+        }
+    }
+
+    #[must_use]
+    pub fn stop(self, seat: Seat) -> Self {
+        self.set(seat, None)
+    }
+
+    fn set(mut self, seat: Seat, deadline: Option<i64>) -> Self {
+        match seat {
+            Seat::North => self.north = deadline,
+            Seat::East => self.east = deadline,
+            Seat::South => self.south = deadline,
+            Seat::West => self.west = deadline,
+        }
+        self
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_start_stop() {
+        let clock = Clock::new().start(Seat::East, 1000);
+        assert_eq!(clock.deadline(Seat::East), Some(1000));
+        assert_eq!(clock.deadline(Seat::North), None);
+        let clock = clock.start(Seat::East, 2000);
+        assert_eq!(clock.deadline(Seat::East), Some(1000));
+        assert!(!clock.is_expired(Seat::East, 999));
+        assert!(clock.is_expired(Seat::East, 1000));
+        assert!(!clock.is_expired(Seat::South, 1000));
+        let clock = clock.stop(Seat::East);
+        assert_eq!(clock, Clock::new());
+    }
+}
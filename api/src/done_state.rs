@@ -1,6 +1,7 @@
 use crate::Seat;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct DoneState {
     state: u8,
 }
@@ -28,6 +29,10 @@ impl DoneState {
         }
     }
 
+    pub fn received_pass(self, seat: Seat) -> bool {
+        self.state & (1 << (4 + seat.idx())) != 0
+    }
+
     pub fn all_recv_pass(self) -> bool {
         self.state & 0xf0 == 0xf0
     }
@@ -1,22 +1,40 @@
 use crate::{Cards, GameId, GamePhase, UserId};
+use serde::Serialize;
 use thiserror::Error;
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, Serialize)]
+#[serde(tag = "type", content = "args", rename_all = "snake_case")]
 pub enum RulesError {
     #[error("{0} has already accepted the claim from {1}")]
     AlreadyAcceptedClaim(UserId, UserId),
+    #[error("{0} has already accepted the undo from {1}")]
+    AlreadyAcceptedUndo(UserId, UserId),
     #[error("{0} has already been charged")]
     AlreadyCharged(Cards),
     #[error("{0} has already made a claim")]
     AlreadyClaiming(UserId),
+    #[error("hand {0} has already been dealt")]
+    AlreadyDealt(u16),
     #[error("{0} has already been passed")]
     AlreadyPassed(Cards),
+    #[error("the players have already been seated")]
+    AlreadySeated,
+    #[error("{0} is already being played by a substitute")]
+    AlreadySubstituted(UserId),
     #[error("game {0} is already complete")]
     GameComplete(GameId),
     #[error("hearts cannot be led if hearts are not broken")]
     HeartsNotBroken,
     #[error("cannot {0}, current phase is {1:?}")]
     IllegalAction(&'static str, GamePhase),
+    #[error(
+        "a deal must split the deck into four hands of 13 cards, passing the way the hand does"
+    )]
+    IllegalDeal,
+    #[error("{0} is not the pass to be received")]
+    IllegalReceive(Cards),
+    #[error("the undo does not restore the game to before the last play")]
+    IllegalUndo,
     #[error("{0} is not a legal pass, passes must have 3 cards")]
     IllegalPassSize(Cards),
     #[error("charged cards cannot be played on the first trick of their suit")]
@@ -25,6 +43,12 @@ pub enum RulesError {
     NoPointsOnFirstTrick,
     #[error("{0} is not claiming, or their claim has been rejected")]
     NotClaiming(UserId),
+    #[error("there is no play to undo in the current hand")]
+    NothingToUndo,
+    #[error("{0} is not requesting an undo, or their request has been rejected")]
+    NotRequestingUndo(UserId),
+    #[error("{0} is not being played by a substitute")]
+    NotSubstituted(UserId),
     #[error("your hand does not contain {0}")]
     NotYourCards(Cards),
     #[error("player {0} makes the next {1}")]
@@ -39,4 +63,15 @@ pub enum RulesError {
     MustFollowSuit,
     #[error("the cards {0} cannot be charged")]
     Unchargeable(Cards),
+    #[error("an undo must follow an undo request accepted by every player")]
+    UndoNotAccepted,
+    #[error("{0} has already requested an undo")]
+    UndoPending(UserId),
+}
+
+#[derive(Debug, Error, Serialize)]
+#[error("line {line}: {message}")]
+pub struct RecordError {
+    pub line: usize,
+    pub message: String,
 }
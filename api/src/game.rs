@@ -1,11 +1,13 @@
 use crate::{
-    Card, Cards, GameEvent, GameId, GamePhase, GameState, HashedSeed, PassDirection, RulesError,
-    Seat, UserId,
+    BotStrategy, Card, Cards, Clock, GameEvent, GameId, GamePhase, GameSnapshot, GameState,
+    HashedSeed, PassDirection, RulesError, Seat, SpectatorPolicy, TimeControl, UserId,
 };
 
 #[derive(Clone, Debug)]
 pub struct Game<S> {
+    // the sit event, then those after the snapshot the game was restored from
     pub events: Vec<GameEvent>,
+    pub restored: Option<GameSnapshot>,
     pub subscribers: Vec<(UserId, S)>,
     pub bots: Vec<(Seat, S)>,
     pub pre_pass_hand: [Cards; 4],
@@ -13,12 +15,20 @@ pub struct Game<S> {
     pub players: [UserId; 4],
     pub state: GameState,
     pub seed: HashedSeed,
+    pub time_control: Option<TimeControl>,
+    pub spectators: SpectatorPolicy,
+    pub clock: Clock,
+    pub substitutes: [Option<BotStrategy>; 4],
+    pub scores: Vec<[i16; 4]>,
+    // stable events sent to subscribers, which number them in order
+    pub broadcasts: usize,
 }
 
 impl<S> Game<S> {
     pub fn new() -> Self {
         Self {
             events: Vec::new(),
+            restored: None,
             subscribers: Vec::new(),
             bots: Vec::new(),
             pre_pass_hand: [Cards::NONE; 4],
@@ -26,6 +36,20 @@ impl<S> Game<S> {
             players: [UserId::null(); 4],
             state: GameState::new(),
             seed: HashedSeed::new(),
+            time_control: None,
+            spectators: SpectatorPolicy::HiddenHands,
+            clock: Clock::new(),
+            substitutes: [None; 4],
+            scores: Vec::new(),
+            broadcasts: 0,
+        }
+    }
+
+    // the id the next event is stored with
+    pub fn event_id(&self) -> usize {
+        match &self.restored {
+            Some(snapshot) => snapshot.event_id + self.events.len() - 1,
+            None => self.events.len(),
         }
     }
 
@@ -51,6 +75,21 @@ impl<S> Game<S> {
             legal_plays: self
                 .state
                 .legal_plays(self.post_pass_hand[leader.idx()] - self.state.played),
+            clock: None,
+        }
+    }
+
+    fn status_event(&self) -> Option<GameEvent> {
+        if self.state.phase.is_passing() {
+            Some(self.state.pass_status_event())
+        } else if self.state.phase.is_charging() {
+            Some(self.state.charge_status_event())
+        } else if self.state.phase.is_playing() {
+            self.state
+                .next_actor
+                .map(|next_actor| self.play_status_event(next_actor))
+        } else {
+            None
         }
     }
 
@@ -58,6 +97,12 @@ impl<S> Game<S> {
     where
         F: FnMut(&mut Game<S>, &GameEvent),
     {
+        let mut broadcast = |game: &mut Game<S>, event: &GameEvent| {
+            if event.is_stable() {
+                game.broadcasts += 1;
+            }
+            broadcast(game, event);
+        };
         broadcast(self, &event);
         self.state.apply(&event);
         self.events.push(event.clone());
@@ -68,6 +113,8 @@ impl<S> Game<S> {
                 south,
                 west,
                 seed,
+                time_control,
+                spectators,
                 ..
             } => {
                 self.players[0] = north.user_id();
@@ -75,6 +122,8 @@ impl<S> Game<S> {
                 self.players[2] = south.user_id();
                 self.players[3] = west.user_id();
                 self.seed = seed.into();
+                self.time_control = *time_control;
+                self.spectators = spectators.unwrap_or(SpectatorPolicy::HiddenHands);
             }
             GameEvent::Deal {
                 north,
@@ -160,6 +209,25 @@ impl<S> Game<S> {
                     self.finish_hand(broadcast);
                 }
             }
+            GameEvent::Undo { .. } => {
+                broadcast(
+                    self,
+                    &self.play_status_event(self.state.next_actor.unwrap()),
+                );
+            }
+            GameEvent::Takeover { seat, strategy } => {
+                self.substitutes[seat.idx()] = Some(*strategy);
+                if let Some(event) = self.status_event() {
+                    broadcast(self, &event);
+                }
+            }
+            GameEvent::Resume { seat } => {
+                self.substitutes[seat.idx()] = None;
+                if let Some(event) = self.status_event() {
+                    broadcast(self, &event);
+                }
+            }
+            GameEvent::Abort => broadcast(self, &self.game_complete_event()),
             _ => {}
         }
     }
@@ -168,7 +236,22 @@ impl<S> Game<S> {
     where
         F: FnMut(&mut Game<S>, &GameEvent),
     {
+        broadcast(
+            self,
+            &GameEvent::RevealHands {
+                north: self.pre_pass_hand[0],
+                east: self.pre_pass_hand[1],
+                south: self.pre_pass_hand[2],
+                west: self.pre_pass_hand[3],
+            },
+        );
         let scores = self.state.scores();
+        self.scores.push([
+            scores.score(Seat::North),
+            scores.score(Seat::East),
+            scores.score(Seat::South),
+            scores.score(Seat::West),
+        ]);
         broadcast(
             self,
             &GameEvent::HandComplete {
@@ -176,18 +259,97 @@ impl<S> Game<S> {
                 east_score: scores.score(Seat::East),
                 south_score: scores.score(Seat::South),
                 west_score: scores.score(Seat::West),
+                north_total: self.state.totals[Seat::North.idx()],
+                east_total: self.state.totals[Seat::East.idx()],
+                south_total: self.state.totals[Seat::South.idx()],
+                west_total: self.state.totals[Seat::West.idx()],
             },
         );
         if self.state.phase.is_complete() {
-            let seed = if let GameEvent::Sit { seed, .. } = &self.events[0] {
-                seed.clone()
-            } else {
-                panic!("First event must be a sit event");
-            };
-            broadcast(self, &GameEvent::GameComplete { seed });
+            broadcast(self, &self.game_complete_event());
+        }
+    }
+
+    fn game_complete_event(&self) -> GameEvent {
+        if let GameEvent::Sit { seed, .. } = &self.events[0] {
+            GameEvent::GameComplete { seed: seed.clone() }
+        } else {
+            panic!("First event must be a sit event");
+        }
+    }
+
+    // server-made events (deals, received passes, undos) must match what it would
+    // have made, except keeper passes in hands not dealt from the seed
+    pub fn verify(&self, game_id: GameId, event: &GameEvent) -> Result<(), RulesError> {
+        match event {
+            GameEvent::Sit { .. } => self.verify_sit(),
+            GameEvent::Deal {
+                north,
+                east,
+                south,
+                west,
+                pass,
+            } => self.verify_deal(game_id, [*north, *east, *south, *west], *pass),
+            GameEvent::RecvPass { to, cards } => self.verify_recv_pass(game_id, *to, *cards),
+            GameEvent::SendPass { from, cards } => self.verify_pass(game_id, *from, *cards),
+            GameEvent::Charge { seat, cards } => self.verify_charge(game_id, *seat, *cards),
+            GameEvent::Play { seat, card } => self.verify_play(game_id, *seat, *card),
+            GameEvent::Claim { seat, .. } => self.verify_claim(game_id, *seat),
+            GameEvent::AcceptClaim { claimer, acceptor } => {
+                self.verify_accept_claim(game_id, *claimer, *acceptor)
+            }
+            GameEvent::RejectClaim { claimer, .. } => self.verify_reject_claim(game_id, *claimer),
+            GameEvent::RequestUndo { .. } => self.verify_request_undo(game_id),
+            GameEvent::AcceptUndo {
+                requester,
+                acceptor,
+            } => self.verify_accept_undo(game_id, *requester, *acceptor),
+            GameEvent::RejectUndo { requester, .. } => self.verify_reject_undo(game_id, *requester),
+            GameEvent::Undo { .. } => self.verify_undo(game_id, event),
+            GameEvent::Takeover { seat, .. } => self.verify_takeover(game_id, *seat),
+            GameEvent::Resume { seat } => self.verify_resume(game_id, *seat),
+            GameEvent::Abort => self.verify_abort(game_id),
+            _ => Ok(()),
         }
     }
 
+    pub fn verify_sit(&self) -> Result<(), RulesError> {
+        if !self.events.is_empty() {
+            return Err(RulesError::AlreadySeated);
+        }
+        Ok(())
+    }
+
+    pub fn verify_deal(
+        &self,
+        game_id: GameId,
+        hands: [Cards; 4],
+        pass: PassDirection,
+    ) -> Result<(), RulesError> {
+        if self.state.phase.is_complete() {
+            return Err(RulesError::GameComplete(game_id));
+        }
+        if self.events.is_empty() {
+            return Err(RulesError::IllegalAction("deal", self.state.phase));
+        }
+        let deals = self
+            .events
+            .iter()
+            .filter(|event| matches!(event, GameEvent::Deal { .. }))
+            .count();
+        if deals > self.state.hand as usize {
+            return Err(RulesError::AlreadyDealt(self.state.hand));
+        }
+        let dealt = hands.iter().fold(Cards::NONE, |dealt, &hand| dealt | hand);
+        if dealt != Cards::ALL
+            || hands.iter().any(|hand| hand.len() != 13)
+            || pass != self.state.pass_direction()
+        {
+            return Err(RulesError::IllegalDeal);
+        }
+        Ok(())
+    }
+
     pub fn verify_pass(&self, game_id: GameId, seat: Seat, cards: Cards) -> Result<(), RulesError> {
         if self.state.phase.is_complete() {
             return Err(RulesError::GameComplete(game_id));
@@ -210,6 +372,57 @@ impl<S> Game<S> {
         Ok(())
     }
 
+    pub fn verify_recv_pass(
+        &self,
+        game_id: GameId,
+        seat: Seat,
+        cards: Cards,
+    ) -> Result<(), RulesError> {
+        if self.state.phase.is_complete() {
+            return Err(RulesError::GameComplete(game_id));
+        }
+        if !self.state.phase.is_passing() || self.state.done.received_pass(seat) {
+            return Err(RulesError::IllegalAction("receive pass", self.state.phase));
+        }
+        let sent = self.sent_passes();
+        let expected = if self.state.phase == GamePhase::PassKeeper {
+            if !Seat::all(|s| self.state.done.sent_pass(s)) {
+                return Err(RulesError::IllegalReceive(cards));
+            }
+            let mut hands = self.pre_pass_hand;
+            for &s in &Seat::VALUES {
+                hands[s.idx()] -= sent[s.idx()];
+            }
+            if !self.is_seed_deal() {
+                // a hand dealt at a table can share out the middle however the
+                // table did, as long as each card goes back to one hand of 13
+                let middle = Cards::ALL - hands.iter().fold(self.received_passes(), |a, &h| a | h);
+                if !middle.contains_all(cards) || hands[seat.idx()].len() + cards.len() != 13 {
+                    return Err(RulesError::IllegalReceive(cards));
+                }
+                return Ok(());
+            }
+            self.seed
+                .keeper_pass(self.state.hand, hands)
+                .iter()
+                .find_map(|event| match event {
+                    GameEvent::RecvPass { to, cards } if *to == seat => Some(*cards),
+                    _ => None,
+                })
+        } else {
+            let sender = self.state.phase.pass_sender(seat);
+            if self.state.done.sent_pass(sender) {
+                Some(sent[sender.idx()])
+            } else {
+                None
+            }
+        };
+        if expected != Some(cards) {
+            return Err(RulesError::IllegalReceive(cards));
+        }
+        Ok(())
+    }
+
     pub fn verify_charge(
         &self,
         game_id: GameId,
@@ -226,8 +439,9 @@ impl<S> Game<S> {
         if !hand_cards.contains_all(cards) {
             return Err(RulesError::NotYourCards(cards - hand_cards));
         }
-        if !Cards::CHARGEABLE.contains_all(cards) {
-            return Err(RulesError::Unchargeable(cards - Cards::CHARGEABLE));
+        let chargeable = self.state.rule_set.chargeable();
+        if !chargeable.contains_all(cards) {
+            return Err(RulesError::Unchargeable(cards - chargeable));
         }
         if self.state.charges.all_charges().contains_any(cards) {
             return Err(RulesError::AlreadyCharged(
@@ -264,8 +478,11 @@ impl<S> Game<S> {
             if plays.contains(Card::TwoClubs) && card != Card::TwoClubs {
                 return Err(RulesError::MustPlayTwoOfClubs);
             }
-            if !Cards::POINTS.contains_all(plays) {
-                plays -= Cards::POINTS;
+            let points = self.state.rule_set.points();
+            if self.state.rule_set.first_trick_points {
+                // anything may be played on the first trick
+            } else if !points.contains_all(plays) {
+                plays -= points;
                 if !plays.contains(card) {
                     return Err(RulesError::NoPointsOnFirstTrick);
                 }
@@ -286,21 +503,24 @@ impl<S> Game<S> {
                     return Err(RulesError::MustFollowSuit);
                 }
                 if !self.state.led_suits.contains(suit) && plays.len() > 1 {
-                    plays -= self.state.charges.all_charges();
+                    plays -= self.state.charges.all_charges() & self.state.rule_set.chargeable();
                     if !plays.contains(card) {
                         return Err(RulesError::NoChargeOnFirstTrickOfSuit);
                     }
                 }
             }
         } else {
-            if !self.state.played.contains_any(Cards::HEARTS) && !Cards::HEARTS.contains_all(plays)
+            if !self.state.rule_set.hearts_broken(self.state.played)
+                && !Cards::HEARTS.contains_all(plays)
             {
                 plays -= Cards::HEARTS;
                 if !plays.contains(card) {
                     return Err(RulesError::HeartsNotBroken);
                 }
             }
-            let unled_charges = self.state.charges.all_charges() - self.state.led_suits.cards();
+            let unled_charges = (self.state.charges.all_charges()
+                & self.state.rule_set.chargeable())
+                - self.state.led_suits.cards();
             if !unled_charges.contains_all(plays) {
                 plays -= unled_charges;
                 if !plays.contains(card) {
@@ -361,12 +581,415 @@ impl<S> Game<S> {
         Ok(())
     }
 
-    pub fn deal_event(&self) -> Option<GameEvent> {
-        match self.state.phase {
-            GamePhase::PlayLeft => Some(self.seed.deal(PassDirection::Right)),
-            GamePhase::PlayRight => Some(self.seed.deal(PassDirection::Across)),
-            GamePhase::PlayAcross => Some(self.seed.deal(PassDirection::Keeper)),
-            _ => None,
+    pub fn verify_request_undo(&self, game_id: GameId) -> Result<(), RulesError> {
+        if self.state.phase.is_complete() {
+            return Err(RulesError::GameComplete(game_id));
+        }
+        if !self.state.phase.is_playing() {
+            return Err(RulesError::IllegalAction("request undo", self.state.phase));
+        }
+        if let Some(requester) = self.state.undo.requester() {
+            return Err(RulesError::UndoPending(self.players[requester.idx()]));
+        }
+        if self.last_play().is_none() {
+            return Err(RulesError::NothingToUndo);
+        }
+        Ok(())
+    }
+
+    pub fn verify_accept_undo(
+        &self,
+        game_id: GameId,
+        requester: Seat,
+        acceptor: Seat,
+    ) -> Result<(), RulesError> {
+        if self.state.phase.is_complete() {
+            return Err(RulesError::GameComplete(game_id));
+        }
+        if !self.state.phase.is_playing() {
+            return Err(RulesError::IllegalAction("accept undo", self.state.phase));
+        }
+        if !self.state.undo.is_requesting(requester) {
+            return Err(RulesError::NotRequestingUndo(self.players[requester.idx()]));
+        }
+        if self.state.undo.has_accepted(acceptor) {
+            return Err(RulesError::AlreadyAcceptedUndo(
+                self.players[acceptor.idx()],
+                self.players[requester.idx()],
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn verify_reject_undo(&self, game_id: GameId, requester: Seat) -> Result<(), RulesError> {
+        if self.state.phase.is_complete() {
+            return Err(RulesError::GameComplete(game_id));
+        }
+        if !self.state.phase.is_playing() {
+            return Err(RulesError::IllegalAction("reject undo", self.state.phase));
+        }
+        if !self.state.undo.is_requesting(requester) {
+            return Err(RulesError::NotRequestingUndo(self.players[requester.idx()]));
+        }
+        Ok(())
+    }
+
+    pub fn verify_undo(&self, game_id: GameId, event: &GameEvent) -> Result<(), RulesError> {
+        if self.state.phase.is_complete() {
+            return Err(RulesError::GameComplete(game_id));
+        }
+        if !self.state.phase.is_playing() {
+            return Err(RulesError::IllegalAction("undo", self.state.phase));
+        }
+        if !self.state.undo.successfully_undone() {
+            return Err(RulesError::UndoNotAccepted);
+        }
+        if self.undo_event().as_ref() != Some(event) {
+            return Err(RulesError::IllegalUndo);
+        }
+        Ok(())
+    }
+
+    pub fn verify_takeover(&self, game_id: GameId, seat: Seat) -> Result<(), RulesError> {
+        if self.state.phase.is_complete() {
+            return Err(RulesError::GameComplete(game_id));
+        }
+        if self.substitutes[seat.idx()].is_some() {
+            return Err(RulesError::AlreadySubstituted(self.players[seat.idx()]));
+        }
+        Ok(())
+    }
+
+    pub fn verify_resume(&self, game_id: GameId, seat: Seat) -> Result<(), RulesError> {
+        if self.state.phase.is_complete() {
+            return Err(RulesError::GameComplete(game_id));
+        }
+        if self.substitutes[seat.idx()].is_none() {
+            return Err(RulesError::NotSubstituted(self.players[seat.idx()]));
+        }
+        Ok(())
+    }
+
+    pub fn verify_abort(&self, game_id: GameId) -> Result<(), RulesError> {
+        if self.state.phase.is_complete() {
+            return Err(RulesError::GameComplete(game_id));
+        }
+        Ok(())
+    }
+
+    // the cards each seat has passed in the current hand
+    fn received_passes(&self) -> Cards {
+        let mut received = Cards::NONE;
+        for event in self.events.iter().rev() {
+            match event {
+                GameEvent::Deal { .. } => break,
+                GameEvent::RecvPass { cards, .. } => received |= *cards,
+                _ => {}
+            }
+        }
+        received
+    }
+
+    // whether the hand was dealt from the game's seed, as it is when it's
+    // played live, rather than at a table and entered by hand
+    fn is_seed_deal(&self) -> bool {
+        match self.seed.deal_hand(self.state.hand) {
+            GameEvent::Deal {
+                north,
+                east,
+                south,
+                west,
+                ..
+            } => [north, east, south, west] == self.pre_pass_hand,
+            _ => false,
+        }
+    }
+
+    fn sent_passes(&self) -> [Cards; 4] {
+        let mut sent = [Cards::NONE; 4];
+        for event in self.events.iter().rev() {
+            match event {
+                GameEvent::Deal { .. } => break,
+                GameEvent::SendPass { from, cards } => sent[from.idx()] |= *cards,
+                _ => {}
+            }
+        }
+        sent
+    }
+
+    fn last_play(&self) -> Option<usize> {
+        let mut undone = 0;
+        for (idx, event) in self.events.iter().enumerate().rev() {
+            match event {
+                GameEvent::Deal { .. } => return None,
+                GameEvent::Undo { .. } => undone += 1,
+                GameEvent::Play { .. } if undone == 0 => return Some(idx),
+                GameEvent::Play { .. } => undone -= 1,
+                _ => {}
+            }
+        }
+        None
+    }
+
+    pub fn undo_event(&self) -> Option<GameEvent> {
+        let idx = self.last_play()?;
+        let (seat, card) = match &self.events[idx] {
+            GameEvent::Play { seat, card } => (*seat, *card),
+            _ => unreachable!(),
+        };
+        let mut game: Game<S> = Game::new();
+        let mut events = &self.events[..idx];
+        if let Some(snapshot) = &self.restored {
+            game.restore(events[0].clone(), snapshot.clone(), |_, _| {});
+            events = &events[1..];
+        }
+        for event in events {
+            game.apply(event, |_, _| {});
+        }
+        Some(GameEvent::Undo {
+            seat,
+            card,
+            state: game.state,
+        })
+    }
+
+    pub fn deal_event(&self, event: &GameEvent) -> Option<GameEvent> {
+        let mut state = self.state.clone();
+        state.apply(event);
+        if state.hand == self.state.hand || state.phase.is_complete() {
+            None
+        } else {
+            Some(self.seed.deal_hand(state.hand))
+        }
+    }
+
+    pub fn snapshot(&self) -> Option<GameSnapshot> {
+        let seed = match self.events.first() {
+            Some(GameEvent::Sit { seed, .. }) => seed.clone(),
+            _ => return None,
+        };
+        Some(GameSnapshot {
+            event_id: self.event_id(),
+            players: self.players,
+            seed,
+            time_control: self.time_control,
+            spectators: self.spectators,
+            substitutes: self.substitutes,
+            pre_pass_hand: self.pre_pass_hand,
+            post_pass_hand: self.post_pass_hand,
+            state: self.state.clone(),
+            scores: self.scores.clone(),
+            broadcasts: self.broadcasts,
+        })
+    }
+
+    // the snapshot the next hand is dealt from, once the event ending this one
+    // is applied
+    pub fn hand_snapshot(&self, event: &GameEvent) -> Option<GameSnapshot> {
+        let mut game: Game<()> = Game::new();
+        game.restore(self.events.first()?.clone(), self.snapshot()?, |_, _| {});
+        game.apply(event, |_, _| {});
+        game.snapshot()
+    }
+
+    // subscribers are sent the sit event and the scores of the hands before
+    // the snapshot, rather than every event, and the events after it are
+    // applied as usual
+    pub fn restore<F>(&mut self, sit: GameEvent, snapshot: GameSnapshot, mut broadcast: F)
+    where
+        F: FnMut(&mut Game<S>, &GameEvent),
+    {
+        self.events = vec![sit.clone()];
+        self.players = snapshot.players;
+        self.seed = HashedSeed::from(&snapshot.seed);
+        self.time_control = snapshot.time_control;
+        self.spectators = snapshot.spectators;
+        self.substitutes = snapshot.substitutes;
+        self.pre_pass_hand = snapshot.pre_pass_hand;
+        self.post_pass_hand = snapshot.post_pass_hand;
+        self.state = snapshot.state.clone();
+        self.scores = snapshot.scores.clone();
+        self.broadcasts = snapshot.broadcasts;
+        broadcast(self, &sit);
+        let mut totals = [0; 4];
+        for scores in &snapshot.scores {
+            for (total, score) in totals.iter_mut().zip(scores) {
+                *total += score;
+            }
+            broadcast(
+                self,
+                &GameEvent::HandComplete {
+                    north_score: scores[0],
+                    east_score: scores[1],
+                    south_score: scores[2],
+                    west_score: scores[3],
+                    north_total: totals[0],
+                    east_total: totals[1],
+                    south_total: totals[2],
+                    west_total: totals[3],
+                },
+            );
+        }
+        self.restored = Some(snapshot);
+    }
+
+    // drops the events before the snapshot, which replays start from instead
+    pub fn rebase(&mut self, snapshot: GameSnapshot) {
+        let offset = self.restored.as_ref().map_or(0, |s| s.event_id - 1);
+        self.events.drain(1..snapshot.event_id - offset);
+        self.restored = Some(snapshot);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ChargingRules, GameLength, HeartsLead, MoonScoring, Player, RuleSet, Seed};
+    use rand::{seq::IteratorRandom, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    // plays a game of random legal cards, charging everything the rules allow,
+    // checking every card in hand against both ways of deciding what's legal
+    fn play_game(rule_set: RuleSet, seed: u64) {
+        let seed = Seed::Chosen {
+            value: seed.to_string(),
+        };
+        let hashed_seed = HashedSeed::from(&seed);
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let game_id = GameId::new();
+        let mut game: Game<()> = Game::new();
+        let apply = |game: &mut Game<()>, event: GameEvent| game.apply(&event, |_, _| {});
+        let player = || {
+            Box::new(Player::Human {
+                user_id: UserId::new(),
+            })
+        };
+        apply(
+            &mut game,
+            GameEvent::Sit {
+                north: player(),
+                east: player(),
+                south: player(),
+                west: player(),
+                rules: ChargingRules::Classic,
+                seed,
+                time_control: None,
+                spectators: None,
+                rule_set: Some(rule_set),
+                length: Some(GameLength::STANDARD),
+            },
+        );
+        apply(&mut game, hashed_seed.deal(PassDirection::Left));
+        while !game.state.phase.is_complete() {
+            if game.state.phase.is_passing() {
+                let hands = game.post_pass_hand;
+                for &seat in &Seat::VALUES {
+                    let cards = hands[seat.idx()].into_iter().take(3).collect();
+                    apply(&mut game, GameEvent::SendPass { from: seat, cards });
+                }
+                if game.state.phase.direction() == PassDirection::Keeper {
+                    for event in &hashed_seed.keeper_pass(game.state.hand, game.post_pass_hand) {
+                        apply(&mut game, event.clone());
+                    }
+                } else {
+                    for &seat in &Seat::VALUES {
+                        let sender = game.state.phase.pass_sender(seat).idx();
+                        let cards = game.pre_pass_hand[sender] - game.post_pass_hand[sender];
+                        apply(&mut game, GameEvent::RecvPass { to: seat, cards });
+                    }
+                }
+            } else if game.state.phase.is_charging() {
+                for &seat in &Seat::VALUES {
+                    if game.state.phase.is_charging()
+                        && game.state.can_charge(seat)
+                        && !game.state.done.charged(seat)
+                    {
+                        let cards = (game.post_pass_hand[seat.idx()] & Cards::CHARGEABLE)
+                            - game.state.charges.all_charges();
+                        let chargeable = cards & rule_set.chargeable();
+                        assert_eq!(
+                            game.verify_charge(game_id, seat, cards).is_ok(),
+                            cards == chargeable
+                        );
+                        game.verify_charge(game_id, seat, chargeable).unwrap();
+                        apply(
+                            &mut game,
+                            GameEvent::Charge {
+                                seat,
+                                cards: chargeable,
+                            },
+                        );
+                    }
+                }
+            } else {
+                let seat = game.state.next_actor.unwrap();
+                let hand = game.post_pass_hand[seat.idx()] - game.state.played;
+                let legal = game.state.legal_plays(hand);
+                for card in hand {
+                    assert_eq!(
+                        game.verify_play(game_id, seat, card).is_ok(),
+                        legal.contains(card),
+                        "{} with {} under {:?}",
+                        card,
+                        hand,
+                        rule_set
+                    );
+                }
+                let card = legal.into_iter().choose(&mut rng).unwrap();
+                let event = GameEvent::Play { seat, card };
+                let deal = game.deal_event(&event);
+                apply(&mut game, event);
+                if let Some(deal) = deal {
+                    apply(&mut game, deal);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_legal_plays() {
+        let rule_sets = [
+            RuleSet::STANDARD,
+            RuleSet {
+                ten_of_clubs: false,
+                ..RuleSet::STANDARD
+            },
+            RuleSet {
+                jack_of_diamonds: false,
+                ..RuleSet::STANDARD
+            },
+            RuleSet {
+                first_trick_points: true,
+                moon: MoonScoring::AddToOthers,
+                hearts_lead: HeartsLead::BrokenOrQueen,
+                ..RuleSet::STANDARD
+            },
+            RuleSet {
+                ten_of_clubs: false,
+                jack_of_diamonds: false,
+                moon: MoonScoring::Disabled,
+                hearts_lead: HeartsLead::Anytime,
+                ..RuleSet::STANDARD
+            },
+        ];
+        for &rule_set in &rule_sets {
+            for seed in 0..10 {
+                play_game(rule_set, seed);
+            }
         }
     }
+
+    #[test]
+    fn test_chargeable() {
+        let rule_set = RuleSet {
+            ten_of_clubs: false,
+            jack_of_diamonds: false,
+            ..RuleSet::STANDARD
+        };
+        assert_eq!(RuleSet::STANDARD.chargeable(), Cards::CHARGEABLE);
+        assert_eq!(
+            rule_set.chargeable(),
+            Cards::CHARGEABLE - Card::TenClubs - Card::JackDiamonds
+        );
+    }
 }
@@ -36,6 +36,28 @@ pub struct RejectClaimRequest {
     pub claimer: Seat,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequestUndoRequest {
+    pub game_id: GameId,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AcceptUndoRequest {
+    pub game_id: GameId,
+    pub requester: Seat,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RejectUndoRequest {
+    pub game_id: GameId,
+    pub requester: Seat,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResumeRequest {
+    pub game_id: GameId,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GameChatRequest {
     pub game_id: GameId,
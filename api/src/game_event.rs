@@ -1,4 +1,7 @@
-use crate::{Card, Cards, ChargingRules, PassDirection, Player, Seat, Seed, UserId};
+use crate::{
+    BotStrategy, Card, Cards, ChargingRules, Clock, GameLength, GameState, PassDirection, Player,
+    RuleSet, Seat, Seed, SpectatorPolicy, TimeControl, UserId,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
@@ -6,6 +9,7 @@ use std::collections::HashSet;
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum GameEvent {
     Ping,
+    Reconnect,
     EndReplay {
         subscribers: HashSet<UserId>,
     },
@@ -20,12 +24,16 @@ pub enum GameEvent {
         message: String,
     },
     Sit {
-        north: Player,
-        east: Player,
-        south: Player,
-        west: Player,
+        north: Box<Player>,
+        east: Box<Player>,
+        south: Box<Player>,
+        west: Box<Player>,
         rules: ChargingRules,
         seed: Seed,
+        time_control: Option<TimeControl>,
+        spectators: Option<SpectatorPolicy>,
+        rule_set: Option<RuleSet>,
+        length: Option<GameLength>,
     },
     Deal {
         north: Cards,
@@ -40,6 +48,7 @@ pub enum GameEvent {
         east_done: bool,
         south_done: bool,
         west_done: bool,
+        clock: Option<Clock>,
     },
     SendPass {
         from: Seat,
@@ -64,6 +73,7 @@ pub enum GameEvent {
         east_done: bool,
         south_done: bool,
         west_done: bool,
+        clock: Option<Clock>,
     },
     BlindCharge {
         seat: Seat,
@@ -86,6 +96,7 @@ pub enum GameEvent {
     PlayStatus {
         next_player: Seat,
         legal_plays: Cards,
+        clock: Option<Clock>,
     },
     StartTrick {
         leader: Seat,
@@ -105,11 +116,46 @@ pub enum GameEvent {
         claimer: Seat,
         rejector: Seat,
     },
+    RequestUndo {
+        seat: Seat,
+    },
+    AcceptUndo {
+        requester: Seat,
+        acceptor: Seat,
+    },
+    RejectUndo {
+        requester: Seat,
+        rejector: Seat,
+    },
+    Undo {
+        seat: Seat,
+        card: Card,
+        state: GameState,
+    },
+    Takeover {
+        seat: Seat,
+        strategy: BotStrategy,
+    },
+    Resume {
+        seat: Seat,
+    },
+    // an admin ended the game early
+    Abort,
+    RevealHands {
+        north: Cards,
+        east: Cards,
+        south: Cards,
+        west: Cards,
+    },
     HandComplete {
         north_score: i16,
         east_score: i16,
         south_score: i16,
         west_score: i16,
+        north_total: i16,
+        east_total: i16,
+        south_total: i16,
+        west_total: i16,
     },
     GameComplete {
         seed: Seed,
@@ -127,11 +173,54 @@ impl GameEvent {
     pub fn is_stable(&self) -> bool {
         use GameEvent::*;
         match self {
-            Ping | EndReplay { .. } | JoinGame { .. } | LeaveGame { .. } => false,
+            Ping | Reconnect | EndReplay { .. } | JoinGame { .. } | LeaveGame { .. } => false,
             _ => true,
         }
     }
 
+    pub fn is_status(&self) -> bool {
+        use GameEvent::*;
+        match self {
+            PassStatus { .. } | ChargeStatus { .. } | PlayStatus { .. } => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_stored(&self) -> bool {
+        use GameEvent::*;
+        match self {
+            Chat { .. }
+            | Sit { .. }
+            | Deal { .. }
+            | SendPass { .. }
+            | RecvPass { .. }
+            | Charge { .. }
+            | Play { .. }
+            | Claim { .. }
+            | AcceptClaim { .. }
+            | RejectClaim { .. }
+            | RequestUndo { .. }
+            | AcceptUndo { .. }
+            | RejectUndo { .. }
+            | Undo { .. }
+            | Takeover { .. }
+            | Resume { .. }
+            | Abort => true,
+            _ => false,
+        }
+    }
+
+    pub fn with_clock(&self, clock: Clock) -> GameEvent {
+        let mut event = self.clone();
+        match &mut event {
+            GameEvent::PassStatus { clock: c, .. }
+            | GameEvent::ChargeStatus { clock: c, .. }
+            | GameEvent::PlayStatus { clock: c, .. } => *c = Some(clock),
+            _ => {}
+        }
+        event
+    }
+
     pub fn redact(&self, seat: Option<Seat>, rules: ChargingRules) -> GameEvent {
         match self {
             GameEvent::Sit {
@@ -141,13 +230,21 @@ impl GameEvent {
                 west,
                 rules,
                 seed,
+                time_control,
+                spectators,
+                rule_set,
+                length,
             } => GameEvent::Sit {
-                north: *north,
-                east: *east,
-                south: *south,
-                west: *west,
+                north: north.clone(),
+                east: east.clone(),
+                south: south.clone(),
+                west: west.clone(),
                 rules: *rules,
                 seed: seed.redact(),
+                time_control: *time_control,
+                spectators: *spectators,
+                rule_set: *rule_set,
+                length: *length,
             },
             GameEvent::Deal {
                 north,
@@ -210,14 +307,50 @@ impl GameEvent {
                 },
                 _ => self.clone(),
             },
-            GameEvent::PlayStatus { next_player, .. } => match seat {
+            GameEvent::PlayStatus {
+                next_player, clock, ..
+            } => match seat {
                 Some(seat) if seat != *next_player => GameEvent::PlayStatus {
                     next_player: *next_player,
                     legal_plays: Cards::NONE,
+                    clock: *clock,
                 },
                 _ => self.clone(),
             },
             _ => self.clone(),
         }
     }
+
+    // for spectators who may only see what's public to the whole table
+    pub fn redact_hands(&self, rules: ChargingRules) -> GameEvent {
+        match self {
+            GameEvent::Deal { pass, .. } => GameEvent::Deal {
+                north: Cards::NONE,
+                east: Cards::NONE,
+                south: Cards::NONE,
+                west: Cards::NONE,
+                pass: *pass,
+            },
+            GameEvent::SendPass { from, cards } => GameEvent::HiddenSendPass {
+                from: *from,
+                count: cards.len(),
+            },
+            GameEvent::RecvPass { to, cards } => GameEvent::HiddenRecvPass {
+                to: *to,
+                count: cards.len(),
+            },
+            GameEvent::Charge { seat, cards } if rules.blind() => GameEvent::BlindCharge {
+                seat: *seat,
+                count: cards.len(),
+            },
+            GameEvent::PlayStatus {
+                next_player, clock, ..
+            } => GameEvent::PlayStatus {
+                next_player: *next_player,
+                legal_plays: Cards::NONE,
+                clock: *clock,
+            },
+            _ => self.redact(None, rules),
+        }
+    }
 }
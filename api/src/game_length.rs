@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameLength {
+    // rounds of four hands: left, right, across, keeper
+    Rounds { rounds: u8 },
+    // until any seat's running total reaches the score
+    TargetScore { score: i16 },
+}
+
+impl GameLength {
+    pub const STANDARD: GameLength = GameLength::Rounds { rounds: 1 };
+    pub const MAX_ROUNDS: u8 = 8;
+    pub const MIN_SCORE: i16 = 25;
+    pub const MAX_SCORE: i16 = 1000;
+
+    pub fn is_valid(self) -> bool {
+        match self {
+            GameLength::Rounds { rounds } => (1..=Self::MAX_ROUNDS).contains(&rounds),
+            GameLength::TargetScore { score } => {
+                (Self::MIN_SCORE..=Self::MAX_SCORE).contains(&score)
+            }
+        }
+    }
+
+    pub fn is_over(self, hands: u16, totals: [i16; 4]) -> bool {
+        match self {
+            GameLength::Rounds { rounds } => hands as usize >= 4 * rounds as usize,
+            GameLength::TargetScore { score } => totals.iter().any(|&total| total >= score),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_over() {
+        let two_rounds = GameLength::Rounds { rounds: 2 };
+        assert!(!two_rounds.is_over(4, [500, 0, 0, 0]));
+        assert!(two_rounds.is_over(8, [0, 0, 0, 0]));
+        let target = GameLength::TargetScore { score: 100 };
+        assert!(!target.is_over(12, [99, -40, 20, 0]));
+        assert!(target.is_over(1, [0, 0, 100, 0]));
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(GameLength::STANDARD.is_valid());
+        assert!(GameLength::Rounds { rounds: 8 }.is_valid());
+        assert!(!GameLength::Rounds { rounds: 0 }.is_valid());
+        assert!(!GameLength::Rounds { rounds: 9 }.is_valid());
+        assert!(GameLength::TargetScore { score: 100 }.is_valid());
+        assert!(!GameLength::TargetScore { score: 0 }.is_valid());
+        assert!(!GameLength::TargetScore { score: 1001 }.is_valid());
+    }
+}
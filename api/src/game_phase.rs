@@ -1,8 +1,10 @@
 use crate::{ChargingRules, PassDirection, Seat};
+use serde::{Deserialize, Serialize};
 use std::mem;
 
 #[repr(u8)]
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum GamePhase {
     PassLeft,
     ChargeLeft,
@@ -30,6 +32,15 @@ impl GamePhase {
         }
     }
 
+    pub fn first(direction: PassDirection) -> Self {
+        match direction {
+            PassDirection::Left => GamePhase::PassLeft,
+            PassDirection::Right => GamePhase::PassRight,
+            PassDirection::Across => GamePhase::PassAcross,
+            PassDirection::Keeper => GamePhase::ChargeKeeper1,
+        }
+    }
+
     pub fn is_complete(self) -> bool {
         self == GamePhase::Complete
     }
@@ -0,0 +1,818 @@
+use crate::{
+    BotOptions, Card, Cards, Game, GameEvent, GameId, GameState, Player, Rank, RecordError, Seat,
+    Seed, Suit,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+    iter::Peekable,
+    str::FromStr,
+};
+
+// a game in a text notation like PGN, with headers and then a line per stored
+// event; result lines are only there for readers and are skipped when parsing
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GameRecord {
+    pub game_id: Option<GameId>,
+    pub created_time: Option<i64>,
+    pub completed_time: Option<i64>,
+    pub events: Vec<GameEvent>,
+}
+
+impl GameRecord {
+    pub fn new(events: Vec<GameEvent>) -> Self {
+        Self {
+            game_id: None,
+            created_time: None,
+            completed_time: None,
+            events,
+        }
+    }
+
+    // the same for every record of a game however its other events were
+    // entered, since no two games are dealt the same hands
+    pub fn deal_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        for event in &self.events {
+            if let GameEvent::Deal {
+                north,
+                east,
+                south,
+                west,
+                ..
+            } = event
+            {
+                hasher.update(format!("{} {} {} {}\n", north, east, south, west));
+            }
+        }
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(game_id) = self.game_id {
+            write_header(f, "Game", &game_id.to_string())?;
+        }
+        if let Some(created_time) = self.created_time {
+            write_header(f, "Created", &created_time.to_string())?;
+        }
+        if let Some(completed_time) = self.completed_time {
+            write_header(f, "Completed", &completed_time.to_string())?;
+        }
+        let mut state = GameState::new();
+        // the seat that made the last play on an unfinished play line
+        let mut last_play: Option<Seat> = None;
+        for event in &self.events {
+            let hand = state.hand;
+            state.apply(event);
+            if let GameEvent::Play { seat, card } = event {
+                match last_play {
+                    Some(last) if last.left() == *seat => write!(f, " {}", card)?,
+                    _ => write!(f, "play {} {}", name(seat)?, card)?,
+                }
+                last_play = Some(*seat);
+                if state.current_trick.is_empty() {
+                    writeln!(f)?;
+                    last_play = None;
+                }
+            } else {
+                if last_play.take().is_some() {
+                    writeln!(f)?;
+                }
+                write_event(f, event)?;
+            }
+            if state.hand != hand {
+                let scores = state.scores();
+                write!(f, "result")?;
+                for seat in &Seat::VALUES {
+                    write!(f, " {} {}", name(seat)?, scores.score(*seat))?;
+                }
+                writeln!(f)?;
+            }
+        }
+        if last_play.is_some() {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_event(f: &mut Formatter, event: &GameEvent) -> fmt::Result {
+    match event {
+        GameEvent::Sit {
+            north,
+            east,
+            south,
+            west,
+            rules,
+            seed,
+            time_control,
+            spectators,
+            rule_set,
+            length,
+        } => {
+            write_header(f, "North", &player(north)?)?;
+            write_header(f, "East", &player(east)?)?;
+            write_header(f, "South", &player(south)?)?;
+            write_header(f, "West", &player(west)?)?;
+            write_header(f, "Rules", &name(rules)?)?;
+            write_header(
+                f,
+                "Seed",
+                &match seed {
+                    Seed::Chosen { value } => format!("chosen {}", value),
+                    Seed::Random { value } => format!("random {}", value),
+                    Seed::Duplicate { value, rotation } => {
+                        format!("duplicate {} {}", rotation, value)
+                    }
+                    Seed::Redacted => "redacted".to_string(),
+                },
+            )?;
+            if let Some(time_control) = time_control {
+                write_header(f, "TimeControl", &json(time_control)?)?;
+            }
+            if let Some(spectators) = spectators {
+                write_header(f, "Spectators", &json(spectators)?)?;
+            }
+            if let Some(rule_set) = rule_set {
+                write_header(f, "RuleSet", &json(rule_set)?)?;
+            }
+            if let Some(length) = length {
+                write_header(f, "Length", &json(length)?)?;
+            }
+            writeln!(f)
+        }
+        GameEvent::Deal {
+            north,
+            east,
+            south,
+            west,
+            pass,
+        } => {
+            writeln!(f, "deal {}", name(pass)?)?;
+            writeln!(f, "  north {}", cards(*north))?;
+            writeln!(f, "  east {}", cards(*east))?;
+            writeln!(f, "  south {}", cards(*south))?;
+            writeln!(f, "  west {}", cards(*west))
+        }
+        GameEvent::SendPass { from, cards: c } => {
+            writeln!(f, "send_pass {} {}", name(from)?, cards(*c))
+        }
+        GameEvent::RecvPass { to, cards: c } => {
+            writeln!(f, "recv_pass {} {}", name(to)?, cards(*c))
+        }
+        GameEvent::Charge { seat, cards: c } => {
+            writeln!(f, "charge {} {}", name(seat)?, cards(*c))
+        }
+        GameEvent::Claim { seat, hand } => writeln!(f, "claim {} {}", name(seat)?, cards(*hand)),
+        GameEvent::AcceptClaim { claimer, acceptor } => {
+            writeln!(f, "accept_claim {} {}", name(claimer)?, name(acceptor)?)
+        }
+        GameEvent::RejectClaim { claimer, rejector } => {
+            writeln!(f, "reject_claim {} {}", name(claimer)?, name(rejector)?)
+        }
+        GameEvent::RequestUndo { seat } => writeln!(f, "request_undo {}", name(seat)?),
+        GameEvent::AcceptUndo {
+            requester,
+            acceptor,
+        } => writeln!(f, "accept_undo {} {}", name(requester)?, name(acceptor)?),
+        GameEvent::RejectUndo {
+            requester,
+            rejector,
+        } => writeln!(f, "reject_undo {} {}", name(requester)?, name(rejector)?),
+        GameEvent::Undo { seat, card, .. } => writeln!(f, "undo {} {}", name(seat)?, card),
+        GameEvent::Takeover { seat, strategy } => {
+            writeln!(f, "takeover {} {}", name(seat)?, name(strategy)?)
+        }
+        GameEvent::Resume { seat } => writeln!(f, "resume {}", name(seat)?),
+        GameEvent::Abort => writeln!(f, "abort"),
+        GameEvent::Chat { user_id, message } => {
+            writeln!(f, "chat {} {}", user_id, json(message)?)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn write_header(f: &mut Formatter, key: &str, value: &str) -> fmt::Result {
+    writeln!(
+        f,
+        "[{} \"{}\"]",
+        key,
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+fn player(player: &Player) -> Result<String, fmt::Error> {
+    Ok(match player {
+        Player::Human { user_id } => format!("human {}", user_id),
+        Player::Bot {
+            user_id,
+            strategy,
+            options,
+        } if options.is_default() => format!("bot {} {}", name(strategy)?, user_id),
+        Player::Bot {
+            user_id,
+            strategy,
+            options,
+        } => format!("bot {} {} {}", name(strategy)?, user_id, json(options)?),
+    })
+}
+
+fn cards(cards: Cards) -> String {
+    if cards.is_empty() {
+        "-".to_string()
+    } else {
+        cards.to_string()
+    }
+}
+
+fn name<T: Serialize>(value: &T) -> Result<String, fmt::Error> {
+    match serde_json::to_value(value) {
+        Ok(Value::String(name)) => Ok(name),
+        _ => Err(fmt::Error),
+    }
+}
+
+fn json<T: Serialize>(value: &T) -> Result<String, fmt::Error> {
+    serde_json::to_string(value).map_err(|_| fmt::Error)
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut record = GameRecord::new(Vec::new());
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with(';'))
+            .peekable();
+
+        let mut headers = HashMap::new();
+        let mut header_line = 1;
+        while let Some(&(line, text)) = lines.peek() {
+            if !text.starts_with('[') {
+                break;
+            }
+            lines.next();
+            let (key, value) = parse_header(line, text)?;
+            match key {
+                "Game" => record.game_id = Some(parse(line, &value)?),
+                "Created" => record.created_time = Some(parse(line, &value)?),
+                "Completed" => record.completed_time = Some(parse(line, &value)?),
+                _ => {
+                    headers.insert(key, (line, value));
+                }
+            }
+            header_line = line;
+        }
+        if !headers.is_empty() {
+            record.events.push(parse_sit(header_line, &headers)?);
+        }
+
+        while let Some((line, text)) = lines.next() {
+            let (keyword, rest) = split_word(text);
+            let mut words = rest.split_whitespace();
+            let mut seat = || parse_name::<Seat>(line, words.next().unwrap_or(""));
+            match keyword {
+                "deal" => {
+                    let pass = parse_name(line, rest)?;
+                    let mut hands = [Cards::NONE; 4];
+                    for &seat in &Seat::VALUES {
+                        let (line, text) = next_line(line, &mut lines)?;
+                        let (hand_seat, hand) = split_word(text);
+                        if parse_name::<Seat>(line, hand_seat)? != seat {
+                            return Err(error(line, format!("expected the {} hand", hand_seat)));
+                        }
+                        hands[seat.idx()] = parse_cards(line, hand)?;
+                    }
+                    record.events.push(GameEvent::Deal {
+                        north: hands[0],
+                        east: hands[1],
+                        south: hands[2],
+                        west: hands[3],
+                        pass,
+                    });
+                }
+                "send_pass" => {
+                    let (from, cards) = split_word(rest);
+                    record.events.push(GameEvent::SendPass {
+                        from: parse_name(line, from)?,
+                        cards: parse_cards(line, cards)?,
+                    });
+                }
+                "recv_pass" => {
+                    let (to, cards) = split_word(rest);
+                    record.events.push(GameEvent::RecvPass {
+                        to: parse_name(line, to)?,
+                        cards: parse_cards(line, cards)?,
+                    });
+                }
+                "charge" => {
+                    let (seat, cards) = split_word(rest);
+                    record.events.push(GameEvent::Charge {
+                        seat: parse_name(line, seat)?,
+                        cards: parse_cards(line, cards)?,
+                    });
+                }
+                "play" => {
+                    let (seat, plays) = split_word(rest);
+                    let mut seat = parse_name::<Seat>(line, seat)?;
+                    for card in plays.split_whitespace() {
+                        record.events.push(GameEvent::Play {
+                            seat,
+                            card: parse_card(line, card)?,
+                        });
+                        seat = seat.left();
+                    }
+                }
+                "claim" => {
+                    let (seat, hand) = split_word(rest);
+                    record.events.push(GameEvent::Claim {
+                        seat: parse_name(line, seat)?,
+                        hand: parse_cards(line, hand)?,
+                    });
+                }
+                "accept_claim" => record.events.push(GameEvent::AcceptClaim {
+                    claimer: seat()?,
+                    acceptor: seat()?,
+                }),
+                "reject_claim" => record.events.push(GameEvent::RejectClaim {
+                    claimer: seat()?,
+                    rejector: seat()?,
+                }),
+                "request_undo" => record.events.push(GameEvent::RequestUndo { seat: seat()? }),
+                "accept_undo" => record.events.push(GameEvent::AcceptUndo {
+                    requester: seat()?,
+                    acceptor: seat()?,
+                }),
+                "reject_undo" => record.events.push(GameEvent::RejectUndo {
+                    requester: seat()?,
+                    rejector: seat()?,
+                }),
+                "undo" => {
+                    let (seat_name, card) = split_word(rest);
+                    let seat = parse_name(line, seat_name)?;
+                    let card = parse_card(line, card)?;
+                    let mut game: Game<()> = Game::new();
+                    for event in &record.events {
+                        game.apply(event, |_, _| {});
+                    }
+                    match game.undo_event() {
+                        Some(GameEvent::Undo {
+                            seat: undone,
+                            card: taken_back,
+                            state,
+                        }) if undone == seat && taken_back == card => {
+                            record.events.push(GameEvent::Undo { seat, card, state })
+                        }
+                        _ => {
+                            return Err(error(
+                                line,
+                                format!("{} has no play of {} to undo", seat_name, card),
+                            ))
+                        }
+                    }
+                }
+                "takeover" => {
+                    let (seat, strategy) = split_word(rest);
+                    record.events.push(GameEvent::Takeover {
+                        seat: parse_name(line, seat)?,
+                        strategy: parse_name(line, strategy)?,
+                    });
+                }
+                "resume" => record.events.push(GameEvent::Resume { seat: seat()? }),
+                "abort" => record.events.push(GameEvent::Abort),
+                "chat" => {
+                    let (user_id, message) = split_word(rest);
+                    record.events.push(GameEvent::Chat {
+                        user_id: parse(line, user_id)?,
+                        message: parse_json(line, message)?,
+                    });
+                }
+                "result" => {}
+                _ => return Err(error(line, format!("unknown event {}", keyword))),
+            }
+        }
+        Ok(record)
+    }
+}
+
+fn parse_header(line: usize, text: &str) -> Result<(&str, String), RecordError> {
+    let text = text
+        .strip_prefix('[')
+        .and_then(|text| text.strip_suffix(']'))
+        .ok_or_else(|| error(line, "headers must be in [Key \"value\"] form"))?;
+    let (key, value) = split_word(text);
+    let quoted = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(|| error(line, format!("the value of {} must be quoted", key)))?;
+    let mut value = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.push(
+                chars
+                    .next()
+                    .ok_or_else(|| error(line, "unfinished escape"))?,
+            ),
+            '"' => return Err(error(line, "quotes inside a value must be escaped")),
+            _ => value.push(c),
+        }
+    }
+    Ok((key, value))
+}
+
+fn parse_sit(
+    line: usize,
+    headers: &HashMap<&str, (usize, String)>,
+) -> Result<GameEvent, RecordError> {
+    let required = |key| {
+        headers
+            .get(key)
+            .map(|(line, value)| (*line, value.as_str()))
+            .ok_or_else(|| error(line, format!("missing the {} header", key)))
+    };
+    let player = |key| {
+        let (line, value) = required(key)?;
+        let (kind, rest) = split_word(value);
+        match kind {
+            "human" => Ok(Player::Human {
+                user_id: parse(line, rest)?,
+            }),
+            "bot" => {
+                let (strategy, rest) = split_word(rest);
+                let (user_id, options) = split_word(rest);
+                Ok(Player::Bot {
+                    user_id: parse(line, user_id)?,
+                    strategy: parse_name(line, strategy)?,
+                    options: if options.is_empty() {
+                        BotOptions::default()
+                    } else {
+                        parse_json(line, options)?
+                    },
+                })
+            }
+            _ => Err(error(line, format!("{} is not a valid player", value))),
+        }
+    };
+    let (rules_line, rules) = required("Rules")?;
+    let (seed_line, seed) = required("Seed")?;
+    let seed = match split_word(seed) {
+        ("chosen", value) => Seed::Chosen {
+            value: value.to_string(),
+        },
+        ("random", value) => Seed::Random {
+            value: value.to_string(),
+        },
+        ("duplicate", rest) => {
+            let (rotation, value) = split_word(rest);
+            Seed::Duplicate {
+                value: value.to_string(),
+                rotation: parse(seed_line, rotation)?,
+            }
+        }
+        ("redacted", "") => Seed::Redacted,
+        _ => return Err(error(seed_line, format!("{} is not a valid seed", seed))),
+    };
+    Ok(GameEvent::Sit {
+        north: Box::new(player("North")?),
+        east: Box::new(player("East")?),
+        south: Box::new(player("South")?),
+        west: Box::new(player("West")?),
+        rules: parse_name(rules_line, rules)?,
+        seed,
+        time_control: parse_optional(headers, "TimeControl")?,
+        spectators: parse_optional(headers, "Spectators")?,
+        rule_set: parse_optional(headers, "RuleSet")?,
+        length: parse_optional(headers, "Length")?,
+    })
+}
+
+fn parse_optional<T: DeserializeOwned>(
+    headers: &HashMap<&str, (usize, String)>,
+    key: &str,
+) -> Result<Option<T>, RecordError> {
+    headers
+        .get(key)
+        .map(|(line, value)| parse_json(*line, value))
+        .transpose()
+}
+
+fn next_line<'a, I>(line: usize, lines: &mut Peekable<I>) -> Result<(usize, &'a str), RecordError>
+where
+    I: Iterator<Item = (usize, &'a str)>,
+{
+    lines
+        .next()
+        .ok_or_else(|| error(line, "the record ends in the middle of a deal"))
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    match text.find(char::is_whitespace) {
+        Some(i) => (&text[..i], text[i..].trim()),
+        None => (text, ""),
+    }
+}
+
+fn parse_cards(line: usize, text: &str) -> Result<Cards, RecordError> {
+    if text == "-" {
+        return Ok(Cards::NONE);
+    }
+    let valid = text.ends_with(|c| Suit::try_from(c).is_ok())
+        && text
+            .chars()
+            .all(|c| c.is_whitespace() || Rank::try_from(c).is_ok() || Suit::try_from(c).is_ok());
+    if !valid {
+        return Err(error(line, format!("{} is not a valid set of cards", text)));
+    }
+    Ok(text.parse().unwrap())
+}
+
+fn parse_card(line: usize, text: &str) -> Result<Card, RecordError> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(rank), Some(suit), None)
+            if Rank::try_from(rank).is_ok() && Suit::try_from(suit).is_ok() =>
+        {
+            Ok(text.parse().unwrap())
+        }
+        _ => Err(error(line, format!("{} is not a valid card", text))),
+    }
+}
+
+fn parse<T: FromStr>(line: usize, text: &str) -> Result<T, RecordError> {
+    text.parse()
+        .map_err(|_| error(line, format!("{} is not a valid value", text)))
+}
+
+fn parse_name<T: DeserializeOwned>(line: usize, text: &str) -> Result<T, RecordError> {
+    serde_json::from_value(Value::String(text.to_string()))
+        .map_err(|_| error(line, format!("{} is not a valid name", text)))
+}
+
+fn parse_json<T: DeserializeOwned>(line: usize, text: &str) -> Result<T, RecordError> {
+    serde_json::from_str(text).map_err(|e| error(line, e.to_string()))
+}
+
+fn error<S: Into<String>>(line: usize, message: S) -> RecordError {
+    RecordError {
+        line,
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::{
+        BotStrategy, ChargingRules, Difficulty, Game, GameLength, HashedSeed, PassDirection, UserId,
+    };
+
+    pub(crate) fn play_game() -> Vec<GameEvent> {
+        let seed = Seed::Chosen {
+            value: "chosen".to_string(),
+        };
+        let hashed_seed = HashedSeed::from(&seed);
+        let mut game: Game<()> = Game::new();
+        let apply = |game: &mut Game<()>, event: GameEvent| game.apply(&event, |_, _| {});
+        let mut undone = false;
+        apply(
+            &mut game,
+            GameEvent::Sit {
+                north: Box::new(Player::Human {
+                    user_id: UserId::new(),
+                }),
+                east: Box::new(Player::Bot {
+                    user_id: UserId::new(),
+                    strategy: BotStrategy::Duck,
+                    options: BotOptions::default(),
+                }),
+                south: Box::new(Player::Human {
+                    user_id: UserId::new(),
+                }),
+                west: Box::new(Player::Human {
+                    user_id: UserId::new(),
+                }),
+                rules: ChargingRules::Classic,
+                seed,
+                time_control: None,
+                spectators: None,
+                rule_set: None,
+                length: Some(GameLength::Rounds { rounds: 1 }),
+            },
+        );
+        apply(&mut game, hashed_seed.deal(PassDirection::Left));
+        apply(
+            &mut game,
+            GameEvent::Chat {
+                user_id: UserId::new(),
+                message: "good \"luck\"".to_string(),
+            },
+        );
+        while !game.state.phase.is_complete() {
+            if game.state.phase.is_passing() {
+                let hands = game.post_pass_hand;
+                for &seat in &Seat::VALUES {
+                    let cards = hands[seat.idx()].into_iter().take(3).collect();
+                    apply(&mut game, GameEvent::SendPass { from: seat, cards });
+                }
+                if game.state.phase.direction() == PassDirection::Keeper {
+                    for event in &hashed_seed.keeper_pass(game.state.hand, game.post_pass_hand) {
+                        apply(&mut game, event.clone());
+                    }
+                } else {
+                    for &seat in &Seat::VALUES {
+                        let sender = game.state.phase.pass_sender(seat).idx();
+                        let cards = game.pre_pass_hand[sender] - game.post_pass_hand[sender];
+                        apply(&mut game, GameEvent::RecvPass { to: seat, cards });
+                    }
+                }
+            } else if game.state.phase.is_charging() {
+                for &seat in &Seat::VALUES {
+                    if game.state.can_charge(seat) && !game.state.done.charged(seat) {
+                        apply(
+                            &mut game,
+                            GameEvent::Charge {
+                                seat,
+                                cards: Cards::NONE,
+                            },
+                        );
+                    }
+                }
+            } else if game.state.played.len() == 2 && game.state.hand == 1 && !undone {
+                undone = true;
+                let seat = game.state.next_actor.unwrap();
+                apply(&mut game, GameEvent::RequestUndo { seat });
+                apply(
+                    &mut game,
+                    GameEvent::AcceptUndo {
+                        requester: seat,
+                        acceptor: seat.left(),
+                    },
+                );
+                let event = game.undo_event().unwrap();
+                apply(&mut game, event);
+                apply(
+                    &mut game,
+                    GameEvent::Takeover {
+                        seat,
+                        strategy: BotStrategy::Random,
+                    },
+                );
+                apply(&mut game, GameEvent::Resume { seat });
+                let seat = game.state.next_actor.unwrap();
+                let card = game
+                    .state
+                    .legal_plays(game.post_pass_hand[seat.idx()])
+                    .max();
+                apply(&mut game, GameEvent::Play { seat, card });
+            } else if game.state.played.len() == 40 && game.state.current_trick.is_empty() {
+                let seat = game.state.next_actor.unwrap();
+                let hand = game.post_pass_hand[seat.idx()] - game.state.played;
+                apply(&mut game, GameEvent::Claim { seat, hand });
+                apply(
+                    &mut game,
+                    GameEvent::RejectClaim {
+                        claimer: seat,
+                        rejector: seat.left(),
+                    },
+                );
+                apply(&mut game, GameEvent::Claim { seat, hand });
+                for &acceptor in &[seat.left(), seat.across(), seat.right()] {
+                    let event = GameEvent::AcceptClaim {
+                        claimer: seat,
+                        acceptor,
+                    };
+                    let deal = game.deal_event(&event);
+                    apply(&mut game, event);
+                    if let Some(deal) = deal {
+                        apply(&mut game, deal);
+                    }
+                }
+            } else {
+                let seat = game.state.next_actor.unwrap();
+                let card = game
+                    .state
+                    .legal_plays(game.post_pass_hand[seat.idx()])
+                    .min();
+                let event = GameEvent::Play { seat, card };
+                let deal = game.deal_event(&event);
+                apply(&mut game, event);
+                if let Some(deal) = deal {
+                    apply(&mut game, deal);
+                }
+            }
+        }
+        game.events
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let record = GameRecord {
+            game_id: Some(GameId::new()),
+            created_time: Some(1_600_000_000_000),
+            completed_time: Some(1_600_000_900_000),
+            events: play_game(),
+        };
+        let text = record.to_string();
+        assert_eq!(text.parse::<GameRecord>().unwrap(), record);
+    }
+
+    #[test]
+    fn test_bot_options() {
+        let mut events = play_game();
+        if let GameEvent::Sit { east, .. } = &mut events[0] {
+            if let Player::Bot { options, .. } = &mut **east {
+                *options = BotOptions {
+                    time_ms: Some(500),
+                    iterations: None,
+                    difficulty: Difficulty::Hard,
+                    seed: Some(7),
+                };
+            }
+        }
+        let record = GameRecord::new(events);
+        let text = record.to_string();
+        assert!(text.contains("[East \"bot duck "));
+        assert_eq!(text.parse::<GameRecord>().unwrap(), record);
+    }
+
+    #[test]
+    fn test_write() {
+        let text = GameRecord::new(play_game()).to_string();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[4], "[Rules \"classic\"]");
+        assert_eq!(lines[5], "[Seed \"chosen chosen\"]");
+        assert_eq!(
+            lines[6],
+            "[Length \"{\\\"type\\\":\\\"rounds\\\",\\\"rounds\\\":1}\"]"
+        );
+        assert_eq!(lines[7], "");
+        assert_eq!(lines[8], "deal left");
+        assert_eq!(lines[9], "  north QJT7S KJ643H J97D AC");
+        assert!(lines[13].starts_with("chat "));
+        assert!(lines[13].ends_with(" \"good \\\"luck\\\"\""));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("play ") && line.split_whitespace().count() == 6));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("undo ") && line.split_whitespace().count() == 3));
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|line| line.starts_with("result "))
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let text = GameRecord::new(play_game()).to_string();
+        let line = text
+            .lines()
+            .position(|line| line.starts_with("play "))
+            .unwrap()
+            + 1;
+        let bad = text
+            .lines()
+            .enumerate()
+            .map(|(i, l)| if i + 1 == line { "play north 1X" } else { l })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let err = bad.parse::<GameRecord>().unwrap_err();
+        assert_eq!(err.line, line);
+        assert_eq!(err.message, "1X is not a valid card");
+
+        let undo = text
+            .lines()
+            .position(|line| line.starts_with("undo "))
+            .unwrap()
+            + 1;
+        let bad = text
+            .lines()
+            .enumerate()
+            .map(|(i, l)| if i + 1 == undo { "undo north 2C" } else { l })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let err = bad.parse::<GameRecord>().unwrap_err();
+        assert_eq!(err.line, undo);
+        assert_eq!(err.message, "north has no play of 2C to undo");
+
+        let err = "[North \"human x\"]".parse::<GameRecord>().unwrap_err();
+        assert_eq!(err.line, 1);
+        assert_eq!(err.message, "missing the Rules header");
+
+        let err = "\ndeal up".parse::<GameRecord>().unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{
+    BotStrategy, Cards, ChargeState, ChargingRules, ClaimState, DoneState, GameLength, GamePhase,
+    GameState, RuleSet, Seat, Seed, SpectatorPolicy, Suits, TimeControl, Trick, UndoState, UserId,
+    WonState,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    pub event_id: usize,
+    pub players: [UserId; 4],
+    pub seed: Seed,
+    pub time_control: Option<TimeControl>,
+    pub spectators: SpectatorPolicy,
+    pub substitutes: [Option<BotStrategy>; 4],
+    pub pre_pass_hand: [Cards; 4],
+    pub post_pass_hand: [Cards; 4],
+    #[serde(with = "full_state")]
+    pub state: GameState,
+    pub scores: Vec<[i16; 4]>,
+    pub broadcasts: usize,
+}
+
+// all of GameState, unlike its own Serialize which skips what undo doesn't need
+mod full_state {
+    use super::*;
+    use serde::{Deserializer, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct FullState {
+        rules: ChargingRules,
+        rule_set: RuleSet,
+        length: GameLength,
+        hand: u16,
+        totals: [i16; 4],
+        phase: GamePhase,
+        done: DoneState,
+        charge_count: u8,
+        charges: ChargeState,
+        next_actor: Option<Seat>,
+        played: Cards,
+        claims: ClaimState,
+        undo: UndoState,
+        won: WonState,
+        led_suits: Suits,
+        current_trick: Trick,
+    }
+
+    pub fn serialize<S>(state: &GameState, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        FullState {
+            rules: state.rules,
+            rule_set: state.rule_set,
+            length: state.length,
+            hand: state.hand,
+            totals: state.totals,
+            phase: state.phase,
+            done: state.done,
+            charge_count: state.charge_count,
+            charges: state.charges,
+            next_actor: state.next_actor,
+            played: state.played,
+            claims: state.claims,
+            undo: state.undo,
+            won: state.won,
+            led_suits: state.led_suits,
+            current_trick: state.current_trick,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<GameState, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let state = FullState::deserialize(deserializer)?;
+        Ok(GameState {
+            rules: state.rules,
+            rule_set: state.rule_set,
+            length: state.length,
+            hand: state.hand,
+            totals: state.totals,
+            phase: state.phase,
+            done: state.done,
+            charge_count: state.charge_count,
+            charges: state.charges,
+            next_actor: state.next_actor,
+            played: state.played,
+            claims: state.claims,
+            undo: state.undo,
+            won: state.won,
+            led_suits: state.led_suits,
+            current_trick: state.current_trick,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{game_record::test::play_game, Game, GameEvent};
+
+    #[test]
+    fn test_restore() {
+        let events = play_game();
+        let mut game: Game<()> = Game::new();
+        let mut broadcast = Vec::new();
+        for event in &events {
+            game.apply(event, |g, e| broadcast.push((g.event_id(), e.clone())));
+        }
+        let expected = game.snapshot().unwrap();
+        for event_id in 1..events.len() {
+            let mut game: Game<()> = Game::new();
+            for event in &events[..event_id] {
+                game.apply(event, |_, _| {});
+            }
+            let snapshot = game.snapshot().unwrap();
+            let json = serde_json::to_string(&snapshot).unwrap();
+            assert_eq!(
+                serde_json::from_str::<GameSnapshot>(&json).unwrap(),
+                snapshot
+            );
+
+            let mut restored: Game<()> = Game::new();
+            let mut summary = Vec::new();
+            restored.restore(events[0].clone(), snapshot, |_, e| summary.push(e.clone()));
+            let hands = broadcast
+                .iter()
+                .filter(|(id, e)| {
+                    *id <= event_id
+                        && matches!(e, GameEvent::Sit { .. } | GameEvent::HandComplete { .. })
+                })
+                .map(|(_, e)| e.clone())
+                .collect::<Vec<_>>();
+            assert_eq!(summary, hands);
+            for event in &events[event_id..] {
+                restored.apply(event, |_, _| {});
+            }
+            assert_eq!(restored.snapshot().unwrap(), expected);
+            assert_eq!(restored.events[1..], events[event_id..]);
+        }
+    }
+}
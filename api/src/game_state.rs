@@ -1,10 +1,10 @@
 use crate::{
-    Card, Cards, ChargeState, ChargingRules, ClaimState, DoneState, GameEvent, GamePhase, Scores,
-    Seat, Suits, Trick, WonState,
+    Card, Cards, ChargeState, ChargingRules, ClaimState, DoneState, GameEvent, GameLength,
+    GamePhase, PassDirection, RuleSet, Scores, Seat, Suits, Trick, UndoState, WonState,
 };
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct GameState {
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
@@ -12,6 +12,22 @@ pub struct GameState {
     pub rules: ChargingRules, // 1
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
+    #[serde(default = "default_rule_set")]
+    pub rule_set: RuleSet, // 5
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    #[serde(default = "default_length")]
+    pub length: GameLength, // 4
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    #[serde(default)]
+    pub hand: u16, // 2
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    #[serde(default)]
+    pub totals: [i16; 4], // 8
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
     #[serde(default = "default_phase")]
     pub phase: GamePhase, // 1
     #[serde(skip_serializing)]
@@ -30,6 +46,10 @@ pub struct GameState {
     #[serde(skip_deserializing)]
     #[serde(default = "default_claims")]
     pub claims: ClaimState, // 2
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    #[serde(default = "default_undo")]
+    pub undo: UndoState, // 2
     pub won: WonState,            // 4
     pub led_suits: Suits,         // 1
     pub current_trick: Trick,     // 8
@@ -39,6 +59,10 @@ impl GameState {
     pub fn new() -> Self {
         Self {
             rules: ChargingRules::Classic,
+            rule_set: RuleSet::STANDARD,
+            length: GameLength::STANDARD,
+            hand: 0,
+            totals: [0; 4],
             phase: GamePhase::PassLeft,
             done: DoneState::new(),
             charge_count: 0,
@@ -46,6 +70,7 @@ impl GameState {
             next_actor: None,
             played: Cards::NONE,
             claims: ClaimState::new(),
+            undo: UndoState::new(),
             won: WonState::new(),
             led_suits: Suits::NONE,
             current_trick: Trick::new(),
@@ -58,6 +83,7 @@ impl GameState {
             east_done: self.done.sent_pass(Seat::East),
             south_done: self.done.sent_pass(Seat::South),
             west_done: self.done.sent_pass(Seat::West),
+            clock: None,
         }
     }
 
@@ -68,11 +94,24 @@ impl GameState {
             east_done: self.done.charged(Seat::East),
             south_done: self.done.charged(Seat::South),
             west_done: self.done.charged(Seat::West),
+            clock: None,
+        }
+    }
+
+    pub fn is_waiting_on(&self, seat: Seat) -> bool {
+        if self.phase.is_passing() {
+            !self.done.sent_pass(seat)
+        } else if self.phase.is_charging() {
+            !self.done.charged(seat) && self.can_charge(seat)
+        } else if self.phase.is_playing() {
+            self.next_actor == Some(seat)
+        } else {
+            false
         }
     }
 
     pub fn scores(&self) -> Scores {
-        self.won.scores(self.charges)
+        self.won.scores(self.charges, self.rule_set)
     }
 
     pub fn can_charge(&self, seat: Seat) -> bool {
@@ -84,8 +123,15 @@ impl GameState {
 
     pub fn apply(&mut self, event: &GameEvent) {
         match event {
-            GameEvent::Sit { rules, .. } => {
+            GameEvent::Sit {
+                rules,
+                rule_set,
+                length,
+                ..
+            } => {
                 self.rules = *rules;
+                self.rule_set = rule_set.unwrap_or(RuleSet::STANDARD);
+                self.length = length.unwrap_or(GameLength::STANDARD);
             }
             GameEvent::Deal { .. } => {
                 self.charge_count = 0;
@@ -93,6 +139,7 @@ impl GameState {
                 self.next_actor = self.phase.first_charger(self.rules);
                 self.played = Cards::NONE;
                 self.claims = ClaimState::new();
+                self.undo = UndoState::new();
                 self.won = WonState::new();
                 self.led_suits = Suits::NONE;
                 self.current_trick = Trick::new();
@@ -132,6 +179,7 @@ impl GameState {
                     .charge(Seat::West, *west);
             }
             GameEvent::Play { seat, card } => {
+                self.undo = UndoState::new();
                 self.played |= *card;
                 self.current_trick = self.current_trick.push(*card);
                 self.next_actor = Some(seat.left());
@@ -142,8 +190,7 @@ impl GameState {
                     self.current_trick = Trick::new();
                     self.next_actor = Some(winning_seat);
                     if self.played == Cards::ALL {
-                        self.phase = self.phase.next(self.charge_count != 0);
-                        self.done = DoneState::new();
+                        self.finish_hand();
                     }
                 }
             }
@@ -158,18 +205,58 @@ impl GameState {
                         (Cards::ALL - self.played) | self.current_trick.cards(),
                     );
                     self.current_trick = Trick::new();
-                    self.phase = self.phase.next(self.charge_count != 0);
-                    self.done = DoneState::new();
+                    self.finish_hand();
                     self.next_actor = None;
                 }
             }
             GameEvent::RejectClaim { claimer, .. } => {
                 self.claims = self.claims.reject(*claimer);
             }
+            GameEvent::RequestUndo { seat } => {
+                self.undo = self.undo.request(*seat);
+            }
+            GameEvent::AcceptUndo { acceptor, .. } => {
+                self.undo = self.undo.accept(*acceptor);
+            }
+            GameEvent::RejectUndo { .. } => {
+                self.undo = self.undo.reject();
+            }
+            GameEvent::Undo { state, .. } => {
+                self.charges = state.charges;
+                self.next_actor = state.next_actor;
+                self.played = state.played;
+                self.claims = ClaimState::new();
+                self.undo = UndoState::new();
+                self.won = state.won;
+                self.led_suits = state.led_suits;
+                self.current_trick = state.current_trick;
+            }
+            GameEvent::Abort => {
+                self.phase = GamePhase::Complete;
+                self.next_actor = None;
+            }
             _ => {}
         }
     }
 
+    pub fn pass_direction(&self) -> PassDirection {
+        PassDirection::from((self.hand % 4) as u8)
+    }
+
+    fn finish_hand(&mut self) {
+        let scores = self.scores();
+        for seat in &Seat::VALUES {
+            self.totals[seat.idx()] += scores.score(*seat);
+        }
+        self.hand += 1;
+        self.phase = if self.length.is_over(self.hand, self.totals) {
+            GamePhase::Complete
+        } else {
+            GamePhase::first(self.pass_direction())
+        };
+        self.done = DoneState::new();
+    }
+
     fn charge(&mut self, seat: Seat, count: usize) {
         if let Some(charger) = &mut self.next_actor {
             *charger = charger.left();
@@ -198,12 +285,14 @@ impl GameState {
             return Card::TwoClubs.into();
         }
 
-        // if this is the first trick
-        if self.current_trick.cards().contains(Card::TwoClubs) {
+        // if this is the first trick and points aren't allowed on it
+        if self.current_trick.cards().contains(Card::TwoClubs) && !self.rule_set.first_trick_points
+        {
+            let points = self.rule_set.points();
             // if you have a non-point card
-            if !Cards::POINTS.contains_all(plays) {
+            if !points.contains_all(plays) {
                 // you cannot play points
-                plays -= Cards::POINTS;
+                plays -= points;
 
             // otherwise, if you have the jack of diamonds
             } else if plays.contains(Card::JackDiamonds) {
@@ -232,14 +321,14 @@ impl GameState {
                     && plays.len() > 1
                 {
                     // you cannot play charged cards from the suit
-                    plays -= self.charges.all_charges();
+                    plays -= self.charges.all_charges() & self.rule_set.chargeable();
                 }
             }
 
         // otherwise, you are leading the trick
         } else {
             // If hearts are not broken
-            if !self.played.contains_any(Cards::HEARTS)
+            if !self.rule_set.hearts_broken(self.played)
                 // and you have a non-heart
                 && !Cards::HEARTS.contains_all(plays)
             {
@@ -247,7 +336,8 @@ impl GameState {
                 plays -= Cards::HEARTS;
             }
 
-            let unled_charges = self.charges.all_charges() - self.led_suits.cards();
+            let unled_charges =
+                (self.charges.all_charges() & self.rule_set.chargeable()) - self.led_suits.cards();
             // if you have cards other than charged cards from unled suits
             if !unled_charges.contains_all(plays) {
                 // you must lead one of them
@@ -262,6 +352,14 @@ fn default_rules() -> ChargingRules {
     ChargingRules::Classic
 }
 
+fn default_rule_set() -> RuleSet {
+    RuleSet::STANDARD
+}
+
+fn default_length() -> GameLength {
+    GameLength::STANDARD
+}
+
 fn default_phase() -> GamePhase {
     GamePhase::PlayLeft
 }
@@ -274,6 +372,10 @@ fn default_claims() -> ClaimState {
     ClaimState::new()
 }
 
+fn default_undo() -> UndoState {
+    UndoState::new()
+}
+
 mod played {
     use crate::Cards;
     use serde::{
@@ -318,7 +420,7 @@ mod played {
 
 #[cfg(test)]
 mod test {
-    use crate::{GamePhase, GameState};
+    use crate::{Card, Cards, GamePhase, GameState, HeartsLead, RuleSet, Trick};
     use serde_test::{assert_tokens, Token};
 
     #[test]
@@ -388,4 +490,51 @@ mod test {
         let new_state: GameState = bincode::deserialize(&bytes).unwrap();
         assert_eq!(state, new_state);
     }
+
+    fn cards(cards: &str) -> Cards {
+        cards.parse().unwrap()
+    }
+
+    #[test]
+    fn test_first_trick_points() {
+        let mut state = GameState::new();
+        state.phase = GamePhase::PlayLeft;
+        state.played = Card::TwoClubs.into();
+        state.current_trick = Trick::new().push(Card::TwoClubs);
+        assert_eq!(state.legal_plays(cards("QS 5H 3D")), cards("3D"));
+        assert_eq!(state.legal_plays(cards("QS JD 5H")), cards("JD"));
+        assert_eq!(state.legal_plays(cards("QS 5H")), cards("QS"));
+
+        state.rule_set.jack_of_diamonds = false;
+        assert_eq!(state.legal_plays(cards("QS JD 5H")), cards("JD"));
+        assert_eq!(state.legal_plays(cards("QS 5H")), cards("QS"));
+
+        state.rule_set = RuleSet {
+            first_trick_points: true,
+            ..RuleSet::STANDARD
+        };
+        assert_eq!(state.legal_plays(cards("QS 5H 3D")), cards("QS 5H 3D"));
+        assert_eq!(state.legal_plays(cards("QS 5H")), cards("QS 5H"));
+    }
+
+    #[test]
+    fn test_hearts_lead() {
+        let mut state = GameState::new();
+        state.phase = GamePhase::PlayLeft;
+        state.played = cards("AKQ2S");
+        assert_eq!(state.legal_plays(cards("5H 3D")), cards("3D"));
+        assert_eq!(state.legal_plays(cards("5H")), cards("5H"));
+
+        state.rule_set.hearts_lead = HeartsLead::BrokenOrQueen;
+        assert_eq!(state.legal_plays(cards("5H 3D")), cards("5H 3D"));
+        state.played = cards("AK32S");
+        assert_eq!(state.legal_plays(cards("5H 3D")), cards("3D"));
+
+        state.rule_set.hearts_lead = HeartsLead::Anytime;
+        assert_eq!(state.legal_plays(cards("5H 3D")), cards("5H 3D"));
+
+        state.rule_set.hearts_lead = HeartsLead::Broken;
+        state.played = cards("AK32S 7H");
+        assert_eq!(state.legal_plays(cards("5H 3D")), cards("5H 3D"));
+    }
 }
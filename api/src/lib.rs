@@ -1,51 +1,69 @@
+mod admin_api;
 mod bot;
 mod card;
 mod cards;
 mod charge_state;
 mod claim_state;
+mod clock;
 mod done_state;
 mod error;
 mod game;
 mod game_api;
 mod game_event;
+mod game_length;
 mod game_phase;
+mod game_record;
+mod game_snapshot;
 mod game_state;
 mod lobby_api;
 mod player;
 mod rank;
+mod rule_set;
 mod scores;
 mod seat;
 mod seed;
+mod socket_api;
+mod spectator;
 mod suit;
 mod suits;
 mod summary_api;
 mod trick;
 mod types;
+mod undo_state;
 mod void;
 mod won_state;
 
+pub use admin_api::*;
 pub use bot::*;
 pub use card::*;
 pub use cards::*;
 pub use charge_state::*;
 pub use claim_state::*;
+pub use clock::*;
 pub use done_state::*;
 pub use error::*;
 pub use game::*;
 pub use game_api::*;
 pub use game_event::*;
+pub use game_length::*;
 pub use game_phase::*;
+pub use game_record::*;
+pub use game_snapshot::*;
 pub use game_state::*;
 pub use lobby_api::*;
 pub use player::*;
 pub use rank::*;
+pub use rule_set::*;
 pub use scores::*;
 pub use seat::*;
 pub use seed::*;
+pub use socket_api::*;
+pub use spectator::*;
 pub use suit::*;
 pub use suits::*;
 pub use summary_api::*;
 pub use trick::*;
 pub use types::*;
+pub use undo_state::*;
 pub use void::*;
 pub use won_state::*;
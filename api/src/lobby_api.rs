@@ -1,4 +1,7 @@
-use crate::{BotStrategy, ChargingRules, GameId, Player, PlayerWithOptions, Seat, Seed, UserId};
+use crate::{
+    BotOptions, BotStrategy, ChargingRules, DuplicateId, GameId, GameLength, JoinToken, Player,
+    PlayerWithOptions, RuleSet, Seat, Seed, SpectatorPolicy, TimeControl, UserId,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
@@ -7,6 +10,44 @@ pub struct NewGameRequest {
     pub rules: ChargingRules,
     pub seat: Option<Seat>,
     pub seed: Option<String>,
+    pub time_control: Option<TimeControl>,
+    pub spectators: Option<SpectatorPolicy>,
+    pub rule_set: Option<RuleSet>,
+    pub length: Option<GameLength>,
+    pub privacy: Option<Privacy>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewGameResponse {
+    pub game_id: GameId,
+    pub join_token: Option<JoinToken>,
+}
+
+// besides the creator and players, only the invited users and, if there's one,
+// holders of the join token may join or watch
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Privacy {
+    pub invited: HashSet<UserId>,
+    pub join_token: bool,
+    // left out of the lobby for everyone who can't join
+    pub hidden: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewDuplicateRequest {
+    pub rules: ChargingRules,
+    pub seat: Option<Seat>,
+    pub tables: u8,
+    pub time_control: Option<TimeControl>,
+    pub spectators: Option<SpectatorPolicy>,
+    pub rule_set: Option<RuleSet>,
+    pub length: Option<GameLength>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewDuplicateResponse {
+    pub duplicate_id: DuplicateId,
+    pub game_ids: Vec<GameId>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -14,6 +55,7 @@ pub struct JoinGameRequest {
     pub game_id: GameId,
     pub rules: ChargingRules,
     pub seat: Option<Seat>,
+    pub join_token: Option<JoinToken>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -31,6 +73,8 @@ pub struct AddBotRequest {
     pub game_id: GameId,
     pub rules: ChargingRules,
     pub strategy: BotStrategy,
+    #[serde(default)]
+    pub options: BotOptions,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -48,6 +92,7 @@ pub struct LobbyChatRequest {
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum LobbyEvent {
     Ping,
+    Reconnect,
     JoinLobby {
         user_id: UserId,
     },
@@ -55,6 +100,21 @@ pub enum LobbyEvent {
         game_id: GameId,
         player: PlayerWithOptions,
         seed: Seed,
+        time_control: Option<TimeControl>,
+        spectators: Option<SpectatorPolicy>,
+        rule_set: Option<RuleSet>,
+        length: Option<GameLength>,
+        private: bool,
+    },
+    NewDuplicate {
+        duplicate_id: DuplicateId,
+        game_ids: Vec<GameId>,
+        player: PlayerWithOptions,
+        seed: Seed,
+        time_control: Option<TimeControl>,
+        spectators: Option<SpectatorPolicy>,
+        rule_set: Option<RuleSet>,
+        length: Option<GameLength>,
     },
     LobbyState {
         subscribers: HashSet<UserId>,
@@ -101,6 +161,12 @@ impl LobbyEvent {
 pub struct LobbyGame {
     pub players: HashSet<PlayerWithOptions>,
     pub seed: Seed,
+    pub time_control: Option<TimeControl>,
+    pub spectators: Option<SpectatorPolicy>,
+    pub rule_set: Option<RuleSet>,
+    pub length: Option<GameLength>,
+    pub private: bool,
+    pub duplicate_id: Option<DuplicateId>,
     pub created_time: i64,
     pub created_by: UserId,
     pub last_updated_time: i64,
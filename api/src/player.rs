@@ -1,4 +1,4 @@
-use crate::{BotStrategy, ChargingRules, Seat, UserId};
+use crate::{BotOptions, BotStrategy, ChargingRules, Seat, UserId};
 use serde::{Deserialize, Serialize};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
@@ -17,6 +17,8 @@ pub enum Player {
     Bot {
         user_id: UserId,
         strategy: BotStrategy,
+        #[serde(default, skip_serializing_if = "BotOptions::is_default")]
+        options: BotOptions,
     },
 }
 
@@ -34,4 +36,11 @@ impl Player {
             _ => None,
         }
     }
+
+    pub fn bot_options(&self) -> Option<BotOptions> {
+        match self {
+            Player::Bot { options, .. } => Some(*options),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,69 @@
+use crate::{Card, Cards};
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct RuleSet {
+    pub ten_of_clubs: bool,
+    pub jack_of_diamonds: bool,
+    // points on the first trick while holding other cards
+    pub first_trick_points: bool,
+    pub moon: MoonScoring,
+    pub hearts_lead: HeartsLead,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MoonScoring {
+    // shooter's points count in their favor
+    Negate,
+    // shooter scores nothing, every other seat takes the points
+    AddToOthers,
+    Disabled,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HeartsLead {
+    Broken,
+    BrokenOrQueen,
+    Anytime,
+}
+
+impl RuleSet {
+    pub const STANDARD: RuleSet = RuleSet {
+        ten_of_clubs: true,
+        jack_of_diamonds: true,
+        first_trick_points: false,
+        moon: MoonScoring::Negate,
+        hearts_lead: HeartsLead::Broken,
+    };
+
+    pub fn chargeable(self) -> Cards {
+        let mut cards = Cards::CHARGEABLE;
+        if !self.ten_of_clubs {
+            cards -= Card::TenClubs;
+        }
+        if !self.jack_of_diamonds {
+            cards -= Card::JackDiamonds;
+        }
+        cards
+    }
+
+    pub fn points(self) -> Cards {
+        if self.jack_of_diamonds {
+            Cards::POINTS
+        } else {
+            Cards::POINTS - Card::JackDiamonds
+        }
+    }
+
+    pub fn hearts_broken(self, played: Cards) -> bool {
+        match self.hearts_lead {
+            HeartsLead::Broken => played.contains_any(Cards::HEARTS),
+            HeartsLead::BrokenOrQueen => {
+                played.contains_any(Cards::HEARTS) || played.contains(Card::QueenSpades)
+            }
+            HeartsLead::Anytime => true,
+        }
+    }
+}
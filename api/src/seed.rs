@@ -12,6 +12,7 @@ use uuid::Uuid;
 pub enum Seed {
     Chosen { value: String },
     Random { value: String },
+    Duplicate { value: String, rotation: u8 },
     Redacted,
 }
 
@@ -24,7 +25,7 @@ impl Seed {
 
     pub fn redact(&self) -> Self {
         match self {
-            Seed::Random { .. } => Seed::Redacted,
+            Seed::Random { .. } | Seed::Duplicate { .. } => Seed::Redacted,
             _ => self.clone(),
         }
     }
@@ -33,6 +34,7 @@ impl Seed {
 #[derive(Clone, Debug)]
 pub struct HashedSeed {
     seed: [u8; 32],
+    rotation: u8,
 }
 
 impl From<&Seed> for HashedSeed {
@@ -41,27 +43,58 @@ impl From<&Seed> for HashedSeed {
             seed: Sha256::digest(match seed {
                 Seed::Chosen { value } => value.as_bytes(),
                 Seed::Random { value } => value.as_bytes(),
+                Seed::Duplicate { value, .. } => value.as_bytes(),
                 Seed::Redacted => panic!("cannot convert redacted seed to bytes"),
             })
             .into(),
+            rotation: match seed {
+                Seed::Duplicate { rotation, .. } => rotation % 4,
+                _ => 0,
+            },
         }
     }
 }
 
 impl HashedSeed {
     pub fn new() -> Self {
-        Self { seed: [0; 32] }
+        Self {
+            seed: [0; 32],
+            rotation: 0,
+        }
+    }
+
+    // the seat dealt the cards seat would hold in an unrotated deal
+    pub fn rotate(&self, seat: Seat) -> Seat {
+        Seat::VALUES[(seat.idx() + self.rotation as usize) % 4]
+    }
+
+    pub fn unrotate(&self, seat: Seat) -> Seat {
+        Seat::VALUES[(seat.idx() + 4 - self.rotation as usize) % 4]
     }
 
     pub fn deal(&self, pass: PassDirection) -> GameEvent {
+        self.deal_hand(pass as u16)
+    }
+
+    // five streams per round, one per deal and one for the keeper pass, so the
+    // first round deals the same cards as a one round game
+    pub fn deal_hand(&self, hand: u16) -> GameEvent {
+        let pass = PassDirection::from((hand % 4) as u8);
         let mut rng = ChaCha20Rng::from_seed(self.seed);
-        rng.set_stream(pass as u64);
+        rng.set_stream(hand as u64 + hand as u64 / 4);
         let mut deck = Cards::ALL.into_iter().collect::<Vec<_>>();
         deck.shuffle(&mut rng);
-        let north = deck[0..13].iter().cloned().collect();
-        let east = deck[13..26].iter().cloned().collect();
-        let south = deck[26..39].iter().cloned().collect();
-        let west = deck[39..52].iter().cloned().collect();
+        let hand = |seat: Seat| {
+            let idx = self.unrotate(seat).idx();
+            deck[13 * idx..13 * (idx + 1)]
+                .iter()
+                .cloned()
+                .collect::<Cards>()
+        };
+        let north = hand(Seat::North);
+        let east = hand(Seat::East);
+        let south = hand(Seat::South);
+        let west = hand(Seat::West);
         info!(
             "deal: north={}, east={}, south={}, west={}, pass={}",
             north, east, south, west, pass
@@ -75,41 +108,47 @@ impl HashedSeed {
         }
     }
 
-    pub fn keeper_pass(&self, partial_hands: [Cards; 4]) -> [GameEvent; 4] {
+    pub fn keeper_pass(&self, hand: u16, partial_hands: [Cards; 4]) -> [GameEvent; 4] {
         let mut rng = ChaCha20Rng::from_seed(self.seed);
-        rng.set_stream(4);
+        rng.set_stream(5 * (hand as u64 / 4) + 4);
         let cards =
             Cards::ALL - partial_hands[0] - partial_hands[1] - partial_hands[2] - partial_hands[3];
         let mut passes: Vec<_> = cards.into_iter().collect();
         passes.shuffle(&mut rng);
         let mut passes = passes.into_iter();
-        [
+        let north = self.rotate(Seat::North);
+        let east = self.rotate(Seat::East);
+        let south = self.rotate(Seat::South);
+        let west = self.rotate(Seat::West);
+        let mut events = [
             GameEvent::RecvPass {
-                to: Seat::North,
+                to: north,
                 cards: passes
                     .borrow_mut()
-                    .take(13 - partial_hands[0].len())
+                    .take(13 - partial_hands[north.idx()].len())
                     .collect(),
             },
             GameEvent::RecvPass {
-                to: Seat::East,
+                to: east,
                 cards: passes
                     .borrow_mut()
-                    .take(13 - partial_hands[1].len())
+                    .take(13 - partial_hands[east.idx()].len())
                     .collect(),
             },
             GameEvent::RecvPass {
-                to: Seat::South,
+                to: south,
                 cards: passes
                     .borrow_mut()
-                    .take(13 - partial_hands[2].len())
+                    .take(13 - partial_hands[south.idx()].len())
                     .collect(),
             },
             GameEvent::RecvPass {
-                to: Seat::West,
+                to: west,
                 cards: passes.collect(),
             },
-        ]
+        ];
+        events.rotate_right(self.rotation as usize);
+        events
     }
 }
 
@@ -203,18 +242,85 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_deal_second_round() {
+        let seed = HashedSeed::from(&Seed::Chosen {
+            value: "chosen".to_string(),
+        });
+        assert_eq!(seed.deal_hand(0), seed.deal(PassDirection::Left));
+        let first = seed.deal(PassDirection::Left);
+        let second = seed.deal_hand(4);
+        assert_ne!(first, second);
+        match second {
+            GameEvent::Deal { pass, .. } => assert_eq!(pass, PassDirection::Left),
+            _ => panic!("expected a deal"),
+        }
+    }
+
+    #[test]
+    fn test_deal_duplicate() {
+        let seed = HashedSeed::from(&Seed::Chosen {
+            value: "chosen".to_string(),
+        });
+        let rotated = HashedSeed::from(&Seed::Duplicate {
+            value: "chosen".to_string(),
+            rotation: 1,
+        });
+        assert_eq!(
+            rotated.deal(PassDirection::Left),
+            GameEvent::Deal {
+                north: "84S T87H AKQT63D K8C".parse().unwrap(),
+                east: "QJT7S KJ643H J97D AC".parse().unwrap(),
+                south: "AK962S Q9H 82D QJ63C".parse().unwrap(),
+                west: "53S A52H 54D T97542C".parse().unwrap(),
+                pass: PassDirection::Left
+            }
+        );
+        let partial_hands: [Cards; 4] = [
+            "AK9865432S AH".parse().unwrap(),
+            "QT987652H AKD".parse().unwrap(),
+            "QT865432D KQC".parse().unwrap(),
+            "JT98765432C".parse().unwrap(),
+        ];
+        let mut rotated_hands = partial_hands;
+        rotated_hands.rotate_right(1);
+        let passes = seed.keeper_pass(3, partial_hands);
+        let rotated_passes = rotated.keeper_pass(3, rotated_hands);
+        for &seat in &Seat::VALUES {
+            match (
+                &passes[seat.idx()],
+                &rotated_passes[rotated.rotate(seat).idx()],
+            ) {
+                (
+                    GameEvent::RecvPass { cards, .. },
+                    GameEvent::RecvPass {
+                        to,
+                        cards: rotated_cards,
+                    },
+                ) => {
+                    assert_eq!(*to, rotated.rotate(seat));
+                    assert_eq!(cards, rotated_cards);
+                }
+                events => panic!("Unexpected events {:?}", events),
+            }
+        }
+    }
+
     #[test]
     fn test_keeper_pass() {
         let seed = HashedSeed::from(&Seed::Chosen {
             value: "chosen".to_string(),
         });
         assert_eq!(
-            seed.keeper_pass([
-                "AK9865432S AH".parse().unwrap(),
-                "QT987652H AKD".parse().unwrap(),
-                "QT865432D KQC".parse().unwrap(),
-                "JT98765432C".parse().unwrap()
-            ]),
+            seed.keeper_pass(
+                3,
+                [
+                    "AK9865432S AH".parse().unwrap(),
+                    "QT987652H AKD".parse().unwrap(),
+                    "QT865432D KQC".parse().unwrap(),
+                    "JT98765432C".parse().unwrap()
+                ]
+            ),
             [
                 GameEvent::RecvPass {
                     to: Seat::North,
@@ -0,0 +1,190 @@
+use crate::{
+    AcceptClaimRequest, AcceptUndoRequest, AddBotRequest, ChargeRequest, ClaimRequest, DuplicateId,
+    GameChatRequest, GameId, JoinGameRequest, JoinToken, LeaveGameRequest, LobbyChatRequest,
+    NewDuplicateRequest, NewGameRequest, PassRequest, PlayRequest, RejectClaimRequest,
+    RejectUndoRequest, RemovePlayerRequest, RequestUndoRequest, ResumeRequest, RulesError, Seat,
+    StartGameRequest, UserId,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SocketQuery {
+    pub last_event_id: Option<usize>,
+    pub follow: Option<Seat>,
+    pub join_token: Option<JoinToken>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SocketRequest<R> {
+    pub request_id: u64,
+    #[serde(flatten)]
+    pub request: R,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameRequest {
+    Pass(PassRequest),
+    Charge(ChargeRequest),
+    Play(PlayRequest),
+    Claim(ClaimRequest),
+    AcceptClaim(AcceptClaimRequest),
+    RejectClaim(RejectClaimRequest),
+    RequestUndo(RequestUndoRequest),
+    AcceptUndo(AcceptUndoRequest),
+    RejectUndo(RejectUndoRequest),
+    Resume(ResumeRequest),
+    Chat(GameChatRequest),
+}
+
+impl GameRequest {
+    pub fn game_id(&self) -> GameId {
+        match self {
+            GameRequest::Pass(PassRequest { game_id, .. })
+            | GameRequest::Charge(ChargeRequest { game_id, .. })
+            | GameRequest::Play(PlayRequest { game_id, .. })
+            | GameRequest::Claim(ClaimRequest { game_id })
+            | GameRequest::AcceptClaim(AcceptClaimRequest { game_id, .. })
+            | GameRequest::RejectClaim(RejectClaimRequest { game_id, .. })
+            | GameRequest::RequestUndo(RequestUndoRequest { game_id })
+            | GameRequest::AcceptUndo(AcceptUndoRequest { game_id, .. })
+            | GameRequest::RejectUndo(RejectUndoRequest { game_id, .. })
+            | GameRequest::Resume(ResumeRequest { game_id })
+            | GameRequest::Chat(GameChatRequest { game_id, .. }) => *game_id,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameAck {
+    Pass,
+    Charge,
+    Play,
+    Claim,
+    AcceptClaim,
+    RejectClaim,
+    RequestUndo,
+    AcceptUndo,
+    RejectUndo,
+    Resume,
+    Chat,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LobbyRequest {
+    NewGame(NewGameRequest),
+    NewDuplicate(NewDuplicateRequest),
+    JoinGame(JoinGameRequest),
+    StartGame(StartGameRequest),
+    LeaveGame(LeaveGameRequest),
+    AddBot(AddBotRequest),
+    RemovePlayer(RemovePlayerRequest),
+    Chat(LobbyChatRequest),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LobbyAck {
+    NewGame {
+        game_id: GameId,
+        join_token: Option<JoinToken>,
+    },
+    NewDuplicate {
+        duplicate_id: DuplicateId,
+        game_ids: Vec<GameId>,
+    },
+    JoinGame,
+    StartGame,
+    LeaveGame,
+    AddBot {
+        user_id: UserId,
+    },
+    RemovePlayer,
+    Chat,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SocketMessage<E, A> {
+    Event {
+        event_id: Option<usize>,
+        event: E,
+    },
+    Ack {
+        request_id: u64,
+        ack: A,
+    },
+    Error {
+        request_id: Option<u64>,
+        message: String,
+        rules: Option<RulesError>,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Card, GameEvent, Seat};
+
+    #[test]
+    fn test_game_request() {
+        let game_id = GameId::new();
+        let json = format!(
+            r#"{{"request_id":7,"type":"play","game_id":"{}","card":"QS"}}"#,
+            game_id
+        );
+        let request: SocketRequest<GameRequest> = serde_json::from_str(&json).unwrap();
+        assert_eq!(request.request_id, 7);
+        match request.request {
+            GameRequest::Play(PlayRequest { game_id: id, card }) => {
+                assert_eq!(id, game_id);
+                assert_eq!(card, Card::QueenSpades);
+            }
+            request => panic!("Unexpected request {:?}", request),
+        }
+    }
+
+    #[test]
+    fn test_lobby_request() {
+        let json = r#"{"request_id":3,"type":"chat","message":"hello"}"#;
+        let request: SocketRequest<LobbyRequest> = serde_json::from_str(json).unwrap();
+        assert_eq!(request.request_id, 3);
+        match request.request {
+            LobbyRequest::Chat(LobbyChatRequest { message }) => assert_eq!(message, "hello"),
+            request => panic!("Unexpected request {:?}", request),
+        }
+    }
+
+    #[test]
+    fn test_socket_message() {
+        let message: SocketMessage<GameEvent, GameAck> = SocketMessage::Event {
+            event_id: Some(4),
+            event: GameEvent::StartTrick {
+                leader: Seat::North,
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"type":"event","event_id":4,"event":{"type":"start_trick","leader":"north"}}"#
+        );
+        let message: SocketMessage<GameEvent, GameAck> = SocketMessage::Ack {
+            request_id: 2,
+            ack: GameAck::Play,
+        };
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"type":"ack","request_id":2,"ack":{"type":"play"}}"#
+        );
+        let message: SocketMessage<GameEvent, GameAck> = SocketMessage::Error {
+            request_id: Some(5),
+            message: RulesError::MustFollowSuit.to_string(),
+            rules: Some(RulesError::MustFollowSuit),
+        };
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"type":"error","request_id":5,"message":"suit must be followed","rules":{"type":"must_follow_suit"}}"#
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SpectatorPolicy {
+    // every hand and pass, delivered after the delay
+    Omniscient { delay_seconds: u32 },
+    // each hand revealed once it's complete
+    HiddenHands,
+    // what the player in the followed seat sees
+    FollowSeat,
+}
+
+impl SpectatorPolicy {
+    pub const MAX_DELAY_SECONDS: u32 = 3600;
+
+    pub fn is_valid(&self) -> bool {
+        match self {
+            SpectatorPolicy::Omniscient { delay_seconds } => {
+                *delay_seconds <= Self::MAX_DELAY_SECONDS
+            }
+            _ => true,
+        }
+    }
+
+    pub fn delay_millis(&self) -> i64 {
+        match self {
+            SpectatorPolicy::Omniscient { delay_seconds } => *delay_seconds as i64 * 1000,
+            _ => 0,
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{Cards, ChargingRules, GameEvent, GameId, Player, UserId};
+use crate::{
+    BotOptions, BotStrategy, Card, Cards, ChargingRules, DuplicateId, GameEvent, GameId,
+    PassDirection, Player, Seat, UserId,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -48,3 +51,173 @@ pub struct GameSummaryEvent {
     pub event: GameEvent,
     pub synthetic_events: Vec<GameEvent>,
 }
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RatedPlayer {
+    Human {
+        user_id: UserId,
+    },
+    // rated separately for each set of options apart from the seed
+    Bot {
+        strategy: BotStrategy,
+        #[serde(default, skip_serializing_if = "BotOptions::is_default")]
+        options: BotOptions,
+    },
+}
+
+impl From<Player> for RatedPlayer {
+    fn from(player: Player) -> Self {
+        match player {
+            Player::Human { user_id } => RatedPlayer::Human { user_id },
+            Player::Bot {
+                strategy, options, ..
+            } => RatedPlayer::Bot {
+                strategy,
+                options: BotOptions {
+                    seed: None,
+                    ..options
+                },
+            },
+        }
+    }
+}
+
+pub type RatingsResponse = Vec<PlayerRating>;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlayerRating {
+    pub player: RatedPlayer,
+    pub rating: f64,
+    pub hands: u32,
+    pub updated_time: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RatingHistoryRequest {
+    pub user_id: Option<UserId>,
+    pub strategy: Option<BotStrategy>,
+    #[serde(default)]
+    pub options: BotOptions,
+}
+
+pub type RatingHistoryResponse = Vec<RatingChange>;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RatingChange {
+    pub game_id: GameId,
+    pub hand: u16,
+    pub completed_time: i64,
+    pub money: i16,
+    pub rating: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicateSummaryResponse {
+    pub duplicate_id: DuplicateId,
+    pub tables: Vec<DuplicateTable>,
+    pub boards: Vec<DuplicateBoard>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicateTable {
+    pub game_id: GameId,
+    pub rotation: u8,
+    pub players: Option<[Player; 4]>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicateBoard {
+    pub pass: PassDirection,
+    pub results: Vec<DuplicateResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicateResult {
+    pub game_id: GameId,
+    // the seat dealt these cards at the unrotated table
+    pub hand: Seat,
+    pub seat: Seat,
+    pub player: Player,
+    pub money: i16,
+    // against the average of every table that played the hand
+    pub difference: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnalysisResponse {
+    pub game_id: GameId,
+    pub solved_cards: usize,
+    pub hands: Vec<HandAnalysis>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HandAnalysis {
+    pub pass: PassDirection,
+    // after passing
+    pub hands: [Cards; 4],
+    // only plays with more than one legal card
+    pub plays: Vec<PlayAnalysis>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayAnalysis {
+    pub seat: Seat,
+    pub card: Card,
+    pub money: Option<i16>,
+    pub best_money: Option<i16>,
+    // None for plays made before solved_cards
+    pub outcomes: Option<Vec<PlayOutcome>>,
+}
+
+impl PlayAnalysis {
+    pub const SOLVED_CARDS: usize = 52;
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayOutcome {
+    pub card: Card,
+    pub money: i16,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportResponse {
+    pub game_id: GameId,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Difficulty;
+    use serde_json::json;
+
+    #[test]
+    fn test_rated_bot() {
+        let bot = |options| {
+            RatedPlayer::from(Player::Bot {
+                user_id: UserId::new(),
+                strategy: BotStrategy::Ismcts,
+                options,
+            })
+        };
+        let default = bot(BotOptions::default());
+        assert_eq!(
+            serde_json::to_value(default).unwrap(),
+            json!({ "type": "bot", "strategy": "ismcts" })
+        );
+        let seeded = bot(BotOptions {
+            seed: Some(7),
+            ..BotOptions::default()
+        });
+        assert_eq!(seeded, default);
+        let hard = bot(BotOptions {
+            difficulty: Difficulty::Hard,
+            ..BotOptions::default()
+        });
+        assert_ne!(hard, default);
+        assert_eq!(
+            serde_json::from_value::<RatedPlayer>(serde_json::to_value(hard).unwrap()).unwrap(),
+            hard
+        );
+    }
+}
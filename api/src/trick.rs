@@ -11,7 +11,7 @@ const SUIT_XORS: [u64; 4] = [
 ];
 const EMPTY: u64 = 0x80_80_80_80_80_80_80_80;
 
-#[derive(Clone, Copy, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Copy, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Trick {
     state: u64,
 }
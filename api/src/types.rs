@@ -49,6 +49,35 @@ impl Display for ChargingRules {
     }
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct DuplicateId(Uuid);
+
+impl DuplicateId {
+    pub fn new() -> DuplicateId {
+        DuplicateId(Uuid::new_v4())
+    }
+}
+
+impl Default for DuplicateId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for DuplicateId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl FromStr for DuplicateId {
+    type Err = <Uuid as FromStr>::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(DuplicateId(s.parse()?))
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct GameId(Uuid);
 
@@ -72,6 +101,35 @@ impl FromStr for GameId {
     }
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct JoinToken(Uuid);
+
+impl JoinToken {
+    pub fn new() -> JoinToken {
+        JoinToken(Uuid::new_v4())
+    }
+}
+
+impl Default for JoinToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for JoinToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl FromStr for JoinToken {
+    type Err = <Uuid as FromStr>::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(JoinToken(s.parse()?))
+    }
+}
+
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -0,0 +1,119 @@
+use crate::Seat;
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct UndoState {
+    requester: Option<Seat>,
+    accepts: u8,
+}
+
+impl UndoState {
+    pub fn new() -> Self {
+        Self {
+            requester: None,
+            accepts: 0,
+        }
+    }
+
+    pub fn requester(self) -> Option<Seat> {
+        self.requester
+    }
+
+    pub fn is_requesting(self, seat: Seat) -> bool {
+        self.requester == Some(seat)
+    }
+
+    pub fn has_accepted(self, seat: Seat) -> bool {
+        self.accepts & (1 << seat.idx()) != 0
+    }
+
+    pub fn successfully_undone(self) -> bool {
+        self.accepts == 0xf
+    }
+
+    #[must_use]
+    pub fn request(self, seat: Seat) -> Self {
+        Self {
+            requester: Some(seat),
+            accepts: 1 << seat.idx(),
+        }
+    }
+
+    #[must_use]
+    pub fn accept(self, acceptor: Seat) -> Self {
+        Self {
+            requester: self.requester,
+            accepts: self.accepts | (1 << acceptor.idx()),
+        }
+    }
+
+    #[must_use]
+    pub fn reject(self) -> Self {
+        Self::new()
+    }
+}
+
+impl Default for UndoState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn no_request() {
+        let state = UndoState::new();
+        assert_eq!(state.requester(), None);
+        assert!(!state.successfully_undone());
+        for &seat in &Seat::VALUES {
+            assert!(!state.is_requesting(seat));
+            assert!(!state.has_accepted(seat));
+        }
+    }
+
+    #[test]
+    fn request() {
+        let state = UndoState::new().request(Seat::South);
+        assert_eq!(state.requester(), Some(Seat::South));
+        assert!(!state.successfully_undone());
+        for &seat in &Seat::VALUES {
+            assert_eq!(state.is_requesting(seat), seat == Seat::South);
+            assert_eq!(state.has_accepted(seat), seat == Seat::South);
+        }
+    }
+
+    #[test]
+    fn accept_some() {
+        let state = UndoState::new()
+            .request(Seat::South)
+            .accept(Seat::West)
+            .accept(Seat::North);
+        assert_eq!(state.requester(), Some(Seat::South));
+        assert!(!state.successfully_undone());
+        for &seat in &Seat::VALUES {
+            assert_eq!(state.has_accepted(seat), seat != Seat::East);
+        }
+    }
+
+    #[test]
+    fn accept_all() {
+        let state = UndoState::new()
+            .request(Seat::South)
+            .accept(Seat::West)
+            .accept(Seat::North)
+            .accept(Seat::East);
+        assert!(state.successfully_undone());
+    }
+
+    #[test]
+    fn reject() {
+        let state = UndoState::new()
+            .request(Seat::South)
+            .accept(Seat::West)
+            .reject();
+        assert_eq!(state, UndoState::new());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Card, Cards, GameEvent, GameState, Seat, Suit};
+use crate::{Card, GameEvent, GameState, Seat, Suit};
 use std::{fmt, fmt::Formatter};
 
 #[derive(Clone, Copy)]
@@ -32,7 +32,7 @@ impl VoidState {
                     self.mark_void(*seat, trick.suit())
                 } else if trick.is_empty()
                     && card.suit() == Suit::Hearts
-                    && !state.played.contains_any(Cards::HEARTS)
+                    && !state.rule_set.hearts_broken(state.played)
                 {
                     // force break hearts
                     self.mark_void(*seat, Suit::Clubs)
@@ -84,3 +84,46 @@ impl fmt::Debug for VoidState {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{GamePhase, HeartsLead};
+
+    // how many other suits leading a heart marks the leader void in
+    fn lead_heart(hearts_lead: HeartsLead, played: &str) -> usize {
+        let mut state = GameState::new();
+        state.phase = GamePhase::PlayLeft;
+        state.rule_set.hearts_lead = hearts_lead;
+        state.played = played.parse().unwrap();
+        let void = VoidState::new().on_event(
+            &state,
+            &GameEvent::Play {
+                seat: Seat::North,
+                card: Card::FiveHearts,
+            },
+        );
+        [Suit::Clubs, Suit::Diamonds, Suit::Spades]
+            .iter()
+            .filter(|&&suit| void.is_void(Seat::North, suit))
+            .count()
+    }
+
+    #[test]
+    fn test_hearts_lead_broken() {
+        assert_eq!(lead_heart(HeartsLead::Broken, "AKQ2S"), 3);
+        assert_eq!(lead_heart(HeartsLead::Broken, "AK2S 7H"), 0);
+    }
+
+    #[test]
+    fn test_hearts_lead_broken_or_queen() {
+        assert_eq!(lead_heart(HeartsLead::BrokenOrQueen, "AK2S"), 3);
+        assert_eq!(lead_heart(HeartsLead::BrokenOrQueen, "AKQ2S"), 0);
+        assert_eq!(lead_heart(HeartsLead::BrokenOrQueen, "AK2S 7H"), 0);
+    }
+
+    #[test]
+    fn test_hearts_lead_anytime() {
+        assert_eq!(lead_heart(HeartsLead::Anytime, "AK2S"), 0);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Card, Cards, ChargeState, Scores, Seat};
+use crate::{Card, Cards, ChargeState, MoonScoring, RuleSet, Scores, Seat};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -86,7 +86,7 @@ impl WonState {
             .cloned()
     }
 
-    pub fn scores(self, charges: ChargeState) -> Scores {
+    pub fn scores(self, charges: ChargeState, rule_set: RuleSet) -> Scores {
         let heart_multiplier = if charges.is_charged(Card::AceHearts) {
             2
         } else {
@@ -98,30 +98,44 @@ impl WonState {
             heart_multiplier * self.hearts(Seat::South) as i16,
             heart_multiplier * self.hearts(Seat::West) as i16,
         ];
-        self.queen_winner().map(|s| {
+        if let Some(s) = self.queen_winner() {
             scores[s.idx()] += if charges.is_charged(Card::QueenSpades) {
                 26
             } else {
                 13
             };
             if self.hearts(s) == 13 {
-                scores[s.idx()] *= -1;
+                match rule_set.moon {
+                    MoonScoring::Negate => scores[s.idx()] *= -1,
+                    MoonScoring::AddToOthers => {
+                        let moon = scores[s.idx()];
+                        for score in scores.iter_mut() {
+                            *score += moon;
+                        }
+                        scores[s.idx()] = 0;
+                    }
+                    MoonScoring::Disabled => {}
+                }
             }
-        });
-        self.jack_winner().map(|s| {
-            scores[s.idx()] += if charges.is_charged(Card::JackDiamonds) {
-                -20
-            } else {
-                -10
-            };
-        });
-        self.ten_winner().map(|s| {
-            scores[s.idx()] *= if charges.is_charged(Card::TenClubs) {
-                4
-            } else {
-                2
-            };
-        });
+        }
+        if let Some(s) = self.jack_winner() {
+            if rule_set.jack_of_diamonds {
+                scores[s.idx()] += if charges.is_charged(Card::JackDiamonds) {
+                    -20
+                } else {
+                    -10
+                };
+            }
+        }
+        if let Some(s) = self.ten_winner() {
+            if rule_set.ten_of_clubs {
+                scores[s.idx()] *= if charges.is_charged(Card::TenClubs) {
+                    4
+                } else {
+                    2
+                };
+            }
+        }
         Scores::new(scores)
     }
 }
@@ -165,4 +179,66 @@ mod test {
                 .win(Seat::East, "TC".parse().unwrap())
         )
     }
+
+    fn scores(won: WonState, charges: ChargeState, rule_set: RuleSet) -> [i16; 4] {
+        let scores = won.scores(charges, rule_set);
+        [
+            scores.score(Seat::North),
+            scores.score(Seat::East),
+            scores.score(Seat::South),
+            scores.score(Seat::West),
+        ]
+    }
+
+    #[test]
+    fn test_scores() {
+        let won = WonState::new()
+            .win(Seat::North, "QS 5432H".parse().unwrap())
+            .win(Seat::East, "JD TC".parse().unwrap());
+        let charges = ChargeState::new();
+        assert_eq!(scores(won, charges, RuleSet::STANDARD), [17, -20, 0, 0]);
+        assert_eq!(
+            scores(
+                won,
+                charges.charge(Seat::East, Card::TenClubs.into()),
+                RuleSet::STANDARD
+            ),
+            [17, -40, 0, 0]
+        );
+        let no_ten = RuleSet {
+            ten_of_clubs: false,
+            ..RuleSet::STANDARD
+        };
+        assert_eq!(scores(won, charges, no_ten), [17, -10, 0, 0]);
+        let no_jack = RuleSet {
+            jack_of_diamonds: false,
+            ..RuleSet::STANDARD
+        };
+        assert_eq!(scores(won, charges, no_jack), [17, 0, 0, 0]);
+        assert_eq!(won.scores(charges, no_jack).money(Seat::North), -51);
+    }
+
+    #[test]
+    fn test_moon() {
+        let won = WonState::new()
+            .win(Seat::North, "QS AKQJT98765432H".parse().unwrap())
+            .win(Seat::East, "JD TC".parse().unwrap());
+        let charges = ChargeState::new();
+        let rule_set = |moon| RuleSet {
+            moon,
+            ..RuleSet::STANDARD
+        };
+        assert_eq!(
+            scores(won, charges, rule_set(MoonScoring::Negate)),
+            [-26, -20, 0, 0]
+        );
+        assert_eq!(
+            scores(won, charges, rule_set(MoonScoring::AddToOthers)),
+            [0, 32, 26, 26]
+        );
+        assert_eq!(
+            scores(won, charges, rule_set(MoonScoring::Disabled)),
+            [26, -20, 0, 0]
+        );
+    }
 }
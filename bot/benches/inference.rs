@@ -8,8 +8,8 @@ use tract_onnx::{
     tract_hir::tract_core::downcast_rs::__std::time::Duration,
 };
 use turbo_hearts_api::{
-    ChargeState, ChargingRules, ClaimState, DoneState, GamePhase, GameState, Seat, Suit, Suits,
-    Trick, WonState,
+    ChargeState, ChargingRules, ClaimState, DoneState, GameLength, GamePhase, GameState, RuleSet,
+    Seat, Suit, Suits, Trick, UndoState, WonState,
 };
 use turbo_hearts_bot::Encoder;
 
@@ -50,6 +50,10 @@ pub fn inference(c: &mut Criterion) -> Result<(), Box<dyn Error>> {
         let model = model.into_optimized()?.into_runnable()?;
         let state = GameState {
             rules: ChargingRules::Classic,
+            rule_set: RuleSet::STANDARD,
+            length: GameLength::STANDARD,
+            hand: 0,
+            totals: [0; 4],
             phase: GamePhase::PlayLeft,
             done: DoneState::new(),
             charge_count: 0,
@@ -57,6 +61,7 @@ pub fn inference(c: &mut Criterion) -> Result<(), Box<dyn Error>> {
             next_actor: Some(Seat::East),
             played: "2C QC JC TC".parse().unwrap(),
             claims: ClaimState::new(),
+            undo: UndoState::new(),
             won: WonState::new(),
             led_suits: Suits::NONE | Suit::Clubs,
             current_trick: Trick::new(),
@@ -0,0 +1,44 @@
+use criterion::{BenchmarkId, Criterion, Throughput};
+use rayon::ThreadPoolBuilder;
+use std::time::Duration;
+use turbo_hearts_api::{BotOptions, BotState, GameState};
+use turbo_hearts_bot::{north_to_play, Algorithm, SimulateBot};
+
+const ITERATIONS: u32 = 64;
+
+// north's play to the second card of the first trick, after passing left
+fn position() -> (SimulateBot, BotState, GameState) {
+    let mut bot = SimulateBot::with_options(BotOptions {
+        iterations: Some(ITERATIONS),
+        ..BotOptions::default()
+    });
+    let (bot_state, game_state) =
+        north_to_play(&mut bot, "AKQJT9S AKH QJ53C 2D", "AS AKH", "876D", "2C");
+    (bot, bot_state, game_state)
+}
+
+// deals searched per second by the simulate bot as its pool grows
+pub fn search(c: &mut Criterion) {
+    let mut g = c.benchmark_group("search");
+    g.throughput(Throughput::Elements(ITERATIONS as u64));
+    let (bot, bot_state, game_state) = position();
+    for &threads in &[1, 2, 4, 8] {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        g.bench_with_input(BenchmarkId::new("simulate", threads), &threads, |b, _| {
+            let mut bot = bot.clone();
+            b.iter(|| pool.install(|| bot.play(&bot_state, &game_state)));
+        });
+    }
+    g.finish();
+}
+
+fn main() {
+    let mut criterion = Criterion::default()
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(20));
+    search(&mut criterion);
+    criterion.final_summary();
+}
@@ -89,7 +89,7 @@ fn main() {
             let mut state = state.clone();
             state.apply(&GameEvent::Play { seat, card });
             let won = brute_force.solve(&mut state);
-            let money = won.scores(state.charges).money(seat);
+            let money = won.scores(state.charges, state.rule_set).money(seat);
             if money > best_money {
                 best_card = card;
                 best_won = won;
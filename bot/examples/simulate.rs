@@ -124,7 +124,7 @@ fn main() {
         game.apply(&GameEvent::Play { seat, card });
     }
     println!("-------------------------------");
-    let scores = game.state.won.scores(game.state.charges);
+    let scores = game.state.scores();
     for &s in &Seat::VALUES {
         println!(
             "{}: score {}, money {}",
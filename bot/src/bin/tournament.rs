@@ -0,0 +1,173 @@
+use rayon::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{
+    env,
+    error::Error,
+    fs::File,
+    io::{self, Write},
+    process,
+};
+use turbo_hearts_api::{BotOptions, BotStrategy, ChargingRules};
+use turbo_hearts_bot::{lineups, play_game, standings, GameResult, Standing};
+
+const USAGE: &str = "usage: tournament [--games N] [--seed PREFIX] [--rules RULES] \
+[--iterations N] [--format json|csv] [--output PATH] STRATEGY STRATEGY...
+
+Plays N deals under every balanced seating of the given strategies
+(duck, gotta_try, heuristic, random, simulate, neural_net, ismcts,
+ismcts_neural_net) and reports
+per-strategy results. Each deal, and every random choice the bots make
+while playing it, is seeded from PREFIX.
+
+The search strategies think for a time by default, so their games change
+from run to run, and they're played one at a time so that each search has
+every thread. With --iterations they search that many iterations instead,
+games are played in parallel, and rerunning with the same PREFIX replays
+the same games.";
+
+#[derive(Serialize)]
+struct Report {
+    rules: ChargingRules,
+    deals: usize,
+    standings: Vec<Standing>,
+    games: Vec<GameResult>,
+}
+
+struct Options {
+    games: usize,
+    seed: String,
+    rules: ChargingRules,
+    iterations: Option<u32>,
+    csv: bool,
+    output: Option<String>,
+    strategies: Vec<BotStrategy>,
+}
+
+fn parse_enum<T: DeserializeOwned>(value: &str) -> Result<T, Box<dyn Error>> {
+    serde_json::from_value(Value::String(value.to_string()))
+        .map_err(|_| format!("unrecognized value {}", value).into())
+}
+
+fn parse_options() -> Result<Options, Box<dyn Error>> {
+    let mut options = Options {
+        games: 100,
+        seed: "tournament".to_string(),
+        rules: ChargingRules::Classic,
+        iterations: None,
+        csv: false,
+        output: None,
+        strategies: Vec::new(),
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--games" => options.games = value()?.parse()?,
+            "--seed" => options.seed = value()?,
+            "--rules" => options.rules = parse_enum(&value()?)?,
+            "--iterations" => options.iterations = Some(value()?.parse()?),
+            "--format" => {
+                options.csv = match value()?.as_str() {
+                    "json" => false,
+                    "csv" => true,
+                    format => return Err(format!("unrecognized format {}", format).into()),
+                }
+            }
+            "--output" => options.output = Some(value()?),
+            "--help" | "-h" => return Err(USAGE.into()),
+            _ => {
+                let strategy = parse_enum(&arg)?;
+                if !options.strategies.contains(&strategy) {
+                    options.strategies.push(strategy);
+                }
+            }
+        }
+    }
+    if options.strategies.len() < 2 {
+        return Err("at least two distinct strategies are required".into());
+    }
+    Ok(options)
+}
+
+fn write_csv(out: &mut dyn Write, standings: &[Standing]) -> Result<(), Box<dyn Error>> {
+    writeln!(
+        out,
+        "strategy,games,mean_money,money_ci,charge_rate,queen_rate,jack_rate,ten_rate"
+    )?;
+    for standing in standings {
+        writeln!(
+            out,
+            "{},{},{:.3},{:.3},{:.4},{:.4},{:.4},{:.4}",
+            serde_json::to_value(standing.strategy)?.as_str().unwrap(),
+            standing.games,
+            standing.mean_money,
+            standing.money_ci,
+            standing.charge_rate,
+            standing.queen_rate,
+            standing.jack_rate,
+            standing.ten_rate
+        )?;
+    }
+    Ok(())
+}
+
+fn searches(strategy: BotStrategy) -> bool {
+    matches!(
+        strategy,
+        BotStrategy::Simulate
+            | BotStrategy::NeuralNet
+            | BotStrategy::Ismcts
+            | BotStrategy::IsmctsNeuralNet
+    )
+}
+
+fn run(options: Options) -> Result<(), Box<dyn Error>> {
+    let lineups = lineups(&options.strategies);
+    let matches = (0..options.games)
+        .flat_map(|i| lineups.iter().map(move |lineup| (i, *lineup)))
+        .collect::<Vec<_>>();
+    let bot_options = BotOptions {
+        iterations: options.iterations,
+        ..BotOptions::default()
+    };
+    let play = |&(i, lineup): &(usize, [BotStrategy; 4])| {
+        let seed = format!("{}-{}", options.seed, i);
+        play_game(options.rules, seed, lineup, bot_options)
+    };
+    let timed = options.iterations.is_none() && options.strategies.iter().any(|&s| searches(s));
+    let games = if timed {
+        matches.iter().map(play).collect::<Result<Vec<_>, _>>()?
+    } else {
+        matches
+            .par_iter()
+            .map(play)
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    let standings = standings(&options.strategies, &games);
+    let mut out: Box<dyn Write> = match &options.output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+    if options.csv {
+        write_csv(&mut out, &standings)?;
+    } else {
+        let report = Report {
+            rules: options.rules,
+            deals: options.games,
+            standings,
+            games,
+        };
+        serde_json::to_writer_pretty(&mut out, &report)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let result = parse_options().and_then(run);
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
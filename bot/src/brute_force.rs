@@ -54,7 +54,7 @@ impl BruteForce {
             let mut state = state.clone();
             state.apply(&GameEvent::Play { seat, card });
             let won = self.solve(&mut state);
-            let money = won.scores(state.charges).money(seat);
+            let money = won.scores(state.charges, state.rule_set).money(seat);
             if money > best_money {
                 best_won = won;
                 best_money = money;
@@ -0,0 +1,120 @@
+use crate::rng;
+use rand::Rng;
+use std::time::{Duration, Instant};
+use turbo_hearts_api::{BotOptions, Card, Cards, Difficulty};
+
+// a search stops at whichever limit it reaches first, after at least one
+// iteration
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Budget {
+    time: Option<Duration>,
+    iterations: Option<u32>,
+}
+
+impl Budget {
+    pub const fn time(time: Duration) -> Self {
+        Self {
+            time: Some(time),
+            iterations: None,
+        }
+    }
+
+    pub const fn iterations(iterations: u32) -> Self {
+        Self {
+            time: None,
+            iterations: Some(iterations),
+        }
+    }
+
+    pub fn with_iterations(self, iterations: u32) -> Self {
+        Self {
+            iterations: Some(iterations),
+            ..self
+        }
+    }
+
+    pub(crate) fn exhausted(&self, start: Instant, iterations: u32) -> bool {
+        iterations > 0
+            && (self.time.is_some_and(|time| start.elapsed() >= time)
+                || self.iterations.is_some_and(|limit| iterations >= limit))
+    }
+
+    // each worker does at least one iteration, so a small budget can't keep
+    // every thread busy
+    pub(crate) fn workers(&self, threads: usize) -> usize {
+        match self.iterations {
+            Some(limit) => threads.min(limit.max(1) as usize),
+            None => threads,
+        }
+    }
+
+    // workers share the time limit but divide the iterations
+    pub(crate) fn share(self, worker: usize, workers: usize) -> Self {
+        let iterations = self.iterations.map(|limit| {
+            let workers = workers as u32;
+            limit / workers + (limit % workers > worker as u32) as u32
+        });
+        Self { iterations, ..self }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Effort {
+    budget: Budget,
+    noise: f64,
+    depth: Option<usize>,
+}
+
+impl Effort {
+    pub fn new(default: Budget, options: BotOptions) -> Self {
+        let (scale, noise, depth) = match options.difficulty {
+            Difficulty::Easy => (0.2, 0.2, Some(8)),
+            Difficulty::Normal => (1.0, 0.0, None),
+            Difficulty::Hard => (2.0, 0.0, None),
+        };
+        let time = match (options.time_ms, options.iterations) {
+            (Some(millis), _) => Some(Duration::from_millis(millis as u64)),
+            (None, Some(_)) => None,
+            (None, None) => default.time.map(|time| time.mul_f64(scale)),
+        };
+        let ceiling = Duration::from_millis(BotOptions::MAX_TIME_MS as u64);
+        let time = Some(time.map_or(ceiling, |time| time.min(ceiling)));
+        let budget = Budget {
+            time,
+            iterations: options.iterations.or(default.iterations),
+        };
+        Self {
+            budget,
+            noise,
+            depth,
+        }
+    }
+
+    pub fn budget(&self) -> Budget {
+        self.budget
+    }
+
+    // how many plays ahead a search may look before falling back on rollouts
+    pub fn depth(&self) -> Option<usize> {
+        self.depth
+    }
+
+    pub(crate) fn blunder(&self, plays: Cards) -> Option<Card> {
+        if self.noise > 0.0 && rng().gen_bool(self.noise) {
+            let index = rng().gen_range(0..plays.len());
+            plays.into_iter().nth(index)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<Budget> for Effort {
+    fn from(budget: Budget) -> Self {
+        Self {
+            budget,
+            noise: 0.0,
+            depth: None,
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Algorithm, DuckBot};
+use crate::{rng, Algorithm, DuckBot};
 use rand::Rng;
 use turbo_hearts_api::{BotState, Card, Cards, GameEvent, GamePhase, GameState, Rank, Suit};
 
@@ -158,7 +158,8 @@ impl Algorithm for GottaTryBot {
         {
             Cards::NONE
         } else {
-            (bot_state.post_pass_hand & Cards::CHARGEABLE) - game_state.charges.all_charges()
+            (bot_state.post_pass_hand & game_state.rule_set.chargeable())
+                - game_state.charges.all_charges()
         }
     }
 
@@ -178,7 +179,7 @@ impl Algorithm for GottaTryBot {
         } else {
             slough(ours, theirs, bot_state, game_state)
         };
-        let index = rand::thread_rng().gen_range(0..good_plays.len());
+        let index = rng().gen_range(0..good_plays.len());
         good_plays.into_iter().nth(index).unwrap()
     }
 
@@ -206,20 +207,20 @@ fn worst(ours: Cards, theirs: Cards) -> Card {
 mod test {
     use super::*;
     use turbo_hearts_api::{
-        ChargeState, ChargingRules, ClaimState, DoneState, GamePhase, Seat, Suits, Trick,
-        VoidState, WonState,
+        ChargeState, ChargingRules, ClaimState, DoneState, GameLength, GamePhase, RuleSet, Seat,
+        Suits, Trick, UndoState, WonState,
     };
 
     #[test]
     fn test_play() {
-        let bot_state = BotState {
-            seat: Seat::South,
-            pre_pass_hand: "A96S A6H KQ3D AKQT5C".parse().unwrap(),
-            post_pass_hand: "A9S AJH AKQD AKQJT5C".parse().unwrap(),
-            void: VoidState::new(),
-        };
+        let mut bot_state = BotState::new(Seat::South, "A96S A6H KQ3D AKQT5C".parse().unwrap());
+        bot_state.post_pass_hand = "A9S AJH AKQD AKQJT5C".parse().unwrap();
         let game_state = GameState {
             rules: ChargingRules::Classic,
+            rule_set: RuleSet::STANDARD,
+            length: GameLength::STANDARD,
+            hand: 0,
+            totals: [0; 4],
             phase: GamePhase::PassRight,
             done: DoneState::new(),
             charge_count: 0,
@@ -229,6 +230,7 @@ mod test {
             next_actor: Some(Seat::South),
             played: "2975648JC QTJ3D K856D 9875AKT6S A4D TH 7D".parse().unwrap(),
             claims: ClaimState::new(),
+            undo: UndoState::new(),
             won: WonState::new().win(
                 Seat::South,
                 "2975648JC QTJ3D K856D 9875AKT6S A4D TH 7D".parse().unwrap(),
@@ -1,15 +1,137 @@
-use rand::seq::SliceRandom;
+use crate::rng;
+use rand::{seq::SliceRandom, Rng};
 use turbo_hearts_api::{Card, Cards, GameEvent, GameState, Seat, Suit, VoidState};
 
+// deals are weighted by how likely the others were to pass, charge and play as
+// they did with those hands
 #[derive(Clone, Debug)]
 pub struct HandMaker {
     hands: [Cards; 4],
+    observations: Vec<Observation>,
+    plays: Vec<Play>,
+}
+
+// What a play told us, so that it can be taken back if the play is undone.
+#[derive(Copy, Clone, Debug)]
+struct Play {
+    seat: Seat,
+    card: Card,
+    // whether the card was known to be in the hand before it was played
+    known: bool,
+    // how many observations there were before the play
+    observations: usize,
+}
+
+// Things players did that say something about the cards they hold.
+#[derive(Copy, Clone, Debug)]
+enum Observation {
+    // charged the card, which is more likely with cards to protect it
+    Charged { seat: Seat, card: Card },
+    // finished charging without charging these chargeable cards
+    Declined { seat: Seat, cards: Cards },
+    // charged this many cards without revealing them
+    BlindCharged { seat: Seat, count: usize },
+    // passed these cards, seen by the receiver
+    Passed { seat: Seat, cards: Cards },
+    // could have gotten rid of the queen of spades and didn't
+    KeptQueen { seat: Seat },
+}
+
+impl Observation {
+    fn likelihood(self, hands: &[Cards; 4]) -> f64 {
+        match self {
+            Observation::Charged { seat, card } => {
+                let hand = hands[seat.idx()];
+                match card {
+                    Card::QueenSpades | Card::TenClubs => {
+                        match (hand & card.suit().cards()).len().saturating_sub(1) {
+                            0 | 1 => 0.25,
+                            2 => 0.5,
+                            3 => 0.8,
+                            _ => 1.0,
+                        }
+                    }
+                    Card::JackDiamonds if !hand.contains_any(Card::JackDiamonds.above()) => 0.4,
+                    _ => 1.0,
+                }
+            }
+            Observation::Declined { seat, cards } => {
+                let hand = hands[seat.idx()];
+                let protected = [Card::QueenSpades, Card::TenClubs].iter().any(|&card| {
+                    cards.contains(card)
+                        && hand.contains(card)
+                        && (hand & card.suit().cards()).len() > 4
+                });
+                if protected {
+                    0.6
+                } else {
+                    1.0
+                }
+            }
+            Observation::BlindCharged { seat, count } => {
+                // not impossible, since charges can come before the pass
+                if (hands[seat.idx()] & Cards::CHARGEABLE).len() < count {
+                    0.05
+                } else {
+                    1.0
+                }
+            }
+            Observation::Passed { seat, cards } => {
+                // giving away high spades while keeping the queen is rare
+                let high_spades = Card::QueenSpades.above();
+                if cards.contains_any(high_spades)
+                    && !cards.contains(Card::QueenSpades)
+                    && hands[seat.idx()].contains(Card::QueenSpades)
+                {
+                    0.3
+                } else {
+                    1.0
+                }
+            }
+            Observation::KeptQueen { seat } => {
+                if hands[seat.idx()].contains(Card::QueenSpades) {
+                    0.15
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+
+    // the queen could have been played with the given card on the current trick
+    fn kept_queen(state: &GameState, seat: Seat, card: Card) -> Option<Self> {
+        let trick = state.current_trick;
+        if trick.is_empty()
+            || card == Card::QueenSpades
+            || state.played.contains(Card::QueenSpades)
+            || trick.cards().contains(Card::TwoClubs)
+            || (state.charges.is_charged(Card::QueenSpades)
+                && !state.led_suits.contains(Suit::Spades))
+        {
+            return None;
+        }
+        let suit = trick.suit();
+        let ducked_under = suit == Suit::Spades
+            && card.suit() == Suit::Spades
+            && (trick.cards() & Cards::SPADES).max() > Card::QueenSpades;
+        let discarded = suit != Suit::Spades && card.suit() != suit;
+        if ducked_under || discarded {
+            Some(Observation::KeptQueen { seat })
+        } else {
+            None
+        }
+    }
 }
 
 impl HandMaker {
+    // how many deals are weighed against each other for each one made
+    const SAMPLES: usize = 8;
+
     pub fn new() -> Self {
         Self {
             hands: [Cards::NONE; 4],
+            observations: Vec::new(),
+            plays: Vec::new(),
         }
     }
 
@@ -34,8 +156,35 @@ impl HandMaker {
                     self.hands[recv.idx()] |= *cards;
                 }
             }
-            GameEvent::RecvPass { to, cards } => self.hands[to.idx()] |= *cards,
-            GameEvent::Charge { seat, cards } => self.hands[seat.idx()] |= *cards,
+            GameEvent::RecvPass { to, cards } => {
+                self.hands[to.idx()] |= *cards;
+                let from = state.phase.pass_sender(*to);
+                if from != *to {
+                    self.observations.push(Observation::Passed {
+                        seat: from,
+                        cards: *cards,
+                    });
+                }
+            }
+            GameEvent::Charge { seat, cards } => {
+                self.hands[seat.idx()] |= *cards;
+                if cards.is_empty() {
+                    self.observations.push(Observation::Declined {
+                        seat: *seat,
+                        cards: state.rule_set.chargeable() - state.charges.all_charges(),
+                    });
+                }
+                for card in *cards {
+                    self.observations
+                        .push(Observation::Charged { seat: *seat, card });
+                }
+            }
+            GameEvent::BlindCharge { seat, count } => {
+                self.observations.push(Observation::BlindCharged {
+                    seat: *seat,
+                    count: *count,
+                });
+            }
             GameEvent::RevealCharges {
                 north,
                 east,
@@ -47,8 +196,32 @@ impl HandMaker {
                 self.hands[2] |= *south;
                 self.hands[3] |= *west;
             }
-            GameEvent::Play { seat, card } => self.hands[seat.idx()] |= *card,
-            GameEvent::HandComplete { .. } => self.hands = [Cards::NONE; 4],
+            GameEvent::Play { seat, card } => {
+                self.plays.push(Play {
+                    seat: *seat,
+                    card: *card,
+                    known: self.hands[seat.idx()].contains(*card),
+                    observations: self.observations.len(),
+                });
+                self.hands[seat.idx()] |= *card;
+                if let Some(observation) = Observation::kept_queen(state, *seat, *card) {
+                    self.observations.push(observation);
+                }
+            }
+            GameEvent::Undo { seat, card, .. } => {
+                if let Some(play) = self.plays.pop() {
+                    debug_assert!(play.seat == *seat && play.card == *card);
+                    if !play.known {
+                        self.hands[seat.idx()] -= *card;
+                    }
+                    self.observations.truncate(play.observations);
+                }
+            }
+            GameEvent::HandComplete { .. } => {
+                self.hands = [Cards::NONE; 4];
+                self.observations.clear();
+                self.plays.clear();
+            }
             _ => {}
         }
     }
@@ -57,46 +230,275 @@ impl HandMaker {
         self.hands[seat.idx()]
     }
 
+    // relative to any other deal
+    pub fn likelihood(&self, hands: &[Cards; 4]) -> f64 {
+        self.observations
+            .iter()
+            .map(|observation| observation.likelihood(hands))
+            .product()
+    }
+
+    // picks among a few uniform deals in proportion to their likelihood
     pub fn make(&self, void: VoidState) -> [Cards; 4] {
-        let mut copy = self.clone();
-        let cards = Cards::ALL - self.hands[0] - self.hands[1] - self.hands[2] - self.hands[3];
+        if self.observations.is_empty() {
+            return self.make_uniform(void);
+        }
+        let mut deals = Vec::with_capacity(Self::SAMPLES);
+        let mut total = 0.0;
+        for _ in 0..Self::SAMPLES {
+            let hands = self.make_uniform(void);
+            let likelihood = self.likelihood(&hands);
+            total += likelihood;
+            deals.push((hands, likelihood));
+        }
+        let mut choice = rng().gen_range(0.0..total);
+        for &(hands, likelihood) in &deals {
+            if choice < likelihood {
+                return hands;
+            }
+            choice -= likelihood;
+        }
+        deals.last().unwrap().0
+    }
+
+    fn make_uniform(&self, void: VoidState) -> [Cards; 4] {
+        let mut hands = self.hands;
+        let cards = Cards::ALL - hands[0] - hands[1] - hands[2] - hands[3];
         let mut shuffled = cards.into_iter().collect::<Vec<_>>();
-        shuffled.shuffle(&mut rand::thread_rng());
+        shuffled.shuffle(&mut rng());
         assert!(
-            copy.assign(void, &mut shuffled, cards),
+            assign(&mut hands, void, &mut shuffled, cards),
             "failed hand assignment for {:?}",
             self
         );
-        copy.hands
+        hands
     }
+}
 
-    fn assign(&mut self, void: VoidState, shuffled: &mut Vec<Card>, cards: Cards) -> bool {
-        if shuffled.is_empty() {
-            return true;
-        }
-        for &seat in &Seat::VALUES {
-            let mut available = 0;
-            for &suit in &Suit::VALUES {
-                if !void.is_void(seat, suit) {
-                    available += (cards & suit.cards()).len();
-                }
-            }
-            if available + self.hands[seat.idx()].len() < 13 {
-                return false;
+fn assign(hands: &mut [Cards; 4], void: VoidState, shuffled: &mut Vec<Card>, cards: Cards) -> bool {
+    if shuffled.is_empty() {
+        return true;
+    }
+    for &seat in &Seat::VALUES {
+        let mut available = 0;
+        for &suit in &Suit::VALUES {
+            if !void.is_void(seat, suit) {
+                available += (cards & suit.cards()).len();
             }
         }
-        let card = shuffled.pop().unwrap();
-        for &seat in &Seat::VALUES {
-            if self.hands[seat.idx()].len() == 13 || void.is_void(seat, card.suit()) {
-                continue;
-            }
-            self.hands[seat.idx()] |= card;
-            if self.assign(void, shuffled, cards - card) {
-                return true;
-            }
-            self.hands[seat.idx()] -= card;
+        if available + hands[seat.idx()].len() < 13 {
+            return false;
+        }
+    }
+    let card = shuffled.pop().unwrap();
+    for &seat in &Seat::VALUES {
+        if hands[seat.idx()].len() == 13 || void.is_void(seat, card.suit()) {
+            continue;
+        }
+        hands[seat.idx()] |= card;
+        if assign(hands, void, shuffled, cards - card) {
+            return true;
         }
-        shuffled.push(card);
-        false
+        hands[seat.idx()] -= card;
+    }
+    shuffled.push(card);
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use turbo_hearts_api::{GamePhase, PassDirection, Trick};
+
+    const DEALS: usize = 4000;
+
+    fn dealt(north: Cards) -> HandMaker {
+        let mut hand_maker = HandMaker::new();
+        hand_maker.on_event(
+            &GameState::new(),
+            &GameEvent::Deal {
+                north,
+                east: Cards::NONE,
+                south: Cards::NONE,
+                west: Cards::NONE,
+                pass: PassDirection::Left,
+            },
+        );
+        hand_maker
+    }
+
+    // the average of `f` over weighted and uniform deals
+    fn averages(hand_maker: &HandMaker, f: impl Fn(&[Cards; 4]) -> usize) -> (f64, f64) {
+        let average = |make: &dyn Fn() -> [Cards; 4]| {
+            (0..DEALS).map(|_| f(&make())).sum::<usize>() as f64 / DEALS as f64
+        };
+        (
+            average(&|| hand_maker.make(VoidState::new())),
+            average(&|| hand_maker.make_uniform(VoidState::new())),
+        )
+    }
+
+    #[test]
+    fn test_kept_queen() {
+        let north = "KJ2S AKQJH AKQJD AKC".parse::<Cards>().unwrap();
+        let mut hand_maker = HandMaker::new();
+        let mut state = GameState::new();
+        hand_maker.on_event(
+            &state,
+            &GameEvent::Deal {
+                north,
+                east: Cards::NONE,
+                south: Cards::NONE,
+                west: Cards::NONE,
+                pass: PassDirection::Keeper,
+            },
+        );
+        state.led_suits |= Suit::Clubs;
+        state.played = Card::KingSpades.into();
+        state.current_trick = Trick::new().push(Card::KingSpades);
+        hand_maker.on_event(
+            &state,
+            &GameEvent::Play {
+                seat: Seat::East,
+                card: Card::FiveSpades,
+            },
+        );
+
+        let deals = 400;
+        let held = (0..deals)
+            .filter(|_| {
+                let hands = hand_maker.make(VoidState::new());
+                assert!(hands[0] == north && hands[1].contains(Card::FiveSpades));
+                hands[1].contains(Card::QueenSpades)
+            })
+            .count();
+        // east would hold the queen in about a third of uniform deals
+        assert!(held * 5 < deals, "east held the queen in {} deals", held);
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut hand_maker = dealt("KJ2S AKQJH AKQJD AKC".parse().unwrap());
+        let mut state = GameState::new();
+        state.led_suits |= Suit::Clubs;
+        state.played = Card::KingSpades.into();
+        state.current_trick = Trick::new().push(Card::KingSpades);
+        let undo = GameEvent::Undo {
+            seat: Seat::East,
+            card: Card::FiveSpades,
+            state: state.clone(),
+        };
+        hand_maker.on_event(
+            &state,
+            &GameEvent::Play {
+                seat: Seat::East,
+                card: Card::FiveSpades,
+            },
+        );
+        assert_eq!(hand_maker.observations.len(), 1);
+        hand_maker.on_event(&state, &undo);
+        assert!(hand_maker.observations.is_empty());
+        assert!(!hand_maker
+            .known_cards(Seat::East)
+            .contains(Card::FiveSpades));
+    }
+
+    #[test]
+    fn test_charged() {
+        let mut hand_maker = dealt("AKQJT98765432H".parse().unwrap());
+        hand_maker.on_event(
+            &GameState::new(),
+            &GameEvent::Charge {
+                seat: Seat::East,
+                cards: Card::QueenSpades.into(),
+            },
+        );
+        let (weighted, uniform) = averages(&hand_maker, |hands| (hands[1] & Cards::SPADES).len());
+        assert!(
+            weighted > uniform + 0.1,
+            "east held {} spades, {} uniformly",
+            weighted,
+            uniform
+        );
+    }
+
+    #[test]
+    fn test_declined() {
+        let mut hand_maker = dealt("AKQJT98765432H".parse().unwrap());
+        hand_maker.on_event(
+            &GameState::new(),
+            &GameEvent::Charge {
+                seat: Seat::East,
+                cards: Cards::NONE,
+            },
+        );
+        let (weighted, uniform) = averages(&hand_maker, |hands| {
+            let protected =
+                hands[1].contains(Card::QueenSpades) && (hands[1] & Cards::SPADES).len() > 4;
+            protected as usize
+        });
+        assert!(
+            weighted < uniform - 0.01,
+            "east held the protected queen in {} of deals, {} uniformly",
+            weighted,
+            uniform
+        );
+    }
+
+    #[test]
+    fn test_blind_charged() {
+        let mut hand_maker = dealt("AKQJT98765432H".parse().unwrap());
+        hand_maker.on_event(
+            &GameState::new(),
+            &GameEvent::BlindCharge {
+                seat: Seat::East,
+                count: 2,
+            },
+        );
+        let (weighted, uniform) =
+            averages(&hand_maker, |hands| (hands[1] & Cards::CHARGEABLE).len());
+        assert!(
+            weighted > uniform + 0.3,
+            "east held {} chargeable cards, {} uniformly",
+            weighted,
+            uniform
+        );
+    }
+
+    #[test]
+    fn test_passed() {
+        let north = "AKQJT98765432H".parse::<Cards>().unwrap();
+        let sent = "AKQH".parse::<Cards>().unwrap();
+        let received = "AK2S".parse::<Cards>().unwrap();
+        let mut hand_maker = dealt(north);
+        let mut state = GameState::new();
+        state.phase = GamePhase::PassLeft;
+        hand_maker.on_event(
+            &state,
+            &GameEvent::SendPass {
+                from: Seat::North,
+                cards: sent,
+            },
+        );
+        hand_maker.on_event(
+            &state,
+            &GameEvent::RecvPass {
+                to: Seat::North,
+                cards: received,
+            },
+        );
+        assert_eq!(
+            hand_maker.known_cards(Seat::North),
+            (north - sent) | received
+        );
+        let (weighted, uniform) = averages(&hand_maker, |hands| {
+            hands[3].contains(Card::QueenSpades) as usize
+        });
+        assert!(
+            weighted < uniform - 0.1,
+            "west held the queen in {} of deals, {} uniformly",
+            weighted,
+            uniform
+        );
     }
 }
@@ -1,4 +1,4 @@
-use crate::Algorithm;
+use crate::{rng, Algorithm};
 use rand::Rng;
 use turbo_hearts_api::{BotState, Card, Cards, GameEvent, GameState, Rank, Suit};
 
@@ -399,7 +399,8 @@ impl Algorithm for HeuristicBot {
 
     fn charge(&mut self, bot_state: &BotState, game_state: &GameState) -> Cards {
         let hand = bot_state.post_pass_hand;
-        let chargeable = hand - game_state.charges.all_charges();
+        let chargeable =
+            (hand & game_state.rule_set.chargeable()) - game_state.charges.all_charges();
         let mut charge = Cards::NONE;
         if chargeable.contains(Card::QueenSpades) {
             if should_real_charge_queen(hand) || should_fake_charge_queen(hand) {
@@ -448,7 +449,7 @@ impl Algorithm for HeuristicBot {
         } else {
             self.slough(ours, theirs, bot_state, game_state)
         };
-        let index = rand::thread_rng().gen_range(0..good_plays.len());
+        let index = rng().gen_range(0..good_plays.len());
         good_plays.into_iter().nth(index).unwrap()
     }
 
@@ -479,7 +480,7 @@ fn should_fake_charge_queen(hand: Cards) -> bool {
     {
         false
     } else {
-        rand::thread_rng().gen_bool(0.1)
+        rng().gen_bool(0.1)
     }
 }
 
@@ -515,7 +516,7 @@ fn should_fake_charge_ten(hand: Cards) -> bool {
     if spades.below(Card::QueenSpades).len() <= 3 && !spades.above(Card::JackSpades).is_empty() {
         return false;
     }
-    rand::thread_rng().gen_bool(0.5)
+    rng().gen_bool(0.5)
 }
 
 fn should_real_charge_ace(hand: Cards) -> bool {
@@ -530,7 +531,7 @@ fn should_fake_charge_ace(hand: Cards) -> bool {
         || (hand & Cards::DIAMONDS - Card::NineDiamonds).is_empty()
         || (hand & Cards::SPADES - Card::NineSpades).is_empty()
     {
-        rand::thread_rng().gen_bool(0.25)
+        rng().gen_bool(0.25)
     } else {
         false
     }
@@ -0,0 +1,307 @@
+use crate::{
+    neural_network::{choose, load_policies, policy_plays},
+    rng,
+    simulate::do_plays,
+    Algorithm, Budget, Effort, HandMaker, MonteCarlo,
+};
+use log::debug;
+use rand::Rng;
+use std::time::{Duration, Instant};
+use turbo_hearts_api::{BotOptions, BotState, Card, Cards, GameEvent, GameState, Seat, VoidState};
+
+// plays out the hand once the search leaves the tree
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Rollout {
+    Heuristic,
+    NeuralNet,
+}
+
+impl Rollout {
+    pub(crate) fn play_out(self, game: &mut GameState, void: VoidState, hands: [Cards; 4]) {
+        match self {
+            Rollout::Heuristic => do_plays(game, void, hands),
+            Rollout::NeuralNet => policy_plays(game, hands),
+        }
+    }
+}
+
+// information set MCTS: one tree shared by every deal of the unseen cards, with
+// UCT choosing among the plays legal in that deal
+#[derive(Clone)]
+pub struct IsmctsBot {
+    hand_maker: HandMaker,
+    monte_carlo: MonteCarlo,
+    effort: Effort,
+    rollout: Rollout,
+}
+
+impl IsmctsBot {
+    pub const DEFAULT_BUDGET: Budget = Budget::time(Duration::from_millis(3500));
+
+    // in units of money, since that's what node rewards are measured in
+    const EXPLORATION: f32 = 25.0;
+
+    pub fn new() -> Self {
+        Self::with_options(BotOptions::default(), Rollout::Heuristic)
+    }
+
+    pub fn with_options(options: BotOptions, rollout: Rollout) -> Self {
+        if rollout == Rollout::NeuralNet {
+            load_policies();
+        }
+        Self {
+            hand_maker: HandMaker::new(),
+            monte_carlo: MonteCarlo::new(
+                Effort::new(MonteCarlo::DEFAULT_BUDGET, options).budget(),
+                rollout,
+            ),
+            effort: Effort::new(Self::DEFAULT_BUDGET, options),
+            rollout,
+        }
+    }
+}
+
+impl Default for IsmctsBot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Algorithm for IsmctsBot {
+    fn pass(&mut self, bot_state: &BotState, game_state: &GameState) -> Cards {
+        self.monte_carlo
+            .pass(&self.hand_maker, bot_state, game_state)
+    }
+
+    fn charge(&mut self, bot_state: &BotState, game_state: &GameState) -> Cards {
+        self.monte_carlo
+            .charge(&self.hand_maker, bot_state, game_state)
+    }
+
+    fn play(&mut self, bot_state: &BotState, game_state: &GameState) -> Card {
+        let legal_plays = game_state.legal_plays(bot_state.post_pass_hand);
+        let distinct_plays =
+            legal_plays.distinct_plays(game_state.played, game_state.current_trick);
+        if distinct_plays.len() == 1 {
+            return choose(bot_state, distinct_plays.max(), legal_plays, distinct_plays);
+        }
+        if let Some(card) = self.effort.blunder(distinct_plays) {
+            return choose(bot_state, card, legal_plays, distinct_plays);
+        }
+        let mut tree = Tree::new(bot_state.seat, self.effort.depth());
+        let mut iters = 0;
+        let now = Instant::now();
+        while !self.effort.budget().exhausted(now, iters) {
+            iters += 1;
+            let hands = self.hand_maker.make(bot_state.void);
+            tree.iterate(game_state.clone(), bot_state.void, hands, self.rollout);
+        }
+        let card = tree.best_play();
+        debug!(
+            "{} iterations, {} nodes, playing {}",
+            iters,
+            tree.nodes.len(),
+            card
+        );
+        choose(bot_state, card, legal_plays, distinct_plays)
+    }
+
+    fn on_event(&mut self, _: &BotState, game_state: &GameState, event: &GameEvent) {
+        self.hand_maker.on_event(game_state, event);
+    }
+}
+
+struct Node {
+    // the seat that made the play leading here, and the play
+    seat: Seat,
+    card: Card,
+    children: Vec<usize>,
+    visits: u32,
+    // how many times this play was possible when its parent was visited
+    available: u32,
+    // total money made by the seat that made the play
+    money: f32,
+}
+
+impl Node {
+    fn uct(&self) -> f32 {
+        self.money / self.visits as f32
+            + IsmctsBot::EXPLORATION * ((self.available as f32).ln() / self.visits as f32).sqrt()
+    }
+}
+
+struct Tree {
+    nodes: Vec<Node>,
+    depth: Option<usize>,
+}
+
+impl Tree {
+    const ROOT: usize = 0;
+
+    fn new(seat: Seat, depth: Option<usize>) -> Self {
+        Self {
+            depth,
+            nodes: vec![Node {
+                seat,
+                card: Card::TwoClubs,
+                children: Vec::new(),
+                visits: 0,
+                available: 0,
+                money: 0.0,
+            }],
+        }
+    }
+
+    fn iterate(
+        &mut self,
+        mut game: GameState,
+        mut void: VoidState,
+        hands: [Cards; 4],
+        rollout: Rollout,
+    ) {
+        let mut path = vec![Self::ROOT];
+        let mut node = Self::ROOT;
+        while game.phase.is_playing() && self.depth.is_none_or(|depth| path.len() <= depth) {
+            let seat = game.next_actor.unwrap();
+            let plays = game
+                .legal_plays(hands[seat.idx()])
+                .distinct_plays(game.played, game.current_trick);
+            let mut untried = plays;
+            for i in 0..self.nodes[node].children.len() {
+                let child = self.nodes[node].children[i];
+                let child = &mut self.nodes[child];
+                if plays.contains(child.card) {
+                    child.available += 1;
+                    untried -= child.card;
+                }
+            }
+            let next = if untried.is_empty() {
+                self.select(node, plays)
+            } else {
+                let index = rng().gen_range(0..untried.len());
+                let card = untried.into_iter().nth(index).unwrap();
+                self.expand(node, seat, card)
+            };
+            let event = GameEvent::Play {
+                seat,
+                card: self.nodes[next].card,
+            };
+            void = void.on_event(&game, &event);
+            game.apply(&event);
+            path.push(next);
+            node = next;
+            if !untried.is_empty() {
+                break;
+            }
+        }
+        rollout.play_out(&mut game, void, hands);
+        let scores = game.scores();
+        for &index in &path {
+            let node = &mut self.nodes[index];
+            node.visits += 1;
+            node.money += scores.money(node.seat) as f32;
+        }
+    }
+
+    fn select(&self, node: usize, plays: Cards) -> usize {
+        let mut best = None;
+        let mut best_score = f32::MIN;
+        for &child in &self.nodes[node].children {
+            if !plays.contains(self.nodes[child].card) {
+                continue;
+            }
+            let score = self.nodes[child].uct();
+            if score > best_score {
+                best = Some(child);
+                best_score = score;
+            }
+        }
+        best.unwrap()
+    }
+
+    fn expand(&mut self, node: usize, seat: Seat, card: Card) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            seat,
+            card,
+            children: Vec::new(),
+            visits: 0,
+            available: 1,
+            money: 0.0,
+        });
+        self.nodes[node].children.push(index);
+        index
+    }
+
+    // the most visited play is the most robust choice
+    fn best_play(&self) -> Card {
+        let best = self.nodes[Self::ROOT]
+            .children
+            .iter()
+            .map(|&child| &self.nodes[child])
+            .max_by_key(|child| child.visits)
+            .unwrap();
+        for &child in &self.nodes[Self::ROOT].children {
+            let child = &self.nodes[child];
+            debug!(
+                "{}: visits={}, mean={:.2}",
+                child.card,
+                child.visits,
+                child.money / child.visits as f32
+            );
+        }
+        best.card
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{north_to_play, seeded};
+
+    // north's play after passing left and making or seeing `plays`, starting
+    // with west leading the two of clubs
+    fn play(options: BotOptions, hand: &str, pass: &str, received: &str, plays: &str) -> Card {
+        let mut bot = IsmctsBot::with_options(options, Rollout::Heuristic);
+        let (bot_state, game_state) = north_to_play(&mut bot, hand, pass, received, plays);
+        seeded(options.seed, &game_state, || {
+            bot.play(&bot_state, &game_state)
+        })
+    }
+
+    // north's play to the second card of the first trick
+    fn first_trick(options: BotOptions) -> Card {
+        play(options, "AKQJT9S AKH QJ53C 2D", "AS AKH", "876D", "2C")
+    }
+
+    #[test]
+    fn test_play() {
+        // the queen would win this spade trick, while ducking leaves north void
+        // in every other suit to throw it away later
+        let card = play(
+            BotOptions {
+                iterations: Some(200),
+                ..BotOptions::default()
+            },
+            "Q43S A6432H 54D KQTC",
+            "AH KTC",
+            "AJTS",
+            "2C QC 6C JC  4D 3D 7D 6D  KS 7S TS 8S  5C AC 3H TC  TH 2H AH KH  4C 9H 3C 4H \
+             5H JH 9C 6H  8H 9S AS 6S  7H 9D 5D KC  QH 8D JS 2D  5S 2S",
+        );
+        assert_ne!(card, Card::QueenSpades);
+    }
+
+    #[test]
+    fn test_seeded_play() {
+        let options = BotOptions {
+            iterations: Some(50),
+            seed: Some(7),
+            ..BotOptions::default()
+        };
+        let card = first_trick(options);
+        for _ in 0..3 {
+            assert_eq!(first_trick(options), card);
+        }
+    }
+}
@@ -1,35 +1,75 @@
 mod brute_force;
 mod duck;
+mod effort;
 mod encoder;
 mod gottatry;
 mod hand_maker;
 mod heuristic;
+mod ismcts;
+mod monte_carlo;
 mod neural_network;
+mod parallel;
+#[cfg(any(test, feature = "bench"))]
+mod position;
 mod random;
+mod rng;
 mod simulate;
+mod tournament;
 mod transposition_table;
 
 pub use brute_force::*;
 pub use duck::*;
+pub use effort::*;
 pub use encoder::*;
 pub use gottatry::*;
 pub use hand_maker::*;
 pub use heuristic::*;
+pub use ismcts::*;
+pub use monte_carlo::*;
 pub use neural_network::*;
+#[cfg(any(test, feature = "bench"))]
+pub use position::*;
 pub use random::*;
+pub use rng::*;
 pub use simulate::*;
+pub use tournament::*;
 pub use transposition_table::*;
-use turbo_hearts_api::{BotState, Card, Cards, GameEvent, GameState};
+use turbo_hearts_api::{BotOptions, BotState, BotStrategy, Card, Cards, GameEvent, GameState};
 
 pub enum Bot {
     Duck(DuckBot),
     GottaTry(GottaTryBot),
     Heuristic(HeuristicBot),
+    Ismcts(IsmctsBot),
     NeuralNetwork(NeuralNetworkBot),
     Random(RandomBot),
     Simulate(SimulateBot),
 }
 
+impl Bot {
+    pub fn new(strategy: BotStrategy) -> Self {
+        Self::with_options(strategy, BotOptions::default())
+    }
+
+    // only the search bots make use of the options
+    pub fn with_options(strategy: BotStrategy, options: BotOptions) -> Self {
+        match strategy {
+            BotStrategy::Duck => Bot::Duck(DuckBot),
+            BotStrategy::GottaTry => Bot::GottaTry(GottaTryBot),
+            BotStrategy::Heuristic => Bot::Heuristic(HeuristicBot),
+            BotStrategy::Random => Bot::Random(RandomBot::new()),
+            BotStrategy::Simulate => Bot::Simulate(SimulateBot::with_options(options)),
+            BotStrategy::NeuralNet => Bot::NeuralNetwork(NeuralNetworkBot::with_options(options)),
+            BotStrategy::Ismcts => {
+                Bot::Ismcts(IsmctsBot::with_options(options, Rollout::Heuristic))
+            }
+            BotStrategy::IsmctsNeuralNet => {
+                Bot::Ismcts(IsmctsBot::with_options(options, Rollout::NeuralNet))
+            }
+        }
+    }
+}
+
 pub trait Algorithm {
     fn pass(&mut self, bot_state: &BotState, game_state: &GameState) -> Cards;
     fn charge(&mut self, bot_state: &BotState, game_state: &GameState) -> Cards;
@@ -43,6 +83,7 @@ impl Algorithm for Bot {
             Bot::Duck(bot) => bot.pass(bot_state, game_state),
             Bot::GottaTry(bot) => bot.pass(bot_state, game_state),
             Bot::Heuristic(bot) => bot.pass(bot_state, game_state),
+            Bot::Ismcts(bot) => bot.pass(bot_state, game_state),
             Bot::NeuralNetwork(bot) => bot.pass(bot_state, game_state),
             Bot::Random(bot) => bot.pass(bot_state, game_state),
             Bot::Simulate(bot) => bot.pass(bot_state, game_state),
@@ -54,6 +95,7 @@ impl Algorithm for Bot {
             Bot::Duck(bot) => bot.charge(bot_state, game_state),
             Bot::GottaTry(bot) => bot.charge(bot_state, game_state),
             Bot::Heuristic(bot) => bot.charge(bot_state, game_state),
+            Bot::Ismcts(bot) => bot.charge(bot_state, game_state),
             Bot::NeuralNetwork(bot) => bot.charge(bot_state, game_state),
             Bot::Random(bot) => bot.charge(bot_state, game_state),
             Bot::Simulate(bot) => bot.charge(bot_state, game_state),
@@ -65,6 +107,7 @@ impl Algorithm for Bot {
             Bot::Duck(bot) => bot.play(bot_state, game_state),
             Bot::GottaTry(bot) => bot.play(bot_state, game_state),
             Bot::Heuristic(bot) => bot.play(bot_state, game_state),
+            Bot::Ismcts(bot) => bot.play(bot_state, game_state),
             Bot::NeuralNetwork(bot) => bot.play(bot_state, game_state),
             Bot::Random(bot) => bot.play(bot_state, game_state),
             Bot::Simulate(bot) => bot.play(bot_state, game_state),
@@ -76,6 +119,7 @@ impl Algorithm for Bot {
             Bot::Duck(bot) => bot.on_event(bot_state, game_state, event),
             Bot::GottaTry(bot) => bot.on_event(bot_state, game_state, event),
             Bot::Heuristic(bot) => bot.on_event(bot_state, game_state, event),
+            Bot::Ismcts(bot) => bot.on_event(bot_state, game_state, event),
             Bot::NeuralNetwork(bot) => bot.on_event(bot_state, game_state, event),
             Bot::Random(bot) => bot.on_event(bot_state, game_state, event),
             Bot::Simulate(bot) => bot.on_event(bot_state, game_state, event),
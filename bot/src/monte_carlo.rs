@@ -0,0 +1,309 @@
+use crate::{
+    parallel, simulate::compute_best, Algorithm, Budget, HandMaker, HeuristicBot, Rollout,
+};
+use log::debug;
+use std::{collections::HashMap, time::Duration};
+use turbo_hearts_api::{
+    BotState, Card, Cards, GameEvent, GamePhase, GameState, Seat, Suit, VoidState,
+};
+
+// scores each pass or charge by playing out many deals of the unseen cards
+#[derive(Clone, Debug)]
+pub struct MonteCarlo {
+    budget: Budget,
+    rollout: Rollout,
+}
+
+impl MonteCarlo {
+    pub const DEFAULT_BUDGET: Budget = Budget::time(Duration::from_millis(3000));
+
+    pub fn new(budget: Budget, rollout: Rollout) -> Self {
+        Self { budget, rollout }
+    }
+
+    pub fn pass(
+        &self,
+        hand_maker: &HandMaker,
+        bot_state: &BotState,
+        game_state: &GameState,
+    ) -> Cards {
+        let heuristic = HeuristicBot.pass(bot_state, game_state);
+        // the keeper pass is dealt from the middle, so there's nothing to search
+        if game_state.phase == GamePhase::PassKeeper {
+            return heuristic;
+        }
+        let candidates = pass_candidates(bot_state.pre_pass_hand, heuristic);
+        let (money_counts, iters) = parallel::search(
+            self.budget,
+            hand_maker,
+            bot_state.void,
+            |money_counts: &mut HashMap<_, _>, hands, _| {
+                let mut passes = [Cards::NONE; 4];
+                for &seat in &Seat::VALUES {
+                    if seat != bot_state.seat {
+                        passes[seat.idx()] =
+                            HeuristicBot.pass(&BotState::new(seat, hands[seat.idx()]), game_state);
+                    }
+                }
+                for &pass in &candidates {
+                    passes[bot_state.seat.idx()] = pass;
+                    let mut game = game_state.clone();
+                    let mut hands = hands;
+                    for &seat in &Seat::VALUES {
+                        let cards = passes[game.phase.pass_sender(seat).idx()];
+                        hands[seat.idx()] = (hands[seat.idx()] - passes[seat.idx()]) | cards;
+                    }
+                    for &seat in &Seat::VALUES {
+                        game.apply(&GameEvent::SendPass {
+                            from: seat,
+                            cards: passes[seat.idx()],
+                        });
+                    }
+                    for &seat in &Seat::VALUES {
+                        let cards = passes[game.phase.pass_sender(seat).idx()];
+                        game.apply(&GameEvent::RecvPass { to: seat, cards });
+                    }
+                    let money = self.play_out(bot_state.seat, &mut game, hands);
+                    *money_counts.entry((pass, money)).or_default() += 1;
+                }
+            },
+        );
+        debug!(
+            "pass: {} candidates, {} iterations",
+            candidates.len(),
+            iters
+        );
+        compute_best(candidates, money_counts)
+    }
+
+    pub fn charge(
+        &self,
+        hand_maker: &HandMaker,
+        bot_state: &BotState,
+        game_state: &GameState,
+    ) -> Cards {
+        // charges before the keeper pass would need the pass played out too
+        if game_state.phase == GamePhase::ChargeKeeper1 {
+            return HeuristicBot.charge(bot_state, game_state);
+        }
+        let chargeable = bot_state.post_pass_hand
+            & (game_state.rule_set.chargeable() - game_state.charges.all_charges());
+        if chargeable.is_empty() {
+            return Cards::NONE;
+        }
+        let candidates = chargeable.powerset().collect::<Vec<_>>();
+        let (money_counts, iters) = parallel::search(
+            self.budget,
+            hand_maker,
+            bot_state.void,
+            |money_counts: &mut HashMap<_, _>, hands, _| {
+                for &charge in &candidates {
+                    let mut game = game_state.clone();
+                    game.apply(&GameEvent::Charge {
+                        seat: bot_state.seat,
+                        cards: charge,
+                    });
+                    let money = self.play_out(bot_state.seat, &mut game, hands);
+                    *money_counts.entry((charge, money)).or_default() += 1;
+                }
+            },
+        );
+        debug!(
+            "charge: {} candidates, {} iterations",
+            candidates.len(),
+            iters
+        );
+        compute_best(candidates, money_counts)
+    }
+
+    // charges for every seat that still can, then plays out the hand, returning
+    // how much money the seat makes
+    fn play_out(&self, seat: Seat, game: &mut GameState, hands: [Cards; 4]) -> i16 {
+        while game.phase.is_charging() {
+            for &charger in &Seat::VALUES {
+                if game.phase.is_charging()
+                    && game.can_charge(charger)
+                    && !game.done.charged(charger)
+                {
+                    let cards =
+                        HeuristicBot.charge(&BotState::new(charger, hands[charger.idx()]), game);
+                    game.apply(&GameEvent::Charge {
+                        seat: charger,
+                        cards,
+                    });
+                }
+            }
+        }
+        if !game.phase.is_playing() {
+            return 0;
+        }
+        game.next_actor = Seat::VALUES
+            .iter()
+            .copied()
+            .find(|s| hands[s.idx()].contains(Card::TwoClubs));
+        self.rollout.play_out(game, VoidState::new(), hands);
+        game.scores().money(seat)
+    }
+}
+
+impl Default for MonteCarlo {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_BUDGET, Rollout::Heuristic)
+    }
+}
+
+// low cards are rarely worth passing, so only the heuristic's pass and the top
+// two of each suit are considered, dropping the lowest extras past nine cards
+fn pass_candidates(hand: Cards, heuristic: Cards) -> Vec<Cards> {
+    let mut pool = heuristic;
+    for &suit in &Suit::VALUES {
+        let cards = hand & suit.cards();
+        for card in cards.into_iter().take(2) {
+            pool |= card;
+        }
+    }
+    while pool.len() > 9 {
+        let lowest = (pool - heuristic)
+            .into_iter()
+            .min_by_key(|card| card.rank())
+            .unwrap();
+        pool -= lowest;
+    }
+    pool.choose(3).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use turbo_hearts_api::{MoonScoring, PassDirection};
+
+    #[test]
+    fn test_pass_and_charge() {
+        let hand = Cards::SPADES.above(Card::FiveSpades)
+            | Card::AceHearts
+            | Card::KingHearts
+            | Card::TenClubs
+            | Card::TwoClubs;
+        let monte_carlo =
+            MonteCarlo::new(Budget::time(Duration::from_millis(50)), Rollout::Heuristic);
+        let mut hand_maker = HandMaker::new();
+        let mut game_state = GameState::new();
+        let deal = GameEvent::Deal {
+            north: hand,
+            east: Cards::NONE,
+            south: Cards::NONE,
+            west: Cards::NONE,
+            pass: PassDirection::Left,
+        };
+        hand_maker.on_event(&game_state, &deal);
+        game_state.apply(&deal);
+        let mut bot_state = BotState::new(Seat::North, hand);
+
+        let pass = monte_carlo.pass(&hand_maker, &bot_state, &game_state);
+        assert_eq!(pass.len(), 3);
+        assert!(hand.contains_all(pass));
+
+        let events = [
+            GameEvent::SendPass {
+                from: Seat::North,
+                cards: pass,
+            },
+            GameEvent::HiddenSendPass {
+                from: Seat::East,
+                count: 3,
+            },
+            GameEvent::HiddenSendPass {
+                from: Seat::South,
+                count: 3,
+            },
+            GameEvent::HiddenSendPass {
+                from: Seat::West,
+                count: 3,
+            },
+            GameEvent::HiddenRecvPass {
+                to: Seat::East,
+                count: 3,
+            },
+            GameEvent::HiddenRecvPass {
+                to: Seat::South,
+                count: 3,
+            },
+            GameEvent::HiddenRecvPass {
+                to: Seat::West,
+                count: 3,
+            },
+        ];
+        for event in &events {
+            hand_maker.on_event(&game_state, event);
+            game_state.apply(event);
+        }
+        let received = (Cards::ALL - hand).into_iter().take(3).collect::<Cards>();
+        let recv = GameEvent::RecvPass {
+            to: Seat::North,
+            cards: received,
+        };
+        hand_maker.on_event(&game_state, &recv);
+        game_state.apply(&recv);
+        assert!(game_state.phase.is_charging());
+        bot_state.post_pass_hand = (hand - pass) | received;
+
+        let charge = monte_carlo.charge(&hand_maker, &bot_state, &game_state);
+        assert!((bot_state.post_pass_hand & Cards::CHARGEABLE).contains_all(charge));
+    }
+
+    #[test]
+    fn test_charge_beats_heuristic() {
+        // north's queen is guarded by every spade below it, so charging it only
+        // costs the others, but the heuristic wants a low card to charge with
+        let hand = Cards::SPADES.below(Card::EightSpades)
+            | Card::JackSpades
+            | Card::QueenSpades
+            | Card::TwoClubs
+            | Card::FiveDiamonds
+            | Card::AceClubs
+            | Card::AceDiamonds
+            | Card::AceHearts;
+        let pass = Card::AceClubs | Card::AceDiamonds | Card::AceHearts;
+        let received = Card::EightSpades | Card::NineSpades | Card::TenSpades;
+        let mut hand_maker = HandMaker::new();
+        let mut game_state = GameState::new();
+        game_state.rule_set.moon = MoonScoring::Disabled;
+        let mut events = vec![
+            GameEvent::Deal {
+                north: hand,
+                east: Cards::NONE,
+                south: Cards::NONE,
+                west: Cards::NONE,
+                pass: PassDirection::Left,
+            },
+            GameEvent::SendPass {
+                from: Seat::North,
+                cards: pass,
+            },
+        ];
+        for &seat in &[Seat::East, Seat::South, Seat::West] {
+            events.push(GameEvent::HiddenSendPass {
+                from: seat,
+                count: 3,
+            });
+            events.push(GameEvent::HiddenRecvPass { to: seat, count: 3 });
+        }
+        events.push(GameEvent::RecvPass {
+            to: Seat::North,
+            cards: received,
+        });
+        for event in &events {
+            hand_maker.on_event(&game_state, event);
+            game_state.apply(event);
+        }
+        let mut bot_state = BotState::new(Seat::North, hand);
+        bot_state.post_pass_hand = (hand - pass) | received;
+
+        assert_eq!(HeuristicBot.charge(&bot_state, &game_state), Cards::NONE);
+        let monte_carlo = MonteCarlo::new(Budget::iterations(200), Rollout::Heuristic);
+        assert_eq!(
+            monte_carlo.charge(&hand_maker, &bot_state, &game_state),
+            Card::QueenSpades.into()
+        );
+    }
+}
@@ -1,8 +1,8 @@
-use crate::{Algorithm, Encoder, HandMaker, HeuristicBot};
+use crate::{parallel, rng, Algorithm, Budget, Effort, Encoder, HandMaker, MonteCarlo, Rollout};
 use log::debug;
 use once_cell::sync::Lazy;
 use rand::Rng;
-use std::{collections::HashMap, error::Error, sync::Arc, time::Instant};
+use std::{collections::HashMap, error::Error, sync::Arc, time::Duration};
 use tract_onnx::{
     prelude::{
         tract_ndarray::Array2, tvec, Datum, Framework, InferenceFact, InferenceModel,
@@ -11,8 +11,8 @@ use tract_onnx::{
     tract_hir::tract_core::downcast_rs::__std::cmp::Ordering,
 };
 use turbo_hearts_api::{
-    can_claim, BotState, Card, Cards, ChargeState, GameEvent, GameState, Rank, Seat, Suit,
-    VoidState, WonState,
+    can_claim, BotOptions, BotState, Card, Cards, ChargeState, GameEvent, GameState, Rank, RuleSet,
+    Seat, Suit, VoidState, WonState,
 };
 
 static LEAD_POLICY: Lazy<TypedRunnableModel<TypedModel>> =
@@ -74,18 +74,31 @@ fn load_model(lead: bool, policy: bool) -> Result<TypedRunnableModel<TypedModel>
 #[derive(Clone)]
 pub struct NeuralNetworkBot {
     hand_maker: HandMaker,
+    monte_carlo: MonteCarlo,
+    effort: Effort,
     initial_state: GameState,
     plays: Vec<Card>,
 }
 
 impl NeuralNetworkBot {
+    pub const DEFAULT_BUDGET: Budget = Budget::time(Duration::from_millis(4500));
+
     pub fn new() -> Self {
+        Self::with_options(BotOptions::default())
+    }
+
+    pub fn with_options(options: BotOptions) -> Self {
         Lazy::force(&LEAD_POLICY);
         Lazy::force(&LEAD_VALUE);
         Lazy::force(&FOLLOW_POLICY);
         Lazy::force(&FOLLOW_VALUE);
         Self {
             hand_maker: HandMaker::new(),
+            monte_carlo: MonteCarlo::new(
+                Effort::new(MonteCarlo::DEFAULT_BUDGET, options).budget(),
+                Rollout::NeuralNet,
+            ),
+            effort: Effort::new(Self::DEFAULT_BUDGET, options),
             initial_state: GameState::new(),
             plays: Vec::with_capacity(52),
         }
@@ -94,11 +107,13 @@ impl NeuralNetworkBot {
 
 impl Algorithm for NeuralNetworkBot {
     fn pass(&mut self, bot_state: &BotState, game_state: &GameState) -> Cards {
-        HeuristicBot.pass(bot_state, game_state)
+        self.monte_carlo
+            .pass(&self.hand_maker, bot_state, game_state)
     }
 
     fn charge(&mut self, bot_state: &BotState, game_state: &GameState) -> Cards {
-        HeuristicBot.charge(bot_state, game_state)
+        self.monte_carlo
+            .charge(&self.hand_maker, bot_state, game_state)
     }
 
     fn play(&mut self, bot_state: &BotState, game_state: &GameState) -> Card {
@@ -108,23 +123,26 @@ impl Algorithm for NeuralNetworkBot {
         if distinct_plays.len() == 1 {
             return choose(bot_state, distinct_plays.max(), legal_plays, distinct_plays);
         }
-        let mut money_counts = HashMap::new();
-        let mut iters = 0;
-        let now = Instant::now();
-        while now.elapsed().as_millis() < 4500 {
-            iters += 1;
-            let hands = self.hand_maker.make(bot_state.void);
-            let brute_force = ShallowBruteForce::new(hands);
-            for card in distinct_plays {
-                let mut game = game_state.clone();
-                game.apply(&GameEvent::Play {
-                    seat: bot_state.seat,
-                    card,
-                });
-                let scores = brute_force.solve(&mut game);
-                *money_counts.entry(card).or_default() += scores.money(bot_state.seat);
-            }
+        if let Some(card) = self.effort.blunder(distinct_plays) {
+            return choose(bot_state, card, legal_plays, distinct_plays);
         }
+        let (money_counts, iters) = parallel::search(
+            self.effort.budget(),
+            &self.hand_maker,
+            bot_state.void,
+            |money_counts: &mut HashMap<_, _>, hands, _| {
+                let brute_force = ShallowBruteForce::new(hands);
+                for card in distinct_plays {
+                    let mut game = game_state.clone();
+                    game.apply(&GameEvent::Play {
+                        seat: bot_state.seat,
+                        card,
+                    });
+                    let scores = brute_force.solve(&mut game);
+                    *money_counts.entry(card).or_default() += scores.money(bot_state.seat);
+                }
+            },
+        );
         if log::log_enabled!(log::Level::Debug) {
             debug!(
                 "{} iterations, {:?}",
@@ -154,6 +172,9 @@ impl Algorithm for NeuralNetworkBot {
                 self.initial_state.next_actor = Some(*leader);
             }
             GameEvent::Play { card, .. } => self.plays.push(*card),
+            GameEvent::Undo { .. } => {
+                self.plays.pop();
+            }
             GameEvent::HandComplete { .. } => self.plays.clear(),
             _ => {}
         }
@@ -176,14 +197,18 @@ impl ShallowBruteForce {
                 let card = (self.hands[seat.idx()] - state.played).max();
                 state.apply(&GameEvent::Play { seat, card });
             }
-            return ApproximateScores::from_won(state.charges, state.won);
+            return ApproximateScores::from_won(state.charges, state.rule_set, state.won);
         }
         let seat = state.next_actor.unwrap();
         if state.current_trick.is_empty()
             && state.won.can_run(seat)
             && can_claim(state, VoidState::new(), seat, self.hands[seat.idx()])
         {
-            return ApproximateScores::from_won(state.charges, state.won.claim(seat));
+            return ApproximateScores::from_won(
+                state.charges,
+                state.rule_set,
+                state.won.claim(seat),
+            );
         }
         if state.current_trick.is_empty() && state.played.len() < 36 {
             return self.generate_value(state);
@@ -218,6 +243,21 @@ impl ShallowBruteForce {
         if distinct.len() <= 3 {
             return distinct;
         }
+        let mut policies = self.policies(game_state, legal);
+        let mut plays = Cards::NONE;
+        while plays.len() < 3 {
+            let card = policies.pop().unwrap().0;
+            plays |= if distinct.contains(card) {
+                card
+            } else {
+                (card.above() & distinct).min()
+            };
+        }
+        plays
+    }
+
+    // every legal play scored by the policy network, in increasing order of preference
+    fn policies(&self, game_state: &GameState, legal: Cards) -> Vec<(Card, f32)> {
         let input = self.model_input(game_state);
         let model = if game_state.current_trick.is_empty() {
             Lazy::force(&LEAD_POLICY)
@@ -274,16 +314,16 @@ impl ShallowBruteForce {
                 Ordering::Greater
             }
         });
-        let mut plays = Cards::NONE;
-        while plays.len() < 3 {
-            let card = policies.pop().unwrap().0;
-            plays |= if distinct.contains(card) {
-                card
-            } else {
-                (card.above() & distinct).min()
-            };
+        policies
+    }
+
+    fn best_play(&self, game_state: &GameState) -> Card {
+        let seat = game_state.next_actor.unwrap();
+        let legal = game_state.legal_plays(self.hands[seat.idx()]);
+        if legal.len() == 1 {
+            return legal.max();
         }
-        plays
+        self.policies(game_state, legal).pop().unwrap().0
     }
 
     fn generate_value(&self, game_state: &mut GameState) -> ApproximateScores {
@@ -396,8 +436,8 @@ impl ApproximateScores {
         Self { scores: [0.0; 4] }
     }
 
-    fn from_won(charges: ChargeState, won: WonState) -> Self {
-        let scores = won.scores(charges);
+    fn from_won(charges: ChargeState, rule_set: RuleSet, won: WonState) -> Self {
+        let scores = won.scores(charges, rule_set);
         Self {
             scores: [
                 scores.score(Seat::North) as f32,
@@ -449,7 +489,9 @@ impl ApproximateScores {
             qf * queen[2] + hf * hearts[2],
             qf * queen[3] + hf * hearts[3],
         ];
-        let jf = if state.charges.is_charged(Card::JackDiamonds) {
+        let jf = if !state.rule_set.jack_of_diamonds {
+            0.0
+        } else if state.charges.is_charged(Card::JackDiamonds) {
             -20.0
         } else {
             -10.0
@@ -463,7 +505,9 @@ impl ApproximateScores {
             scores[2] += jf * jack[south];
             scores[3] += jf * jack[west];
         };
-        let tf = if state.charges.is_charged(Card::TenClubs) {
+        let tf = if !state.rule_set.ten_of_clubs {
+            0.0
+        } else if state.charges.is_charged(Card::TenClubs) {
             3.0
         } else {
             1.0
@@ -486,7 +530,35 @@ impl ApproximateScores {
     }
 }
 
-fn choose(bot_state: &BotState, card: Card, legal_plays: Cards, distinct_plays: Cards) -> Card {
+pub(crate) fn load_policies() {
+    Lazy::force(&LEAD_POLICY);
+    Lazy::force(&FOLLOW_POLICY);
+}
+
+// plays out the hand with every seat making the policy network's favourite
+// play, claiming whenever a leader can
+pub(crate) fn policy_plays(game: &mut GameState, hands: [Cards; 4]) {
+    let policy = ShallowBruteForce::new(hands);
+    while game.phase.is_playing() {
+        let seat = game.next_actor.unwrap();
+        if game.current_trick.is_empty()
+            && game.won.can_run(seat)
+            && can_claim(game, VoidState::new(), seat, hands[seat.idx()])
+        {
+            game.won = game.won.claim(seat);
+            return;
+        }
+        let card = policy.best_play(game);
+        game.apply(&GameEvent::Play { seat, card });
+    }
+}
+
+pub(crate) fn choose(
+    bot_state: &BotState,
+    card: Card,
+    legal_plays: Cards,
+    distinct_plays: Cards,
+) -> Card {
     let other_plays = distinct_plays.below(card);
     let mut cards = if other_plays.is_empty() {
         legal_plays.below(card) | card
@@ -497,6 +569,6 @@ fn choose(bot_state: &BotState, card: Card, legal_plays: Cards, distinct_plays:
     if !passed.is_empty() {
         cards = passed;
     }
-    let index = rand::thread_rng().gen_range(0..cards.len());
+    let index = rng().gen_range(0..cards.len());
     cards.into_iter().nth(index).unwrap()
 }
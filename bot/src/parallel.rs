@@ -0,0 +1,86 @@
+use crate::{rng, Budget, HandMaker};
+use rayon::prelude::*;
+use std::{collections::HashMap, hash::Hash, ops::AddAssign, time::Instant};
+use turbo_hearts_api::{Cards, VoidState};
+
+// each worker's generator is forked from this thread's, and results are merged
+// in worker order, so a seeded search is reproducible
+pub(crate) fn search<T, F>(
+    budget: Budget,
+    hand_maker: &HandMaker,
+    void: VoidState,
+    step: F,
+) -> (T, u32)
+where
+    T: Merge + Default + Send,
+    F: Fn(&mut T, [Cards; 4], u32) + Sync,
+{
+    let now = Instant::now();
+    let workers = budget.workers(rayon::current_num_threads());
+    let seeds = (0..workers).map(|_| rng::fork()).collect::<Vec<_>>();
+    seeds
+        .into_par_iter()
+        .enumerate()
+        .map(|(worker, seed)| {
+            rng::with_seed(seed, || {
+                let hand_maker = hand_maker.clone();
+                let budget = budget.share(worker, workers);
+                let mut result = T::default();
+                let mut iters = 0;
+                while !budget.exhausted(now, iters) {
+                    let hands = hand_maker.make(void);
+                    step(&mut result, hands, iters);
+                    iters += 1;
+                }
+                (result, iters)
+            })
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .fold(
+            (T::default(), 0),
+            |(mut total, total_iters), (result, iters)| {
+                total.merge(result);
+                (total, total_iters + iters)
+            },
+        )
+}
+
+pub(crate) trait Merge {
+    fn merge(&mut self, other: Self);
+}
+
+impl<K, V> Merge for HashMap<K, V>
+where
+    K: Eq + Hash,
+    V: AddAssign + Default,
+{
+    fn merge(&mut self, other: Self) {
+        for (key, value) in other {
+            *self.entry(key).or_default() += value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rayon::ThreadPoolBuilder;
+
+    #[test]
+    fn test_iterations_split() {
+        let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        for &limit in &[1, 3, 10, 101] {
+            let (counts, iters) = pool.install(|| {
+                search(
+                    Budget::iterations(limit),
+                    &HandMaker::new(),
+                    VoidState::new(),
+                    |counts: &mut HashMap<(), u32>, _, _| *counts.entry(()).or_default() += 1,
+                )
+            });
+            assert_eq!(iters, limit);
+            assert_eq!(counts[&()], limit);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use crate::Algorithm;
+use turbo_hearts_api::{BotState, Cards, GameEvent, GameState, PassDirection, Seat};
+
+// north passes left, nobody charges and plays start from the two of clubs
+pub fn north_to_play<A: Algorithm>(
+    bot: &mut A,
+    hand: &str,
+    pass: &str,
+    received: &str,
+    plays: &str,
+) -> (BotState, GameState) {
+    let hand = hand.parse::<Cards>().unwrap();
+    let pass = pass.parse::<Cards>().unwrap();
+    let received = received.parse::<Cards>().unwrap();
+    let mut bot_state = BotState::new(Seat::North, hand);
+    let mut game_state = GameState::new();
+    let mut events = vec![
+        GameEvent::Deal {
+            north: hand,
+            east: Cards::NONE,
+            south: Cards::NONE,
+            west: Cards::NONE,
+            pass: PassDirection::Left,
+        },
+        GameEvent::SendPass {
+            from: Seat::North,
+            cards: pass,
+        },
+    ];
+    for &seat in &[Seat::East, Seat::South, Seat::West] {
+        events.push(GameEvent::HiddenSendPass {
+            from: seat,
+            count: 3,
+        });
+        events.push(GameEvent::HiddenRecvPass { to: seat, count: 3 });
+    }
+    events.push(GameEvent::RecvPass {
+        to: Seat::North,
+        cards: received,
+    });
+    for &seat in &Seat::VALUES {
+        events.push(GameEvent::Charge {
+            seat,
+            cards: Cards::NONE,
+        });
+    }
+    events.push(GameEvent::StartTrick { leader: Seat::West });
+    for event in &events {
+        bot.on_event(&bot_state, &game_state, event);
+        bot_state.on_event(&game_state, event);
+        game_state.apply(event);
+    }
+    for card in plays.split_whitespace() {
+        let event = GameEvent::Play {
+            seat: game_state.next_actor.unwrap_or(Seat::West),
+            card: card.parse().unwrap(),
+        };
+        bot.on_event(&bot_state, &game_state, &event);
+        bot_state.on_event(&game_state, &event);
+        game_state.apply(&event);
+    }
+    bot_state.post_pass_hand = (hand - pass) | received;
+    assert!(game_state.phase.is_playing());
+    assert_eq!(game_state.next_actor, Some(Seat::North));
+    (bot_state, game_state)
+}
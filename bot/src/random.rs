@@ -1,4 +1,4 @@
-use crate::Algorithm;
+use crate::{rng, Algorithm};
 use rand::{seq::SliceRandom, Rng};
 use turbo_hearts_api::{BotState, Card, Cards, GameEvent, GameState};
 
@@ -15,7 +15,7 @@ impl RandomBot {
 impl Algorithm for RandomBot {
     fn pass(&mut self, bot_state: &BotState, _: &GameState) -> Cards {
         let mut hand = bot_state.pre_pass_hand.into_iter().collect::<Vec<_>>();
-        hand.partial_shuffle(&mut rand::thread_rng(), 3);
+        hand.partial_shuffle(&mut rng(), 3);
         hand.into_iter().take(3).collect()
     }
 
@@ -23,17 +23,14 @@ impl Algorithm for RandomBot {
         if self.charged {
             return Cards::NONE;
         }
-        let cards =
-            (bot_state.post_pass_hand & Cards::CHARGEABLE) - game_state.charges.all_charges();
-        cards
-            .into_iter()
-            .filter(|_| rand::thread_rng().gen())
-            .collect()
+        let cards = (bot_state.post_pass_hand & game_state.rule_set.chargeable())
+            - game_state.charges.all_charges();
+        cards.into_iter().filter(|_| rng().gen()).collect()
     }
 
     fn play(&mut self, bot_state: &BotState, game_state: &GameState) -> Card {
         let cards = game_state.legal_plays(bot_state.post_pass_hand);
-        let index = rand::thread_rng().gen_range(0..cards.len());
+        let index = rng().gen_range(0..cards.len());
         cards.into_iter().nth(index).unwrap()
     }
 
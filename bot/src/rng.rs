@@ -0,0 +1,87 @@
+use rand::{Error, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use turbo_hearts_api::GameState;
+
+thread_local! {
+    static SEEDED: RefCell<Option<ChaCha20Rng>> = const { RefCell::new(None) };
+}
+
+// with a seed, the random choices f makes are drawn from a generator seeded by
+// it and the position, so the same position always gets the same decision
+pub fn seeded<T, F>(seed: Option<u64>, game_state: &GameState, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    match seed {
+        Some(seed) => {
+            let digest = Sha256::new()
+                .chain(seed.to_le_bytes())
+                .chain(game_state.hand.to_le_bytes())
+                .chain(game_state.played.bits.to_le_bytes())
+                .finalize();
+            with_seed(Some(digest_seed(&digest)), f)
+        }
+        None => f(),
+    }
+}
+
+pub(crate) fn seed_from_str(seed: &str) -> u64 {
+    digest_seed(&Sha256::digest(seed.as_bytes()))
+}
+
+fn digest_seed(digest: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+// for work handed off to another thread, None unless this thread is seeded
+pub(crate) fn fork() -> Option<u64> {
+    SEEDED.with(|seeded| seeded.borrow_mut().as_mut().map(|rng| rng.next_u64()))
+}
+
+pub(crate) fn with_seed<T, F>(seed: Option<u64>, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let rng = seed.map(ChaCha20Rng::seed_from_u64);
+    let previous = SEEDED.with(|seeded| seeded.replace(rng));
+    let result = f();
+    SEEDED.with(|seeded| seeded.replace(previous));
+    result
+}
+
+pub(crate) fn rng() -> BotRng {
+    BotRng
+}
+
+pub(crate) struct BotRng;
+
+impl BotRng {
+    fn with<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+        SEEDED.with(|seeded| match &mut *seeded.borrow_mut() {
+            Some(rng) => f(rng),
+            None => f(&mut rand::thread_rng()),
+        })
+    }
+}
+
+impl RngCore for BotRng {
+    fn next_u32(&mut self) -> u32 {
+        Self::with(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        Self::with(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        Self::with(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        Self::with(|rng| rng.try_fill_bytes(dest))
+    }
+}
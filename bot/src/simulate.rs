@@ -1,28 +1,47 @@
-use crate::{Algorithm, BruteForce, HandMaker, HeuristicBot};
+use crate::{
+    parallel, Algorithm, BruteForce, Budget, Effort, HandMaker, HeuristicBot, MonteCarlo, Rollout,
+};
 use log::debug;
-use std::{collections::HashMap, fmt::Display, hash::Hash, time::Instant};
-use turbo_hearts_api::{can_claim, BotState, Card, Cards, GameEvent, GameState, VoidState};
+use std::{collections::HashMap, fmt::Display, hash::Hash, time::Duration};
+use turbo_hearts_api::{
+    can_claim, BotOptions, BotState, Card, Cards, GameEvent, GameState, VoidState,
+};
 
 #[derive(Clone)]
 pub struct SimulateBot {
     hand_maker: HandMaker,
+    monte_carlo: MonteCarlo,
+    effort: Effort,
 }
 
 impl SimulateBot {
+    pub const DEFAULT_BUDGET: Budget = Budget::time(Duration::from_millis(3500));
+
     pub fn new() -> Self {
+        Self::with_options(BotOptions::default())
+    }
+
+    pub fn with_options(options: BotOptions) -> Self {
         Self {
             hand_maker: HandMaker::new(),
+            monte_carlo: MonteCarlo::new(
+                Effort::new(MonteCarlo::DEFAULT_BUDGET, options).budget(),
+                Rollout::Heuristic,
+            ),
+            effort: Effort::new(Self::DEFAULT_BUDGET, options),
         }
     }
 }
 
 impl Algorithm for SimulateBot {
     fn pass(&mut self, bot_state: &BotState, game_state: &GameState) -> Cards {
-        HeuristicBot.pass(bot_state, game_state)
+        self.monte_carlo
+            .pass(&self.hand_maker, bot_state, game_state)
     }
 
     fn charge(&mut self, bot_state: &BotState, game_state: &GameState) -> Cards {
-        HeuristicBot.charge(bot_state, game_state)
+        self.monte_carlo
+            .charge(&self.hand_maker, bot_state, game_state)
     }
 
     fn play(&mut self, bot_state: &BotState, game_state: &GameState) -> Card {
@@ -30,36 +49,49 @@ impl Algorithm for SimulateBot {
         if cards.contains(Card::TwoClubs) {
             return Card::TwoClubs;
         }
-        let mut money_counts = HashMap::new();
-        let now = Instant::now();
-        let mut iter = 0;
-        while now.elapsed().as_millis() < 3500 {
-            let hands = self.hand_maker.make(bot_state.void);
-            for card in cards {
-                let event = GameEvent::Play {
-                    seat: bot_state.seat,
-                    card,
-                };
-                let void = bot_state.void.on_event(&game_state, &event);
-                let mut game = game_state.clone();
-                game.apply(&event);
-                if game.played.len() > 28 && iter > 0 {
-                    let mut brute_force = BruteForce::new(hands);
-                    let won = brute_force.solve(&mut game);
-                    *money_counts
-                        .entry((card, won.scores(game.charges).money(bot_state.seat)))
-                        .or_default() += 1;
-                } else {
-                    for _ in 0..50 {
-                        let mut game = game.clone();
-                        do_plays(&mut game, void, hands);
-                        let money = money(&bot_state, &game);
-                        *money_counts.entry((card, money)).or_default() += 1;
-                    }
-                };
-            }
-            iter += 1;
+        if let Some(card) = self.effort.blunder(cards) {
+            return card;
         }
+        // the endgame is solved exactly unless that would look too far ahead
+        let solve_after = match self.effort.depth() {
+            Some(depth) => 28.max(52 - depth),
+            None => 28,
+        };
+        let (money_counts, iters) = parallel::search(
+            self.effort.budget(),
+            &self.hand_maker,
+            bot_state.void,
+            |money_counts: &mut HashMap<_, _>, hands, iter| {
+                for card in cards {
+                    let event = GameEvent::Play {
+                        seat: bot_state.seat,
+                        card,
+                    };
+                    let void = bot_state.void.on_event(&game_state, &event);
+                    let mut game = game_state.clone();
+                    game.apply(&event);
+                    if game.played.len() > solve_after && iter > 0 {
+                        let mut brute_force = BruteForce::new(hands);
+                        let won = brute_force.solve(&mut game);
+                        *money_counts
+                            .entry((
+                                card,
+                                won.scores(game.charges, game.rule_set)
+                                    .money(bot_state.seat),
+                            ))
+                            .or_default() += 1;
+                    } else {
+                        for _ in 0..50 {
+                            let mut game = game.clone();
+                            do_plays(&mut game, void, hands);
+                            let money = money(&bot_state, &game);
+                            *money_counts.entry((card, money)).or_default() += 1;
+                        }
+                    };
+                }
+            },
+        );
+        debug!("{} iterations", iters);
         compute_best(cards, money_counts)
     }
 
@@ -68,7 +100,7 @@ impl Algorithm for SimulateBot {
     }
 }
 
-fn do_plays(game: &mut GameState, mut void: VoidState, hands: [Cards; 4]) {
+pub(crate) fn do_plays(game: &mut GameState, mut void: VoidState, hands: [Cards; 4]) {
     while game.phase.is_playing() {
         let seat = game.next_actor.unwrap();
         if game.current_trick.is_empty()
@@ -90,7 +122,7 @@ fn money(bot_state: &BotState, game: &GameState) -> i16 {
     scores.money(bot_state.seat)
 }
 
-fn compute_best<T, I>(choices: I, money_counts: HashMap<(T, i16), u32>) -> T
+pub(crate) fn compute_best<T, I>(choices: I, money_counts: HashMap<(T, i16), u32>) -> T
 where
     T: Copy + Display + Eq + Hash,
     I: IntoIterator<Item = T>,
@@ -0,0 +1,428 @@
+use crate::{rng, Algorithm, Bot};
+use serde::{Deserialize, Serialize};
+use turbo_hearts_api::{
+    BotOptions, BotState, BotStrategy, Cards, ChargingRules, Game, GameEvent, GameId, GamePhase,
+    GameState, PassDirection, Player, RulesError, Seat, Seed, UserId,
+};
+
+// every strategy sits in every seat, and with more than four every pair meets
+// equally often
+pub fn lineups(strategies: &[BotStrategy]) -> Vec<[BotStrategy; 4]> {
+    fn rotations(strategies: &[BotStrategy], lineups: &mut Vec<[BotStrategy; 4]>) {
+        let n = strategies.len();
+        for r in 0..n {
+            lineups.push([
+                strategies[r % n],
+                strategies[(r + 1) % n],
+                strategies[(r + 2) % n],
+                strategies[(r + 3) % n],
+            ]);
+        }
+    }
+
+    let mut lineups = Vec::new();
+    let n = strategies.len();
+    if n <= 4 {
+        rotations(strategies, &mut lineups);
+    } else {
+        for a in 0..n {
+            for b in a + 1..n {
+                for c in b + 1..n {
+                    for d in c + 1..n {
+                        rotations(
+                            &[strategies[a], strategies[b], strategies[c], strategies[d]],
+                            &mut lineups,
+                        );
+                    }
+                }
+            }
+        }
+    }
+    lineups
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameResult {
+    pub seed: String,
+    pub strategies: [BotStrategy; 4],
+    pub money: [i16; 4],
+    pub charges: [u8; 4],
+    pub queens: [u8; 4],
+    pub jacks: [u8; 4],
+    pub tens: [u8; 4],
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Standing {
+    pub strategy: BotStrategy,
+    pub games: u32,
+    pub mean_money: f64,
+    // half-width of the 95% confidence interval
+    pub money_ci: f64,
+    pub charge_rate: f64,
+    pub queen_rate: f64,
+    pub jack_rate: f64,
+    pub ten_rate: f64,
+}
+
+// a strategy seated more than once in a game is scored by its mean across those
+// seats, so each game is one sample
+pub fn standings(strategies: &[BotStrategy], results: &[GameResult]) -> Vec<Standing> {
+    let mut standings = Vec::new();
+    for &strategy in strategies {
+        let mut money = Vec::new();
+        let mut seats = 0;
+        let mut counts = [0u32; 4];
+        for result in results {
+            let mut game_money = 0.0;
+            let mut game_seats = 0;
+            for (i, _) in result
+                .strategies
+                .iter()
+                .enumerate()
+                .filter(|(_, &s)| s == strategy)
+            {
+                game_money += result.money[i] as f64;
+                game_seats += 1;
+                counts[0] += result.charges[i] as u32;
+                counts[1] += result.queens[i] as u32;
+                counts[2] += result.jacks[i] as u32;
+                counts[3] += result.tens[i] as u32;
+            }
+            if game_seats > 0 {
+                money.push(game_money / game_seats as f64);
+                seats += game_seats;
+            }
+        }
+        let games = money.len() as f64;
+        let hands = 4.0 * seats as f64;
+        let mean = money.iter().sum::<f64>() / games;
+        let variance = if money.len() > 1 {
+            money.iter().map(|m| (m - mean).powi(2)).sum::<f64>() / (games - 1.0)
+        } else {
+            0.0
+        };
+        standings.push(Standing {
+            strategy,
+            games: money.len() as u32,
+            mean_money: mean,
+            money_ci: 1.96 * (variance / games).sqrt(),
+            charge_rate: counts[0] as f64 / hands,
+            queen_rate: counts[1] as f64 / hands,
+            jack_rate: counts[2] as f64 / hands,
+            ten_rate: counts[3] as f64 / hands,
+        });
+    }
+    standings.sort_by(|a, b| b.mean_money.partial_cmp(&a.mean_money).unwrap());
+    standings
+}
+
+struct Contestant {
+    bot: Bot,
+    bot_state: BotState,
+    game_state: GameState,
+}
+
+impl Contestant {
+    fn new(seat: Seat, strategy: BotStrategy, options: BotOptions) -> Self {
+        Self {
+            bot: Bot::with_options(strategy, options),
+            bot_state: BotState::new(seat, Cards::NONE),
+            game_state: GameState::new(),
+        }
+    }
+
+    fn apply(&mut self, event: &GameEvent) {
+        let event = event.redact(Some(self.bot_state.seat), self.game_state.rules);
+        self.bot_state.on_event(&self.game_state, &event);
+        self.bot.on_event(&self.bot_state, &self.game_state, &event);
+        let phase = self.game_state.phase;
+        self.game_state.apply(&event);
+        if phase.is_playing() && !self.game_state.phase.is_playing() {
+            self.bot_state.pre_pass_hand = Cards::NONE;
+            self.bot_state.post_pass_hand = Cards::NONE;
+        }
+        match event {
+            GameEvent::Deal {
+                north,
+                east,
+                south,
+                west,
+                ..
+            } => {
+                self.bot_state.pre_pass_hand = north | east | south | west;
+                self.bot_state.post_pass_hand = self.bot_state.pre_pass_hand;
+            }
+            GameEvent::SendPass { cards, .. } => {
+                self.bot_state.post_pass_hand -= cards;
+            }
+            GameEvent::RecvPass { cards, .. } => {
+                self.bot_state.post_pass_hand |= cards;
+            }
+            _ => {}
+        }
+    }
+}
+
+struct Table {
+    game_id: GameId,
+    game: Game<()>,
+    contestants: Vec<Contestant>,
+}
+
+impl Table {
+    fn apply(&mut self, event: &GameEvent) {
+        let mut events = Vec::new();
+        self.game.apply(event, |_, e| events.push(e.clone()));
+        for event in &events {
+            for contestant in &mut self.contestants {
+                contestant.apply(event);
+            }
+        }
+    }
+
+    fn pass(&mut self) -> Result<(), RulesError> {
+        for &seat in &Seat::VALUES {
+            let contestant = &mut self.contestants[seat.idx()];
+            let cards = contestant
+                .bot
+                .pass(&contestant.bot_state, &contestant.game_state);
+            self.game.verify_pass(self.game_id, seat, cards)?;
+            self.apply(&GameEvent::SendPass { from: seat, cards });
+        }
+        if self.game.state.phase == GamePhase::PassKeeper {
+            for event in &self
+                .game
+                .seed
+                .keeper_pass(self.game.state.hand, self.game.post_pass_hand)
+            {
+                self.apply(event);
+            }
+        } else {
+            for &seat in &Seat::VALUES {
+                let sender = self.game.state.phase.pass_sender(seat).idx();
+                let cards = self.game.pre_pass_hand[sender] - self.game.post_pass_hand[sender];
+                self.apply(&GameEvent::RecvPass { to: seat, cards });
+            }
+        }
+        Ok(())
+    }
+
+    fn charge(&mut self) -> Result<(), RulesError> {
+        for &seat in &Seat::VALUES {
+            if self.game.state.phase.is_charging()
+                && self.game.state.can_charge(seat)
+                && !self.game.state.done.charged(seat)
+            {
+                let contestant = &mut self.contestants[seat.idx()];
+                let cards = contestant
+                    .bot
+                    .charge(&contestant.bot_state, &contestant.game_state);
+                self.game.verify_charge(self.game_id, seat, cards)?;
+                self.apply(&GameEvent::Charge { seat, cards });
+            }
+        }
+        Ok(())
+    }
+
+    fn play(&mut self) -> Result<Option<GameEvent>, RulesError> {
+        let seat = self.game.state.next_actor.unwrap();
+        let contestant = &mut self.contestants[seat.idx()];
+        let card = contestant
+            .bot
+            .play(&contestant.bot_state, &contestant.game_state);
+        self.game.verify_play(self.game_id, seat, card)?;
+        let event = GameEvent::Play { seat, card };
+        let deal = self.game.deal_event(&event);
+        self.apply(&event);
+        Ok(deal)
+    }
+}
+
+// bots draw their random choices from the seed too, so a seed replays the same
+// game as long as the search bots have an iteration budget
+pub fn play_game(
+    rules: ChargingRules,
+    seed: String,
+    strategies: [BotStrategy; 4],
+    options: BotOptions,
+) -> Result<GameResult, RulesError> {
+    rng::with_seed(Some(rng::seed_from_str(&seed)), || {
+        play_seeded_game(rules, seed, strategies, options)
+    })
+}
+
+fn play_seeded_game(
+    rules: ChargingRules,
+    seed: String,
+    strategies: [BotStrategy; 4],
+    options: BotOptions,
+) -> Result<GameResult, RulesError> {
+    let mut table = Table {
+        game_id: GameId::new(),
+        game: Game::new(),
+        contestants: Seat::VALUES
+            .iter()
+            .map(|&seat| Contestant::new(seat, strategies[seat.idx()], options))
+            .collect(),
+    };
+    let player = |seat: Seat| {
+        Box::new(Player::Bot {
+            user_id: UserId::new(),
+            strategy: strategies[seat.idx()],
+            options,
+        })
+    };
+    table.apply(&GameEvent::Sit {
+        north: player(Seat::North),
+        east: player(Seat::East),
+        south: player(Seat::South),
+        west: player(Seat::West),
+        rules,
+        seed: Seed::Chosen {
+            value: seed.clone(),
+        },
+        time_control: None,
+        spectators: None,
+        rule_set: None,
+        length: None,
+    });
+    table.apply(&table.game.seed.deal(PassDirection::Left));
+    let mut result = GameResult {
+        seed,
+        strategies,
+        money: [0; 4],
+        charges: [0; 4],
+        queens: [0; 4],
+        jacks: [0; 4],
+        tens: [0; 4],
+    };
+    while !table.game.state.phase.is_complete() {
+        let state = &table.game.state;
+        if state.phase.is_passing() {
+            table.pass()?;
+        } else if state.phase.is_charging() {
+            table.charge()?;
+        } else if state.next_actor.is_some() {
+            let deal = table.play()?;
+            let state = &table.game.state;
+            if !state.phase.is_playing() {
+                let scores = state.scores();
+                for &seat in &Seat::VALUES {
+                    let i = seat.idx();
+                    result.money[i] += scores.money(seat);
+                    result.charges[i] += !state.charges.charges(seat).is_empty() as u8;
+                    result.queens[i] += state.won.queen(seat) as u8;
+                    result.jacks[i] += state.won.jack(seat) as u8;
+                    result.tens[i] += state.won.ten(seat) as u8;
+                }
+                if let Some(deal) = deal {
+                    table.apply(&deal);
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lineups_balanced() {
+        let strategies = [
+            BotStrategy::Duck,
+            BotStrategy::GottaTry,
+            BotStrategy::Heuristic,
+            BotStrategy::Random,
+            BotStrategy::Simulate,
+        ];
+        for n in 2..=strategies.len() {
+            let lineups = lineups(&strategies[..n]);
+            for &strategy in &strategies[..n] {
+                for &seat in &Seat::VALUES {
+                    let count = lineups
+                        .iter()
+                        .filter(|lineup| lineup[seat.idx()] == strategy)
+                        .count();
+                    assert_eq!(count * n, lineups.len(), "{:?} {:?}", strategy, seat);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_play_game() {
+        let strategies = [
+            BotStrategy::Duck,
+            BotStrategy::GottaTry,
+            BotStrategy::Heuristic,
+            BotStrategy::Random,
+        ];
+        let result = play_game(
+            ChargingRules::Chain,
+            "test".to_string(),
+            strategies,
+            BotOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result.money.iter().sum::<i16>(), 0);
+        assert_eq!(result.queens.iter().sum::<u8>(), 4);
+        assert_eq!(result.jacks.iter().sum::<u8>(), 4);
+        assert_eq!(result.tens.iter().sum::<u8>(), 4);
+    }
+
+    #[test]
+    fn test_play_game_seeded() {
+        let strategies = [
+            BotStrategy::Random,
+            BotStrategy::Simulate,
+            BotStrategy::Heuristic,
+            BotStrategy::Random,
+        ];
+        // searches only replay when they're held to an iteration budget
+        let options = BotOptions {
+            iterations: Some(4),
+            ..BotOptions::default()
+        };
+        let play = || {
+            play_game(
+                ChargingRules::Chain,
+                "test".to_string(),
+                strategies,
+                options,
+            )
+            .unwrap()
+        };
+        let first = play();
+        let second = play();
+        assert_eq!(first.money, second.money);
+        assert_eq!(first.charges, second.charges);
+    }
+
+    #[test]
+    fn test_standings_per_game() {
+        let result = |money| GameResult {
+            seed: String::new(),
+            strategies: [
+                BotStrategy::Duck,
+                BotStrategy::Random,
+                BotStrategy::Duck,
+                BotStrategy::Random,
+            ],
+            money,
+            charges: [0; 4],
+            queens: [0; 4],
+            jacks: [0; 4],
+            tens: [0; 4],
+        };
+        let results = [result([10, -10, 30, -30]), result([-20, 20, 0, 0])];
+        let standings = standings(&[BotStrategy::Duck, BotStrategy::Random], &results);
+        let duck = &standings[0];
+        assert_eq!(duck.strategy, BotStrategy::Duck);
+        assert_eq!(duck.games, 2);
+        assert_eq!(duck.mean_money, 5.0);
+        assert!((duck.money_ci - 1.96 * 15.0).abs() < 1e-9);
+    }
+}
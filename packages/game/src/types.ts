@@ -143,6 +143,10 @@ export interface HandCompleteEventData {
   eastScore: number;
   southScore: number;
   westScore: number;
+  northTotal: number;
+  eastTotal: number;
+  southTotal: number;
+  westTotal: number;
 }
 
 export interface GameCompleteEventData {
@@ -1,4 +1,4 @@
-import { BotStrategy, Rules, GameResult } from "./types";
+import { BotOptions, BotStrategy, Rules, GameResult } from "./types";
 
 export class TurboHeartsLobbyService {
     private userNames: { [key: string]: string } = {};
@@ -25,8 +25,8 @@ export class TurboHeartsLobbyService {
         return fetch(`/lobby/join`, this.requestWithBody({ game_id: gameId, rules }));
     }
 
-    public addBot(gameId: string, rules: Rules, strategy: BotStrategy) {
-        return fetch(`/lobby/add_bot`, this.requestWithBody({ game_id: gameId, rules, strategy }));
+    public addBot(gameId: string, rules: Rules, strategy: BotStrategy, options?: BotOptions) {
+        return fetch(`/lobby/add_bot`, this.requestWithBody({ game_id: gameId, rules, strategy, options }));
     }
 
     public startGame(gameId: string) {
@@ -96,6 +96,8 @@ class GameListItemInternal extends React.PureComponent<GameListItem.Props> {
                     <option value="heuristic" className="button">Heuristic</option>
                     <option value="simulate" className="button">Simulate</option>
                     <option value="neural_net" className="button">Neural Net</option>
+                    <option value="ismcts" className="button">Tree Search</option>
+                    <option value="ismcts_neural_net" className="button">Neural Tree Search</option>
                 </select>
                 {this.props.game.players.length >= 4 && (
                     <div className="button" onClick={this.props.startGame}>
@@ -1,4 +1,13 @@
-export type BotStrategy = "random" | "duck" | "gotta_try" | "heuristic" | "simulate" | "neural_net";
+export type BotStrategy = "random" | "duck" | "gotta_try" | "heuristic" | "simulate" | "neural_net" | "ismcts" | "ismcts_neural_net";
+
+export type Difficulty = "easy" | "normal" | "hard";
+
+export interface BotOptions {
+    time_ms?: number;
+    iterations?: number;
+    difficulty?: Difficulty;
+    seed?: number;
+}
 
 export type Rules = "classic" | "blind" | "bridge" | "blind-bridge" | "chain" | "blind-chain";
 
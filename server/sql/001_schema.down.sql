@@ -0,0 +1,6 @@
+DROP TABLE event;
+DROP TABLE game_player;
+DROP TABLE game;
+DROP TABLE lobby_chat;
+DROP TABLE auth_token;
+DROP TABLE user;
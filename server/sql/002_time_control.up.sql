@@ -0,0 +1 @@
+ALTER TABLE game ADD COLUMN time_control TEXT;
@@ -0,0 +1,3 @@
+DROP TABLE rated_game;
+DROP TABLE rating_history;
+DROP TABLE rating;
@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS rating (
+    player TEXT NOT NULL,
+    rating REAL NOT NULL,
+    hands INTEGER NOT NULL,
+    updated_time INTEGER NOT NULL,
+    PRIMARY KEY (player)
+) WITHOUT ROWID;
+
+CREATE TABLE IF NOT EXISTS rating_history (
+    player TEXT NOT NULL,
+    game_id TEXT NOT NULL,
+    hand INTEGER NOT NULL,
+    completed_time INTEGER NOT NULL,
+    money INTEGER NOT NULL,
+    rating REAL NOT NULL,
+    PRIMARY KEY (player, game_id, hand)
+) WITHOUT ROWID;
+
+CREATE TABLE IF NOT EXISTS rated_game (
+    game_id TEXT NOT NULL,
+    completed_time INTEGER NOT NULL,
+    PRIMARY KEY (game_id)
+) WITHOUT ROWID;
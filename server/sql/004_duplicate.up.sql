@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS duplicate_game (
+    duplicate_id TEXT NOT NULL,
+    game_id TEXT NOT NULL,
+    rotation INTEGER NOT NULL,
+    PRIMARY KEY (duplicate_id, game_id)
+) WITHOUT ROWID;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_duplicate_game ON duplicate_game (game_id);
@@ -0,0 +1 @@
+ALTER TABLE game ADD COLUMN spectators TEXT;
@@ -0,0 +1,2 @@
+DROP TABLE game_invite;
+DROP TABLE private_game;
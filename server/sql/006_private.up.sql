@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS private_game (
+    game_id TEXT NOT NULL,
+    hidden INTEGER NOT NULL,
+    join_token TEXT,
+    PRIMARY KEY (game_id)
+) WITHOUT ROWID;
+
+CREATE TABLE IF NOT EXISTS game_invite (
+    game_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    PRIMARY KEY (game_id, user_id)
+) WITHOUT ROWID;
+CREATE INDEX IF NOT EXISTS idx_game_invite ON game_invite (user_id);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS analysis (
+    game_id TEXT NOT NULL,
+    analysis TEXT NOT NULL,
+    PRIMARY KEY (game_id)
+) WITHOUT ROWID;
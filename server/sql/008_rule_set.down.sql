@@ -0,0 +1,20 @@
+-- SQLite can't drop a column, so the table is rebuilt without it
+CREATE TABLE game_down (
+    game_id TEXT NOT NULL,
+    seed TEXT NOT NULL,
+    created_time INTEGER NOT NULL,
+    created_by TEXT NOT NULL,
+    last_updated_time INTEGER NOT NULL,
+    last_updated_by TEXT NOT NULL,
+    started_time INTEGER,
+    completed_time INTEGER,
+    time_control TEXT,
+    spectators TEXT,
+    PRIMARY KEY (game_id)
+) WITHOUT ROWID;
+INSERT INTO game_down (game_id, seed, created_time, created_by, last_updated_time, last_updated_by, started_time, completed_time, time_control, spectators)
+    SELECT game_id, seed, created_time, created_by, last_updated_time, last_updated_by, started_time, completed_time, time_control, spectators FROM game;
+DROP TABLE game;
+ALTER TABLE game_down RENAME TO game;
+CREATE INDEX IF NOT EXISTS idx_game_completed ON game(game_id) WHERE completed_time IS NULL;
+CREATE INDEX IF NOT EXISTS idx_game_completed_time ON game(completed_time) WHERE completed_time IS NOT NULL;
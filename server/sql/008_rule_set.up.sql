@@ -0,0 +1 @@
+ALTER TABLE game ADD COLUMN rule_set TEXT;
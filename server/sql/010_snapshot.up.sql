@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS snapshot (
+    game_id TEXT NOT NULL,
+    event_id INTEGER NOT NULL,
+    snapshot TEXT NOT NULL,
+    PRIMARY KEY (game_id, event_id)
+) WITHOUT ROWID;
@@ -0,0 +1,2 @@
+-- the archive files are left in place, but games in them can no longer be found
+DROP TABLE archived_game;
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS archived_game (
+    game_id TEXT NOT NULL,
+    path TEXT NOT NULL,
+    byte_offset INTEGER NOT NULL,
+    byte_length INTEGER NOT NULL,
+    PRIMARY KEY (game_id)
+) WITHOUT ROWID;
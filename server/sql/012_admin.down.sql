@@ -0,0 +1,15 @@
+DROP TABLE audit_log;
+
+-- SQLite can't drop a column, so the table is rebuilt without them
+CREATE TABLE user_down (
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    realm TEXT NOT NULL,
+    external_id TEXT NOT NULL,
+    PRIMARY KEY (user_id)
+) WITHOUT ROWID;
+INSERT INTO user_down (user_id, name, realm, external_id)
+    SELECT user_id, name, realm, external_id FROM user;
+DROP TABLE user;
+ALTER TABLE user_down RENAME TO user;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_user ON user (realm, external_id);
@@ -0,0 +1,9 @@
+ALTER TABLE user ADD COLUMN role TEXT NOT NULL DEFAULT 'player';
+ALTER TABLE user ADD COLUMN banned_time INTEGER;
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    timestamp INTEGER NOT NULL,
+    user_id TEXT NOT NULL,
+    action TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_audit_log ON audit_log (timestamp);
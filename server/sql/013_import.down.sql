@@ -0,0 +1,2 @@
+DROP TABLE import_confirmation;
+DROP TABLE imported_game;
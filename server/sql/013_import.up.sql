@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS imported_game (
+    game_id TEXT NOT NULL,
+    deal_hash TEXT NOT NULL,
+    PRIMARY KEY (game_id)
+) WITHOUT ROWID;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_imported_game ON imported_game (deal_hash);
+
+CREATE TABLE IF NOT EXISTS import_confirmation (
+    game_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    PRIMARY KEY (game_id, user_id)
+) WITHOUT ROWID;
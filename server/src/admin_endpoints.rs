@@ -0,0 +1,152 @@
+use crate::{Games, Lobby, Storage, Users};
+use turbo_hearts_api::{DeleteChatRequest, GameId, RenameUserRequest, UserId};
+use warp::{Filter, Rejection, Reply};
+
+const AUDIT_LOG_SIZE: u32 = 500;
+
+pub fn router(
+    lobby: infallible!(&'static Lobby),
+    games: infallible!(&'static Games),
+    users: infallible!(&'static Users),
+    storage: infallible!(&'static dyn Storage),
+    admin_id: rejection!(UserId),
+) -> reply!() {
+    warp::path("admin")
+        .and(
+            delete_game(lobby.clone(), games.clone(), admin_id.clone())
+                .or(abort_game(lobby.clone(), games.clone(), admin_id.clone()))
+                .or(rename_user(users.clone(), admin_id.clone()))
+                .or(ban_user(lobby.clone(), games, users, admin_id.clone()))
+                .or(delete_chat(lobby, admin_id.clone()))
+                .or(audit_log(storage, admin_id)),
+        )
+        .boxed()
+}
+
+fn delete_game<'a>(
+    lobby: infallible!(&'a Lobby),
+    games: infallible!(&'a Games),
+    admin_id: rejection!(UserId),
+) -> reply!() {
+    async fn handle(
+        game_id: GameId,
+        lobby: &Lobby,
+        games: &Games,
+        admin_id: UserId,
+    ) -> Result<impl Reply, Rejection> {
+        lobby.delete_game(admin_id, game_id).await?;
+        games.remove(game_id).await;
+        Ok(warp::reply())
+    }
+
+    warp::path!("game" / GameId)
+        .and(warp::delete())
+        .and(lobby)
+        .and(games)
+        .and(admin_id)
+        .and_then(handle)
+}
+
+fn abort_game<'a>(
+    lobby: infallible!(&'a Lobby),
+    games: infallible!(&'a Games),
+    admin_id: rejection!(UserId),
+) -> reply!() {
+    async fn handle(
+        game_id: GameId,
+        lobby: &Lobby,
+        games: &Games,
+        admin_id: UserId,
+    ) -> Result<impl Reply, Rejection> {
+        games.abort(game_id, admin_id).await?;
+        lobby.finish_game(game_id).await;
+        Ok(warp::reply())
+    }
+
+    warp::path!("game" / GameId / "abort")
+        .and(warp::post())
+        .and(lobby)
+        .and(games)
+        .and(admin_id)
+        .and_then(handle)
+}
+
+fn rename_user<'a>(users: infallible!(&'a Users), admin_id: rejection!(UserId)) -> reply!() {
+    async fn handle(
+        user_id: UserId,
+        users: &Users,
+        admin_id: UserId,
+        request: RenameUserRequest,
+    ) -> Result<impl Reply, Rejection> {
+        let RenameUserRequest { name } = request;
+        users.rename(admin_id, user_id, name).await?;
+        Ok(warp::reply())
+    }
+
+    warp::path!("user" / UserId / "rename")
+        .and(warp::post())
+        .and(users)
+        .and(admin_id)
+        .and(warp::body::json())
+        .and_then(handle)
+}
+
+fn ban_user<'a>(
+    lobby: infallible!(&'a Lobby),
+    games: infallible!(&'a Games),
+    users: infallible!(&'a Users),
+    admin_id: rejection!(UserId),
+) -> reply!() {
+    async fn handle(
+        user_id: UserId,
+        lobby: &Lobby,
+        games: &Games,
+        users: &Users,
+        admin_id: UserId,
+    ) -> Result<impl Reply, Rejection> {
+        users.ban(admin_id, user_id).await?;
+        lobby.disconnect(user_id).await;
+        games.disconnect(user_id).await;
+        Ok(warp::reply())
+    }
+
+    warp::path!("user" / UserId / "ban")
+        .and(warp::post())
+        .and(lobby)
+        .and(games)
+        .and(users)
+        .and(admin_id)
+        .and_then(handle)
+}
+
+fn delete_chat<'a>(lobby: infallible!(&'a Lobby), admin_id: rejection!(UserId)) -> reply!() {
+    async fn handle(
+        lobby: &Lobby,
+        admin_id: UserId,
+        request: DeleteChatRequest,
+    ) -> Result<impl Reply, Rejection> {
+        let DeleteChatRequest { user_id, timestamp } = request;
+        lobby.delete_chat(admin_id, user_id, timestamp).await?;
+        Ok(warp::reply())
+    }
+
+    warp::path!("chat")
+        .and(warp::delete())
+        .and(lobby)
+        .and(admin_id)
+        .and(warp::body::json())
+        .and_then(handle)
+}
+
+fn audit_log<'a>(storage: infallible!(&'a dyn Storage), admin_id: rejection!(UserId)) -> reply!() {
+    async fn handle(storage: &dyn Storage, _: UserId) -> Result<impl Reply, Rejection> {
+        let entries = storage.load_audit_log(AUDIT_LOG_SIZE)?;
+        Ok(warp::reply::json(&entries))
+    }
+
+    warp::path!("audit")
+        .and(warp::get())
+        .and(storage)
+        .and(admin_id)
+        .and_then(handle)
+}
@@ -0,0 +1,207 @@
+use crate::{check_boards_played, CardsError, Storage};
+use log::{error, info};
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Mutex,
+};
+use tokio::sync::oneshot;
+use turbo_hearts_api::{
+    AnalysisResponse, Card, Cards, Game, GameEvent, GameId, GameState, HandAnalysis, PassDirection,
+    PlayAnalysis, PlayOutcome, Seat,
+};
+use turbo_hearts_bot::BruteForce;
+
+// own pool so analysis never takes more than its threads from the bots,
+// and concurrent requests for a game wait on the same analysis
+pub struct Analyzer {
+    storage: &'static dyn Storage,
+    pool: ThreadPool,
+    solved_cards: usize,
+    waiting: Mutex<HashMap<GameId, Vec<oneshot::Sender<()>>>>,
+}
+
+impl Analyzer {
+    pub fn new(storage: &'static dyn Storage, threads: usize, solved_cards: usize) -> Self {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("analysis-{}", i))
+            .panic_handler(|_| error!("analyze: panicked"))
+            .build()
+            .unwrap();
+        Self {
+            storage,
+            pool,
+            solved_cards,
+            waiting: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn analyze(&'static self, game_id: GameId) -> Result<AnalysisResponse, CardsError> {
+        // analyses cached before the server was set to solve more are redone
+        match self.storage.load_analysis(game_id)? {
+            Some(analysis) if analysis.solved_cards >= self.solved_cards => return Ok(analysis),
+            _ => {}
+        }
+        let events = load_events(self.storage, game_id)?;
+        let hands = replay(game_id, &events)?;
+        let (tx, rx) = oneshot::channel();
+        let start = match self.waiting.lock().unwrap().entry(game_id) {
+            Entry::Occupied(mut entry) => {
+                entry.get_mut().push(tx);
+                false
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(vec![tx]);
+                true
+            }
+        };
+        if start {
+            self.pool.spawn(move || {
+                // wakes everyone waiting, even if solving panics
+                let _finished = Finished(self, game_id);
+                let analysis = AnalysisResponse {
+                    game_id,
+                    solved_cards: self.solved_cards,
+                    hands: hands
+                        .into_par_iter()
+                        .map(|hand| analyze_hand(hand, self.solved_cards))
+                        .collect(),
+                };
+                let result = self.storage.save_analysis(&analysis);
+                info!(
+                    "analyze: game_id={}, error={:?}",
+                    game_id,
+                    result.as_ref().err()
+                );
+            });
+        }
+        let _ = rx.await;
+        self.storage
+            .load_analysis(game_id)?
+            .ok_or(CardsError::AnalysisFailed(game_id))
+    }
+}
+
+struct Finished(&'static Analyzer, GameId);
+
+impl Drop for Finished {
+    fn drop(&mut self) {
+        let Finished(analyzer, game_id) = self;
+        if let Ok(mut waiting) = analyzer.waiting.lock() {
+            waiting.remove(game_id);
+        }
+    }
+}
+
+struct Decision {
+    state: GameState,
+    seat: Seat,
+    card: Card,
+    legal_plays: Cards,
+}
+
+struct Hand {
+    pass: PassDirection,
+    hands: [Cards; 4],
+    decisions: Vec<Decision>,
+}
+
+pub fn load_events(storage: &dyn Storage, game_id: GameId) -> Result<Vec<GameEvent>, CardsError> {
+    check_boards_played(storage, game_id)?;
+    let events = storage
+        .load_game_events(game_id)?
+        .into_iter()
+        .map(|e| e.event)
+        .collect::<Vec<_>>();
+    if events.is_empty() {
+        return Err(CardsError::UnknownGame(game_id));
+    }
+    Ok(events)
+}
+
+// collects every decision of a completed game, which is cheap next to solving them
+fn replay(game_id: GameId, events: &[GameEvent]) -> Result<Vec<Hand>, CardsError> {
+    let mut game: Game<()> = Game::new();
+    let mut hands = Vec::new();
+    for event in events {
+        match event {
+            GameEvent::Deal { pass, .. } => hands.push(Hand {
+                pass: *pass,
+                hands: [Cards::NONE; 4],
+                decisions: Vec::new(),
+            }),
+            GameEvent::Play { seat, card } => {
+                let hand = hands.last_mut().unwrap();
+                hand.hands = game.post_pass_hand;
+                hand.decisions.push(Decision {
+                    state: game.state.clone(),
+                    seat: *seat,
+                    card: *card,
+                    legal_plays: game
+                        .state
+                        .legal_plays(game.post_pass_hand[seat.idx()] - game.state.played),
+                });
+            }
+            GameEvent::Undo { .. } => {
+                hands.last_mut().unwrap().decisions.pop();
+            }
+            _ => {}
+        }
+        game.apply(event, |_, _| {});
+    }
+    if !game.state.phase.is_complete() {
+        return Err(CardsError::IncompleteGame(game_id));
+    }
+    Ok(hands)
+}
+
+fn analyze_hand(hand: Hand, solved_cards: usize) -> HandAnalysis {
+    let mut brute_force = BruteForce::new(hand.hands);
+    let mut plays = Vec::new();
+    for decision in hand.decisions {
+        if decision.legal_plays.len() < 2 {
+            continue;
+        }
+        if 52 - decision.state.played.len() > solved_cards {
+            plays.push(PlayAnalysis {
+                seat: decision.seat,
+                card: decision.card,
+                money: None,
+                best_money: None,
+                outcomes: None,
+            });
+            continue;
+        }
+        let mut outcomes = Vec::new();
+        for card in decision.legal_plays {
+            let mut state = decision.state.clone();
+            state.apply(&GameEvent::Play {
+                seat: decision.seat,
+                card,
+            });
+            let won = brute_force.solve(&mut state);
+            outcomes.push(PlayOutcome {
+                card,
+                money: won
+                    .scores(state.charges, state.rule_set)
+                    .money(decision.seat),
+            });
+        }
+        plays.push(PlayAnalysis {
+            seat: decision.seat,
+            card: decision.card,
+            money: outcomes
+                .iter()
+                .find(|outcome| outcome.card == decision.card)
+                .map(|outcome| outcome.money),
+            best_money: outcomes.iter().map(|outcome| outcome.money).max(),
+            outcomes: Some(outcomes),
+        });
+    }
+    HandAnalysis {
+        pass: hand.pass,
+        hands: hand.hands,
+        plays,
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{util, CardsError, Storage, StoredEvent};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+use turbo_hearts_api::{GameEvent, GameId};
+
+const ARCHIVE_PAGE_SIZE: u32 = 1000;
+
+// the gzip member of an archive file that holds a game's events, which is
+// read without touching the rest of the file
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ArchiveEntry {
+    pub path: String,
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchivedGame {
+    game_id: GameId,
+    events: Vec<ArchivedEvent>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchivedEvent {
+    timestamp: i64,
+    event: GameEvent,
+}
+
+// each game is its own gzip member, so the file still decompresses to one game
+// per line, and it's synced before any events are deleted
+pub fn archive_games(storage: &dyn Storage, dir: &Path, cutoff: i64) -> Result<usize, CardsError> {
+    let mut games: Vec<ArchivedGame> = Vec::new();
+    for StoredEvent {
+        game_id,
+        timestamp,
+        event,
+    } in storage.load_archivable_games(cutoff, ARCHIVE_PAGE_SIZE)?
+    {
+        match games.last_mut() {
+            Some(game) if game.game_id == game_id => {}
+            _ => games.push(ArchivedGame {
+                game_id,
+                events: Vec::new(),
+            }),
+        }
+        games
+            .last_mut()
+            .unwrap()
+            .events
+            .push(ArchivedEvent { timestamp, event });
+    }
+    if games.is_empty() {
+        return Ok(0);
+    }
+    fs::create_dir_all(dir)?;
+    let timestamp = util::timestamp();
+    let partial = dir.join(format!("partial-{}.gz", timestamp));
+    let mut output = File::create(&partial)?;
+    let mut offset = 0;
+    let mut members = Vec::with_capacity(games.len());
+    for game in &games {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        serde_json::to_writer(&mut encoder, game)?;
+        encoder.write_all(b"\n")?;
+        let member = encoder.finish()?;
+        output.write_all(&member)?;
+        let length = member.len() as u64;
+        members.push((offset, length));
+        offset += length;
+    }
+    output.sync_all()?;
+    drop(output);
+    let path = dir.join(format!("complete-{}.gz", timestamp));
+    fs::rename(partial, &path)?;
+    let path = path.to_string_lossy();
+    let archived = games
+        .iter()
+        .zip(members)
+        .map(|(game, (offset, length))| {
+            let entry = ArchiveEntry {
+                path: path.to_string(),
+                offset,
+                length,
+            };
+            (game.game_id, game.events.len(), entry)
+        })
+        .collect::<Vec<_>>();
+    storage.mark_archived(&archived)
+}
+
+pub fn load_archived_events(
+    game_id: GameId,
+    entry: &ArchiveEntry,
+) -> Result<Vec<StoredEvent>, CardsError> {
+    let mut file = File::open(&entry.path)?;
+    file.seek(SeekFrom::Start(entry.offset))?;
+    let game: ArchivedGame = serde_json::from_reader(GzDecoder::new(file.take(entry.length)))?;
+    Ok(game
+        .events
+        .into_iter()
+        .map(|ArchivedEvent { timestamp, event }| StoredEvent {
+            game_id,
+            timestamp,
+            event,
+        })
+        .collect())
+}
@@ -0,0 +1,87 @@
+use http::header;
+use reqwest::Client;
+use std::{env, error::Error, fs, process};
+use turbo_hearts_api::ImportResponse;
+
+const USAGE: &str = "usage: import [--server URL] --token AUTH_TOKEN PATH...
+
+Imports completed games you played in into a server as if they had been
+played there. Files ending in .json hold a list of game events, anything
+else is a text game record. Each game is checked against the rules before
+it is stored, and the first illegal event is reported if it isn't.";
+
+struct Options {
+    server: String,
+    token: String,
+    paths: Vec<String>,
+}
+
+fn parse_options() -> Result<Options, Box<dyn Error>> {
+    let mut server = "http://localhost:7380".to_string();
+    let mut token = None;
+    let mut paths = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--server" => server = value()?,
+            "--token" => token = Some(value()?),
+            "--help" | "-h" => return Err(USAGE.into()),
+            _ => paths.push(arg),
+        }
+    }
+    let token = token.ok_or("an auth token is required, see --help")?;
+    if paths.is_empty() {
+        return Err("at least one game is required, see --help".into());
+    }
+    Ok(Options {
+        server: server.trim_end_matches('/').to_string(),
+        token,
+        paths,
+    })
+}
+
+async fn import(client: &Client, options: &Options, path: &str) -> Result<(), Box<dyn Error>> {
+    let body = fs::read_to_string(path)?;
+    let content_type = if path.ends_with(".json") {
+        "application/json"
+    } else {
+        "text/plain; charset=utf-8"
+    };
+    let response = client
+        .post(&format!("{}/record", options.server))
+        .header(header::COOKIE, format!("AUTH_TOKEN={}", options.token))
+        .header(header::CONTENT_TYPE, content_type)
+        .body(body)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        return Err(format!("{}: {} {}", path, status, response.text().await?).into());
+    }
+    let ImportResponse { game_id } = response.json().await?;
+    println!("{} {}", path, game_id);
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    let client = Client::new();
+    let mut failed = false;
+    for path in &options.paths {
+        if let Err(e) = import(&client, &options, path).await {
+            eprintln!("{}", e);
+            failed = true;
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
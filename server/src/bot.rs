@@ -1,44 +1,45 @@
-use crate::{CardsError, Games};
+use crate::{record_bot_decision, CardsError, Games};
 use futures_util::FutureExt;
 use rand::distributions::Distribution;
 use rand_distr::Gamma;
 use std::time::Instant;
 use tokio::{sync::mpsc::UnboundedReceiver, time, time::Duration};
 use turbo_hearts_api::{
-    can_claim, should_claim, BotState, BotStrategy, Card, Cards, GameEvent, GameId, GameState,
-    Seat, UserId,
-};
-use turbo_hearts_bot::{
-    Algorithm, Bot, DuckBot, GottaTryBot, HeuristicBot, NeuralNetworkBot, RandomBot, SimulateBot,
+    can_claim, should_claim, BotOptions, BotState, BotStrategy, Card, Cards, GameEvent, GameId,
+    GameState, Seat, UserId,
 };
+use turbo_hearts_bot::{seeded, Algorithm, Bot};
 
 pub struct BotRunner {
     user_id: UserId,
+    strategy: BotStrategy,
+    options: BotOptions,
     bot_state: BotState,
     game_state: GameState,
     claim_hands: [Cards; 4],
-    bot: Bot,
+    // taken while the bot is deciding
+    bot: Option<Bot>,
 }
 
 impl BotRunner {
-    pub fn new(user_id: UserId, strategy: BotStrategy) -> Self {
-        let bot = match strategy {
-            BotStrategy::Duck => Bot::Duck(DuckBot),
-            BotStrategy::GottaTry => Bot::GottaTry(GottaTryBot),
-            BotStrategy::Heuristic => Bot::Heuristic(HeuristicBot),
-            BotStrategy::Random => Bot::Random(RandomBot::new()),
-            BotStrategy::Simulate => Bot::Simulate(SimulateBot::new()),
-            BotStrategy::NeuralNet => Bot::NeuralNetwork(NeuralNetworkBot::new()),
-        };
+    pub fn new(user_id: UserId, strategy: BotStrategy, options: BotOptions) -> Self {
         Self {
             user_id,
+            strategy,
+            options,
             bot_state: BotState::new(Seat::North, Cards::NONE),
             game_state: GameState::new(),
             claim_hands: [Cards::NONE; 4],
-            bot,
+            bot: Some(Bot::with_options(strategy, options)),
         }
     }
 
+    // a bot replayed from a snapshot isn't sent the hands before it
+    pub fn with_state(mut self, game_state: GameState) -> Self {
+        self.game_state = game_state;
+        self
+    }
+
     pub async fn run(
         mut self,
         game_id: GameId,
@@ -87,6 +88,11 @@ impl BotRunner {
                 }
                 self.claim_hands[seat.idx()] = Cards::NONE;
             }
+            if let Some(requester) = self.game_state.undo.requester() {
+                if !self.game_state.undo.has_accepted(self.bot_state.seat) {
+                    let _ = games.accept_undo(game_id, self.user_id, requester).await;
+                }
+            }
             match action {
                 Some(Action::Pass) => {
                     let cards = self.pass().await;
@@ -118,36 +124,47 @@ impl BotRunner {
     }
 
     async fn pass(&mut self) -> Cards {
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        rayon::scope(|scope| {
-            scope.spawn(|_| {
-                let cards = self.bot.pass(&self.bot_state, &self.game_state);
-                tx.send(cards).unwrap();
-            });
-        });
-        rx.await.unwrap()
+        self.decide("pass", |bot, bot_state, game_state| {
+            bot.pass(bot_state, game_state)
+        })
+        .await
     }
 
     async fn charge(&mut self) -> Cards {
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        rayon::scope(|scope| {
-            scope.spawn(|_| {
-                let cards = self.bot.charge(&self.bot_state, &self.game_state);
-                tx.send(cards).unwrap();
-            });
-        });
-        rx.await.unwrap()
+        self.decide("charge", |bot, bot_state, game_state| {
+            bot.charge(bot_state, game_state)
+        })
+        .await
     }
 
     async fn play(&mut self) -> Card {
+        self.decide("play", |bot, bot_state, game_state| {
+            bot.play(bot_state, game_state)
+        })
+        .await
+    }
+
+    // The bot is handed to the rayon pool while it thinks, so that searches
+    // never hold up the runtime's threads.
+    async fn decide<T, F>(&mut self, decision: &'static str, f: F) -> T
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Bot, &BotState, &GameState) -> T + Send + 'static,
+    {
+        let start = Instant::now();
         let (tx, rx) = tokio::sync::oneshot::channel();
-        rayon::scope(|scope| {
-            scope.spawn(|_| {
-                let card = self.bot.play(&self.bot_state, &self.game_state);
-                tx.send(card).unwrap();
-            });
+        let mut bot = self.bot.take().unwrap();
+        let bot_state = self.bot_state.clone();
+        let game_state = self.game_state.clone();
+        let seed = self.options.seed;
+        rayon::spawn(move || {
+            let result = seeded(seed, &game_state, || f(&mut bot, &bot_state, &game_state));
+            let _ = tx.send((bot, result));
         });
-        rx.await.unwrap()
+        let (bot, result) = rx.await.unwrap();
+        self.bot = Some(bot);
+        record_bot_decision(self.strategy, decision, start.elapsed());
+        result
     }
 
     async fn delay(delay: Option<Duration>, start: Instant) {
@@ -159,7 +176,10 @@ impl BotRunner {
 
     fn apply(&mut self, event: &GameEvent) -> Option<Action> {
         self.bot_state.on_event(&self.game_state, event);
-        self.bot.on_event(&self.bot_state, &self.game_state, event);
+        self.bot
+            .as_mut()
+            .unwrap()
+            .on_event(&self.bot_state, &self.game_state, event);
         let phase = self.game_state.phase;
         self.game_state.apply(event);
         if phase.is_playing() && !self.game_state.phase.is_playing() {
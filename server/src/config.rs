@@ -18,6 +18,22 @@ pub struct Config {
     pub github: OAuthCredentials,
     pub google: OAuthCredentials,
     pub port: u16,
+    #[serde(default)]
+    pub check_snapshots: bool,
+    #[serde(default)]
+    pub archive: Option<ArchiveConfig>,
+    #[serde(default)]
+    pub bot_threads: Option<usize>,
+    #[serde(default)]
+    pub analysis_threads: Option<usize>,
+    #[serde(default)]
+    pub analysis_solved_cards: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArchiveConfig {
+    pub path: String,
+    pub after_days: u32,
 }
 
 #[derive(Debug, Deserialize)]
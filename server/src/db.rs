@@ -1,43 +1,47 @@
-use crate::CardsError;
+use crate::{latest_version, migrate, record_sqlite_retry, schema_version, CardsError, Role};
 use r2d2::{CustomizeConnection, Pool};
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::{
     types::{FromSqlError, ToSqlOutput, Value, ValueRef},
-    Connection, DropBehavior, Row, ToSql, Transaction, TransactionBehavior, NO_PARAMS,
+    Connection, DropBehavior, Row, ToSql, Transaction, TransactionBehavior,
 };
-use serde::{de::DeserializeOwned, Serialize};
-use std::{fmt::Debug, path::Path, str::FromStr, time::Duration};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{fmt::Debug, path::Path, str::FromStr, sync::RwLock, time::Duration};
 use tokio::task;
-use turbo_hearts_api::{BotStrategy, ChargingRules, GameEvent, GameId, Seat, Seed, UserId};
-
-static SQL: &[&'static str] = &[include_str!("../sql/schema.sql")];
+use turbo_hearts_api::{
+    AdminAction, AnalysisResponse, BotOptions, BotStrategy, ChargingRules, DuplicateId, GameEvent,
+    GameId, GameLength, GameSnapshot, JoinToken, Player, RatedPlayer, RuleSet, Seat, Seed,
+    SpectatorPolicy, TimeControl, UserId,
+};
 
 pub struct Database {
     pool: Pool<SqliteConnectionManager>,
+    // held for reading by every transaction, so closing waits for them
+    closed: RwLock<bool>,
 }
 
 impl Database {
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, rusqlite::Error> {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, CardsError> {
         let manager = SqliteConnectionManager::file(path);
         let pool = Pool::builder()
             .connection_customizer(Box::new(Customizer))
             .build(manager)
             .unwrap();
-        let conn = pool.get().unwrap();
-        let version = conn.query_row("PRAGMA user_version", NO_PARAMS, |row| {
-            Ok(row.get::<_, i64>(0)? as usize)
-        })?;
-        if version < SQL.len() {
-            if version == 0 {
-                conn.execute_batch(SQL[0])?;
-            } else {
-                for upgrade in SQL[version..].iter() {
-                    conn.execute_batch(upgrade)?;
-                }
-            }
-            conn.execute_batch(&format!("PRAGMA user_version = {}", SQL.len()))?;
+        let mut conn = pool.get().unwrap();
+        conn.execute_batch("PRAGMA journal_mode = WAL")?;
+        // a database from a newer server is left alone rather than rolled back
+        if schema_version(&conn)? < latest_version() {
+            migrate(&mut conn, latest_version(), false)?;
         }
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            closed: RwLock::new(false),
+        })
+    }
+
+    // waits for transactions in progress, any started afterwards fail
+    pub fn close(&self) {
+        task::block_in_place(|| *self.closed.write().unwrap() = true);
     }
 
     pub fn run_read_only<F, T>(&self, f: F) -> Result<T, CardsError>
@@ -65,6 +69,10 @@ impl Database {
     where
         F: FnMut(Transaction) -> Result<T, CardsError>,
     {
+        let closed = self.closed.read().unwrap();
+        if *closed {
+            return Err(CardsError::ShuttingDown);
+        }
         let mut conn = self.pool.get().unwrap();
         for i in 0.. {
             let result = conn
@@ -76,7 +84,10 @@ impl Database {
                 .map_err(|e| e.into())
                 .and_then(&mut f);
             match result {
-                Err(e) if i < 5 && e.is_retriable() => continue,
+                Err(e) if i < 5 && e.is_retriable() => {
+                    record_sqlite_retry();
+                    continue;
+                }
                 v => return v,
             }
         }
@@ -112,7 +123,10 @@ macro_rules! sql_str {
     };
 }
 
+sql_str!(DuplicateId);
 sql_str!(GameId);
+sql_str!(JoinToken);
+sql_str!(Role);
 sql_str!(UserId);
 
 impl<T> ToSql for SqlStr<T>
@@ -178,11 +192,20 @@ macro_rules! sql_json {
     };
 }
 
+sql_json!(AdminAction);
+sql_json!(AnalysisResponse);
 sql_json!(BotStrategy);
 sql_json!(ChargingRules);
 sql_json!(GameEvent);
+sql_json!(GameLength);
+sql_json!(GameSnapshot);
+sql_json!(RatedPlayer);
+sql_json!(RuleSet);
 sql_json!(Seat);
 sql_json!(Seed);
+sql_json!(SpectatorPolicy);
+sql_json!(StoredBot);
+sql_json!(TimeControl);
 
 impl<T> ToSql for SqlJson<T>
 where
@@ -194,6 +217,44 @@ where
     }
 }
 
+// bots added without options are stored as just their strategy, as every bot
+// was before options existed
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StoredBot {
+    Strategy(BotStrategy),
+    WithOptions {
+        strategy: BotStrategy,
+        options: BotOptions,
+    },
+}
+
+impl StoredBot {
+    pub fn from_player(player: &Player) -> Option<Self> {
+        match *player {
+            Player::Human { .. } => None,
+            Player::Bot {
+                strategy, options, ..
+            } if options.is_default() => Some(StoredBot::Strategy(strategy)),
+            Player::Bot {
+                strategy, options, ..
+            } => Some(StoredBot::WithOptions { strategy, options }),
+        }
+    }
+
+    pub fn into_player(self, user_id: UserId) -> Player {
+        let (strategy, options) = match self {
+            StoredBot::Strategy(strategy) => (strategy, BotOptions::default()),
+            StoredBot::WithOptions { strategy, options } => (strategy, options),
+        };
+        Player::Bot {
+            user_id,
+            strategy,
+            options,
+        }
+    }
+}
+
 pub trait GetJson {
     fn get_json<T>(&self, idx: usize) -> Result<T, rusqlite::Error>
     where
@@ -0,0 +1,147 @@
+use crate::{CardsError, Storage, StoredEvent};
+use std::collections::HashSet;
+use turbo_hearts_api::{
+    DuplicateBoard, DuplicateId, DuplicateResult, DuplicateSummaryResponse, DuplicateTable,
+    GameEvent, GameId, GameState, HashedSeed, PassDirection, Seat,
+};
+
+// the other tables of a set still being played are dealt the same cards
+pub fn check_boards_played(storage: &dyn Storage, game_id: GameId) -> Result<(), CardsError> {
+    if let Some(duplicate_id) = storage.load_duplicate_id(game_id)? {
+        for table in storage.load_duplicate_games(duplicate_id)? {
+            if storage.load_game_times(table)?.1.is_none() {
+                return Err(CardsError::DuplicateInProgress(duplicate_id));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn load_duplicate_summary(
+    storage: &dyn Storage,
+    duplicate_id: DuplicateId,
+) -> Result<DuplicateSummaryResponse, CardsError> {
+    let mut tables = storage
+        .load_duplicate_games(duplicate_id)?
+        .into_iter()
+        .enumerate()
+        .map(|(rotation, game_id)| DuplicateTable {
+            game_id,
+            rotation: rotation as u8,
+            players: None,
+        })
+        .collect::<Vec<_>>();
+    if tables.is_empty() {
+        return Err(CardsError::UnknownDuplicate(duplicate_id));
+    }
+
+    let mut boards = vec![
+        DuplicateBoard {
+            pass: PassDirection::Left,
+            results: Vec::new(),
+        },
+        DuplicateBoard {
+            pass: PassDirection::Right,
+            results: Vec::new(),
+        },
+        DuplicateBoard {
+            pass: PassDirection::Across,
+            results: Vec::new(),
+        },
+        DuplicateBoard {
+            pass: PassDirection::Keeper,
+            results: Vec::new(),
+        },
+    ];
+    // tables that won't play any more boards, having completed or been aborted
+    let mut finished = HashSet::new();
+    for table in &mut tables {
+        let game_id = table.game_id;
+        let mut seed = HashedSeed::new();
+        let mut state = GameState::new();
+        let mut hand = 0;
+        for StoredEvent { event, .. } in storage.load_game_events(game_id)? {
+            if let GameEvent::Sit {
+                north,
+                east,
+                south,
+                west,
+                seed: sit_seed,
+                ..
+            } = &event
+            {
+                table.players = Some([**north, **east, **south, **west]);
+                seed = sit_seed.into();
+            }
+            // the hand an aborted game was in the middle of has no result
+            if let GameEvent::Abort = event {
+                finished.insert(game_id);
+                continue;
+            }
+            let was_playing = state.phase.is_playing();
+            state.apply(&event);
+            if was_playing && !state.phase.is_playing() {
+                let scores = state.scores();
+                let players = table.players.unwrap();
+                // games longer than a round deal a new board every hand
+                if hand == boards.len() {
+                    boards.push(DuplicateBoard {
+                        pass: PassDirection::from((hand % 4) as u8),
+                        results: Vec::new(),
+                    });
+                }
+                for &seat in &Seat::VALUES {
+                    boards[hand].results.push(DuplicateResult {
+                        game_id,
+                        hand: seed.unrotate(seat),
+                        seat,
+                        player: players[seat.idx()],
+                        money: scores.money(seat),
+                        difference: 0.0,
+                    });
+                }
+                hand += 1;
+                if state.phase.is_complete() {
+                    finished.insert(game_id);
+                }
+            }
+        }
+    }
+
+    // a board is only shown once every table has played it, since until then
+    // its results give away the cards to the tables that haven't
+    for board in &mut boards {
+        let played = board
+            .results
+            .iter()
+            .map(|result| result.game_id)
+            .collect::<HashSet<_>>();
+        if tables
+            .iter()
+            .any(|table| !played.contains(&table.game_id) && !finished.contains(&table.game_id))
+        {
+            board.results.clear();
+        }
+    }
+
+    // each hand is compared against everyone else who was dealt the same cards
+    for board in &mut boards {
+        for &hand in &Seat::VALUES {
+            let money = board
+                .results
+                .iter()
+                .filter(|result| result.hand == hand)
+                .map(|result| result.money as f64)
+                .collect::<Vec<_>>();
+            let average = money.iter().sum::<f64>() / money.len() as f64;
+            for result in board.results.iter_mut().filter(|r| r.hand == hand) {
+                result.difference = result.money as f64 - average;
+            }
+        }
+    }
+    Ok(DuplicateSummaryResponse {
+        duplicate_id,
+        tables,
+        boards,
+    })
+}
@@ -3,19 +3,92 @@ use http::{header, Response, StatusCode};
 use rusqlite::ErrorCode;
 use std::convert::Infallible;
 use thiserror::Error;
-use turbo_hearts_api::{GameId, RulesError, UserId};
+use turbo_hearts_api::{
+    BotOptions, DuplicateId, GameEvent, GameId, GameLength, JoinToken, RecordError, RulesError,
+    SpectatorPolicy, TimeControl, UserId,
+};
 use warp::{reject::Reject, Rejection, Reply};
 
 #[derive(Debug, Error)]
 pub enum CardsError {
+    #[error("game {0} could not be analyzed")]
+    AnalysisFailed(GameId),
+    #[error("{0} has been banned")]
+    BannedUser(UserId),
+    #[error("This game was already imported as game {0}")]
+    DuplicateImport(GameId),
+    #[error("Duplicate set {0} is still being played")]
+    DuplicateInProgress(DuplicateId),
     #[error("game {0} has already started")]
     GameHasStarted(GameId),
+    #[error("event {event_id} is illegal, {source}: {event:?}")]
+    IllegalEvent {
+        event_id: usize,
+        event: Box<GameEvent>,
+        source: RulesError,
+    },
     #[error("Game {0} hasn't completed yet")]
     IncompleteGame(GameId),
+    #[error("Game {0} hydrated from its latest snapshot differs from a full replay")]
+    InconsistentSnapshot(GameId),
+    #[error("Duplicate sets need between 2 and 4 tables, not {0}")]
+    InvalidTableCount(u8),
+    #[error(
+        "bots can think for at most {}ms and {} iterations, not {0:?}",
+        BotOptions::MAX_TIME_MS,
+        BotOptions::MAX_ITERATIONS
+    )]
+    InvalidBotOptions(BotOptions),
+    #[error(
+        "games last between 1 and {} rounds or to a target score between {} and {}, not {0:?}",
+        GameLength::MAX_ROUNDS,
+        GameLength::MIN_SCORE,
+        GameLength::MAX_SCORE
+    )]
+    InvalidGameLength(GameLength),
+    #[error("{0} is not a valid join token for game {1}")]
+    InvalidJoinToken(JoinToken, GameId),
     #[error("{0} is not a member of game {1}")]
     InvalidPlayer(UserId, GameId),
+    #[error(
+        "spectators can be delayed by at most {} seconds, not {0:?}",
+        SpectatorPolicy::MAX_DELAY_SECONDS
+    )]
+    InvalidSpectatorPolicy(SpectatorPolicy),
+    #[error(
+        "clocks must have between 1 and {} seconds, not {0:?}",
+        TimeControl::MAX_SECONDS
+    )]
+    InvalidTimeControl(TimeControl),
+    #[error("unexpected io error")]
+    Io {
+        #[from]
+        source: std::io::Error,
+    },
+    #[error("unexpected metrics error")]
+    Metrics {
+        #[from]
+        source: prometheus::Error,
+    },
+    #[error("Spectators of game {0} must choose a seat to follow")]
+    MissingFollow(GameId),
+    #[error("Game records must start by seating four different players")]
+    MissingPlayers,
+    #[error("{0} is not an admin")]
+    NotAdmin(UserId),
     #[error("Games need at least 4 players to start")]
     NotEnoughPlayers,
+    #[error("Game {0} wasn't imported, so there's nothing to confirm")]
+    NotImported(GameId),
+    #[error("{0} is not invited to private game {1}")]
+    NotInvited(UserId, GameId),
+    #[error("invalid game record, {source}")]
+    Record {
+        #[from]
+        source: RecordError,
+    },
+    #[error("Game records need a seed, any chosen seed will do for games dealt by hand")]
+    RedactedSeed,
     #[error("unexpected rules error")]
     Rules {
         #[from]
@@ -26,6 +99,8 @@ pub enum CardsError {
         #[from]
         source: serde_json::Error,
     },
+    #[error("the server is shutting down, try again once it restarts")]
+    ShuttingDown,
     #[error("unexpected sqlite error")]
     Sqlite {
         #[from]
@@ -33,8 +108,18 @@ pub enum CardsError {
     },
     #[error("{0} is not a known auth token")]
     UnknownAuthToken(String),
+    #[error("{0} has no chat message at {1}")]
+    UnknownChat(UserId, i64),
+    #[error("{0} is not a known duplicate set id")]
+    UnknownDuplicate(DuplicateId),
     #[error("{0} is not a known game id")]
     UnknownGame(GameId),
+    #[error("the database is at schema version {0}, which is newer than this server")]
+    UnknownSchemaVersion(usize),
+    #[error("{0} is not a known user id")]
+    UnknownUser(UserId),
+    #[error("a request for game {0} can't be sent over the socket of game {1}")]
+    WrongGame(GameId, GameId),
 }
 
 impl CardsError {
@@ -57,11 +142,21 @@ pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible>
             .unwrap()
     } else if let Some(error) = err.find::<CardsError>() {
         let status = match error {
-            CardsError::Serde { .. } | CardsError::Sqlite { .. } => {
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-            CardsError::UnknownGame { .. } => StatusCode::NOT_FOUND,
+            CardsError::AnalysisFailed { .. }
+            | CardsError::Io { .. }
+            | CardsError::Metrics { .. }
+            | CardsError::Serde { .. }
+            | CardsError::Sqlite { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            CardsError::UnknownChat { .. }
+            | CardsError::UnknownDuplicate { .. }
+            | CardsError::UnknownGame { .. }
+            | CardsError::UnknownUser { .. } => StatusCode::NOT_FOUND,
             CardsError::UnknownAuthToken { .. } => StatusCode::UNAUTHORIZED,
+            CardsError::ShuttingDown => StatusCode::SERVICE_UNAVAILABLE,
+            CardsError::BannedUser { .. }
+            | CardsError::InvalidJoinToken { .. }
+            | CardsError::NotAdmin { .. }
+            | CardsError::NotInvited { .. } => StatusCode::FORBIDDEN,
             _ => StatusCode::BAD_REQUEST,
         };
         Response::builder()
@@ -1,39 +1,61 @@
-use crate::{util, BotRunner, CardsError, Database, GetJson, Subscriber, ToSqlJson, ToSqlStr};
-use log::info;
+use crate::{
+    record_action, record_hydration, util, BotRunner, CardsError, GameOptions, Storage, Subscriber,
+};
+use log::{error, info};
 use rand_distr::Gamma;
-use rusqlite::{ToSql, Transaction};
 use std::{
-    collections::{hash_map::Entry, HashMap, HashSet},
-    sync::Arc,
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 use tokio::{
     sync::{mpsc, mpsc::UnboundedReceiver, Mutex},
     task,
+    time::{self, Duration, Instant},
 };
 use turbo_hearts_api::{
-    Card, Cards, GameEvent, GameId, GamePhase, HashedSeed, PassDirection, Player,
-    PlayerWithOptions, Seat, Seed, UserId,
+    BotOptions, Card, Cards, ChargingRules, GameEvent, GameId, GamePhase, GameSnapshot, HashedSeed,
+    JoinToken, PassDirection, Player, PlayerWithOptions, RulesError, Seat, Seed, SpectatorPolicy,
+    TimeControl, UserId,
 };
 
 type Game = turbo_hearts_api::Game<Subscriber>;
 
 #[derive(Clone)]
 pub struct Games {
-    db: &'static Database,
+    storage: &'static dyn Storage,
     bot_delay: Option<Gamma<f32>>,
+    check_snapshots: bool,
+    closed: Arc<AtomicBool>,
     inner: Arc<Mutex<HashMap<GameId, Arc<Mutex<Game>>>>>,
+    // the seat a substitute acting through this handle plays for
+    substitute: Option<Seat>,
 }
 
 impl Games {
-    pub fn new(db: &'static Database, delay: bool) -> Self {
+    pub fn new(storage: &'static dyn Storage, delay: bool, check_snapshots: bool) -> Self {
         Self {
-            db,
+            storage,
             bot_delay: if delay {
                 Some(Gamma::new(2.5, 0.8).unwrap())
             } else {
                 None
             },
+            check_snapshots,
+            closed: Arc::new(AtomicBool::new(false)),
             inner: Arc::new(Mutex::new(HashMap::new())),
+            substitute: None,
+        }
+    }
+
+    // actions are refused once the player resumes, even if the bot was already
+    // deciding on one
+    pub fn substitute(&self, seat: Seat) -> Self {
+        Self {
+            substitute: Some(seat),
+            ..self.clone()
         }
     }
 
@@ -43,7 +65,13 @@ impl Games {
         for (game_id, game) in inner.iter() {
             let mut game = game.lock().await;
             broadcast(&mut game, &GameEvent::Ping);
-            if game.subscribers.is_empty() {
+            // games stay loaded while a bot has to act, even if no one is watching
+            if game.subscribers.is_empty()
+                && !game
+                    .bots
+                    .iter()
+                    .any(|(seat, _)| game.state.is_waiting_on(*seat))
+            {
                 unwatched.push(*game_id);
             }
         }
@@ -52,6 +80,48 @@ impl Games {
         }
     }
 
+    // so the bots carry on without anyone watching
+    pub async fn load_bot_games(&self) -> Result<usize, CardsError> {
+        let game_ids = self.storage.load_bot_games()?;
+        let mut loaded = 0;
+        for game_id in game_ids {
+            match self.with_game(game_id, |_| Ok(())).await {
+                Ok(()) => loaded += 1,
+                Err(e) => error!("load_bot_games: game_id={}, error={:?}", game_id, e),
+            }
+        }
+        Ok(loaded)
+    }
+
+    // waits for actions in progress before disconnecting everyone
+    pub async fn shutdown(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        let mut inner = self.inner.lock().await;
+        for game in inner.values() {
+            let mut game = game.lock().await;
+            for (_, sub) in &game.subscribers {
+                sub.send(GameEvent::Reconnect);
+            }
+            game.subscribers.clear();
+            game.bots.clear();
+        }
+        inner.clear();
+        info!("shutdown: games closed");
+    }
+
+    pub async fn counts(&self) -> (usize, usize) {
+        // games are counted without holding up everyone loading one
+        let games = {
+            let inner = self.inner.lock().await;
+            inner.values().cloned().collect::<Vec<_>>()
+        };
+        let mut subscribers = 0;
+        for game in &games {
+            subscribers += game.lock().await.subscribers.len();
+        }
+        (games.len(), subscribers)
+    }
+
     async fn with_game<F, T>(&self, game_id: GameId, f: F) -> Result<T, CardsError>
     where
         F: FnOnce(&mut Game) -> Result<T, CardsError>,
@@ -70,9 +140,24 @@ impl Games {
             }
         };
         let mut game = game.lock().await;
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(CardsError::ShuttingDown);
+        }
         if game.events.is_empty() {
-            self.db
-                .run_read_only(|tx| hydrate_events(&tx, game_id, &mut game))?;
+            let start = Instant::now();
+            let (mut snapshot, mut events) = self.storage.load_game(game_id)?;
+            if self.check_snapshots {
+                if let Err(e) = check_snapshot(self.storage, game_id) {
+                    error!("hydrate: game_id={}, error={:?}", game_id, e);
+                    snapshot = None;
+                    events = stored_events(self.storage, game_id)?;
+                }
+            }
+            hydrate(&mut game, snapshot, events);
+            record_hydration(start.elapsed());
+            if let Some(time_control) = game.time_control {
+                start_clocks(&mut game, time_control);
+            }
         }
         if game.events.is_empty() {
             Err(CardsError::UnknownGame(game_id))
@@ -86,10 +171,28 @@ impl Games {
                     ..
                 } = game.events[0].clone()
                 {
-                    self.run_bot(game_id, Seat::North, north, &mut game);
-                    self.run_bot(game_id, Seat::East, east, &mut game);
-                    self.run_bot(game_id, Seat::South, south, &mut game);
-                    self.run_bot(game_id, Seat::West, west, &mut game);
+                    self.run_bot(game_id, Seat::North, *north, &mut game);
+                    self.run_bot(game_id, Seat::East, *east, &mut game);
+                    self.run_bot(game_id, Seat::South, *south, &mut game);
+                    self.run_bot(game_id, Seat::West, *west, &mut game);
+                }
+                for &seat in &Seat::VALUES {
+                    if let Some(strategy) = game.substitutes[seat.idx()] {
+                        let user_id = game.players[seat.idx()];
+                        let options = BotOptions::default();
+                        let player = Player::Bot {
+                            user_id,
+                            strategy,
+                            options,
+                        };
+                        self.run_bot(game_id, seat, player, &mut game);
+                    }
+                }
+            }
+            if let Some(seat) = self.substitute {
+                if game.substitutes[seat.idx()].is_none() {
+                    let user_id = game.players[seat.idx()];
+                    return Err(RulesError::NotSubstituted(user_id).into());
                 }
             }
             f(&mut game)
@@ -97,16 +200,25 @@ impl Games {
     }
 
     fn run_bot(&self, game_id: GameId, seat: Seat, player: Player, game: &mut Game) {
-        if let Player::Bot { user_id, strategy } = player {
+        if let Player::Bot {
+            user_id,
+            strategy,
+            options,
+        } = player
+        {
             let (tx, rx) = mpsc::unbounded_channel();
-            let sub = Subscriber::new(tx, None);
-            task::spawn(BotRunner::new(user_id, strategy).run(
-                game_id,
-                self.clone(),
-                rx,
-                self.bot_delay,
-            ));
-            self.replay_events(&game, &sub, Some(seat));
+            let sub = Subscriber::new(tx, None, None);
+            let games = match game.substitutes[seat.idx()] {
+                Some(_) => self.substitute(seat),
+                None => self.clone(),
+            };
+            let mut runner = BotRunner::new(user_id, strategy, options);
+            if let Some(snapshot) = &game.restored {
+                runner = runner.with_state(snapshot.state.clone());
+            }
+            task::spawn(runner.run(game_id, games, rx, self.bot_delay));
+            // a bot is never behind a snapshot, so its replay can't fail
+            let _ = self.replay_events(game_id, game, &sub, Some(seat));
             game.bots.push((seat, sub));
             info!(
                 "run_bot: game_id={}, seat={}, player={:?}",
@@ -120,31 +232,34 @@ impl Games {
         game_id: GameId,
         players: [PlayerWithOptions; 4],
         seed: Seed,
+        options: GameOptions,
     ) -> Result<(), CardsError> {
         let hashed_seed = HashedSeed::from(&seed);
-        let result = self.db.run_with_retry(|tx| {
-            persist_events(
-                &tx,
-                game_id,
-                0,
-                &[
-                    GameEvent::Sit {
-                        north: players[0].player,
-                        east: players[1].player,
-                        south: players[2].player,
-                        west: players[3].player,
-                        rules: players[0].rules,
-                        seed: seed.clone(),
-                    },
-                    hashed_seed.deal(PassDirection::Left),
-                ],
-            )
-        });
+        let result = self.storage.persist_events(
+            game_id,
+            0,
+            &[
+                GameEvent::Sit {
+                    north: Box::new(players[0].player),
+                    east: Box::new(players[1].player),
+                    south: Box::new(players[2].player),
+                    west: Box::new(players[3].player),
+                    rules: players[0].rules,
+                    seed: seed.clone(),
+                    time_control: options.time_control,
+                    spectators: options.spectators,
+                    rule_set: options.rule_set,
+                    length: options.length,
+                },
+                hashed_seed.deal(PassDirection::Left),
+            ],
+        );
         info!(
             "start_game: game_id={}, error={:?}",
             game_id,
             result.as_ref().err()
         );
+        record_action("start_game", &result);
         result
     }
 
@@ -153,36 +268,160 @@ impl Games {
         game_id: GameId,
         user_id: UserId,
         last_event_id: Option<usize>,
+        follow: Option<Seat>,
+        join_token: Option<JoinToken>,
     ) -> Result<UnboundedReceiver<(GameEvent, usize)>, CardsError> {
+        self.storage.check_access(game_id, user_id, join_token)?;
         let (tx, rx) = mpsc::unbounded_channel();
-        let sub = Subscriber::new(tx, last_event_id);
-        self.with_game(game_id, |game| {
-            let seat = game.seat(user_id);
-            let mut subscribers = game
-                .subscribers
-                .iter()
-                .map(|(user_id, _)| *user_id)
-                .collect::<HashSet<_>>();
-            if subscribers.insert(user_id) {
-                broadcast(game, &GameEvent::JoinGame { user_id });
-            }
-            self.replay_events(&game, &sub, seat);
-            sub.send(GameEvent::EndReplay { subscribers });
-            game.subscribers.push((user_id, sub));
-            Ok(())
-        })
-        .await?;
-        info!("subscribe: game_id={}, user_id={}", game_id, user_id);
-        Ok(rx)
+        let sub = Subscriber::new(tx, last_event_id, follow);
+        let delay = self
+            .with_game(game_id, |game| {
+                let seat = game.seat(user_id);
+                if seat.is_none()
+                    && game.spectators == SpectatorPolicy::FollowSeat
+                    && follow.is_none()
+                {
+                    return Err(CardsError::MissingFollow(game_id));
+                }
+                let mut subscribers = game
+                    .subscribers
+                    .iter()
+                    .map(|(user_id, _)| *user_id)
+                    .collect::<HashSet<_>>();
+                if subscribers.insert(user_id) {
+                    broadcast(game, &GameEvent::JoinGame { user_id });
+                }
+                self.replay_events(game_id, game, &sub, seat)?;
+                sub.send(GameEvent::EndReplay { subscribers });
+                game.subscribers.push((user_id, sub));
+                Ok(match seat {
+                    Some(_) => 0,
+                    None => game.spectators.delay_millis(),
+                })
+            })
+            .await?;
+        info!(
+            "subscribe: game_id={}, user_id={}, follow={:?}",
+            game_id, user_id, follow
+        );
+        if delay > 0 {
+            Ok(delay_events(rx, Duration::from_millis(delay as u64)))
+        } else {
+            Ok(rx)
+        }
     }
 
-    fn replay_events(&self, game: &Game, sub: &Subscriber, seat: Option<Seat>) {
+    fn replay_events(
+        &self,
+        game_id: GameId,
+        game: &Game,
+        sub: &Subscriber,
+        seat: Option<Seat>,
+    ) -> Result<(), CardsError> {
         let mut copy = Game::new();
-        for event in &game.events {
-            copy.apply(event, |g, e| {
-                sub.send(e.redact(seat, g.state.rules));
-            });
+        let mut send = |g: &mut Game, e: &GameEvent| {
+            if game.time_control.is_some() && e.is_status() && g.event_id() == game.event_id() {
+                let e = &e.with_clock(game.clock);
+                sub.send(redact(e, g.state.rules, game.spectators, seat, sub));
+            } else {
+                sub.send(redact(e, g.state.rules, game.spectators, seat, sub));
+            }
+        };
+        let events = match &game.restored {
+            // a subscriber that missed events from before the snapshot is
+            // sent every event
+            Some(snapshot) if (1..snapshot.broadcasts).contains(&sub.last_event_id()) => {
+                stored_events(self.storage, game_id)?
+            }
+            Some(snapshot) => {
+                copy.restore(game.events[0].clone(), snapshot.clone(), &mut send);
+                sub.skip_to(snapshot.broadcasts);
+                game.events[1..].to_vec()
+            }
+            None => game.events.clone(),
+        };
+        for event in &events {
+            copy.apply(event, &mut send);
         }
+        Ok(())
+    }
+
+    pub async fn expire_clocks(&self) {
+        let game_ids = {
+            let inner = self.inner.lock().await;
+            inner.keys().copied().collect::<Vec<_>>()
+        };
+        let now = util::timestamp();
+        for game_id in game_ids {
+            let result = self
+                .with_game(game_id, |game| {
+                    let strategy = match game.time_control {
+                        Some(time_control) => time_control.strategy,
+                        None => return Ok(Vec::new()),
+                    };
+                    let events = Seat::VALUES
+                        .iter()
+                        .filter(|&&seat| is_idle(game, seat) && game.clock.is_expired(seat, now))
+                        .map(|&seat| GameEvent::Takeover { seat, strategy })
+                        .collect::<Vec<_>>();
+                    if events.is_empty() {
+                        return Ok(events);
+                    }
+                    self.storage
+                        .persist_events(game_id, game.event_id(), &events)?;
+                    for event in &events {
+                        game.apply(event, |g, e| broadcast(g, e));
+                        if let GameEvent::Takeover { seat, strategy } = *event {
+                            let user_id = game.players[seat.idx()];
+                            let options = BotOptions::default();
+                            let player = Player::Bot {
+                                user_id,
+                                strategy,
+                                options,
+                            };
+                            self.run_bot(game_id, seat, player, game);
+                        }
+                    }
+                    Ok(events)
+                })
+                .await;
+            match result {
+                Ok(events) => {
+                    for event in events {
+                        info!("expire_clocks: game_id={}, event={:?}", game_id, event);
+                    }
+                }
+                Err(e) => info!("expire_clocks: game_id={}, error={:?}", game_id, e),
+            }
+        }
+    }
+
+    pub async fn resume(&self, game_id: GameId, user_id: UserId) -> Result<(), CardsError> {
+        let result = self
+            .with_game(game_id, |game| match game.seat(user_id) {
+                None => Err(CardsError::InvalidPlayer(user_id, game_id)),
+                Some(seat) => {
+                    game.verify_resume(game_id, seat)?;
+                    let event = GameEvent::Resume { seat };
+                    self.storage.persist_events(
+                        game_id,
+                        game.event_id(),
+                        std::slice::from_ref(&event),
+                    )?;
+                    game.apply(&event, |g, e| broadcast(g, e));
+                    game.bots.retain(|(s, _)| *s != seat);
+                    Ok(())
+                }
+            })
+            .await;
+        info!(
+            "resume: game_id={}, user_id={}, error={:?}",
+            game_id,
+            user_id,
+            result.as_ref().err()
+        );
+        record_action("resume", &result);
+        result
     }
 
     pub async fn pass_cards(
@@ -219,11 +458,10 @@ impl Games {
                     {
                         let mut hands = game.post_pass_hand;
                         hands[seat.idx()] -= cards;
-                        events.extend_from_slice(&game.seed.keeper_pass(hands));
+                        events.extend_from_slice(&game.seed.keeper_pass(game.state.hand, hands));
                     }
-                    self.db.run_with_retry(|tx| {
-                        persist_events(&tx, game_id, game.events.len(), &events)
-                    })?;
+                    self.storage
+                        .persist_events(game_id, game.event_id(), &events)?;
                     for event in events {
                         game.apply(&event, |g, e| broadcast(g, e));
                     }
@@ -238,6 +476,7 @@ impl Games {
             cards,
             result.as_ref().err()
         );
+        record_action("pass", &result);
         result
     }
 
@@ -253,9 +492,11 @@ impl Games {
                 Some(seat) => {
                     game.verify_charge(game_id, seat, cards)?;
                     let event = GameEvent::Charge { seat, cards };
-                    self.db.run_with_retry(|tx| {
-                        persist_events(&tx, game_id, game.events.len(), &[event.clone()])
-                    })?;
+                    self.storage.persist_events(
+                        game_id,
+                        game.event_id(),
+                        std::slice::from_ref(&event),
+                    )?;
                     game.apply(&event, |g, e| broadcast(g, e));
                     Ok(())
                 }
@@ -268,6 +509,7 @@ impl Games {
             cards,
             result.as_ref().err()
         );
+        record_action("charge", &result);
         result
     }
 
@@ -282,23 +524,31 @@ impl Games {
                 None => Err(CardsError::InvalidPlayer(user_id, game_id)),
                 Some(seat) => {
                     game.verify_play(game_id, seat, card)?;
-                    let mut events = vec![GameEvent::Play { seat, card }];
+                    let event = GameEvent::Play { seat, card };
                     let ends_hand = game.state.played | card == Cards::ALL;
-                    if ends_hand {
-                        if let Some(event) = game.deal_event() {
-                            events.push(event);
-                        }
-                    }
-                    self.db.run_with_retry(|tx| {
-                        persist_events(&tx, game_id, game.events.len(), &events)?;
-                        if ends_hand && game.state.phase == GamePhase::PlayKeeper {
-                            finish_game(&tx, game_id)?;
-                        }
-                        Ok(())
-                    })?;
+                    let deal = game.deal_event(&event);
+                    let ends_game = ends_hand && deal.is_none();
+                    let starts_hand = deal.is_some();
+                    let snapshot = if starts_hand {
+                        game.hand_snapshot(&event)
+                    } else {
+                        None
+                    };
+                    let mut events = vec![event];
+                    events.extend(deal);
+                    self.storage.persist_hand(
+                        game_id,
+                        game.event_id(),
+                        &events,
+                        snapshot.as_ref(),
+                        ends_game,
+                    )?;
                     for event in events {
                         game.apply(&event, |g, e| broadcast(g, e));
                     }
+                    if let Some(snapshot) = snapshot {
+                        game.rebase(snapshot);
+                    }
                     Ok(game.state.phase == GamePhase::Complete)
                 }
             })
@@ -310,6 +560,7 @@ impl Games {
             card,
             result.as_ref().err()
         );
+        record_action("play", &result);
         result
     }
 
@@ -323,9 +574,11 @@ impl Games {
                         seat,
                         hand: game.post_pass_hand[seat.idx()] - game.state.played,
                     };
-                    self.db.run_with_retry(|tx| {
-                        persist_events(&tx, game_id, game.events.len(), &[event.clone()])
-                    })?;
+                    self.storage.persist_events(
+                        game_id,
+                        game.event_id(),
+                        std::slice::from_ref(&event),
+                    )?;
                     game.apply(&event, |g, e| broadcast(g, e));
                     Ok(())
                 }
@@ -337,6 +590,7 @@ impl Games {
             user_id,
             result.as_ref().err()
         );
+        record_action("claim", &result);
         result
     }
 
@@ -351,30 +605,38 @@ impl Games {
                 None => Err(CardsError::InvalidPlayer(user_id, game_id)),
                 Some(seat) => {
                     game.verify_accept_claim(game_id, claimer, seat)?;
-                    let mut events = vec![GameEvent::AcceptClaim {
+                    let event = GameEvent::AcceptClaim {
                         claimer,
                         acceptor: seat,
-                    }];
+                    };
                     let ends_hand = game
                         .state
                         .claims
                         .accept(claimer, seat)
                         .successfully_claimed(claimer);
-                    if ends_hand {
-                        if let Some(event) = game.deal_event() {
-                            events.push(event);
-                        }
-                    }
-                    self.db.run_with_retry(|tx| {
-                        persist_events(&tx, game_id, game.events.len(), &events)?;
-                        if ends_hand && game.state.phase == GamePhase::PlayKeeper {
-                            finish_game(&tx, game_id)?;
-                        }
-                        Ok(())
-                    })?;
+                    let deal = game.deal_event(&event);
+                    let ends_game = ends_hand && deal.is_none();
+                    let starts_hand = deal.is_some();
+                    let snapshot = if starts_hand {
+                        game.hand_snapshot(&event)
+                    } else {
+                        None
+                    };
+                    let mut events = vec![event];
+                    events.extend(deal);
+                    self.storage.persist_hand(
+                        game_id,
+                        game.event_id(),
+                        &events,
+                        snapshot.as_ref(),
+                        ends_game,
+                    )?;
                     for event in events {
                         game.apply(&event, |g, e| broadcast(g, e));
                     }
+                    if let Some(snapshot) = snapshot {
+                        game.rebase(snapshot);
+                    }
                     Ok(game.state.phase == GamePhase::Complete)
                 }
             })
@@ -386,6 +648,7 @@ impl Games {
             claimer,
             result.as_ref().err()
         );
+        record_action("accept_claim", &result);
         result
     }
 
@@ -404,9 +667,11 @@ impl Games {
                         claimer,
                         rejector: seat,
                     };
-                    self.db.run_with_retry(|tx| {
-                        persist_events(&tx, game_id, game.events.len(), &[event.clone()])
-                    })?;
+                    self.storage.persist_events(
+                        game_id,
+                        game.event_id(),
+                        std::slice::from_ref(&event),
+                    )?;
                     game.apply(&event, |g, e| broadcast(g, e));
                     Ok(())
                 }
@@ -419,6 +684,110 @@ impl Games {
             claimer,
             result.as_ref().err()
         );
+        record_action("reject_claim", &result);
+        result
+    }
+
+    pub async fn request_undo(&self, game_id: GameId, user_id: UserId) -> Result<(), CardsError> {
+        let result = self
+            .with_game(game_id, |game| match game.seat(user_id) {
+                None => Err(CardsError::InvalidPlayer(user_id, game_id)),
+                Some(seat) => {
+                    game.verify_request_undo(game_id)?;
+                    let event = GameEvent::RequestUndo { seat };
+                    self.storage.persist_events(
+                        game_id,
+                        game.event_id(),
+                        std::slice::from_ref(&event),
+                    )?;
+                    game.apply(&event, |g, e| broadcast(g, e));
+                    Ok(())
+                }
+            })
+            .await;
+        info!(
+            "request_undo: game_id={}, user_id={}, error={:?}",
+            game_id,
+            user_id,
+            result.as_ref().err()
+        );
+        record_action("request_undo", &result);
+        result
+    }
+
+    pub async fn accept_undo(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+        requester: Seat,
+    ) -> Result<(), CardsError> {
+        let result = self
+            .with_game(game_id, |game| match game.seat(user_id) {
+                None => Err(CardsError::InvalidPlayer(user_id, game_id)),
+                Some(seat) => {
+                    game.verify_accept_undo(game_id, requester, seat)?;
+                    let mut events = vec![GameEvent::AcceptUndo {
+                        requester,
+                        acceptor: seat,
+                    }];
+                    if game.state.undo.accept(seat).successfully_undone() {
+                        if let Some(event) = game.undo_event() {
+                            events.push(event);
+                        }
+                    }
+                    self.storage
+                        .persist_events(game_id, game.event_id(), &events)?;
+                    for event in events {
+                        game.apply(&event, |g, e| broadcast(g, e));
+                    }
+                    Ok(())
+                }
+            })
+            .await;
+        info!(
+            "accept_undo: game_id={}, user_id={}, requester={}, error={:?}",
+            game_id,
+            user_id,
+            requester,
+            result.as_ref().err()
+        );
+        record_action("accept_undo", &result);
+        result
+    }
+
+    pub async fn reject_undo(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+        requester: Seat,
+    ) -> Result<(), CardsError> {
+        let result = self
+            .with_game(game_id, |game| match game.seat(user_id) {
+                None => Err(CardsError::InvalidPlayer(user_id, game_id)),
+                Some(seat) => {
+                    game.verify_reject_undo(game_id, requester)?;
+                    let event = GameEvent::RejectUndo {
+                        requester,
+                        rejector: seat,
+                    };
+                    self.storage.persist_events(
+                        game_id,
+                        game.event_id(),
+                        std::slice::from_ref(&event),
+                    )?;
+                    game.apply(&event, |g, e| broadcast(g, e));
+                    Ok(())
+                }
+            })
+            .await;
+        info!(
+            "reject_undo: game_id={}, user_id={}, requester={}, error={:?}",
+            game_id,
+            user_id,
+            requester,
+            result.as_ref().err()
+        );
+        record_action("reject_undo", &result);
         result
     }
 
@@ -431,9 +800,11 @@ impl Games {
         let result = self
             .with_game(game_id, |game| {
                 let event = GameEvent::Chat { user_id, message };
-                self.db.run_with_retry(|tx| {
-                    persist_events(&tx, game_id, game.events.len(), &[event.clone()])
-                })?;
+                self.storage.persist_events(
+                    game_id,
+                    game.event_id(),
+                    std::slice::from_ref(&event),
+                )?;
                 game.apply(&event, |g, e| broadcast(g, e));
                 Ok(())
             })
@@ -444,17 +815,74 @@ impl Games {
             user_id,
             result.as_ref().err()
         );
+        record_action("chat", &result);
+        result
+    }
+
+    pub async fn abort(&self, game_id: GameId, admin_id: UserId) -> Result<(), CardsError> {
+        let result = self
+            .with_game(game_id, |game| {
+                game.verify_abort(game_id)?;
+                let event = GameEvent::Abort;
+                self.storage.abort_game(
+                    admin_id,
+                    game_id,
+                    game.event_id(),
+                    std::slice::from_ref(&event),
+                )?;
+                game.apply(&event, |g, e| broadcast(g, e));
+                game.bots.clear();
+                Ok(())
+            })
+            .await;
+        info!(
+            "abort: game_id={}, admin_id={}, error={:?}",
+            game_id,
+            admin_id,
+            result.as_ref().err()
+        );
+        record_action("abort", &result);
         result
     }
+
+    pub async fn remove(&self, game_id: GameId) {
+        let mut inner = self.inner.lock().await;
+        inner.remove(&game_id);
+    }
+
+    // closing the sockets also drops anything they'd send over them
+    pub async fn disconnect(&self, user_id: UserId) {
+        let games = {
+            let inner = self.inner.lock().await;
+            inner.values().cloned().collect::<Vec<_>>()
+        };
+        for game in &games {
+            let mut game = game.lock().await;
+            let before = game.subscribers.len();
+            game.subscribers.retain(|(id, _)| *id != user_id);
+            if game.subscribers.len() < before {
+                broadcast(&mut game, &GameEvent::LeaveGame { user_id });
+            }
+        }
+        info!("disconnect: user_id={}", user_id);
+    }
 }
 
 fn broadcast(game: &mut Game, event: &GameEvent) {
+    let event = &match game.time_control {
+        Some(time_control) if event.is_status() => {
+            start_clocks(game, time_control);
+            event.with_clock(game.clock)
+        }
+        _ => event.clone(),
+    };
     let rules = game.state.rules;
+    let spectators = game.spectators;
     let players = game.players;
     let mut disconnects = HashSet::new();
     game.subscribers.retain(|(user_id, tx)| {
         let seat = seat(players, *user_id);
-        if tx.send(event.redact(seat, rules)) {
+        if tx.send(redact(event, rules, spectators, seat, tx)) {
             true
         } else {
             disconnects.insert(*user_id);
@@ -474,6 +902,89 @@ fn broadcast(game: &mut Game, event: &GameEvent) {
     }
 }
 
+fn redact(
+    event: &GameEvent,
+    rules: ChargingRules,
+    spectators: SpectatorPolicy,
+    seat: Option<Seat>,
+    sub: &Subscriber,
+) -> GameEvent {
+    match (seat, spectators) {
+        (Some(seat), _) => event.redact(Some(seat), rules),
+        (None, SpectatorPolicy::Omniscient { .. }) => event.redact(None, rules),
+        (None, SpectatorPolicy::HiddenHands) => event.redact_hands(rules),
+        // spectators without a seat to follow are refused when they subscribe
+        (None, SpectatorPolicy::FollowSeat) => match sub.follow() {
+            Some(seat) => event.redact(Some(seat), rules),
+            None => event.redact_hands(rules),
+        },
+    }
+}
+
+// holds each event back until the delay has passed since it was sent, except
+// for pings which keep the connection alive and the reconnect sent on shutdown
+fn delay_events(
+    mut rx: UnboundedReceiver<(GameEvent, usize)>,
+    delay: Duration,
+) -> UnboundedReceiver<(GameEvent, usize)> {
+    let (tx, delayed) = mpsc::unbounded_channel();
+    task::spawn(async move {
+        let mut queue = VecDeque::new();
+        loop {
+            let release = queue.front().map(|(sent, _)| *sent + delay);
+            let sleep = time::sleep_until(release.unwrap_or_else(Instant::now));
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Some((event @ GameEvent::Ping, _)) | Some((event @ GameEvent::Reconnect, _)) => {
+                        if tx.send((event, 0)).is_err() {
+                            break;
+                        }
+                    }
+                    Some(event) => queue.push_back((Instant::now(), event)),
+                    None => break,
+                },
+                _ = sleep, if release.is_some() => {
+                    let (_, event) = queue.pop_front().unwrap();
+                    if tx.send(event).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    delayed
+}
+
+fn start_clocks(game: &mut Game, time_control: TimeControl) {
+    let deadline = util::timestamp() + time_control.millis();
+    for &seat in &Seat::VALUES {
+        game.clock = if is_idle(game, seat) {
+            game.clock.start(seat, deadline)
+        } else {
+            game.clock.stop(seat)
+        };
+    }
+}
+
+// whether a seat is waiting on a human who hasn't been substituted, bots
+// being left to take as long as they need
+fn is_idle(game: &Game, seat: Seat) -> bool {
+    let human = match game.events.first() {
+        Some(GameEvent::Sit {
+            north,
+            east,
+            south,
+            west,
+            ..
+        }) => matches!(
+            **[north, east, south, west][seat.idx()],
+            Player::Human { .. }
+        ),
+        _ => false,
+    };
+    human && game.substitutes[seat.idx()].is_none() && game.state.is_waiting_on(seat)
+}
+
 fn seat(players: [UserId; 4], user_id: UserId) -> Option<Seat> {
     players
         .iter()
@@ -481,40 +992,36 @@ fn seat(players: [UserId; 4], user_id: UserId) -> Option<Seat> {
         .map(|idx| Seat::VALUES[idx])
 }
 
-fn finish_game(tx: &Transaction, game_id: GameId) -> Result<(), CardsError> {
-    tx.execute::<&[&dyn ToSql]>(
-        "UPDATE game SET completed_time = ? WHERE game_id = ?",
-        &[&util::timestamp(), &game_id.sql()],
-    )?;
-    Ok(())
-}
-
-pub fn persist_events(
-    tx: &Transaction,
-    game_id: GameId,
-    event_id: usize,
-    events: &[GameEvent],
-) -> Result<(), CardsError> {
-    let mut stmt = tx.prepare_cached(
-        "INSERT INTO event (game_id, event_id, timestamp, event) VALUES (?, ?, ?, ?)",
-    )?;
-    let timestamp = util::timestamp();
-    let mut event_id = event_id as isize;
+// events are the sit event and those after the snapshot, if there is one
+fn hydrate(game: &mut Game, snapshot: Option<GameSnapshot>, events: Vec<GameEvent>) {
+    let mut events = events.into_iter();
+    if let Some(snapshot) = snapshot {
+        if let Some(sit) = events.next() {
+            game.restore(sit, snapshot, |_, _| {});
+        }
+    }
     for event in events {
-        stmt.execute::<&[&dyn ToSql]>(&[&game_id.sql(), &event_id, &timestamp, &event.sql()])?;
-        event_id += 1;
+        game.apply(&event, |_, _| {});
     }
-    Ok(())
 }
 
-fn hydrate_events(tx: &Transaction, game_id: GameId, game: &mut Game) -> Result<(), CardsError> {
-    let mut stmt = tx.prepare_cached(
-        "SELECT event FROM event WHERE game_id = ? AND event_id >= ? ORDER BY event_id",
-    )?;
-    let mut rows = stmt.query::<&[&dyn ToSql]>(&[&game_id.sql(), &(game.events.len() as i64)])?;
-    while let Some(row) = rows.next()? {
-        let event = row.get_json(0)?;
-        game.apply(&event, |_, _| {});
+fn stored_events(storage: &dyn Storage, game_id: GameId) -> Result<Vec<GameEvent>, CardsError> {
+    Ok(storage
+        .load_game_events(game_id)?
+        .into_iter()
+        .map(|e| e.event)
+        .collect())
+}
+
+// a game hydrated from its snapshot must match one replayed from every event
+pub fn check_snapshot(storage: &dyn Storage, game_id: GameId) -> Result<(), CardsError> {
+    let (snapshot, events) = storage.load_game(game_id)?;
+    let mut hydrated = Game::new();
+    hydrate(&mut hydrated, snapshot, events);
+    let mut replayed = Game::new();
+    hydrate(&mut replayed, None, stored_events(storage, game_id)?);
+    if hydrated.snapshot() != replayed.snapshot() {
+        return Err(CardsError::InconsistentSnapshot(game_id));
     }
     Ok(())
 }
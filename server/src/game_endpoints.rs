@@ -1,28 +1,101 @@
-use crate::{auth_redirect, Games, Lobby};
+use crate::{auth_redirect, socket, CardsError, Games, Lobby};
+use serde::de::DeserializeOwned;
 use tokio::sync::mpsc::UnboundedReceiver;
 use tokio_stream::{wrappers::UnboundedReceiverStream, Stream, StreamExt};
 use turbo_hearts_api::{
-    AcceptClaimRequest, ChargeRequest, ClaimRequest, GameChatRequest, GameEvent, GameId,
-    PassRequest, PlayRequest, RejectClaimRequest, UserId,
+    AcceptClaimRequest, AcceptUndoRequest, ChargeRequest, ClaimRequest, GameAck, GameChatRequest,
+    GameEvent, GameId, GameRequest, PassRequest, PlayRequest, RejectClaimRequest,
+    RejectUndoRequest, RequestUndoRequest, ResumeRequest, SocketMessage, SocketQuery, UserId,
 };
-use warp::{sse, sse::Event, Filter, Rejection, Reply};
+use warp::{sse, sse::Event, ws::Ws, Filter, Rejection, Reply};
 
-pub fn router<'a>(
-    lobby: infallible!(&'a Lobby),
-    games: infallible!(&'a Games),
+pub fn router(
+    lobby: infallible!(&'static Lobby),
+    games: infallible!(&'static Games),
     user_id: rejection!(UserId),
 ) -> reply!() {
     warp::path("game")
         .and(
             html()
                 .or(subscribe(games.clone(), user_id.clone()))
-                .or(pass_cards(games.clone(), user_id.clone()))
-                .or(charge_cards(games.clone(), user_id.clone()))
-                .or(play_card(lobby, games.clone(), user_id.clone()))
-                .or(claim(games.clone(), user_id.clone()))
-                .or(accept_claim(games.clone(), user_id.clone()))
-                .or(reject_claim(games.clone(), user_id.clone()))
-                .or(chat(games, user_id)),
+                .or(socket(lobby.clone(), games.clone(), user_id.clone()))
+                .or(post(
+                    "pass",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    GameRequest::Pass,
+                ))
+                .or(post(
+                    "charge",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    GameRequest::Charge,
+                ))
+                .or(post(
+                    "play",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    GameRequest::Play,
+                ))
+                .or(post(
+                    "claim",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    GameRequest::Claim,
+                ))
+                .or(post(
+                    "accept_claim",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    GameRequest::AcceptClaim,
+                ))
+                .or(post(
+                    "reject_claim",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    GameRequest::RejectClaim,
+                ))
+                .or(post(
+                    "request_undo",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    GameRequest::RequestUndo,
+                ))
+                .or(post(
+                    "accept_undo",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    GameRequest::AcceptUndo,
+                ))
+                .or(post(
+                    "reject_undo",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    GameRequest::RejectUndo,
+                ))
+                .or(post(
+                    "resume",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    GameRequest::Resume,
+                ))
+                .or(post(
+                    "chat",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    GameRequest::Chat,
+                )),
         )
         .boxed()
 }
@@ -41,8 +114,18 @@ fn subscribe<'a>(games: infallible!(&'a Games), user_id: rejection!(UserId)) ->
         games: &Games,
         user_id: UserId,
         last_event_id: Option<usize>,
+        query: SocketQuery,
     ) -> Result<impl Reply, Rejection> {
-        let rx = games.subscribe(game_id, user_id, last_event_id).await?;
+        let last_event_id = last_event_id.or(query.last_event_id);
+        let rx = games
+            .subscribe(
+                game_id,
+                user_id,
+                last_event_id,
+                query.follow,
+                query.join_token,
+            )
+            .await?;
         Ok(sse::reply(stream(rx)))
     }
 
@@ -69,147 +152,152 @@ fn subscribe<'a>(games: infallible!(&'a Games), user_id: rejection!(UserId)) ->
         .and(games)
         .and(user_id)
         .and(warp::sse::last_event_id())
+        .and(warp::query())
         .and_then(handle)
 }
 
-fn pass_cards<'a>(games: infallible!(&'a Games), user_id: rejection!(UserId)) -> reply!() {
-    async fn handle(
-        games: &Games,
-        user_id: UserId,
-        request: PassRequest,
-    ) -> Result<impl Reply, Rejection> {
-        let PassRequest { game_id, cards } = request;
-        games.pass_cards(game_id, user_id, cards).await?;
-        Ok(warp::reply())
-    }
-
-    warp::path!("pass")
-        .and(warp::post())
-        .and(games)
-        .and(user_id)
-        .and(warp::body::json())
-        .and_then(handle)
-}
-
-fn charge_cards<'a>(games: infallible!(&'a Games), user_id: rejection!(UserId)) -> reply!() {
+fn socket(
+    lobby: infallible!(&'static Lobby),
+    games: infallible!(&'static Games),
+    user_id: rejection!(UserId),
+) -> reply!() {
     async fn handle(
-        games: &Games,
+        game_id: GameId,
+        ws: Ws,
+        lobby: &'static Lobby,
+        games: &'static Games,
         user_id: UserId,
-        request: ChargeRequest,
+        query: SocketQuery,
     ) -> Result<impl Reply, Rejection> {
-        let ChargeRequest { game_id, cards } = request;
-        games.charge_cards(game_id, user_id, cards).await?;
-        Ok(warp::reply())
+        let SocketQuery {
+            last_event_id,
+            follow,
+            join_token,
+        } = query;
+        let rx = games
+            .subscribe(game_id, user_id, last_event_id, follow, join_token)
+            .await?;
+        Ok(ws.on_upgrade(move |socket| {
+            let events = UnboundedReceiverStream::new(rx).map(|(event, id)| {
+                if event.is_ping() {
+                    None
+                } else if event.is_stable() {
+                    Some(SocketMessage::Event {
+                        event_id: Some(id),
+                        event,
+                    })
+                } else {
+                    Some(SocketMessage::Event {
+                        event_id: None,
+                        event,
+                    })
+                }
+            });
+            socket::serve(socket, events, move |request: GameRequest| async move {
+                // a socket only acts on the game it's subscribed to
+                if request.game_id() != game_id {
+                    return Err(CardsError::WrongGame(request.game_id(), game_id));
+                }
+                handle_request(lobby, games, user_id, request).await
+            })
+        }))
     }
 
-    warp::path!("charge")
-        .and(warp::post())
+    warp::path!("socket" / GameId)
+        .and(warp::ws())
+        .and(lobby)
         .and(games)
         .and(user_id)
-        .and(warp::body::json())
+        .and(warp::query())
         .and_then(handle)
 }
 
-fn play_card<'a>(
+// every request can also be posted on its own, which is handled just as if it
+// had been sent over a socket
+fn post<'a, R>(
+    path: &'static str,
     lobby: infallible!(&'a Lobby),
     games: infallible!(&'a Games),
     user_id: rejection!(UserId),
-) -> reply!() {
+    request: fn(R) -> GameRequest,
+) -> reply!()
+where
+    R: DeserializeOwned + Send + 'static,
+{
     async fn handle(
         lobby: &Lobby,
         games: &Games,
         user_id: UserId,
-        request: PlayRequest,
+        request: GameRequest,
     ) -> Result<impl Reply, Rejection> {
-        let PlayRequest { game_id, card } = request;
-        let complete = games.play_card(game_id, user_id, card).await?;
-        if complete {
-            lobby.finish_game(game_id).await;
-        }
+        handle_request(lobby, games, user_id, request).await?;
         Ok(warp::reply())
     }
 
-    warp::path!("play")
+    warp::path(path)
+        .and(warp::path::end())
         .and(warp::post())
         .and(lobby)
         .and(games)
         .and(user_id)
-        .and(warp::body::json())
-        .and_then(handle)
-}
-
-fn claim<'a>(games: infallible!(&'a Games), user_id: rejection!(UserId)) -> reply!() {
-    async fn handle(
-        games: &Games,
-        user_id: UserId,
-        request: ClaimRequest,
-    ) -> Result<impl Reply, Rejection> {
-        let ClaimRequest { game_id } = request;
-        games.claim(game_id, user_id).await?;
-        Ok(warp::reply())
-    }
-
-    warp::path!("claim")
-        .and(warp::post())
-        .and(games)
-        .and(user_id)
-        .and(warp::body::json())
+        .and(warp::body::json().map(request))
         .and_then(handle)
 }
 
-fn accept_claim<'a>(games: infallible!(&'a Games), user_id: rejection!(UserId)) -> reply!() {
-    async fn handle(
-        games: &Games,
-        user_id: UserId,
-        request: AcceptClaimRequest,
-    ) -> Result<impl Reply, Rejection> {
-        let AcceptClaimRequest { game_id, claimer } = request;
-        games.accept_claim(game_id, user_id, claimer).await?;
-        Ok(warp::reply())
-    }
-
-    warp::path!("accept_claim")
-        .and(warp::post())
-        .and(games)
-        .and(user_id)
-        .and(warp::body::json())
-        .and_then(handle)
-}
-
-fn reject_claim<'a>(games: infallible!(&'a Games), user_id: rejection!(UserId)) -> reply!() {
-    async fn handle(
-        games: &Games,
-        user_id: UserId,
-        request: RejectClaimRequest,
-    ) -> Result<impl Reply, Rejection> {
-        let RejectClaimRequest { game_id, claimer } = request;
-        games.reject_claim(game_id, user_id, claimer).await?;
-        Ok(warp::reply())
-    }
-
-    warp::path!("reject_claim")
-        .and(warp::post())
-        .and(games)
-        .and(user_id)
-        .and(warp::body::json())
-        .and_then(handle)
-}
-
-fn chat<'a>(games: infallible!(&'a Games), user_id: rejection!(UserId)) -> reply!() {
-    async fn handle(
-        games: &Games,
-        user_id: UserId,
-        request: GameChatRequest,
-    ) -> Result<impl Reply, Rejection> {
-        let GameChatRequest { game_id, message } = request;
-        games.chat(game_id, user_id, message).await?;
-        Ok(warp::reply())
-    }
-
-    warp::path!("chat")
-        .and(warp::post())
-        .and(games)
-        .and(user_id)
-        .and(warp::body::json())
-        .and_then(handle)
+async fn handle_request(
+    lobby: &Lobby,
+    games: &Games,
+    user_id: UserId,
+    request: GameRequest,
+) -> Result<GameAck, CardsError> {
+    Ok(match request {
+        GameRequest::Pass(PassRequest { game_id, cards }) => {
+            games.pass_cards(game_id, user_id, cards).await?;
+            GameAck::Pass
+        }
+        GameRequest::Charge(ChargeRequest { game_id, cards }) => {
+            games.charge_cards(game_id, user_id, cards).await?;
+            GameAck::Charge
+        }
+        GameRequest::Play(PlayRequest { game_id, card }) => {
+            if games.play_card(game_id, user_id, card).await? {
+                lobby.finish_game(game_id).await;
+            }
+            GameAck::Play
+        }
+        GameRequest::Claim(ClaimRequest { game_id }) => {
+            games.claim(game_id, user_id).await?;
+            GameAck::Claim
+        }
+        GameRequest::AcceptClaim(AcceptClaimRequest { game_id, claimer }) => {
+            if games.accept_claim(game_id, user_id, claimer).await? {
+                lobby.finish_game(game_id).await;
+            }
+            GameAck::AcceptClaim
+        }
+        GameRequest::RejectClaim(RejectClaimRequest { game_id, claimer }) => {
+            games.reject_claim(game_id, user_id, claimer).await?;
+            GameAck::RejectClaim
+        }
+        GameRequest::RequestUndo(RequestUndoRequest { game_id }) => {
+            games.request_undo(game_id, user_id).await?;
+            GameAck::RequestUndo
+        }
+        GameRequest::AcceptUndo(AcceptUndoRequest { game_id, requester }) => {
+            games.accept_undo(game_id, user_id, requester).await?;
+            GameAck::AcceptUndo
+        }
+        GameRequest::RejectUndo(RejectUndoRequest { game_id, requester }) => {
+            games.reject_undo(game_id, user_id, requester).await?;
+            GameAck::RejectUndo
+        }
+        GameRequest::Resume(ResumeRequest { game_id }) => {
+            games.resume(game_id, user_id).await?;
+            GameAck::Resume
+        }
+        GameRequest::Chat(GameChatRequest { game_id, message }) => {
+            games.chat(game_id, user_id, message).await?;
+            GameAck::Chat
+        }
+    })
 }
@@ -1,26 +1,57 @@
-use crate::{util, CardsError, Database, GetJson, GetStr, ToSqlJson, ToSqlStr};
+use crate::{CardsError, Storage};
 use log::info;
-use rusqlite::{OptionalExtension, ToSql, Transaction, NO_PARAMS};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use tokio::sync::{
     mpsc::{self, UnboundedReceiver, UnboundedSender},
     Mutex,
 };
 use turbo_hearts_api::{
-    GameId, LobbyChat, LobbyEvent, LobbyGame, Player, PlayerWithOptions, Seat, Seed, UserId,
+    DuplicateId, GameId, GameLength, JoinToken, LobbyEvent, PlayerWithOptions, Privacy, RuleSet,
+    Seat, Seed, SpectatorPolicy, TimeControl, UserId,
 };
+use uuid::Uuid;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct GameOptions {
+    pub time_control: Option<TimeControl>,
+    pub spectators: Option<SpectatorPolicy>,
+    pub rule_set: Option<RuleSet>,
+    pub length: Option<GameLength>,
+}
+
+impl GameOptions {
+    fn check(&self) -> Result<(), CardsError> {
+        if let Some(time_control) = self.time_control {
+            if !time_control.is_valid() {
+                return Err(CardsError::InvalidTimeControl(time_control));
+            }
+        }
+        if let Some(length) = self.length {
+            if !length.is_valid() {
+                return Err(CardsError::InvalidGameLength(length));
+            }
+        }
+        if let Some(spectators) = self.spectators {
+            if !spectators.is_valid() {
+                return Err(CardsError::InvalidSpectatorPolicy(spectators));
+            }
+        }
+        Ok(())
+    }
+}
 
 pub struct Lobby {
-    db: &'static Database,
+    storage: &'static dyn Storage,
     inner: Mutex<Inner>,
 }
 
 impl Lobby {
-    pub fn new(db: &'static Database) -> Result<Self, CardsError> {
+    pub fn new(storage: &'static dyn Storage) -> Result<Self, CardsError> {
         Ok(Self {
-            db,
+            storage,
             inner: Mutex::new(Inner {
                 subscribers: Vec::new(),
+                closed: false,
             }),
         })
     }
@@ -30,27 +61,31 @@ impl Lobby {
         inner.broadcast(LobbyEvent::Ping);
     }
 
+    pub async fn shutdown(&self) {
+        let mut inner = self.inner.lock().await;
+        inner.closed = true;
+        for (_, tx) in inner.subscribers.drain(..) {
+            let _ = tx.send(LobbyEvent::Reconnect);
+        }
+        info!("shutdown: lobby closed");
+    }
+
+    pub async fn disconnect(&self, user_id: UserId) {
+        let mut inner = self.inner.lock().await;
+        let before = inner.subscribers.len();
+        inner.subscribers.retain(|(id, _)| *id != user_id);
+        if inner.subscribers.len() < before {
+            inner.broadcast(LobbyEvent::LeaveLobby { user_id });
+        }
+        info!("disconnect: user_id={}", user_id);
+    }
+
+    pub async fn subscriber_count(&self) -> usize {
+        self.inner.lock().await.subscribers.len()
+    }
+
     pub async fn delete_stale_games(&self) -> Result<(), CardsError> {
-        self.db.run_with_retry(|tx| {
-            let now = util::timestamp();
-            let rows = tx.execute(
-                "DELETE FROM game WHERE (started_time IS NULL AND last_updated_time < ?)
-                    OR (completed_time IS NULL AND last_updated_time < ?)",
-                &[now - 24 * 60 * 60 * 1000, now - 21 * 24 * 60 * 60 * 1000],
-            )?;
-            if rows > 0 {
-                info!("Deleted {} stale game(s)", rows);
-            }
-            let rows = tx.execute(
-                "DELETE FROM game_player WHERE game_id NOT IN (SELECT game_id FROM game)",
-                NO_PARAMS,
-            )?;
-            if rows > 0 {
-                info!("Deleted {} stale game player(s)", rows);
-            }
-            Ok(())
-        })?;
-        Ok(())
+        self.storage.delete_stale_games()
     }
 
     pub async fn subscribe(
@@ -58,12 +93,11 @@ impl Lobby {
         user_id: UserId,
     ) -> Result<UnboundedReceiver<LobbyEvent>, CardsError> {
         let (tx, rx) = mpsc::unbounded_channel();
-        let (chat, games) = self.db.run_read_only(|tx| {
-            let chat = load_recent_chat(&tx)?;
-            let games = load_games(&tx)?;
-            Ok((chat, games))
-        })?;
+        let (chat, games) = self.storage.load_lobby(user_id)?;
         let mut inner = self.inner.lock().await;
+        if inner.closed {
+            return Err(CardsError::ShuttingDown);
+        }
         let mut subscribers = inner
             .subscribers
             .iter()
@@ -87,172 +121,234 @@ impl Lobby {
         &self,
         player: PlayerWithOptions,
         seed: Option<String>,
-    ) -> Result<GameId, CardsError> {
+        options: GameOptions,
+        privacy: Option<Privacy>,
+    ) -> Result<(GameId, Option<JoinToken>), CardsError> {
+        options.check()?;
         let game_id = GameId::new();
-        let user_id = player.player.user_id();
-        let timestamp = util::timestamp();
         let seed = seed.map_or_else(|| Seed::random(), |value| Seed::Chosen { value });
-        self.db.run_with_retry(|tx| {
-            tx.execute::<&[&dyn ToSql]>(
-                "INSERT INTO game (game_id, seed, created_time, created_by,
-                    last_updated_time, last_updated_by) VALUES (?, ?, ?, ?, ?, ?)",
-                &[
-                    &game_id.sql(),
-                    &seed.sql(),
-                    &timestamp,
-                    &user_id.sql(),
-                    &timestamp,
-                    &user_id.sql(),
-                ],
-            )?;
-            insert_player(&tx, game_id, &player)?;
-            Ok(())
-        })?;
-        let mut inner = self.inner.lock().await;
-        inner.broadcast(LobbyEvent::NewGame {
+        let join_token = match &privacy {
+            Some(privacy) if privacy.join_token => Some(JoinToken::new()),
+            _ => None,
+        };
+        let audience = self.storage.insert_game(
             game_id,
+            &seed,
+            options,
+            &player,
+            privacy.as_ref(),
+            join_token,
+        )?;
+        let mut inner = self.inner.lock().await;
+        inner.broadcast_to(
+            LobbyEvent::NewGame {
+                game_id,
+                player,
+                seed: seed.redact(),
+                time_control: options.time_control,
+                spectators: options.spectators,
+                rule_set: options.rule_set,
+                length: options.length,
+                private: privacy.is_some(),
+            },
+            audience.as_ref(),
+        );
+        info!(
+            "new_game: game_id={}, player={:?}, seed={:?}, options={:?}, privacy={:?}",
+            game_id, player, seed, options, privacy
+        );
+        Ok((game_id, join_token))
+    }
+
+    pub async fn new_duplicate(
+        &self,
+        player: PlayerWithOptions,
+        tables: u8,
+        options: GameOptions,
+    ) -> Result<(DuplicateId, Vec<GameId>), CardsError> {
+        if !(2..=4).contains(&tables) {
+            return Err(CardsError::InvalidTableCount(tables));
+        }
+        options.check()?;
+        let duplicate_id = DuplicateId::new();
+        let game_ids = (0..tables).map(|_| GameId::new()).collect::<Vec<_>>();
+        let value = Uuid::new_v4().to_string();
+        self.storage
+            .insert_duplicate(duplicate_id, &game_ids, &value, options, &player)?;
+        let mut inner = self.inner.lock().await;
+        inner.broadcast(LobbyEvent::NewDuplicate {
+            duplicate_id,
+            game_ids: game_ids.clone(),
             player,
-            seed: seed.redact(),
+            seed: Seed::Redacted,
+            time_control: options.time_control,
+            spectators: options.spectators,
+            rule_set: options.rule_set,
+            length: options.length,
         });
         info!(
-            "new_game: game_id={}, player={:?}, seed={:?}",
-            game_id, player, seed
+            "new_duplicate: duplicate_id={}, game_ids={:?}, player={:?}, options={:?}",
+            duplicate_id, game_ids, player, options
         );
-        Ok(game_id)
+        Ok((duplicate_id, game_ids))
     }
 
     pub async fn join_game(
         &self,
         game_id: GameId,
         player: PlayerWithOptions,
+        join_token: Option<JoinToken>,
+    ) -> Result<(), CardsError> {
+        self.add_player(game_id, player.player.user_id(), player, join_token)
+            .await
+    }
+
+    pub async fn add_bot(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+        bot: PlayerWithOptions,
+    ) -> Result<(), CardsError> {
+        if let Some(options) = bot.player.bot_options() {
+            if !options.is_valid() {
+                return Err(CardsError::InvalidBotOptions(options));
+            }
+        }
+        self.add_player(game_id, user_id, bot, None).await
+    }
+
+    // seats the player on behalf of user_id, who has to be allowed into the game
+    async fn add_player(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+        player: PlayerWithOptions,
+        join_token: Option<JoinToken>,
     ) -> Result<(), CardsError> {
-        let joined =
-            self.db.run_with_retry(|tx| {
-                validate_game_unstarted(&tx, game_id)?;
-                if insert_player(&tx, game_id, &player)? {
-                    tx.execute::<&[&dyn ToSql]>(
-                    "UPDATE game SET last_updated_time = ?, last_updated_by = ? WHERE game_id = ?",
-                    &[&util::timestamp(), &player.player.user_id().sql(), &game_id.sql()],
-                )?;
-                    Ok(true)
-                } else {
-                    Ok(false)
-                }
-            })?;
+        let (joined, audience) = self
+            .storage
+            .insert_player(game_id, user_id, &player, join_token)?;
         if joined {
             let mut inner = self.inner.lock().await;
-            inner.broadcast(LobbyEvent::JoinGame { game_id, player });
+            inner.broadcast_to(LobbyEvent::JoinGame { game_id, player }, audience.as_ref());
         }
-        info!("join_game: game_id={}, player={:?}", game_id, player);
+        info!(
+            "join_game: game_id={}, user_id={}, player={:?}",
+            game_id, user_id, player
+        );
         Ok(())
     }
 
     pub async fn start_game(
         &self,
         game_id: GameId,
-    ) -> Result<([PlayerWithOptions; 4], Seed), CardsError> {
-        let (players, seed) = self.db.run_with_retry(|tx| {
-            validate_game_unstarted(&tx, game_id)?;
-            let mut stmt = tx.prepare_cached(
-                "SELECT user_id, strategy, rules, seat FROM game_player
-                    WHERE game_id = ? ORDER BY random() LIMIT 4",
-            )?;
-            let mut rows = stmt.query(&[game_id.sql()])?;
-            let mut players = Vec::with_capacity(4);
-            while let Some(row) = rows.next()? {
-                let user_id = row.get_str(0)?;
-                let player = if let Some(strategy) = row.get_opt_json(1)? {
-                    Player::Bot { user_id, strategy }
-                } else {
-                    Player::Human { user_id }
-                };
-                players.push(PlayerWithOptions {
-                    player,
-                    rules: row.get_json(2)?,
-                    seat: row.get_opt_json(3)?,
-                });
-            }
-            if players.len() < 4 {
-                return Err(CardsError::NotEnoughPlayers);
-            }
-            tx.execute::<&[&dyn ToSql]>(
-                "UPDATE game SET started_time = ? WHERE game_id = ?",
-                &[&util::timestamp(), &game_id.sql()],
-            )?;
-            for &seat in &Seat::VALUES {
-                if let Some(idx) = players.iter().position(|p| p.seat == Some(seat)) {
-                    players.swap(idx, seat.idx());
-                }
+    ) -> Result<([PlayerWithOptions; 4], Seed, GameOptions), CardsError> {
+        let (mut players, seed, options, audience) = self.storage.start_game(game_id)?;
+        for &seat in &Seat::VALUES {
+            if let Some(idx) = players.iter().position(|p| p.seat == Some(seat)) {
+                players.swap(idx, seat.idx());
             }
-            let seed = tx.query_row(
-                "SELECT seed FROM game WHERE game_id = ?",
-                &[game_id.sql()],
-                |row| Ok(row.get_json(0)?),
-            )?;
-            Ok((players, seed))
-        })?;
+        }
         let mut inner = self.inner.lock().await;
-        inner.broadcast(LobbyEvent::StartGame {
-            game_id,
-            north: players[0].player,
-            east: players[1].player,
-            south: players[2].player,
-            west: players[3].player,
-        });
+        inner.broadcast_to(
+            LobbyEvent::StartGame {
+                game_id,
+                north: players[0].player,
+                east: players[1].player,
+                south: players[2].player,
+                west: players[3].player,
+            },
+            audience.as_ref(),
+        );
         info!("start_game: game_id={}", game_id);
-        Ok(([players[0], players[1], players[2], players[3]], seed))
+        Ok((
+            [players[0], players[1], players[2], players[3]],
+            seed,
+            options,
+        ))
     }
 
     pub async fn leave_game(&self, game_id: GameId, user_id: UserId) -> Result<(), CardsError> {
-        let player = self.db.run_with_retry(|tx| {
-            validate_game_unstarted(&tx, game_id)?;
-            let player = remove_player(&tx, game_id, user_id)?;
-            if player.is_some() {
-                tx.execute::<&[&dyn ToSql]>(
-                    "UPDATE game SET last_updated_time = ?, last_updated_by = ? WHERE game_id = ?",
-                    &[&util::timestamp(), &user_id.sql(), &game_id.sql()],
-                )?;
-            }
-            Ok(player)
-        })?;
+        let (player, audience) = self.storage.remove_player(game_id, user_id)?;
         if let Some(player) = player {
             let mut inner = self.inner.lock().await;
-            inner.broadcast(LobbyEvent::LeaveGame { game_id, player });
+            inner.broadcast_to(LobbyEvent::LeaveGame { game_id, player }, audience.as_ref());
         }
         info!("leave_game: game_id={}, user_id={}", game_id, user_id);
         Ok(())
     }
 
+    pub async fn remove_player(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+        player_id: UserId,
+    ) -> Result<(), CardsError> {
+        self.storage.check_access(game_id, user_id, None)?;
+        self.leave_game(game_id, player_id).await
+    }
+
     pub async fn finish_game(&self, game_id: GameId) {
+        match self.storage.audience(game_id) {
+            Ok(audience) => {
+                let mut inner = self.inner.lock().await;
+                inner.broadcast_to(LobbyEvent::FinishGame { game_id }, audience.as_ref());
+                info!("finish_game: game_id={}", game_id);
+            }
+            Err(e) => info!("finish_game: game_id={}, error={:?}", game_id, e),
+        }
+    }
+
+    pub async fn delete_game(&self, admin_id: UserId, game_id: GameId) -> Result<(), CardsError> {
+        let audience = self.storage.delete_game(admin_id, game_id)?;
         let mut inner = self.inner.lock().await;
-        inner.broadcast(LobbyEvent::FinishGame { game_id });
-        info!("finish_game: game_id={}", game_id);
+        inner.broadcast_to(LobbyEvent::FinishGame { game_id }, audience.as_ref());
+        info!("delete_game: game_id={}, admin_id={}", game_id, admin_id);
+        Ok(())
     }
 
     pub async fn chat(&self, user_id: UserId, message: String) -> Result<(), CardsError> {
-        self.db.run_with_retry(|tx| {
-            tx.execute::<&[&dyn ToSql]>(
-                "INSERT INTO lobby_chat (timestamp, user_id, message) VALUES (?, ?, ?)",
-                &[&util::timestamp(), &user_id.sql(), &message],
-            )?;
-            Ok(())
-        })?;
+        self.storage.insert_chat(user_id, &message)?;
         let mut inner = self.inner.lock().await;
         inner.broadcast(LobbyEvent::Chat { user_id, message });
         info!("chat: user_id={}", user_id);
         Ok(())
     }
+
+    pub async fn delete_chat(
+        &self,
+        admin_id: UserId,
+        user_id: UserId,
+        timestamp: i64,
+    ) -> Result<(), CardsError> {
+        self.storage.delete_chat(admin_id, user_id, timestamp)?;
+        info!(
+            "delete_chat: user_id={}, timestamp={}, admin_id={}",
+            user_id, timestamp, admin_id
+        );
+        Ok(())
+    }
 }
 
 struct Inner {
     subscribers: Vec<(UserId, UnboundedSender<LobbyEvent>)>,
+    closed: bool,
 }
 
 impl Inner {
     fn broadcast(&mut self, event: LobbyEvent) {
+        self.broadcast_to(event, None);
+    }
+
+    // only the given users hear about hidden games; everyone hears about the rest
+    fn broadcast_to(&mut self, event: LobbyEvent, audience: Option<&HashSet<UserId>>) {
         let mut disconnects = HashSet::new();
         self.subscribers.retain(|(user_id, tx)| {
-            if tx.send(event.clone()).is_ok() {
+            let listening = match audience {
+                Some(audience) => audience.contains(user_id),
+                None => true,
+            };
+            if !listening || tx.send(event.clone()).is_ok() {
                 true
             } else {
                 disconnects.insert(*user_id);
@@ -269,126 +365,3 @@ impl Inner {
         }
     }
 }
-
-fn load_recent_chat(tx: &Transaction) -> Result<Vec<LobbyChat>, CardsError> {
-    let mut stmt = tx.prepare_cached(
-        "SELECT timestamp, user_id, message FROM lobby_chat ORDER BY timestamp DESC LIMIT 500",
-    )?;
-    let mut rows = stmt.query(NO_PARAMS)?;
-    let mut messages = Vec::with_capacity(500);
-    while let Some(row) = rows.next()? {
-        messages.push(LobbyChat {
-            timestamp: row.get(0)?,
-            user_id: row.get_str(1)?,
-            message: row.get(2)?,
-        });
-    }
-    messages.reverse();
-    Ok(messages)
-}
-
-fn load_games(tx: &Transaction) -> Result<HashMap<GameId, LobbyGame>, CardsError> {
-    let mut games = HashMap::new();
-    let mut stmt = tx.prepare_cached(
-        "SELECT game_id, seed, created_time, created_by,
-                last_updated_time, last_updated_by, started_time
-                FROM game WHERE completed_time IS NULL AND last_updated_time > ?",
-    )?;
-    let mut rows = stmt.query(&[util::timestamp() - 8 * 60 * 60 * 1000])?;
-    while let Some(row) = rows.next()? {
-        games.insert(
-            row.get_str(0)?,
-            LobbyGame {
-                players: HashSet::new(),
-                seed: row.get_json::<Seed>(1)?.redact(),
-                created_time: row.get(2)?,
-                created_by: row.get_str(3)?,
-                last_updated_time: row.get(4)?,
-                last_updated_by: row.get_str(5)?,
-                started_time: row.get(6)?,
-            },
-        );
-    }
-    let mut stmt = tx.prepare_cached(
-        "SELECT gp.game_id, gp.user_id, gp.strategy, gp.rules, gp.seat
-            FROM game_player gp, game g
-            WHERE gp.game_id = g.game_id AND g.completed_time IS NULL",
-    )?;
-    let mut rows = stmt.query(NO_PARAMS)?;
-    while let Some(row) = rows.next()? {
-        let game_id = row.get_str(0)?;
-        if let Some(game) = games.get_mut(&game_id) {
-            let user_id = row.get_str(1)?;
-            let player = if let Some(strategy) = row.get_opt_json(2)? {
-                Player::Bot { user_id, strategy }
-            } else {
-                Player::Human { user_id }
-            };
-            game.players.insert(PlayerWithOptions {
-                player,
-                rules: row.get_json(3)?,
-                seat: row.get_opt_json(4)?,
-            });
-        }
-    }
-    games.retain(|_, game| !game.players.is_empty());
-    Ok(games)
-}
-
-fn validate_game_unstarted(tx: &Transaction, game_id: GameId) -> Result<(), CardsError> {
-    let started = tx
-        .query_row(
-            "SELECT started_time FROM game WHERE game_id = ?",
-            &[game_id.sql()],
-            |row| Ok(row.get::<_, Option<i64>>(0)?.is_some()),
-        )
-        .optional()?;
-    match started {
-        None => Err(CardsError::UnknownGame(game_id)),
-        Some(true) => Err(CardsError::GameHasStarted(game_id)),
-        _ => Ok(()),
-    }
-}
-
-fn insert_player(
-    tx: &Transaction,
-    game_id: GameId,
-    player: &PlayerWithOptions,
-) -> Result<bool, CardsError> {
-    let rows = tx.execute::<&[&dyn ToSql]>(
-        "INSERT OR IGNORE INTO game_player (game_id, user_id, strategy, rules, seat)
-            VALUES (?, ?, ?, ?, ?)",
-        &[
-            &game_id.sql(),
-            &player.player.user_id().sql(),
-            &player.player.strategy().as_ref().map(|s| s.sql()),
-            &player.rules.sql(),
-            &player.seat.as_ref().map(|s| s.sql()),
-        ],
-    )?;
-    Ok(rows > 0)
-}
-
-fn remove_player(
-    tx: &Transaction,
-    game_id: GameId,
-    user_id: UserId,
-) -> Result<Option<Player>, CardsError> {
-    let player = tx
-        .query_row::<_, &[&dyn ToSql], _>(
-            "SELECT strategy FROM game_player WHERE game_id = ? AND user_id = ?",
-            &[&game_id.sql(), &user_id.sql()],
-            |row| {
-                Ok(match row.get_opt_json(0)? {
-                    Some(strategy) => Player::Bot { user_id, strategy },
-                    None => Player::Human { user_id },
-                })
-            },
-        )
-        .optional()?;
-    tx.execute::<&[&dyn ToSql]>(
-        "DELETE FROM game_player WHERE game_id = ? AND user_id = ?",
-        &[&game_id.sql(), &user_id.sql()],
-    )?;
-    Ok(player)
-}
@@ -1,28 +1,80 @@
-use crate::{auth_redirect, Games, Lobby};
+use crate::{auth_redirect, socket, CardsError, GameOptions, Games, Lobby};
+use serde::de::DeserializeOwned;
 use tokio::sync::mpsc::UnboundedReceiver;
 use tokio_stream::{wrappers::UnboundedReceiverStream, Stream, StreamExt};
 use turbo_hearts_api::{
-    AddBotRequest, JoinGameRequest, LeaveGameRequest, LobbyChatRequest, LobbyEvent, NewGameRequest,
-    Player, PlayerWithOptions, RemovePlayerRequest, StartGameRequest, UserId,
+    AddBotRequest, JoinGameRequest, LeaveGameRequest, LobbyAck, LobbyChatRequest, LobbyEvent,
+    LobbyRequest, NewDuplicateRequest, NewDuplicateResponse, NewGameRequest, NewGameResponse,
+    Player, PlayerWithOptions, RemovePlayerRequest, SocketMessage, StartGameRequest, UserId,
 };
-use warp::{sse, sse::Event, Filter, Rejection, Reply};
+use warp::{sse, sse::Event, ws::Ws, Filter, Rejection, Reply};
 
-pub fn router<'a>(
-    lobby: infallible!(&'a Lobby),
-    games: infallible!(&'a Games),
+pub fn router(
+    lobby: infallible!(&'static Lobby),
+    games: infallible!(&'static Games),
     user_id: rejection!(UserId),
 ) -> reply!() {
     warp::path("lobby")
         .and(
             html()
                 .or(subscribe(lobby.clone(), user_id.clone()))
-                .or(new_game(lobby.clone(), user_id.clone()))
-                .or(join_game(lobby.clone(), user_id.clone()))
-                .or(start_game(lobby.clone(), games, user_id.clone()))
-                .or(leave_game(lobby.clone(), user_id.clone()))
-                .or(add_bot(lobby.clone(), user_id.clone()))
-                .or(remove(lobby.clone(), user_id.clone()))
-                .or(chat(lobby, user_id)),
+                .or(socket(lobby.clone(), games.clone(), user_id.clone()))
+                .or(post(
+                    "new",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    LobbyRequest::NewGame,
+                ))
+                .or(post(
+                    "new_duplicate",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    LobbyRequest::NewDuplicate,
+                ))
+                .or(post(
+                    "join",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    LobbyRequest::JoinGame,
+                ))
+                .or(post(
+                    "start",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    LobbyRequest::StartGame,
+                ))
+                .or(post(
+                    "leave",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    LobbyRequest::LeaveGame,
+                ))
+                .or(post(
+                    "add_bot",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    LobbyRequest::AddBot,
+                ))
+                .or(post(
+                    "remove",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    LobbyRequest::RemovePlayer,
+                ))
+                .or(post(
+                    "chat",
+                    lobby.clone(),
+                    games.clone(),
+                    user_id.clone(),
+                    LobbyRequest::Chat,
+                )),
         )
         .boxed()
 }
@@ -59,169 +111,207 @@ fn subscribe<'a>(lobby: infallible!(&'a Lobby), user_id: rejection!(UserId)) ->
         .and_then(handle)
 }
 
-fn new_game<'a>(lobby: infallible!(&'a Lobby), user_id: rejection!(UserId)) -> reply!() {
-    async fn handle(
-        lobby: &Lobby,
-        user_id: UserId,
-        request: NewGameRequest,
-    ) -> Result<impl Reply, Rejection> {
-        let NewGameRequest { rules, seat, seed } = request;
-        let player = PlayerWithOptions {
-            player: Player::Human { user_id },
-            rules,
-            seat,
-        };
-        let game_id = lobby.new_game(player, seed).await?;
-        Ok(warp::reply::json(&game_id))
-    }
-
-    warp::path!("new")
-        .and(warp::post())
-        .and(lobby)
-        .and(user_id)
-        .and(warp::body::json())
-        .and_then(handle)
-}
-
-fn join_game<'a>(lobby: infallible!(&'a Lobby), user_id: rejection!(UserId)) -> reply!() {
+fn socket(
+    lobby: infallible!(&'static Lobby),
+    games: infallible!(&'static Games),
+    user_id: rejection!(UserId),
+) -> reply!() {
     async fn handle(
-        lobby: &Lobby,
+        ws: Ws,
+        lobby: &'static Lobby,
+        games: &'static Games,
         user_id: UserId,
-        request: JoinGameRequest,
     ) -> Result<impl Reply, Rejection> {
-        let JoinGameRequest {
-            game_id,
-            rules,
-            seat,
-        } = request;
-        let player = PlayerWithOptions {
-            player: Player::Human { user_id },
-            rules,
-            seat,
-        };
-        lobby.join_game(game_id, player).await?;
-        Ok(warp::reply())
+        let rx = lobby.subscribe(user_id).await?;
+        Ok(ws.on_upgrade(move |socket| {
+            let events = UnboundedReceiverStream::new(rx).map(|event| {
+                if event.is_ping() {
+                    None
+                } else {
+                    Some(SocketMessage::Event {
+                        event_id: None,
+                        event,
+                    })
+                }
+            });
+            socket::serve(socket, events, move |request| {
+                handle_request(lobby, games, user_id, request)
+            })
+        }))
     }
 
-    warp::path!("join")
-        .and(warp::post())
+    warp::path!("socket")
+        .and(warp::ws())
         .and(lobby)
+        .and(games)
         .and(user_id)
-        .and(warp::body::json())
         .and_then(handle)
 }
 
-fn start_game<'a>(
+// every request can also be posted on its own, which is handled just as if it
+// had been sent over a socket
+fn post<'a, R>(
+    path: &'static str,
     lobby: infallible!(&'a Lobby),
     games: infallible!(&'a Games),
     user_id: rejection!(UserId),
-) -> reply!() {
+    request: fn(R) -> LobbyRequest,
+) -> reply!()
+where
+    R: DeserializeOwned + Send + 'static,
+{
     async fn handle(
         lobby: &Lobby,
         games: &Games,
-        _user_id: UserId,
-        request: StartGameRequest,
-    ) -> Result<impl Reply, Rejection> {
-        let StartGameRequest { game_id } = request;
-        let (players, seed) = lobby.start_game(game_id).await?;
-        games.start_game(game_id, players, seed)?;
-        Ok(warp::reply())
-    }
-
-    warp::path!("start")
-        .and(warp::post())
-        .and(lobby)
-        .and(games)
-        .and(user_id)
-        .and(warp::body::json())
-        .and_then(handle)
-}
-
-fn leave_game<'a>(lobby: infallible!(&'a Lobby), user_id: rejection!(UserId)) -> reply!() {
-    async fn handle(
-        lobby: &Lobby,
         user_id: UserId,
-        request: LeaveGameRequest,
+        request: LobbyRequest,
     ) -> Result<impl Reply, Rejection> {
-        let LeaveGameRequest { game_id } = request;
-        lobby.leave_game(game_id, user_id).await?;
-        Ok(warp::reply())
+        Ok(
+            match handle_request(lobby, games, user_id, request).await? {
+                LobbyAck::NewGame {
+                    game_id,
+                    join_token,
+                } => warp::reply::json(&NewGameResponse {
+                    game_id,
+                    join_token,
+                })
+                .into_response(),
+                LobbyAck::NewDuplicate {
+                    duplicate_id,
+                    game_ids,
+                } => warp::reply::json(&NewDuplicateResponse {
+                    duplicate_id,
+                    game_ids,
+                })
+                .into_response(),
+                LobbyAck::AddBot { user_id } => warp::reply::json(&user_id).into_response(),
+                _ => warp::reply().into_response(),
+            },
+        )
     }
 
-    warp::path!("leave")
+    warp::path(path)
+        .and(warp::path::end())
         .and(warp::post())
         .and(lobby)
+        .and(games)
         .and(user_id)
-        .and(warp::body::json())
+        .and(warp::body::json().map(request))
         .and_then(handle)
 }
 
-fn add_bot<'a>(lobby: infallible!(&'a Lobby), user_id: rejection!(UserId)) -> reply!() {
-    async fn handle(
-        lobby: &Lobby,
-        _user_id: UserId,
-        request: AddBotRequest,
-    ) -> Result<impl Reply, Rejection> {
-        let AddBotRequest {
+async fn handle_request(
+    lobby: &Lobby,
+    games: &Games,
+    user_id: UserId,
+    request: LobbyRequest,
+) -> Result<LobbyAck, CardsError> {
+    Ok(match request {
+        LobbyRequest::NewGame(NewGameRequest {
+            rules,
+            seat,
+            seed,
+            time_control,
+            spectators,
+            rule_set,
+            length,
+            privacy,
+        }) => {
+            let player = PlayerWithOptions {
+                player: Player::Human { user_id },
+                rules,
+                seat,
+            };
+            let options = GameOptions {
+                time_control,
+                spectators,
+                rule_set,
+                length,
+            };
+            let (game_id, join_token) = lobby.new_game(player, seed, options, privacy).await?;
+            LobbyAck::NewGame {
+                game_id,
+                join_token,
+            }
+        }
+        LobbyRequest::NewDuplicate(NewDuplicateRequest {
+            rules,
+            seat,
+            tables,
+            time_control,
+            spectators,
+            rule_set,
+            length,
+        }) => {
+            let player = PlayerWithOptions {
+                player: Player::Human { user_id },
+                rules,
+                seat,
+            };
+            let options = GameOptions {
+                time_control,
+                spectators,
+                rule_set,
+                length,
+            };
+            let (duplicate_id, game_ids) = lobby.new_duplicate(player, tables, options).await?;
+            LobbyAck::NewDuplicate {
+                duplicate_id,
+                game_ids,
+            }
+        }
+        LobbyRequest::JoinGame(JoinGameRequest {
             game_id,
             rules,
-            strategy,
-        } = request;
-        let bot_id = UserId::new();
-        let player = PlayerWithOptions {
-            player: Player::Bot {
-                user_id: bot_id,
-                strategy,
-            },
+            seat,
+            join_token,
+        }) => {
+            let player = PlayerWithOptions {
+                player: Player::Human { user_id },
+                rules,
+                seat,
+            };
+            lobby.join_game(game_id, player, join_token).await?;
+            LobbyAck::JoinGame
+        }
+        LobbyRequest::StartGame(StartGameRequest { game_id }) => {
+            let (players, seed, options) = lobby.start_game(game_id).await?;
+            games.start_game(game_id, players, seed, options)?;
+            LobbyAck::StartGame
+        }
+        LobbyRequest::LeaveGame(LeaveGameRequest { game_id }) => {
+            lobby.leave_game(game_id, user_id).await?;
+            LobbyAck::LeaveGame
+        }
+        LobbyRequest::AddBot(AddBotRequest {
+            game_id,
             rules,
-            seat: None,
-        };
-        lobby.join_game(game_id, player).await?;
-        Ok(warp::reply::json(&bot_id))
-    }
-
-    warp::path!("add_bot")
-        .and(warp::post())
-        .and(lobby)
-        .and(user_id)
-        .and(warp::body::json())
-        .and_then(handle)
-}
-
-fn remove<'a>(lobby: infallible!(&'a Lobby), user_id: rejection!(UserId)) -> reply!() {
-    async fn handle(
-        lobby: &Lobby,
-        _user_id: UserId,
-        request: RemovePlayerRequest,
-    ) -> Result<impl Reply, Rejection> {
-        let RemovePlayerRequest { game_id, user_id } = request;
-        lobby.leave_game(game_id, user_id).await?;
-        Ok(warp::reply())
-    }
-
-    warp::path!("remove")
-        .and(warp::post())
-        .and(lobby)
-        .and(user_id)
-        .and(warp::body::json())
-        .and_then(handle)
-}
-
-fn chat<'a>(lobby: infallible!(&'a Lobby), user_id: rejection!(UserId)) -> reply!() {
-    async fn handle(
-        lobby: &Lobby,
-        user_id: UserId,
-        request: LobbyChatRequest,
-    ) -> Result<impl Reply, Rejection> {
-        let LobbyChatRequest { message } = request;
-        lobby.chat(user_id, message).await?;
-        Ok(warp::reply())
-    }
-
-    warp::path!("chat")
-        .and(warp::post())
-        .and(lobby)
-        .and(user_id)
-        .and(warp::body::json())
-        .and_then(handle)
+            strategy,
+            options,
+        }) => {
+            let bot_id = UserId::new();
+            let player = PlayerWithOptions {
+                player: Player::Bot {
+                    user_id: bot_id,
+                    strategy,
+                    options,
+                },
+                rules,
+                seat: None,
+            };
+            lobby.add_bot(game_id, user_id, player).await?;
+            LobbyAck::AddBot { user_id: bot_id }
+        }
+        LobbyRequest::RemovePlayer(RemovePlayerRequest {
+            game_id,
+            user_id: player_id,
+        }) => {
+            lobby.remove_player(game_id, user_id, player_id).await?;
+            LobbyAck::RemovePlayer
+        }
+        LobbyRequest::Chat(LobbyChatRequest { message }) => {
+            lobby.chat(user_id, message).await?;
+            LobbyAck::Chat
+        }
+    })
 }
@@ -1,40 +1,66 @@
 use crate::CardsError;
 use http::header;
 use reqwest::Client;
-use tokio::{time, time::Duration};
-use turbo_hearts_api::UserId;
+use std::{path::Path, sync::Arc};
+use tokio::{sync::Notify, time, time::Duration};
+use turbo_hearts_api::{PlayAnalysis, UserId};
 use warp::{http::Uri, Filter, Rejection};
 
 #[macro_use]
 mod macros;
 
+mod admin_endpoints;
+mod analysis;
+mod archive;
 mod asset_endpoints;
 mod auth;
 mod auth_endpoints;
 mod bot;
 mod config;
 mod db;
+mod duplicate;
 mod error;
 mod game;
 mod game_endpoints;
 mod lobby;
 mod lobby_endpoints;
+mod metrics;
+mod metrics_endpoints;
+mod migration;
+mod rating;
+mod record;
+mod record_endpoints;
+mod socket;
+mod sqlite;
+mod storage;
 mod subscriber;
 mod summary;
 mod user;
 mod user_endpoints;
 mod util;
 
+#[cfg(test)]
+mod memory;
 #[cfg(test)]
 mod test;
 
+pub use analysis::*;
+pub use archive::*;
 pub use auth::*;
 pub use bot::*;
 pub use config::*;
 pub use db::*;
+pub use duplicate::*;
 pub use error::*;
 pub use game::*;
 pub use lobby::*;
+#[cfg(test)]
+pub use memory::*;
+pub use metrics::*;
+pub use migration::*;
+pub use rating::*;
+pub use record::*;
+pub use storage::*;
 pub use subscriber::*;
 pub use user::*;
 
@@ -46,6 +72,14 @@ fn user_id<'a>(users: infallible!(&'a Users)) -> rejection!(UserId) {
     users.and(warp::cookie("AUTH_TOKEN")).and_then(handle)
 }
 
+fn admin_id<'a>(users: infallible!(&'a Users)) -> rejection!(UserId) {
+    async fn handle(users: &Users, auth_token: String) -> Result<UserId, Rejection> {
+        Ok(users.get_admin_id(auth_token).await?)
+    }
+
+    users.and(warp::cookie("AUTH_TOKEN")).and_then(handle)
+}
+
 fn start_stale_game_cleanup(lobby: &'static Lobby) {
     tokio::task::spawn(async move {
         let mut ticker = time::interval(Duration::from_secs(60 * 60));
@@ -58,6 +92,40 @@ fn start_stale_game_cleanup(lobby: &'static Lobby) {
     });
 }
 
+fn start_rating_updates(storage: &'static dyn Storage) {
+    tokio::task::spawn(async move {
+        let mut ticker = time::interval(Duration::from_secs(60));
+        loop {
+            ticker.tick().await;
+            match storage.update_ratings() {
+                Ok(games) if games > 0 => log::info!("Rated {} completed games", games),
+                Ok(_) => {}
+                Err(e) => log::error!("Failed to update ratings {:?}", e),
+            }
+        }
+    });
+}
+
+fn start_archiving(storage: &'static dyn Storage, config: &'static ArchiveConfig) {
+    tokio::task::spawn(async move {
+        let mut ticker = time::interval(Duration::from_secs(60 * 60));
+        loop {
+            ticker.tick().await;
+            let cutoff = util::timestamp() - config.after_days as i64 * 24 * 60 * 60 * 1000;
+            let archived = tokio::task::spawn_blocking(move || {
+                archive_games(storage, Path::new(&config.path), cutoff)
+            })
+            .await;
+            match archived {
+                Ok(Ok(games)) if games > 0 => log::info!("Archived {} completed games", games),
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => log::error!("Failed to archive games {:?}", e),
+                Err(e) => log::error!("Failed to archive games {:?}", e),
+            }
+        }
+    });
+}
+
 fn start_background_pings(lobby: &'static Lobby, games: &'static Games) {
     tokio::task::spawn(async move {
         let mut ticker = time::interval(Duration::from_secs(15));
@@ -69,43 +137,113 @@ fn start_background_pings(lobby: &'static Lobby, games: &'static Games) {
     });
 }
 
+fn start_turn_clocks(games: &'static Games) {
+    tokio::task::spawn(async move {
+        let mut ticker = time::interval(Duration::from_secs(1));
+        loop {
+            ticker.tick().await;
+            games.expire_clocks().await;
+        }
+    });
+}
+
+fn start_bot_games(games: &'static Games) {
+    tokio::task::spawn(async move {
+        match games.load_bot_games().await {
+            Ok(games) if games > 0 => log::info!("Loaded {} games with bots", games),
+            Ok(_) => {}
+            Err(e) => log::error!("Failed to load games with bots {:?}", e),
+        }
+    });
+}
+
+// resolves once the server is asked to stop, after the games and lobby have
+// told their subscribers to reconnect
+async fn shutdown(lobby: &'static Lobby, games: &'static Games) {
+    let signal = Arc::new(Notify::new());
+    let notify = Arc::clone(&signal);
+    ctrlc::set_handler(move || notify.notify_one()).expect("Failed to handle signals");
+    signal.notified().await;
+    log::info!("Shutting down");
+    games.shutdown().await;
+    lobby.shutdown().await;
+}
+
 #[tokio::main]
 async fn main() -> Result<(), CardsError> {
     env_logger::init();
 
+    if std::env::args().nth(1).as_deref() == Some("migrate") {
+        migrate_command();
+        return Ok(());
+    }
+
+    if let Some(threads) = CONFIG.bot_threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .unwrap();
+    }
+
     let db = Database::new(&CONFIG.db_path)?;
     let db = &*Box::leak(Box::new(db));
+    let storage: &'static dyn Storage = db;
 
-    let lobby = Lobby::new(db)?;
+    let lobby = Lobby::new(storage)?;
     let lobby = &*Box::leak(Box::new(lobby));
 
-    let games = Games::new(db, true);
+    let games = Games::new(storage, true, CONFIG.check_snapshots);
     let games = &*Box::leak(Box::new(games));
 
-    let users = Users::new(db);
+    let analyzer = Analyzer::new(
+        storage,
+        CONFIG.analysis_threads.unwrap_or(1),
+        CONFIG
+            .analysis_solved_cards
+            .unwrap_or(PlayAnalysis::SOLVED_CARDS),
+    );
+    let analyzer = &*Box::leak(Box::new(analyzer));
+
+    let users = Users::new(storage);
     let users = &*Box::leak(Box::new(users));
 
     let http_client = Client::new();
     let http_client = &*Box::leak(Box::new(http_client));
 
     start_stale_game_cleanup(lobby);
+    start_rating_updates(storage);
+    if let Some(archive) = &CONFIG.archive {
+        start_archiving(storage, archive);
+    }
     start_background_pings(lobby, games);
+    start_turn_clocks(games);
+    start_bot_games(games);
+    let shutdown = shutdown(lobby, games);
+    // the filters below shadow the database, which is closed once the server stops
+    let database = db;
 
-    let db = warp::any().map(move || db);
+    let storage = warp::any().map(move || storage);
     let lobby = warp::any().map(move || lobby);
     let games = warp::any().map(move || games);
     let users = warp::any().map(move || users);
+    let analyzer = warp::any().map(move || analyzer);
     let http_client = warp::any().map(move || http_client);
     let user_id = user_id(users);
+    let admin_id = admin_id(users);
 
     let app = warp::path::end()
         .map(|| warp::redirect(Uri::from_static("/lobby")))
         .or(asset_endpoints::router())
         .or(game_endpoints::router(lobby, games, user_id.clone()))
-        .or(lobby_endpoints::router(lobby, games, user_id))
+        .or(lobby_endpoints::router(lobby, games, user_id.clone()))
+        .or(record_endpoints::router(storage, user_id.clone()))
         .or(auth_endpoints::router(users, http_client))
         .or(user_endpoints::router(users))
-        .or(summary::router(db))
+        .or(summary::router(storage, analyzer, user_id))
+        .or(metrics_endpoints::router(lobby, games))
+        .or(admin_endpoints::router(
+            lobby, games, users, storage, admin_id,
+        ))
         .with(
             warp::cors()
                 .allow_any_origin()
@@ -115,6 +253,9 @@ async fn main() -> Result<(), CardsError> {
         )
         .recover(error::handle_rejection)
         .with(warp::log("request"));
-    warp::serve(app).run(([127, 0, 0, 1], CONFIG.port)).await;
+    let (_, server) =
+        warp::serve(app).bind_with_graceful_shutdown(([127, 0, 0, 1], CONFIG.port), shutdown);
+    server.await;
+    database.close();
     Ok(())
 }
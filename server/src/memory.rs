@@ -0,0 +1,950 @@
+use crate::{
+    load_archived_events, rate_games, test::TestStorage, util, ArchiveEntry, Audience, CardsError,
+    ExternalUser, GameOptions, Rating, Role, Storage, StoredEvent, User,
+};
+use rand::seq::SliceRandom;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+use turbo_hearts_api::{
+    AdminAction, AnalysisResponse, AuditEntry, DuplicateId, GameEvent, GameId, GameRecord,
+    GameSnapshot, JoinToken, LobbyChat, LobbyGame, Player, PlayerRating, PlayerWithOptions,
+    Privacy, RatedPlayer, RatingChange, Seed, UserId,
+};
+
+// for tests, which don't need a database file
+pub struct MemoryStorage {
+    inner: Mutex<Inner>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                games: HashMap::new(),
+                events: HashMap::new(),
+                snapshots: HashMap::new(),
+                chat: Vec::new(),
+                users: HashMap::new(),
+                auth_tokens: HashMap::new(),
+                banned: HashSet::new(),
+                audit_log: Vec::new(),
+                duplicates: HashMap::new(),
+                ratings: HashMap::new(),
+                rating_history: Vec::new(),
+                rated: HashSet::new(),
+                analysis: HashMap::new(),
+                archived: HashMap::new(),
+                imported: HashMap::new(),
+                confirmations: HashMap::new(),
+            }),
+        }
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Inner {
+    games: HashMap<GameId, GameRow>,
+    events: HashMap<GameId, Vec<StoredEvent>>,
+    // every snapshot of a game, latest last
+    snapshots: HashMap<GameId, Vec<GameSnapshot>>,
+    chat: Vec<LobbyChat>,
+    users: HashMap<UserId, User>,
+    auth_tokens: HashMap<String, UserId>,
+    banned: HashSet<UserId>,
+    audit_log: Vec<AuditEntry>,
+    duplicates: HashMap<DuplicateId, Vec<GameId>>,
+    ratings: HashMap<RatedPlayer, Rating>,
+    rating_history: Vec<(RatedPlayer, RatingChange)>,
+    rated: HashSet<GameId>,
+    analysis: HashMap<GameId, AnalysisResponse>,
+    // where archived games were written, and how many events they had
+    archived: HashMap<GameId, (usize, ArchiveEntry)>,
+    // imported games by the hash of their deals
+    imported: HashMap<String, GameId>,
+    // who has confirmed each imported game
+    confirmations: HashMap<GameId, HashSet<UserId>>,
+}
+
+struct GameRow {
+    seed: Seed,
+    options: GameOptions,
+    players: Vec<PlayerWithOptions>,
+    duplicate_id: Option<DuplicateId>,
+    privacy: Option<Privacy>,
+    join_token: Option<JoinToken>,
+    created_time: i64,
+    created_by: UserId,
+    last_updated_time: i64,
+    last_updated_by: UserId,
+    started_time: Option<i64>,
+    completed_time: Option<i64>,
+}
+
+impl GameRow {
+    fn new(seed: Seed, options: GameOptions, user_id: UserId) -> Self {
+        let timestamp = util::timestamp();
+        Self {
+            seed,
+            options,
+            players: Vec::new(),
+            duplicate_id: None,
+            privacy: None,
+            join_token: None,
+            created_time: timestamp,
+            created_by: user_id,
+            last_updated_time: timestamp,
+            last_updated_by: user_id,
+            started_time: None,
+            completed_time: None,
+        }
+    }
+
+    fn is_member(&self, user_id: UserId) -> bool {
+        self.players.iter().any(|p| p.player.user_id() == user_id)
+    }
+
+    fn is_invited(&self, user_id: UserId) -> bool {
+        match &self.privacy {
+            Some(privacy) => privacy.invited.contains(&user_id),
+            None => false,
+        }
+    }
+
+    fn audience(&self) -> Audience {
+        match &self.privacy {
+            Some(privacy) if privacy.hidden => {
+                let mut audience = privacy.invited.clone();
+                audience.insert(self.created_by);
+                audience.extend(self.players.iter().map(|p| p.player.user_id()));
+                Some(audience)
+            }
+            _ => None,
+        }
+    }
+
+    fn insert_player(&mut self, player: &PlayerWithOptions) -> bool {
+        if self.is_member(player.player.user_id()) {
+            false
+        } else {
+            self.players.push(*player);
+            true
+        }
+    }
+}
+
+impl Inner {
+    fn unstarted_game(&mut self, game_id: GameId) -> Result<&mut GameRow, CardsError> {
+        match self.games.get_mut(&game_id) {
+            None => Err(CardsError::UnknownGame(game_id)),
+            Some(game) if game.started_time.is_some() => Err(CardsError::GameHasStarted(game_id)),
+            Some(game) => Ok(game),
+        }
+    }
+
+    fn check_access(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+        join_token: Option<JoinToken>,
+    ) -> Result<(), CardsError> {
+        let game = match self.games.get(&game_id) {
+            Some(game) if game.privacy.is_some() => game,
+            _ => return Ok(()),
+        };
+        if game.created_by == user_id || (join_token.is_some() && join_token == game.join_token) {
+            return Ok(());
+        }
+        match join_token {
+            _ if game.is_invited(user_id) || game.is_member(user_id) => Ok(()),
+            Some(join_token) => Err(CardsError::InvalidJoinToken(join_token, game_id)),
+            None => Err(CardsError::NotInvited(user_id, game_id)),
+        }
+    }
+
+    fn persist_events(&mut self, game_id: GameId, event_id: usize, events: &[GameEvent]) {
+        let timestamp = util::timestamp();
+        let archived = self.archived.get(&game_id).map_or(0, |(events, _)| *events);
+        let stored = self.events.entry(game_id).or_default();
+        debug_assert_eq!(archived + stored.len(), event_id);
+        stored.extend(events.iter().map(|event| StoredEvent {
+            game_id,
+            timestamp,
+            event: event.clone(),
+        }));
+    }
+
+    fn stored_events(&self, game_id: GameId) -> Result<Vec<StoredEvent>, CardsError> {
+        let mut events = match self.archived.get(&game_id) {
+            Some((_, entry)) => load_archived_events(game_id, entry)?,
+            None => Vec::new(),
+        };
+        events.extend(self.events.get(&game_id).into_iter().flatten().cloned());
+        Ok(events)
+    }
+
+    fn in_unfinished_duplicate(&self, game_id: GameId) -> bool {
+        match self.games.get(&game_id).and_then(|game| game.duplicate_id) {
+            Some(duplicate_id) => self.duplicates[&duplicate_id].iter().any(|table| {
+                self.games
+                    .get(table)
+                    .is_some_and(|table| table.completed_time.is_none())
+            }),
+            None => false,
+        }
+    }
+
+    fn completed_games(&self, filter: impl Fn(&GameId) -> bool) -> Vec<(GameId, i64)> {
+        let mut games = self
+            .games
+            .iter()
+            .filter(|(game_id, _)| filter(game_id))
+            .filter_map(|(&game_id, game)| game.completed_time.map(|time| (game_id, time)))
+            .collect::<Vec<_>>();
+        games.sort_by_key(|(game_id, completed_time)| (*completed_time, game_id.to_string()));
+        games
+    }
+
+    // whether every human player has confirmed a game, if it was imported
+    fn is_confirmed(&self, game_id: &GameId) -> bool {
+        match (self.confirmations.get(game_id), self.games.get(game_id)) {
+            (Some(confirmations), Some(game)) => game
+                .players
+                .iter()
+                .filter(|p| p.player.strategy().is_none())
+                .all(|p| confirmations.contains(&p.player.user_id())),
+            _ => true,
+        }
+    }
+
+    fn insert_audit(&mut self, user_id: UserId, action: AdminAction) {
+        self.audit_log.push(AuditEntry {
+            timestamp: util::timestamp(),
+            user_id,
+            action,
+        });
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn persist_events(
+        &self,
+        game_id: GameId,
+        event_id: usize,
+        events: &[GameEvent],
+    ) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.persist_events(game_id, event_id, events);
+        Ok(())
+    }
+
+    fn persist_hand(
+        &self,
+        game_id: GameId,
+        event_id: usize,
+        events: &[GameEvent],
+        snapshot: Option<&GameSnapshot>,
+        completed: bool,
+    ) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(snapshot) = snapshot {
+            inner
+                .snapshots
+                .entry(game_id)
+                .or_default()
+                .push(snapshot.clone());
+        }
+        inner.persist_events(game_id, event_id, events);
+        if completed {
+            if let Some(game) = inner.games.get_mut(&game_id) {
+                game.completed_time = Some(util::timestamp());
+            }
+        }
+        Ok(())
+    }
+
+    fn load_game(
+        &self,
+        game_id: GameId,
+    ) -> Result<(Option<GameSnapshot>, Vec<GameEvent>), CardsError> {
+        let inner = self.inner.lock().unwrap();
+        let events = inner.stored_events(game_id)?;
+        let snapshot = inner
+            .snapshots
+            .get(&game_id)
+            .and_then(|snapshots| snapshots.last())
+            .filter(|snapshot| snapshot.event_id < events.len())
+            .cloned();
+        let from = snapshot.as_ref().map_or(0, |snapshot| snapshot.event_id);
+        let events = events
+            .into_iter()
+            .enumerate()
+            .filter(|&(event_id, _)| event_id == 0 || event_id >= from)
+            .map(|(_, e)| e.event)
+            .collect();
+        Ok((snapshot, events))
+    }
+
+    fn check_access(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+        join_token: Option<JoinToken>,
+    ) -> Result<(), CardsError> {
+        let inner = self.inner.lock().unwrap();
+        inner.check_access(game_id, user_id, join_token)
+    }
+
+    fn load_bot_games(&self) -> Result<Vec<GameId>, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .games
+            .iter()
+            .filter(|(_, game)| game.started_time.is_some() && game.completed_time.is_none())
+            .filter(|(game_id, _)| {
+                let events = inner.events.get(game_id).map_or(&[][..], |e| &e[..]);
+                events.iter().any(|e| match &e.event {
+                    GameEvent::Sit {
+                        north,
+                        east,
+                        south,
+                        west,
+                        ..
+                    } => [north, east, south, west]
+                        .iter()
+                        .any(|player| matches!(***player, Player::Bot { .. })),
+                    GameEvent::Takeover { .. } => true,
+                    _ => false,
+                })
+            })
+            .map(|(game_id, _)| *game_id)
+            .collect())
+    }
+
+    fn delete_stale_games(&self) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        let now = util::timestamp();
+        let stale = inner
+            .games
+            .iter()
+            .filter(|(_, game)| {
+                (game.started_time.is_none() && game.last_updated_time < now - 24 * 60 * 60 * 1000)
+                    || (game.completed_time.is_none()
+                        && game.last_updated_time < now - 21 * 24 * 60 * 60 * 1000)
+            })
+            .map(|(game_id, _)| *game_id)
+            .collect::<Vec<_>>();
+        for game_id in stale {
+            inner.games.remove(&game_id);
+            inner.events.remove(&game_id);
+            inner.snapshots.remove(&game_id);
+        }
+        Ok(())
+    }
+
+    fn load_lobby(
+        &self,
+        user_id: UserId,
+    ) -> Result<(Vec<LobbyChat>, HashMap<GameId, LobbyGame>), CardsError> {
+        let inner = self.inner.lock().unwrap();
+        let chat = inner.chat[inner.chat.len().saturating_sub(500)..].to_vec();
+        let since = util::timestamp() - 8 * 60 * 60 * 1000;
+        let games = inner
+            .games
+            .iter()
+            .filter(|(_, game)| game.completed_time.is_none() && game.last_updated_time > since)
+            .filter(|(_, game)| !game.players.is_empty() || game.duplicate_id.is_some())
+            .filter(|(_, game)| match &game.privacy {
+                Some(privacy) if privacy.hidden => {
+                    game.created_by == user_id
+                        || game.is_invited(user_id)
+                        || game.is_member(user_id)
+                }
+                _ => true,
+            })
+            .map(|(&game_id, game)| {
+                (
+                    game_id,
+                    LobbyGame {
+                        players: game.players.iter().copied().collect(),
+                        seed: game.seed.redact(),
+                        time_control: game.options.time_control,
+                        spectators: game.options.spectators,
+                        rule_set: game.options.rule_set,
+                        length: game.options.length,
+                        private: game.privacy.is_some(),
+                        duplicate_id: game.duplicate_id,
+                        created_time: game.created_time,
+                        created_by: game.created_by,
+                        last_updated_time: game.last_updated_time,
+                        last_updated_by: game.last_updated_by,
+                        started_time: game.started_time,
+                    },
+                )
+            })
+            .collect();
+        Ok((chat, games))
+    }
+
+    fn insert_game(
+        &self,
+        game_id: GameId,
+        seed: &Seed,
+        options: GameOptions,
+        player: &PlayerWithOptions,
+        privacy: Option<&Privacy>,
+        join_token: Option<JoinToken>,
+    ) -> Result<Audience, CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut game = GameRow::new(seed.clone(), options, player.player.user_id());
+        game.insert_player(player);
+        game.privacy = privacy.cloned();
+        game.join_token = join_token;
+        let audience = game.audience();
+        inner.games.insert(game_id, game);
+        Ok(audience)
+    }
+
+    fn insert_duplicate(
+        &self,
+        duplicate_id: DuplicateId,
+        game_ids: &[GameId],
+        value: &str,
+        options: GameOptions,
+        player: &PlayerWithOptions,
+    ) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        for (rotation, &game_id) in game_ids.iter().enumerate() {
+            let seed = Seed::Duplicate {
+                value: value.to_string(),
+                rotation: rotation as u8,
+            };
+            let mut game = GameRow::new(seed, options, player.player.user_id());
+            game.duplicate_id = Some(duplicate_id);
+            if rotation == 0 {
+                game.insert_player(player);
+            }
+            inner.games.insert(game_id, game);
+        }
+        inner.duplicates.insert(duplicate_id, game_ids.to_vec());
+        Ok(())
+    }
+
+    fn insert_player(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+        player: &PlayerWithOptions,
+        join_token: Option<JoinToken>,
+    ) -> Result<(bool, Audience), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.unstarted_game(game_id)?;
+        inner.check_access(game_id, user_id, join_token)?;
+        let game = inner.unstarted_game(game_id)?;
+        if game.insert_player(player) {
+            game.last_updated_time = util::timestamp();
+            game.last_updated_by = player.player.user_id();
+            Ok((true, game.audience()))
+        } else {
+            Ok((false, None))
+        }
+    }
+
+    fn remove_player(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+    ) -> Result<(Option<Player>, Audience), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        let game = inner.unstarted_game(game_id)?;
+        let audience = game.audience();
+        let player = match game
+            .players
+            .iter()
+            .position(|p| p.player.user_id() == user_id)
+        {
+            Some(idx) => {
+                game.last_updated_time = util::timestamp();
+                game.last_updated_by = user_id;
+                Some(game.players.remove(idx).player)
+            }
+            None => None,
+        };
+        Ok((player, audience))
+    }
+
+    fn start_game(
+        &self,
+        game_id: GameId,
+    ) -> Result<(Vec<PlayerWithOptions>, Seed, GameOptions, Audience), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        let game = inner.unstarted_game(game_id)?;
+        if game.players.len() < 4 {
+            return Err(CardsError::NotEnoughPlayers);
+        }
+        game.started_time = Some(util::timestamp());
+        let players = game
+            .players
+            .choose_multiple(&mut rand::thread_rng(), 4)
+            .copied()
+            .collect();
+        Ok((players, game.seed.clone(), game.options, game.audience()))
+    }
+
+    fn audience(&self, game_id: GameId) -> Result<Audience, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.games.get(&game_id).and_then(|game| game.audience()))
+    }
+
+    fn insert_chat(&self, user_id: UserId, message: &str) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.chat.push(LobbyChat {
+            timestamp: util::timestamp(),
+            user_id,
+            message: message.to_string(),
+        });
+        Ok(())
+    }
+
+    fn load_user_for_token(&self, auth_token: &str) -> Result<Option<User>, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .auth_tokens
+            .get(auth_token)
+            .and_then(|id| inner.users.get(id))
+            .cloned())
+    }
+
+    fn load_users(&self, ids: &[UserId]) -> Result<Vec<User>, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(ids
+            .iter()
+            .filter_map(|id| inner.users.get(id))
+            .cloned()
+            .collect())
+    }
+
+    fn insert_user(&self, auth_token: &str, user: &ExternalUser) -> Result<User, CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        let existing = inner
+            .users
+            .values()
+            .find(|u| u.realm == user.realm && u.external_id == user.external_id)
+            .map(|u| u.id);
+        let id = match existing {
+            Some(id) => id,
+            None => {
+                let id = UserId::new();
+                inner.users.insert(
+                    id,
+                    User {
+                        id,
+                        name: user.name.clone(),
+                        realm: user.realm.clone(),
+                        external_id: user.external_id.clone(),
+                        role: Role::Player,
+                    },
+                );
+                id
+            }
+        };
+        if inner.banned.contains(&id) {
+            return Err(CardsError::BannedUser(id));
+        }
+        inner
+            .auth_tokens
+            .entry(auth_token.to_string())
+            .or_insert(id);
+        Ok(inner.users[&id].clone())
+    }
+
+    fn load_completed_games(
+        &self,
+        after: Option<GameId>,
+        page_size: u32,
+    ) -> Result<Vec<StoredEvent>, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        let before = match after {
+            Some(game_id) => match inner.games.get(&game_id).and_then(|g| g.completed_time) {
+                Some(completed_time) => completed_time,
+                None => return Ok(Vec::new()),
+            },
+            None => i64::MAX,
+        };
+        let mut games = inner
+            .games
+            .iter()
+            .filter_map(|(&game_id, game)| match game.completed_time {
+                Some(completed_time) if completed_time < before => Some((game_id, completed_time)),
+                _ => None,
+            })
+            .filter(|(game_id, _)| {
+                match inner.stored_events(*game_id).unwrap_or_default().first() {
+                    Some(first) => match &first.event {
+                        GameEvent::Sit {
+                            north,
+                            east,
+                            south,
+                            west,
+                            ..
+                        } => [north, east, south, west]
+                            .iter()
+                            .all(|p| p.strategy().is_none()),
+                        _ => false,
+                    },
+                    None => false,
+                }
+            })
+            .filter(|(game_id, _)| {
+                let game = &inner.games[game_id];
+                game.audience().is_none() && !inner.in_unfinished_duplicate(*game_id)
+            })
+            .collect::<Vec<_>>();
+        games.sort_by_key(|&(_, completed_time)| -completed_time);
+        games.truncate(page_size as usize);
+        let mut game_ids = games
+            .into_iter()
+            .map(|(game_id, _)| game_id)
+            .collect::<Vec<_>>();
+        game_ids.sort_by_key(|game_id| game_id.to_string());
+        let mut events = Vec::new();
+        for game_id in game_ids {
+            events.extend(inner.stored_events(game_id)?);
+        }
+        Ok(events)
+    }
+
+    fn load_game_events(&self, game_id: GameId) -> Result<Vec<StoredEvent>, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        inner.stored_events(game_id)
+    }
+
+    fn load_game_times(&self, game_id: GameId) -> Result<(i64, Option<i64>), CardsError> {
+        let inner = self.inner.lock().unwrap();
+        match inner.games.get(&game_id) {
+            Some(game) => Ok((game.created_time, game.completed_time)),
+            None => Err(CardsError::UnknownGame(game_id)),
+        }
+    }
+
+    fn insert_record(
+        &self,
+        user_id: UserId,
+        seed: &Seed,
+        options: GameOptions,
+        players: &[PlayerWithOptions],
+        record: &GameRecord,
+    ) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        let game_id = record.game_id.unwrap();
+        let deal_hash = record.deal_hash();
+        if let Some(&imported) = inner.imported.get(&deal_hash) {
+            return Err(CardsError::DuplicateImport(imported));
+        }
+        let mut game = GameRow::new(seed.clone(), options, user_id);
+        for player in players {
+            game.insert_player(player);
+        }
+        game.created_time = record.created_time.unwrap();
+        game.last_updated_time = record.completed_time.unwrap();
+        game.started_time = record.created_time;
+        game.completed_time = record.completed_time;
+        inner.games.insert(game_id, game);
+        inner.imported.insert(deal_hash, game_id);
+        inner
+            .confirmations
+            .insert(game_id, vec![user_id].into_iter().collect());
+        inner.persist_events(game_id, 0, &record.events);
+        Ok(())
+    }
+
+    fn confirm_import(&self, game_id: GameId, user_id: UserId) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        let human = match inner.games.get(&game_id) {
+            Some(game) => game
+                .players
+                .iter()
+                .any(|p| p.player.user_id() == user_id && p.player.strategy().is_none()),
+            None => false,
+        };
+        let confirmations = match inner.confirmations.get_mut(&game_id) {
+            Some(confirmations) => confirmations,
+            None => return Err(CardsError::NotImported(game_id)),
+        };
+        if !human {
+            return Err(CardsError::InvalidPlayer(user_id, game_id));
+        }
+        confirmations.insert(user_id);
+        Ok(())
+    }
+
+    fn load_duplicate_games(&self, duplicate_id: DuplicateId) -> Result<Vec<GameId>, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .duplicates
+            .get(&duplicate_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn load_duplicate_id(&self, game_id: GameId) -> Result<Option<DuplicateId>, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.games.get(&game_id).and_then(|game| game.duplicate_id))
+    }
+
+    fn update_ratings(&self) -> Result<usize, CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut events = Vec::new();
+        for (game_id, completed_time) in inner.completed_games(|game_id| {
+            !inner.rated.contains(game_id) && inner.is_confirmed(game_id)
+        }) {
+            for stored in inner.events.get(&game_id).into_iter().flatten() {
+                events.push((game_id, completed_time, stored.event.clone()));
+            }
+        }
+        let update = rate_games(events, |player| {
+            Ok(inner.ratings.get(&player).copied().unwrap_or_default())
+        })?;
+        inner.ratings.extend(update.ratings);
+        inner.rating_history.extend(update.history);
+        inner
+            .rated
+            .extend(update.games.iter().map(|(game_id, _)| *game_id));
+        Ok(update.games.len())
+    }
+
+    fn load_ratings(&self) -> Result<Vec<PlayerRating>, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        let mut ratings = inner
+            .ratings
+            .iter()
+            .map(|(&player, rating)| PlayerRating {
+                player,
+                rating: rating.rating,
+                hands: rating.hands,
+                updated_time: rating.updated_time,
+            })
+            .collect::<Vec<_>>();
+        ratings.sort_by(|a, b| b.rating.partial_cmp(&a.rating).unwrap());
+        Ok(ratings)
+    }
+
+    fn load_rating_history(&self, player: RatedPlayer) -> Result<Vec<RatingChange>, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        let mut history = inner
+            .rating_history
+            .iter()
+            .filter(|(p, _)| *p == player)
+            .map(|(_, change)| change.clone())
+            .collect::<Vec<_>>();
+        history.sort_by_key(|change| {
+            (
+                change.completed_time,
+                change.game_id.to_string(),
+                change.hand,
+            )
+        });
+        Ok(history)
+    }
+
+    fn load_analysis(&self, game_id: GameId) -> Result<Option<AnalysisResponse>, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.analysis.get(&game_id).cloned())
+    }
+
+    fn save_analysis(&self, analysis: &AnalysisResponse) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.analysis.insert(analysis.game_id, analysis.clone());
+        Ok(())
+    }
+
+    fn load_archivable_games(
+        &self,
+        cutoff: i64,
+        limit: u32,
+    ) -> Result<Vec<StoredEvent>, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        let mut game_ids = inner
+            .completed_games(|game_id| {
+                inner.rated.contains(game_id) && !inner.archived.contains_key(game_id)
+            })
+            .into_iter()
+            .filter(|&(_, completed_time)| completed_time < cutoff)
+            .take(limit as usize)
+            .map(|(game_id, _)| game_id)
+            .collect::<Vec<_>>();
+        game_ids.sort_by_key(|game_id| game_id.to_string());
+        Ok(game_ids
+            .iter()
+            .flat_map(|game_id| inner.events.get(game_id).into_iter().flatten().cloned())
+            .collect())
+    }
+
+    fn mark_archived(&self, games: &[(GameId, usize, ArchiveEntry)]) -> Result<usize, CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut archived = 0;
+        for (game_id, events, entry) in games {
+            if inner.events.get(game_id).map_or(0, |e| e.len()) != *events {
+                continue;
+            }
+            inner.events.remove(game_id);
+            inner.snapshots.remove(game_id);
+            inner.archived.insert(*game_id, (*events, entry.clone()));
+            archived += 1;
+        }
+        Ok(archived)
+    }
+
+    fn delete_game(&self, admin_id: UserId, game_id: GameId) -> Result<Audience, CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        let game = match inner.games.remove(&game_id) {
+            Some(game) => game,
+            None => return Err(CardsError::UnknownGame(game_id)),
+        };
+        inner.events.remove(&game_id);
+        inner.snapshots.remove(&game_id);
+        inner.analysis.remove(&game_id);
+        inner.archived.remove(&game_id);
+        inner.imported.retain(|_, id| *id != game_id);
+        inner.confirmations.remove(&game_id);
+        for game_ids in inner.duplicates.values_mut() {
+            game_ids.retain(|id| *id != game_id);
+        }
+        inner.insert_audit(admin_id, AdminAction::DeleteGame { game_id });
+        Ok(game.audience())
+    }
+
+    fn abort_game(
+        &self,
+        admin_id: UserId,
+        game_id: GameId,
+        event_id: usize,
+        events: &[GameEvent],
+    ) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.persist_events(game_id, event_id, events);
+        if let Some(game) = inner.games.get_mut(&game_id) {
+            game.completed_time = Some(util::timestamp());
+        }
+        inner.insert_audit(admin_id, AdminAction::AbortGame { game_id });
+        Ok(())
+    }
+
+    fn rename_user(&self, admin_id: UserId, user_id: UserId, name: &str) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        let user = match inner.users.get_mut(&user_id) {
+            Some(user) => user,
+            None => return Err(CardsError::UnknownUser(user_id)),
+        };
+        let old_name = std::mem::replace(&mut user.name, name.to_string());
+        inner.insert_audit(
+            admin_id,
+            AdminAction::RenameUser {
+                user_id,
+                old_name,
+                new_name: name.to_string(),
+            },
+        );
+        Ok(())
+    }
+
+    fn ban_user(&self, admin_id: UserId, user_id: UserId) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.users.contains_key(&user_id) {
+            return Err(CardsError::UnknownUser(user_id));
+        }
+        inner.banned.insert(user_id);
+        inner.auth_tokens.retain(|_, id| *id != user_id);
+        inner.insert_audit(admin_id, AdminAction::BanUser { user_id });
+        Ok(())
+    }
+
+    fn delete_chat(
+        &self,
+        admin_id: UserId,
+        user_id: UserId,
+        timestamp: i64,
+    ) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        let idx = match inner
+            .chat
+            .iter()
+            .position(|c| c.user_id == user_id && c.timestamp == timestamp)
+        {
+            Some(idx) => idx,
+            None => return Err(CardsError::UnknownChat(user_id, timestamp)),
+        };
+        let LobbyChat { message, .. } = inner.chat.remove(idx);
+        inner.insert_audit(
+            admin_id,
+            AdminAction::DeleteChat {
+                user_id,
+                timestamp,
+                message,
+            },
+        );
+        Ok(())
+    }
+
+    fn load_audit_log(&self, limit: u32) -> Result<Vec<AuditEntry>, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .audit_log
+            .iter()
+            .rev()
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+impl TestStorage for MemoryStorage {
+    fn count_snapshots(&self, game_id: GameId) -> Result<usize, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.snapshots.get(&game_id).map_or(0, |s| s.len()))
+    }
+
+    fn corrupt_snapshots(&self, game_id: GameId) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        for snapshot in inner.snapshots.entry(game_id).or_default() {
+            snapshot.state.totals[0] = 1000;
+        }
+        Ok(())
+    }
+
+    fn count_events(&self, game_id: GameId) -> Result<usize, CardsError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.events.get(&game_id).map_or(0, |e| e.len()))
+    }
+
+    fn make_admin(&self, user_id: UserId) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(user) = inner.users.get_mut(&user_id) {
+            user.role = Role::Admin;
+        }
+        Ok(())
+    }
+
+    fn insert_duplicate_table(
+        &self,
+        duplicate_id: DuplicateId,
+        game_id: GameId,
+    ) -> Result<(), CardsError> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(game) = inner.games.get_mut(&game_id) {
+            game.duplicate_id = Some(duplicate_id);
+        }
+        inner
+            .duplicates
+            .entry(duplicate_id)
+            .or_default()
+            .insert(0, game_id);
+        Ok(())
+    }
+}
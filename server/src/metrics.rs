@@ -0,0 +1,108 @@
+use crate::{CardsError, Games, Lobby};
+use once_cell::sync::Lazy;
+use prometheus::{
+    exponential_buckets, register_histogram, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge, register_int_gauge_vec, Histogram, HistogramVec,
+    IntCounter, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+};
+use std::time::Duration;
+use turbo_hearts_api::BotStrategy;
+
+static ACTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "turbo_hearts_actions_total",
+        "Game actions by type and outcome, which is ok or the error returned",
+        &["action", "outcome"]
+    )
+    .unwrap()
+});
+
+static ACTIVE_GAMES: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "turbo_hearts_active_games",
+        "Games currently loaded in memory"
+    )
+    .unwrap()
+});
+
+static SUBSCRIBERS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "turbo_hearts_subscribers",
+        "Open event streams to the lobby and to games",
+        &["stream"]
+    )
+    .unwrap()
+});
+
+static BOT_DECISION_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "turbo_hearts_bot_decision_seconds",
+        "Time bots take to decide on a pass, charge or play",
+        &["strategy", "decision"],
+        exponential_buckets(0.001, 4.0, 9).unwrap()
+    )
+    .unwrap()
+});
+
+static SQLITE_RETRIES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "turbo_hearts_sqlite_retries_total",
+        "Transactions retried because the database was busy or locked"
+    )
+    .unwrap()
+});
+
+static HYDRATION_SECONDS: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "turbo_hearts_hydration_seconds",
+        "Time taken to load a game from storage and replay its events",
+        exponential_buckets(0.001, 4.0, 8).unwrap()
+    )
+    .unwrap()
+});
+
+// failures are labelled with the error name, or the rules error for rejected moves
+pub fn record_action<T>(action: &str, result: &Result<T, CardsError>) {
+    let outcome = match result {
+        Ok(_) => "ok".to_string(),
+        Err(CardsError::Rules { source }) => variant(format!("{:?}", source)),
+        Err(e) => variant(format!("{:?}", e)),
+    };
+    ACTIONS.with_label_values(&[action, &outcome]).inc();
+}
+
+pub fn record_bot_decision(strategy: BotStrategy, decision: &str, elapsed: Duration) {
+    BOT_DECISION_SECONDS
+        .with_label_values(&[&format!("{:?}", strategy), decision])
+        .observe(elapsed.as_secs_f64());
+}
+
+pub fn record_sqlite_retry() {
+    SQLITE_RETRIES.inc();
+}
+
+pub fn record_hydration(elapsed: Duration) {
+    HYDRATION_SECONDS.observe(elapsed.as_secs_f64());
+}
+
+// the leading identifier of a debug formatted enum
+fn variant(debug: String) -> String {
+    debug
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+// gauges read from the lobby and games are updated on each scrape
+pub async fn render_metrics(lobby: &Lobby, games: &Games) -> Result<String, CardsError> {
+    let (active_games, game_subscribers) = games.counts().await;
+    ACTIVE_GAMES.set(active_games as i64);
+    SUBSCRIBERS
+        .with_label_values(&["game"])
+        .set(game_subscribers as i64);
+    SUBSCRIBERS
+        .with_label_values(&["lobby"])
+        .set(lobby.subscriber_count().await as i64);
+    Ok(TextEncoder::new().encode_to_string(&prometheus::gather())?)
+}
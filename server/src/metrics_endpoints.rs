@@ -0,0 +1,20 @@
+use crate::{render_metrics, Games, Lobby};
+use prometheus::TEXT_FORMAT;
+use warp::{http::header, Filter, Rejection, Reply};
+
+pub fn router<'a>(lobby: infallible!(&'a Lobby), games: infallible!(&'a Games)) -> reply!() {
+    async fn handle(lobby: &Lobby, games: &Games) -> Result<impl Reply, Rejection> {
+        let metrics = render_metrics(lobby, games).await?;
+        Ok(warp::reply::with_header(
+            metrics,
+            header::CONTENT_TYPE,
+            TEXT_FORMAT,
+        ))
+    }
+
+    warp::path!("metrics")
+        .and(warp::get())
+        .and(lobby)
+        .and(games)
+        .and_then(handle)
+}
@@ -0,0 +1,195 @@
+use crate::CardsError;
+use rusqlite::{Connection, OpenFlags, NO_PARAMS};
+use std::{env, error::Error, process};
+
+// a database's version is the number of migrations applied, kept in
+// PRAGMA user_version
+pub struct Migration {
+    pub name: &'static str,
+    up: &'static str,
+    down: &'static str,
+}
+
+macro_rules! migration {
+    ($name:literal) => {
+        Migration {
+            name: $name,
+            up: include_str!(concat!("../sql/", $name, ".up.sql")),
+            down: include_str!(concat!("../sql/", $name, ".down.sql")),
+        }
+    };
+}
+
+pub static MIGRATIONS: &[Migration] = &[
+    migration!("001_schema"),
+    migration!("002_time_control"),
+    migration!("003_ratings"),
+    migration!("004_duplicate"),
+    migration!("005_spectators"),
+    migration!("006_private"),
+    migration!("007_analysis"),
+    migration!("008_rule_set"),
+    migration!("009_length"),
+    migration!("010_snapshot"),
+    migration!("011_archive"),
+    migration!("012_admin"),
+    migration!("013_import"),
+];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Step {
+    pub direction: Direction,
+    pub version: usize,
+    pub name: &'static str,
+}
+
+impl Step {
+    fn sql(&self) -> &'static str {
+        let migration = &MIGRATIONS[self.version - 1];
+        match self.direction {
+            Direction::Up => migration.up,
+            Direction::Down => migration.down,
+        }
+    }
+
+    // the version the database is at once the step has run
+    fn target(&self) -> usize {
+        match self.direction {
+            Direction::Up => self.version,
+            Direction::Down => self.version - 1,
+        }
+    }
+}
+
+pub fn latest_version() -> usize {
+    MIGRATIONS.len()
+}
+
+pub fn schema_version(conn: &Connection) -> Result<usize, rusqlite::Error> {
+    conn.query_row("PRAGMA user_version", NO_PARAMS, |row| {
+        Ok(row.get::<_, i64>(0)? as usize)
+    })
+}
+
+pub fn plan(from: usize, to: usize) -> Vec<Step> {
+    let step = |direction, version| Step {
+        direction,
+        version,
+        name: MIGRATIONS[version - 1].name,
+    };
+    if from <= to {
+        (from + 1..=to).map(|v| step(Direction::Up, v)).collect()
+    } else {
+        (to + 1..=from)
+            .rev()
+            .map(|v| step(Direction::Down, v))
+            .collect()
+    }
+}
+
+// one transaction, so a failing script leaves the database as it was and a
+// dry run just rolls back
+pub fn migrate(
+    conn: &mut Connection,
+    version: usize,
+    dry_run: bool,
+) -> Result<Vec<Step>, CardsError> {
+    let from = schema_version(conn)?;
+    if from > latest_version() {
+        return Err(CardsError::UnknownSchemaVersion(from));
+    }
+    let steps = plan(from, version.min(latest_version()));
+    let tx = conn.transaction()?;
+    for step in &steps {
+        tx.execute_batch(step.sql())?;
+        tx.execute_batch(&format!("PRAGMA user_version = {}", step.target()))?;
+    }
+    if dry_run {
+        tx.rollback()?;
+    } else {
+        tx.commit()?;
+    }
+    Ok(steps)
+}
+
+const USAGE: &str = "usage: turbo-hearts migrate [--dry-run] [--to VERSION] DB_PATH
+
+Migrates a database to the latest schema version, or up or down to the given
+one. Every migration runs in one transaction, so the database is left as it
+was if any of them fail. A dry run checks that the migrations apply and then
+rolls them back. Undoing a migration discards the data it added.";
+
+struct Options {
+    dry_run: bool,
+    version: usize,
+    path: String,
+}
+
+fn parse_options<I>(mut args: I) -> Result<Options, Box<dyn Error>>
+where
+    I: Iterator<Item = String>,
+{
+    let mut dry_run = false;
+    let mut version = latest_version();
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--to" => {
+                let value = args.next().ok_or("missing value for --to")?;
+                version = value.parse()?;
+                if version > latest_version() {
+                    return Err(format!("the latest version is {}", latest_version()).into());
+                }
+            }
+            "--help" | "-h" => return Err(USAGE.into()),
+            _ => path = Some(arg),
+        }
+    }
+    let path = path.ok_or("a database path is required, see --help")?;
+    Ok(Options {
+        dry_run,
+        version,
+        path,
+    })
+}
+
+fn run<I>(args: I) -> Result<(), Box<dyn Error>>
+where
+    I: Iterator<Item = String>,
+{
+    let options = parse_options(args)?;
+    let mut conn = Connection::open_with_flags(&options.path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+    let from = schema_version(&conn)?;
+    let steps = migrate(&mut conn, options.version, options.dry_run)?;
+    for step in &steps {
+        let direction = match step.direction {
+            Direction::Up => "up",
+            Direction::Down => "down",
+        };
+        println!("{:<4} {}", direction, step.name);
+    }
+    let to = steps.last().map_or(from, |step| step.target());
+    if options.dry_run {
+        println!(
+            "{} would migrate from version {} to {}",
+            options.path, from, to
+        );
+    } else {
+        println!("{} migrated from version {} to {}", options.path, from, to);
+    }
+    Ok(())
+}
+
+pub fn migrate_command() {
+    if let Err(e) = run(env::args().skip(2)) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
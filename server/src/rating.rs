@@ -0,0 +1,137 @@
+use crate::CardsError;
+use std::{cmp::Ordering, collections::HashMap};
+use turbo_hearts_api::{GameEvent, GameId, GameState, RatedPlayer, RatingChange, Seat, UserId};
+
+const INITIAL_RATING: f64 = 1500.0;
+const K_FACTOR: f64 = 16.0;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Rating {
+    pub rating: f64,
+    pub hands: u32,
+    pub updated_time: i64,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            rating: INITIAL_RATING,
+            hands: 0,
+            updated_time: 0,
+        }
+    }
+}
+
+pub struct RatingUpdate {
+    pub ratings: HashMap<RatedPlayer, Rating>,
+    // after every hand each player played
+    pub history: Vec<(RatedPlayer, RatingChange)>,
+    pub games: Vec<(GameId, i64)>,
+}
+
+// events must be in the order their games completed, and players start from
+// whatever rating load_rating gives them
+pub fn rate_games<I, F>(events: I, mut load_rating: F) -> Result<RatingUpdate, CardsError>
+where
+    I: IntoIterator<Item = (GameId, i64, GameEvent)>,
+    F: FnMut(RatedPlayer) -> Result<Rating, CardsError>,
+{
+    let mut update = RatingUpdate {
+        ratings: HashMap::new(),
+        history: Vec::new(),
+        games: Vec::new(),
+    };
+    let mut players = [RatedPlayer::Human {
+        user_id: UserId::null(),
+    }; 4];
+    let mut state = GameState::new();
+    let mut hand = 0u16;
+    for (game_id, completed_time, event) in events {
+        if let GameEvent::Sit {
+            north,
+            east,
+            south,
+            west,
+            ..
+        } = &event
+        {
+            players = [
+                (**north).into(),
+                (**east).into(),
+                (**south).into(),
+                (**west).into(),
+            ];
+            state = GameState::new();
+            hand = 0;
+            update.games.push((game_id, completed_time));
+        }
+        // the hand an aborted game was in the middle of is never rated
+        if let GameEvent::Abort = event {
+            continue;
+        }
+        let was_playing = state.phase.is_playing();
+        state.apply(&event);
+        if was_playing && !state.phase.is_playing() {
+            let scores = state.scores();
+            let money = [
+                scores.money(Seat::North),
+                scores.money(Seat::East),
+                scores.money(Seat::South),
+                scores.money(Seat::West),
+            ];
+            let mut before = [INITIAL_RATING; 4];
+            for (i, player) in players.iter().enumerate() {
+                if !update.ratings.contains_key(player) {
+                    update.ratings.insert(*player, load_rating(*player)?);
+                }
+                before[i] = update.ratings[player].rating;
+            }
+            let after = rate_hand(before, money);
+            let mut changes: HashMap<RatedPlayer, (f64, i16)> = HashMap::new();
+            for (i, player) in players.iter().enumerate() {
+                let change = changes.entry(*player).or_default();
+                change.0 += after[i] - before[i];
+                change.1 += money[i];
+            }
+            for (player, (delta, money)) in changes {
+                let rating = update.ratings.get_mut(&player).unwrap();
+                rating.rating += delta;
+                rating.hands += 1;
+                rating.updated_time = completed_time;
+                update.history.push((
+                    player,
+                    RatingChange {
+                        game_id,
+                        hand,
+                        completed_time,
+                        money,
+                        rating: rating.rating,
+                    },
+                ));
+            }
+            hand += 1;
+        }
+    }
+    Ok(update)
+}
+
+// each hand is scored as a head to head match between every pair of seats,
+// won by whoever took more money from the hand
+fn rate_hand(ratings: [f64; 4], money: [i16; 4]) -> [f64; 4] {
+    let mut result = ratings;
+    for i in 0..4 {
+        for j in 0..4 {
+            if i == j {
+                continue;
+            }
+            let expected = 1.0 / (1.0 + 10f64.powf((ratings[j] - ratings[i]) / 400.0));
+            let actual = match money[i].cmp(&money[j]) {
+                Ordering::Greater => 1.0,
+                Ordering::Equal => 0.5,
+                Ordering::Less => 0.0,
+            };
+            result[i] += K_FACTOR * (actual - expected) / 3.0;
+        }
+    }
+    result
+}
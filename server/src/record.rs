@@ -0,0 +1,112 @@
+use crate::{load_events, util, CardsError, GameOptions, Storage};
+use std::collections::HashSet;
+use turbo_hearts_api::{
+    Game, GameEvent, GameId, GameRecord, PlayerWithOptions, Seat, Seed, UserId,
+};
+
+pub fn load_record(storage: &dyn Storage, game_id: GameId) -> Result<GameRecord, CardsError> {
+    let (created_time, completed_time) = storage.load_game_times(game_id)?;
+    let completed_time = completed_time.ok_or(CardsError::IncompleteGame(game_id))?;
+    let mut record = GameRecord::new(load_events(storage, game_id)?);
+    record.game_id = Some(game_id);
+    record.created_time = Some(created_time);
+    record.completed_time = Some(completed_time);
+    Ok(record)
+}
+
+// every action is checked as it would be in a live game, and the game isn't
+// rated until the other human players confirm it with confirm_import
+pub fn import_record(
+    storage: &dyn Storage,
+    user_id: UserId,
+    record: &GameRecord,
+) -> Result<GameId, CardsError> {
+    let game_id = GameId::new();
+    let (players, rules, seed, time_control, spectators, rule_set, length) =
+        match record.events.first() {
+            Some(GameEvent::Sit {
+                north,
+                east,
+                south,
+                west,
+                rules,
+                seed,
+                time_control,
+                spectators,
+                rule_set,
+                length,
+            }) => (
+                [**north, **east, **south, **west],
+                rules,
+                seed,
+                time_control,
+                spectators,
+                rule_set,
+                length,
+            ),
+            _ => return Err(CardsError::MissingPlayers),
+        };
+    if let Seed::Redacted = seed {
+        return Err(CardsError::RedactedSeed);
+    }
+    let user_ids = players.iter().map(|p| p.user_id()).collect::<HashSet<_>>();
+    if user_ids.len() < 4 {
+        return Err(CardsError::MissingPlayers);
+    }
+    // a game can only be imported by someone who played in it, alongside
+    // people who have signed in here, so nobody can put games on strangers
+    let humans = players
+        .iter()
+        .filter(|player| player.strategy().is_none())
+        .map(|player| player.user_id())
+        .collect::<Vec<_>>();
+    if !humans.contains(&user_id) {
+        return Err(CardsError::InvalidPlayer(user_id, game_id));
+    }
+    let known = storage
+        .load_users(&humans)?
+        .into_iter()
+        .map(|user| user.id)
+        .collect::<HashSet<_>>();
+    if let Some(&unknown) = humans.iter().find(|id| !known.contains(id)) {
+        return Err(CardsError::UnknownUser(unknown));
+    }
+    let mut game: Game<()> = Game::new();
+    for (event_id, event) in record.events.iter().enumerate() {
+        if let Err(source) = game.verify(game_id, event) {
+            return Err(CardsError::IllegalEvent {
+                event_id,
+                event: Box::new(event.clone()),
+                source,
+            });
+        }
+        game.apply(event, |_, _| {});
+    }
+    if !game.state.phase.is_complete() {
+        return Err(CardsError::IncompleteGame(game_id));
+    }
+    let now = util::timestamp();
+    let imported = GameRecord {
+        game_id: Some(game_id),
+        created_time: Some(record.created_time.unwrap_or(now)),
+        completed_time: Some(record.completed_time.unwrap_or(now)),
+        events: record.events.clone(),
+    };
+    let options = GameOptions {
+        time_control: *time_control,
+        spectators: *spectators,
+        rule_set: *rule_set,
+        length: *length,
+    };
+    let players = Seat::VALUES
+        .iter()
+        .zip(players.iter())
+        .map(|(&seat, &player)| PlayerWithOptions {
+            player,
+            rules: *rules,
+            seat: Some(seat),
+        })
+        .collect::<Vec<_>>();
+    storage.insert_record(user_id, seed, options, &players, &imported)?;
+    Ok(game_id)
+}
@@ -0,0 +1,93 @@
+use crate::{import_record, load_record, CardsError, Storage};
+use http::header;
+use log::info;
+use turbo_hearts_api::{GameEvent, GameId, GameRecord, ImportResponse, RecordError, UserId};
+use warp::{hyper::body::Bytes, Filter, Rejection, Reply};
+
+const MAX_RECORD_BYTES: u64 = 1024 * 1024;
+
+pub fn router<'a>(storage: infallible!(&'a dyn Storage), user_id: rejection!(UserId)) -> reply!() {
+    warp::path("record")
+        .and(
+            export(storage.clone(), user_id.clone())
+                .or(import(storage.clone(), user_id.clone()))
+                .or(confirm(storage, user_id)),
+        )
+        .boxed()
+}
+
+fn export<'a>(storage: infallible!(&'a dyn Storage), user_id: rejection!(UserId)) -> reply!() {
+    async fn handle(
+        game_id: GameId,
+        storage: &dyn Storage,
+        user_id: UserId,
+    ) -> Result<impl Reply, Rejection> {
+        storage.check_access(game_id, user_id, None)?;
+        let record = load_record(storage, game_id)?;
+        Ok(warp::reply::with_header(
+            record.to_string(),
+            header::CONTENT_TYPE,
+            "text/plain; charset=utf-8",
+        ))
+    }
+
+    warp::path!(GameId)
+        .and(warp::get())
+        .and(storage)
+        .and(user_id)
+        .and_then(handle)
+}
+
+fn import<'a>(storage: infallible!(&'a dyn Storage), user_id: rejection!(UserId)) -> reply!() {
+    async fn handle(
+        storage: &dyn Storage,
+        user_id: UserId,
+        content_type: Option<String>,
+        body: Bytes,
+    ) -> Result<impl Reply, Rejection> {
+        let body = String::from_utf8_lossy(&body);
+        let record = match content_type {
+            // a raw list of events, like those stored for a game or sent to subscribers
+            Some(content_type) if content_type.starts_with("application/json") => {
+                let events = serde_json::from_str::<Vec<GameEvent>>(&body).map_err(|e| {
+                    CardsError::from(RecordError {
+                        line: e.line(),
+                        message: e.to_string(),
+                    })
+                })?;
+                GameRecord::new(events.into_iter().filter(|e| e.is_stored()).collect())
+            }
+            _ => body.parse::<GameRecord>().map_err(CardsError::from)?,
+        };
+        let game_id = import_record(storage, user_id, &record)?;
+        info!("{} imported game {}", user_id, game_id);
+        Ok(warp::reply::json(&ImportResponse { game_id }))
+    }
+
+    warp::path::end()
+        .and(warp::post())
+        .and(storage)
+        .and(user_id)
+        .and(warp::header::optional(header::CONTENT_TYPE.as_str()))
+        .and(warp::body::content_length_limit(MAX_RECORD_BYTES))
+        .and(warp::body::bytes())
+        .and_then(handle)
+}
+
+fn confirm<'a>(storage: infallible!(&'a dyn Storage), user_id: rejection!(UserId)) -> reply!() {
+    async fn handle(
+        game_id: GameId,
+        storage: &dyn Storage,
+        user_id: UserId,
+    ) -> Result<impl Reply, Rejection> {
+        storage.confirm_import(game_id, user_id)?;
+        info!("{} confirmed imported game {}", user_id, game_id);
+        Ok(warp::reply())
+    }
+
+    warp::path!(GameId / "confirm")
+        .and(warp::post())
+        .and(storage)
+        .and(user_id)
+        .and_then(handle)
+}
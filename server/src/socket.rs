@@ -0,0 +1,71 @@
+use crate::CardsError;
+use futures_util::{stream::Stream, SinkExt, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+use std::future::Future;
+use turbo_hearts_api::{SocketMessage, SocketRequest};
+use warp::ws::{Message, WebSocket};
+
+pub async fn serve<S, E, R, A, F, Fut>(socket: WebSocket, mut events: S, mut handle: F)
+where
+    S: Stream<Item = Option<SocketMessage<E, A>>> + Unpin,
+    E: Serialize,
+    R: DeserializeOwned,
+    A: Serialize,
+    F: FnMut(R) -> Fut,
+    Fut: Future<Output = Result<A, CardsError>>,
+{
+    let (mut tx, mut rx) = socket.split();
+    loop {
+        let message = tokio::select! {
+            event = events.next() => match event {
+                Some(Some(message)) => message,
+                Some(None) => {
+                    if tx.send(Message::ping(Vec::new())).await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+                None => return,
+            },
+            message = rx.next() => match message {
+                Some(Ok(message)) if message.is_close() => return,
+                Some(Ok(message)) => match message.to_str() {
+                    Ok(text) => match serde_json::from_str::<SocketRequest<R>>(text) {
+                        Ok(SocketRequest { request_id, request }) => {
+                            match handle(request).await {
+                                Ok(ack) => SocketMessage::Ack { request_id, ack },
+                                Err(e) => error(request_id, e),
+                            }
+                        }
+                        Err(e) => SocketMessage::Error {
+                            request_id: None,
+                            message: e.to_string(),
+                            rules: None,
+                        },
+                    },
+                    Err(_) => continue,
+                },
+                _ => return,
+            },
+        };
+        let text = serde_json::to_string(&message).unwrap();
+        if tx.send(Message::text(text)).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn error<E, A>(request_id: u64, error: CardsError) -> SocketMessage<E, A> {
+    match error {
+        CardsError::Rules { source } => SocketMessage::Error {
+            request_id: Some(request_id),
+            message: source.to_string(),
+            rules: Some(source),
+        },
+        error => SocketMessage::Error {
+            request_id: Some(request_id),
+            message: error.to_string(),
+            rules: None,
+        },
+    }
+}
@@ -0,0 +1,1320 @@
+use crate::{
+    load_archived_events, rate_games, util, ArchiveEntry, Audience, CardsError, Database,
+    ExternalUser, GameOptions, GetJson, GetStr, Rating, Storage, StoredBot, StoredEvent, ToSqlJson,
+    ToSqlStr, User,
+};
+use log::info;
+use rusqlite::{OptionalExtension, Rows, ToSql, Transaction, NO_PARAMS};
+use std::collections::{HashMap, HashSet};
+use turbo_hearts_api::{
+    AdminAction, AnalysisResponse, AuditEntry, DuplicateId, GameEvent, GameId, GameRecord,
+    GameSnapshot, JoinToken, LobbyChat, LobbyGame, Player, PlayerRating, PlayerWithOptions,
+    Privacy, RatedPlayer, RatingChange, Seed, UserId,
+};
+
+const SELECT_GAME_EVENTS: &str = r#"
+SELECT   game_id,
+         timestamp,
+         event
+FROM     event
+WHERE    game_id = ?
+ORDER BY event_id"#;
+
+// games with bots are left out of the leaderboard, as are the tables of a
+// duplicate set until every table has played all of its boards
+const SELECT_FIRST_PAGE_OF_COMPLETED_GAMES: &str = r#"
+SELECT   g.game_id
+FROM     game g
+WHERE    g.completed_time IS NOT NULL
+AND      NOT EXISTS
+         (
+                SELECT 1
+                FROM   game_player p
+                WHERE  p.game_id = g.game_id
+                AND    p.strategy IS NOT NULL)
+AND      NOT EXISTS
+         (
+                SELECT 1
+                FROM   duplicate_game d,
+                       duplicate_game t,
+                       game           tg
+                WHERE  d.game_id = g.game_id
+                AND    t.duplicate_id = d.duplicate_id
+                AND    tg.game_id = t.game_id
+                AND    tg.completed_time IS NULL)
+AND      g.game_id NOT IN
+         (
+                SELECT game_id
+                FROM   private_game
+                WHERE  hidden)
+ORDER BY g.completed_time DESC limit ?"#;
+
+const SELECT_NEXT_PAGE_OF_COMPLETED_GAMES: &str = r#"
+SELECT   g.game_id
+FROM     game g
+WHERE    g.completed_time IS NOT NULL
+AND      g.completed_time <
+         (
+                SELECT completed_time
+                FROM   game
+                WHERE  game_id = ?)
+AND      NOT EXISTS
+         (
+                SELECT 1
+                FROM   game_player p
+                WHERE  p.game_id = g.game_id
+                AND    p.strategy IS NOT NULL)
+AND      NOT EXISTS
+         (
+                SELECT 1
+                FROM   duplicate_game d,
+                       duplicate_game t,
+                       game           tg
+                WHERE  d.game_id = g.game_id
+                AND    t.duplicate_id = d.duplicate_id
+                AND    tg.game_id = t.game_id
+                AND    tg.completed_time IS NULL)
+AND      g.game_id NOT IN
+         (
+                SELECT game_id
+                FROM   private_game
+                WHERE  hidden)
+ORDER BY g.completed_time DESC limit ?"#;
+
+const SELECT_UNRATED_GAME_EVENTS: &str = r#"
+SELECT   g.game_id,
+         g.completed_time,
+         e.event
+FROM     game g,
+         event e
+WHERE    e.game_id = g.game_id
+AND      g.completed_time IS NOT NULL
+AND      g.game_id NOT IN
+         (
+                SELECT game_id
+                FROM   rated_game)
+AND      g.game_id NOT IN
+         (
+                SELECT p.game_id
+                FROM   imported_game i,
+                       game_player p
+                WHERE  p.game_id = i.game_id
+                AND    p.strategy IS NULL
+                AND    p.user_id NOT IN
+                       (
+                              SELECT c.user_id
+                              FROM   import_confirmation c
+                              WHERE  c.game_id = p.game_id))
+ORDER BY g.completed_time,
+         g.game_id,
+         e.event_id"#;
+
+const SELECT_ARCHIVABLE_GAME_EVENTS: &str = r#"
+WITH ids AS
+(
+         SELECT   g.game_id
+         FROM     game g,
+                  rated_game r
+         WHERE    g.game_id = r.game_id
+         AND      g.completed_time < ?
+         AND      g.game_id NOT IN
+                  (
+                         SELECT game_id
+                         FROM   archived_game)
+         ORDER BY g.completed_time limit ?)
+SELECT   game_id,
+         timestamp,
+         event
+FROM     event
+WHERE    game_id IN ids
+ORDER BY game_id,
+         event_id"#;
+
+const SELECT_BOT_GAMES: &str = r#"
+SELECT DISTINCT g.game_id
+FROM            game g,
+                event e
+WHERE           g.game_id = e.game_id
+AND             g.started_time IS NOT NULL
+AND             g.completed_time IS NULL
+AND             ((e.event_id = 0 AND e.event LIKE '%"type":"bot"%')
+                OR e.event LIKE '{"type":"takeover"%')"#;
+
+impl Storage for Database {
+    fn persist_events(
+        &self,
+        game_id: GameId,
+        event_id: usize,
+        events: &[GameEvent],
+    ) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| persist_events(&tx, game_id, event_id, events))
+    }
+
+    fn persist_hand(
+        &self,
+        game_id: GameId,
+        event_id: usize,
+        events: &[GameEvent],
+        snapshot: Option<&GameSnapshot>,
+        completed: bool,
+    ) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| {
+            if let Some(snapshot) = snapshot {
+                tx.execute::<&[&dyn ToSql]>(
+                    "INSERT OR REPLACE INTO snapshot (game_id, event_id, snapshot)
+                        VALUES (?, ?, ?)",
+                    &[&game_id.sql(), &(snapshot.event_id as i64), &snapshot.sql()],
+                )?;
+            }
+            persist_events(&tx, game_id, event_id, events)?;
+            if completed {
+                tx.execute::<&[&dyn ToSql]>(
+                    "UPDATE game SET completed_time = ? WHERE game_id = ?",
+                    &[&util::timestamp(), &game_id.sql()],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    fn load_game(
+        &self,
+        game_id: GameId,
+    ) -> Result<(Option<GameSnapshot>, Vec<GameEvent>), CardsError> {
+        self.run_read_only(|tx| {
+            let snapshot: Option<GameSnapshot> = tx
+                .query_row(
+                    "SELECT snapshot FROM snapshot WHERE game_id = ? AND EXISTS (
+                        SELECT 1 FROM event
+                        WHERE event.game_id = snapshot.game_id AND event.event_id = snapshot.event_id
+                    ) ORDER BY event_id DESC LIMIT 1",
+                    &[game_id.sql()],
+                    |row| row.get_json(0),
+                )
+                .optional()?;
+            let from = snapshot.as_ref().map_or(0, |snapshot| snapshot.event_id as i64);
+            let mut stmt = tx.prepare_cached(
+                "SELECT event FROM event WHERE game_id = ? AND (event_id = 0 OR event_id >= ?)
+                    ORDER BY event_id",
+            )?;
+            let mut rows = stmt.query::<&[&dyn ToSql]>(&[&game_id.sql(), &from])?;
+            let mut events = match load_archive_entry(&tx, game_id)? {
+                Some(entry) => load_archived_events(game_id, &entry)?
+                    .into_iter()
+                    .map(|e| e.event)
+                    .collect(),
+                None => Vec::new(),
+            };
+            while let Some(row) = rows.next()? {
+                events.push(row.get_json(0)?);
+            }
+            Ok((snapshot, events))
+        })
+    }
+
+    fn check_access(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+        join_token: Option<JoinToken>,
+    ) -> Result<(), CardsError> {
+        self.run_read_only(|tx| check_access(&tx, game_id, user_id, join_token))
+    }
+
+    fn load_bot_games(&self) -> Result<Vec<GameId>, CardsError> {
+        self.run_read_only(|tx| {
+            let mut stmt = tx.prepare_cached(SELECT_BOT_GAMES)?;
+            let mut rows = stmt.query(NO_PARAMS)?;
+            let mut game_ids = Vec::new();
+            while let Some(row) = rows.next()? {
+                game_ids.push(row.get_str(0)?);
+            }
+            Ok(game_ids)
+        })
+    }
+
+    fn delete_stale_games(&self) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| {
+            let now = util::timestamp();
+            let rows = tx.execute(
+                "DELETE FROM game WHERE (started_time IS NULL AND last_updated_time < ?)
+                    OR (completed_time IS NULL AND last_updated_time < ?)",
+                [now - 24 * 60 * 60 * 1000, now - 21 * 24 * 60 * 60 * 1000],
+            )?;
+            if rows > 0 {
+                info!("Deleted {} stale game(s)", rows);
+            }
+            let rows = tx.execute(
+                "DELETE FROM game_player WHERE game_id NOT IN (SELECT game_id FROM game)",
+                NO_PARAMS,
+            )?;
+            if rows > 0 {
+                info!("Deleted {} stale game player(s)", rows);
+            }
+            let rows = tx.execute(
+                "DELETE FROM duplicate_game WHERE game_id NOT IN (SELECT game_id FROM game)",
+                NO_PARAMS,
+            )?;
+            if rows > 0 {
+                info!("Deleted {} stale duplicate game(s)", rows);
+            }
+            let rows = tx.execute(
+                "DELETE FROM private_game WHERE game_id NOT IN (SELECT game_id FROM game)",
+                NO_PARAMS,
+            )?;
+            if rows > 0 {
+                info!("Deleted {} stale private game(s)", rows);
+            }
+            tx.execute(
+                "DELETE FROM game_invite WHERE game_id NOT IN (SELECT game_id FROM game)",
+                NO_PARAMS,
+            )?;
+            tx.execute(
+                "DELETE FROM snapshot WHERE game_id NOT IN (SELECT game_id FROM game)",
+                NO_PARAMS,
+            )?;
+            Ok(())
+        })
+    }
+
+    fn load_lobby(
+        &self,
+        user_id: UserId,
+    ) -> Result<(Vec<LobbyChat>, HashMap<GameId, LobbyGame>), CardsError> {
+        self.run_read_only(|tx| {
+            let chat = load_recent_chat(&tx)?;
+            let games = load_games(&tx, user_id)?;
+            Ok((chat, games))
+        })
+    }
+
+    fn insert_game(
+        &self,
+        game_id: GameId,
+        seed: &Seed,
+        options: GameOptions,
+        player: &PlayerWithOptions,
+        privacy: Option<&Privacy>,
+        join_token: Option<JoinToken>,
+    ) -> Result<Audience, CardsError> {
+        self.run_with_retry(|tx| {
+            insert_game(&tx, game_id, seed, options, player.player.user_id())?;
+            insert_player(&tx, game_id, player)?;
+            if let Some(privacy) = privacy {
+                insert_privacy(&tx, game_id, privacy, join_token)?;
+            }
+            audience(&tx, game_id)
+        })
+    }
+
+    fn insert_duplicate(
+        &self,
+        duplicate_id: DuplicateId,
+        game_ids: &[GameId],
+        value: &str,
+        options: GameOptions,
+        player: &PlayerWithOptions,
+    ) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| {
+            for (rotation, &game_id) in game_ids.iter().enumerate() {
+                let seed = Seed::Duplicate {
+                    value: value.to_string(),
+                    rotation: rotation as u8,
+                };
+                insert_game(&tx, game_id, &seed, options, player.player.user_id())?;
+                tx.execute::<&[&dyn ToSql]>(
+                    "INSERT INTO duplicate_game (duplicate_id, game_id, rotation) VALUES (?, ?, ?)",
+                    &[&duplicate_id.sql(), &game_id.sql(), &(rotation as u8)],
+                )?;
+            }
+            insert_player(&tx, game_ids[0], player)?;
+            Ok(())
+        })
+    }
+
+    fn insert_player(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+        player: &PlayerWithOptions,
+        join_token: Option<JoinToken>,
+    ) -> Result<(bool, Audience), CardsError> {
+        self.run_with_retry(|tx| {
+            validate_game_unstarted(&tx, game_id)?;
+            check_access(&tx, game_id, user_id, join_token)?;
+            if insert_player(&tx, game_id, player)? {
+                tx.execute::<&[&dyn ToSql]>(
+                    "UPDATE game SET last_updated_time = ?, last_updated_by = ? WHERE game_id = ?",
+                    &[
+                        &util::timestamp(),
+                        &player.player.user_id().sql(),
+                        &game_id.sql(),
+                    ],
+                )?;
+                Ok((true, audience(&tx, game_id)?))
+            } else {
+                Ok((false, None))
+            }
+        })
+    }
+
+    fn remove_player(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+    ) -> Result<(Option<Player>, Audience), CardsError> {
+        self.run_with_retry(|tx| {
+            validate_game_unstarted(&tx, game_id)?;
+            let audience = audience(&tx, game_id)?;
+            let player = remove_player(&tx, game_id, user_id)?;
+            if player.is_some() {
+                tx.execute::<&[&dyn ToSql]>(
+                    "UPDATE game SET last_updated_time = ?, last_updated_by = ? WHERE game_id = ?",
+                    &[&util::timestamp(), &user_id.sql(), &game_id.sql()],
+                )?;
+            }
+            Ok((player, audience))
+        })
+    }
+
+    fn start_game(
+        &self,
+        game_id: GameId,
+    ) -> Result<(Vec<PlayerWithOptions>, Seed, GameOptions, Audience), CardsError> {
+        self.run_with_retry(|tx| {
+            validate_game_unstarted(&tx, game_id)?;
+            let mut stmt = tx.prepare_cached(
+                "SELECT user_id, strategy, rules, seat FROM game_player
+                    WHERE game_id = ? ORDER BY random() LIMIT 4",
+            )?;
+            let mut rows = stmt.query(&[game_id.sql()])?;
+            let mut players = Vec::with_capacity(4);
+            while let Some(row) = rows.next()? {
+                let user_id = row.get_str(0)?;
+                let player = match row.get_opt_json::<StoredBot>(1)? {
+                    Some(bot) => bot.into_player(user_id),
+                    None => Player::Human { user_id },
+                };
+                players.push(PlayerWithOptions {
+                    player,
+                    rules: row.get_json(2)?,
+                    seat: row.get_opt_json(3)?,
+                });
+            }
+            if players.len() < 4 {
+                return Err(CardsError::NotEnoughPlayers);
+            }
+            tx.execute::<&[&dyn ToSql]>(
+                "UPDATE game SET started_time = ? WHERE game_id = ?",
+                &[&util::timestamp(), &game_id.sql()],
+            )?;
+            let (seed, options) = tx.query_row(
+                "SELECT seed, time_control, spectators, rule_set, length FROM game
+                    WHERE game_id = ?",
+                &[game_id.sql()],
+                |row| {
+                    Ok((
+                        row.get_json(0)?,
+                        GameOptions {
+                            time_control: row.get_opt_json(1)?,
+                            spectators: row.get_opt_json(2)?,
+                            rule_set: row.get_opt_json(3)?,
+                            length: row.get_opt_json(4)?,
+                        },
+                    ))
+                },
+            )?;
+            let audience = audience(&tx, game_id)?;
+            Ok((players, seed, options, audience))
+        })
+    }
+
+    fn audience(&self, game_id: GameId) -> Result<Audience, CardsError> {
+        self.run_read_only(|tx| audience(&tx, game_id))
+    }
+
+    fn insert_chat(&self, user_id: UserId, message: &str) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| {
+            tx.execute::<&[&dyn ToSql]>(
+                "INSERT INTO lobby_chat (timestamp, user_id, message) VALUES (?, ?, ?)",
+                &[&util::timestamp(), &user_id.sql(), &message],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn load_user_for_token(&self, auth_token: &str) -> Result<Option<User>, CardsError> {
+        self.run_read_only(|tx| {
+            Ok(tx
+                .query_row(
+                    "SELECT user.user_id, user.name, user.realm, user.external_id, user.role
+                        FROM auth_token, user
+                        WHERE auth_token.token = ? AND auth_token.user_id = user.user_id
+                        AND user.banned_time IS NULL",
+                    &[auth_token],
+                    |row| {
+                        Ok(User {
+                            id: row.get_str(0)?,
+                            name: row.get(1)?,
+                            realm: row.get(2)?,
+                            external_id: row.get(3)?,
+                            role: row.get_str(4)?,
+                        })
+                    },
+                )
+                .optional()?)
+        })
+    }
+
+    fn load_users(&self, ids: &[UserId]) -> Result<Vec<User>, CardsError> {
+        self.run_read_only(|tx| {
+            let mut users = Vec::with_capacity(ids.len());
+            for id in ids {
+                users.extend(load_user(&tx, *id)?);
+            }
+            Ok(users)
+        })
+    }
+
+    fn insert_user(&self, auth_token: &str, user: &ExternalUser) -> Result<User, CardsError> {
+        self.run_with_retry(|tx| {
+            let mut id = UserId::new();
+            let edits = tx.execute::<&[&dyn ToSql]>(
+                "INSERT INTO user (user_id, name, realm, external_id)
+                    VALUES (?, ?, ?, ?) ON CONFLICT DO NOTHING",
+                &[&id.sql(), &user.name, &user.realm, &user.external_id],
+            )?;
+            if edits == 0 {
+                id = tx.query_row_and_then(
+                    "SELECT user_id FROM user WHERE realm = ? and external_id = ?",
+                    &[&user.realm, &user.external_id],
+                    |row| row.get_str(0),
+                )?;
+            }
+            let banned: bool = tx.query_row(
+                "SELECT banned_time IS NOT NULL FROM user WHERE user_id = ?",
+                &[id.sql()],
+                |row| row.get(0),
+            )?;
+            if banned {
+                return Err(CardsError::BannedUser(id));
+            }
+            tx.execute::<&[&dyn ToSql]>(
+                "INSERT INTO auth_token (token, user_id)
+                    VALUES (?, ?) ON CONFLICT DO NOTHING",
+                &[&auth_token, &id.sql()],
+            )?;
+            Ok(load_user(&tx, id)?.unwrap())
+        })
+    }
+
+    fn load_completed_games(
+        &self,
+        after: Option<GameId>,
+        page_size: u32,
+    ) -> Result<Vec<StoredEvent>, CardsError> {
+        self.run_read_only(|tx| {
+            let mut game_ids = match after {
+                None => {
+                    let mut stmt = tx.prepare_cached(SELECT_FIRST_PAGE_OF_COMPLETED_GAMES)?;
+                    let rows = stmt.query([page_size])?;
+                    read_game_ids(rows)?
+                }
+                Some(game_id) => {
+                    let mut stmt = tx.prepare_cached(SELECT_NEXT_PAGE_OF_COMPLETED_GAMES)?;
+                    let rows = stmt.query::<&[&dyn ToSql]>(&[&game_id.sql(), &page_size])?;
+                    read_game_ids(rows)?
+                }
+            };
+            game_ids.sort_by_key(|game_id| game_id.to_string());
+            let mut events = Vec::new();
+            for game_id in game_ids {
+                events.extend(load_stored_events(&tx, game_id)?);
+            }
+            Ok(events)
+        })
+    }
+
+    fn load_game_events(&self, game_id: GameId) -> Result<Vec<StoredEvent>, CardsError> {
+        self.run_read_only(|tx| load_stored_events(&tx, game_id))
+    }
+
+    fn load_game_times(&self, game_id: GameId) -> Result<(i64, Option<i64>), CardsError> {
+        self.run_read_only(|tx| {
+            tx.query_row(
+                "SELECT created_time, completed_time FROM game WHERE game_id = ?",
+                &[game_id.sql()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or(CardsError::UnknownGame(game_id))
+        })
+    }
+
+    fn insert_record(
+        &self,
+        user_id: UserId,
+        seed: &Seed,
+        options: GameOptions,
+        players: &[PlayerWithOptions],
+        record: &GameRecord,
+    ) -> Result<(), CardsError> {
+        let game_id = record.game_id.unwrap();
+        let created_time = record.created_time.unwrap();
+        let completed_time = record.completed_time.unwrap();
+        let deal_hash = record.deal_hash();
+        self.run_with_retry(|tx| {
+            let imported = tx
+                .query_row(
+                    "SELECT game_id FROM imported_game WHERE deal_hash = ?",
+                    &[&deal_hash],
+                    |row| row.get_str(0),
+                )
+                .optional()?;
+            if let Some(imported) = imported {
+                return Err(CardsError::DuplicateImport(imported));
+            }
+            tx.execute::<&[&dyn ToSql]>(
+                "INSERT INTO game (game_id, seed, time_control, spectators, rule_set, length,
+                    created_time, created_by, last_updated_time, last_updated_by, started_time,
+                    completed_time)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                &[
+                    &game_id.sql(),
+                    &seed.sql(),
+                    &options.time_control.as_ref().map(|t| t.sql()),
+                    &options.spectators.as_ref().map(|s| s.sql()),
+                    &options.rule_set.as_ref().map(|r| r.sql()),
+                    &options.length.as_ref().map(|l| l.sql()),
+                    &created_time,
+                    &user_id.sql(),
+                    &completed_time,
+                    &user_id.sql(),
+                    &created_time,
+                    &completed_time,
+                ],
+            )?;
+            for player in players {
+                insert_player(&tx, game_id, player)?;
+            }
+            tx.execute::<&[&dyn ToSql]>(
+                "INSERT INTO imported_game (game_id, deal_hash) VALUES (?, ?)",
+                &[&game_id.sql(), &deal_hash],
+            )?;
+            tx.execute::<&[&dyn ToSql]>(
+                "INSERT INTO import_confirmation (game_id, user_id) VALUES (?, ?)",
+                &[&game_id.sql(), &user_id.sql()],
+            )?;
+            persist_events(&tx, game_id, 0, &record.events)
+        })
+    }
+
+    fn confirm_import(&self, game_id: GameId, user_id: UserId) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| {
+            let imported = tx.query_row(
+                "SELECT EXISTS (SELECT 1 FROM imported_game WHERE game_id = ?)",
+                &[game_id.sql()],
+                |row| row.get::<_, bool>(0),
+            )?;
+            if !imported {
+                return Err(CardsError::NotImported(game_id));
+            }
+            let human = tx.query_row::<_, &[&dyn ToSql], _>(
+                "SELECT EXISTS (SELECT 1 FROM game_player
+                    WHERE game_id = ? AND user_id = ? AND strategy IS NULL)",
+                &[&game_id.sql(), &user_id.sql()],
+                |row| row.get::<_, bool>(0),
+            )?;
+            if !human {
+                return Err(CardsError::InvalidPlayer(user_id, game_id));
+            }
+            tx.execute::<&[&dyn ToSql]>(
+                "INSERT OR IGNORE INTO import_confirmation (game_id, user_id) VALUES (?, ?)",
+                &[&game_id.sql(), &user_id.sql()],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn load_duplicate_games(&self, duplicate_id: DuplicateId) -> Result<Vec<GameId>, CardsError> {
+        self.run_read_only(|tx| {
+            let mut stmt = tx.prepare_cached(
+                "SELECT game_id FROM duplicate_game WHERE duplicate_id = ? ORDER BY rotation",
+            )?;
+            let rows = stmt.query(&[duplicate_id.sql()])?;
+            read_game_ids(rows)
+        })
+    }
+
+    fn load_duplicate_id(&self, game_id: GameId) -> Result<Option<DuplicateId>, CardsError> {
+        self.run_read_only(|tx| {
+            Ok(tx
+                .query_row(
+                    "SELECT duplicate_id FROM duplicate_game WHERE game_id = ?",
+                    &[game_id.sql()],
+                    |row| row.get_str(0),
+                )
+                .optional()?)
+        })
+    }
+
+    fn update_ratings(&self) -> Result<usize, CardsError> {
+        self.run_with_retry(|tx| {
+            let mut stmt = tx.prepare_cached(SELECT_UNRATED_GAME_EVENTS)?;
+            let mut rows = stmt.query(NO_PARAMS)?;
+            let mut events = Vec::new();
+            while let Some(row) = rows.next()? {
+                events.push((row.get_str(0)?, row.get(1)?, row.get_json(2)?));
+            }
+            let update = rate_games(events, |player| {
+                let rating = tx
+                    .query_row(
+                        "SELECT rating, hands, updated_time FROM rating WHERE player = ?",
+                        &[player.sql()],
+                        |row| {
+                            Ok(Rating {
+                                rating: row.get(0)?,
+                                hands: row.get(1)?,
+                                updated_time: row.get(2)?,
+                            })
+                        },
+                    )
+                    .optional()?;
+                Ok(rating.unwrap_or_default())
+            })?;
+            for (player, change) in &update.history {
+                tx.execute::<&[&dyn ToSql]>(
+                    "INSERT OR REPLACE INTO rating_history
+                        (player, game_id, hand, completed_time, money, rating)
+                        VALUES (?, ?, ?, ?, ?, ?)",
+                    &[
+                        &player.sql(),
+                        &change.game_id.sql(),
+                        &change.hand,
+                        &change.completed_time,
+                        &change.money,
+                        &change.rating,
+                    ],
+                )?;
+            }
+            for (player, rating) in &update.ratings {
+                tx.execute::<&[&dyn ToSql]>(
+                    "INSERT OR REPLACE INTO rating (player, rating, hands, updated_time)
+                        VALUES (?, ?, ?, ?)",
+                    &[
+                        &player.sql(),
+                        &rating.rating,
+                        &rating.hands,
+                        &rating.updated_time,
+                    ],
+                )?;
+            }
+            for (game_id, completed_time) in &update.games {
+                tx.execute::<&[&dyn ToSql]>(
+                    "INSERT INTO rated_game (game_id, completed_time) VALUES (?, ?)",
+                    &[&game_id.sql(), completed_time],
+                )?;
+            }
+            Ok(update.games.len())
+        })
+    }
+
+    fn load_ratings(&self) -> Result<Vec<PlayerRating>, CardsError> {
+        self.run_read_only(|tx| {
+            let mut stmt = tx.prepare_cached(
+                "SELECT player, rating, hands, updated_time FROM rating ORDER BY rating DESC",
+            )?;
+            let mut rows = stmt.query(NO_PARAMS)?;
+            let mut ratings = Vec::new();
+            while let Some(row) = rows.next()? {
+                ratings.push(PlayerRating {
+                    player: row.get_json(0)?,
+                    rating: row.get(1)?,
+                    hands: row.get(2)?,
+                    updated_time: row.get(3)?,
+                });
+            }
+            Ok(ratings)
+        })
+    }
+
+    fn load_rating_history(&self, player: RatedPlayer) -> Result<Vec<RatingChange>, CardsError> {
+        self.run_read_only(|tx| {
+            let mut stmt = tx.prepare_cached(
+                "SELECT game_id, hand, completed_time, money, rating FROM rating_history
+                    WHERE player = ? ORDER BY completed_time, game_id, hand",
+            )?;
+            let mut rows = stmt.query(&[player.sql()])?;
+            let mut history = Vec::new();
+            while let Some(row) = rows.next()? {
+                history.push(RatingChange {
+                    game_id: row.get_str(0)?,
+                    hand: row.get(1)?,
+                    completed_time: row.get(2)?,
+                    money: row.get(3)?,
+                    rating: row.get(4)?,
+                });
+            }
+            Ok(history)
+        })
+    }
+
+    fn load_analysis(&self, game_id: GameId) -> Result<Option<AnalysisResponse>, CardsError> {
+        self.run_read_only(|tx| {
+            Ok(tx
+                .query_row(
+                    "SELECT analysis FROM analysis WHERE game_id = ?",
+                    &[game_id.sql()],
+                    |row| row.get_json(0),
+                )
+                .optional()?)
+        })
+    }
+
+    fn save_analysis(&self, analysis: &AnalysisResponse) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| {
+            tx.execute::<&[&dyn ToSql]>(
+                "INSERT OR REPLACE INTO analysis (game_id, analysis) VALUES (?, ?)",
+                &[&analysis.game_id.sql(), &analysis.sql()],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn load_archivable_games(
+        &self,
+        cutoff: i64,
+        limit: u32,
+    ) -> Result<Vec<StoredEvent>, CardsError> {
+        self.run_read_only(|tx| {
+            let mut stmt = tx.prepare_cached(SELECT_ARCHIVABLE_GAME_EVENTS)?;
+            let rows = stmt.query::<&[&dyn ToSql]>(&[&cutoff, &limit])?;
+            read_events(rows)
+        })
+    }
+
+    fn mark_archived(&self, games: &[(GameId, usize, ArchiveEntry)]) -> Result<usize, CardsError> {
+        self.run_with_retry(|tx| {
+            let mut archived = 0;
+            for (game_id, events, entry) in games {
+                let stored: i64 = tx.query_row(
+                    "SELECT COUNT(*) FROM event WHERE game_id = ?",
+                    &[game_id.sql()],
+                    |row| row.get(0),
+                )?;
+                if stored as usize != *events {
+                    continue;
+                }
+                tx.execute::<&[&dyn ToSql]>(
+                    "INSERT INTO archived_game (game_id, path, byte_offset, byte_length)
+                     VALUES (?, ?, ?, ?)",
+                    &[
+                        &game_id.sql(),
+                        &entry.path,
+                        &(entry.offset as i64),
+                        &(entry.length as i64),
+                    ],
+                )?;
+                tx.execute("DELETE FROM event WHERE game_id = ?", &[game_id.sql()])?;
+                tx.execute("DELETE FROM snapshot WHERE game_id = ?", &[game_id.sql()])?;
+                archived += 1;
+            }
+            Ok(archived)
+        })
+    }
+
+    fn delete_game(&self, admin_id: UserId, game_id: GameId) -> Result<Audience, CardsError> {
+        self.run_with_retry(|tx| {
+            let audience = audience(&tx, game_id)?;
+            let rows = tx.execute("DELETE FROM game WHERE game_id = ?", &[game_id.sql()])?;
+            if rows == 0 {
+                return Err(CardsError::UnknownGame(game_id));
+            }
+            // ratings already earned in the game are kept
+            for table in &[
+                "game_player",
+                "event",
+                "snapshot",
+                "duplicate_game",
+                "private_game",
+                "game_invite",
+                "analysis",
+                "archived_game",
+                "imported_game",
+                "import_confirmation",
+            ] {
+                tx.execute(
+                    &format!("DELETE FROM {} WHERE game_id = ?", table),
+                    &[game_id.sql()],
+                )?;
+            }
+            insert_audit(&tx, admin_id, &AdminAction::DeleteGame { game_id })?;
+            Ok(audience)
+        })
+    }
+
+    fn abort_game(
+        &self,
+        admin_id: UserId,
+        game_id: GameId,
+        event_id: usize,
+        events: &[GameEvent],
+    ) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| {
+            persist_events(&tx, game_id, event_id, events)?;
+            tx.execute::<&[&dyn ToSql]>(
+                "UPDATE game SET completed_time = ? WHERE game_id = ?",
+                &[&util::timestamp(), &game_id.sql()],
+            )?;
+            insert_audit(&tx, admin_id, &AdminAction::AbortGame { game_id })
+        })
+    }
+
+    fn rename_user(&self, admin_id: UserId, user_id: UserId, name: &str) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| {
+            let old_name = match load_user(&tx, user_id)? {
+                Some(user) => user.name,
+                None => return Err(CardsError::UnknownUser(user_id)),
+            };
+            tx.execute::<&[&dyn ToSql]>(
+                "UPDATE user SET name = ? WHERE user_id = ?",
+                &[&name, &user_id.sql()],
+            )?;
+            insert_audit(
+                &tx,
+                admin_id,
+                &AdminAction::RenameUser {
+                    user_id,
+                    old_name,
+                    new_name: name.to_string(),
+                },
+            )
+        })
+    }
+
+    fn ban_user(&self, admin_id: UserId, user_id: UserId) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| {
+            let rows = tx.execute::<&[&dyn ToSql]>(
+                "UPDATE user SET banned_time = ? WHERE user_id = ?",
+                &[&util::timestamp(), &user_id.sql()],
+            )?;
+            if rows == 0 {
+                return Err(CardsError::UnknownUser(user_id));
+            }
+            tx.execute("DELETE FROM auth_token WHERE user_id = ?", &[user_id.sql()])?;
+            insert_audit(&tx, admin_id, &AdminAction::BanUser { user_id })
+        })
+    }
+
+    fn delete_chat(
+        &self,
+        admin_id: UserId,
+        user_id: UserId,
+        timestamp: i64,
+    ) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| {
+            let message = tx
+                .query_row::<String, &[&dyn ToSql], _>(
+                    "SELECT message FROM lobby_chat WHERE user_id = ? AND timestamp = ?",
+                    &[&user_id.sql(), &timestamp],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let message = match message {
+                Some(message) => message,
+                None => return Err(CardsError::UnknownChat(user_id, timestamp)),
+            };
+            tx.execute::<&[&dyn ToSql]>(
+                "DELETE FROM lobby_chat WHERE user_id = ? AND timestamp = ?",
+                &[&user_id.sql(), &timestamp],
+            )?;
+            insert_audit(
+                &tx,
+                admin_id,
+                &AdminAction::DeleteChat {
+                    user_id,
+                    timestamp,
+                    message,
+                },
+            )
+        })
+    }
+
+    fn load_audit_log(&self, limit: u32) -> Result<Vec<AuditEntry>, CardsError> {
+        self.run_read_only(|tx| {
+            let mut stmt = tx.prepare_cached(
+                "SELECT timestamp, user_id, action FROM audit_log
+                    ORDER BY timestamp DESC, rowid DESC LIMIT ?",
+            )?;
+            let mut rows = stmt.query([limit])?;
+            let mut entries = Vec::new();
+            while let Some(row) = rows.next()? {
+                entries.push(AuditEntry {
+                    timestamp: row.get(0)?,
+                    user_id: row.get_str(1)?,
+                    action: row.get_json(2)?,
+                });
+            }
+            Ok(entries)
+        })
+    }
+}
+
+fn persist_events(
+    tx: &Transaction,
+    game_id: GameId,
+    event_id: usize,
+    events: &[GameEvent],
+) -> Result<(), CardsError> {
+    let mut stmt = tx.prepare_cached(
+        "INSERT INTO event (game_id, event_id, timestamp, event) VALUES (?, ?, ?, ?)",
+    )?;
+    let timestamp = util::timestamp();
+    for (offset, event) in events.iter().enumerate() {
+        let event_id = (event_id + offset) as isize;
+        stmt.execute::<&[&dyn ToSql]>(&[&game_id.sql(), &event_id, &timestamp, &event.sql()])?;
+    }
+    Ok(())
+}
+
+fn read_events(mut rows: Rows<'_>) -> Result<Vec<StoredEvent>, CardsError> {
+    let mut events = Vec::new();
+    while let Some(row) = rows.next()? {
+        events.push(StoredEvent {
+            game_id: row.get_str(0)?,
+            timestamp: row.get(1)?,
+            event: row.get_json(2)?,
+        });
+    }
+    Ok(events)
+}
+
+// the events of a game, read back from its archive if it has been archived
+fn load_stored_events(tx: &Transaction, game_id: GameId) -> Result<Vec<StoredEvent>, CardsError> {
+    let mut stmt = tx.prepare_cached(SELECT_GAME_EVENTS)?;
+    let rows = stmt.query(&[game_id.sql()])?;
+    let mut events = match load_archive_entry(tx, game_id)? {
+        Some(entry) => load_archived_events(game_id, &entry)?,
+        None => Vec::new(),
+    };
+    events.extend(read_events(rows)?);
+    Ok(events)
+}
+
+fn load_archive_entry(
+    tx: &Transaction,
+    game_id: GameId,
+) -> Result<Option<ArchiveEntry>, CardsError> {
+    Ok(tx
+        .query_row(
+            "SELECT path, byte_offset, byte_length FROM archived_game WHERE game_id = ?",
+            &[game_id.sql()],
+            |row| {
+                Ok(ArchiveEntry {
+                    path: row.get(0)?,
+                    offset: row.get::<_, i64>(1)? as u64,
+                    length: row.get::<_, i64>(2)? as u64,
+                })
+            },
+        )
+        .optional()?)
+}
+
+fn read_game_ids(mut rows: Rows<'_>) -> Result<Vec<GameId>, CardsError> {
+    let mut game_ids = Vec::new();
+    while let Some(row) = rows.next()? {
+        game_ids.push(row.get_str(0)?);
+    }
+    Ok(game_ids)
+}
+
+fn load_user(tx: &Transaction, user_id: UserId) -> Result<Option<User>, CardsError> {
+    let mut stmt =
+        tx.prepare_cached("SELECT name, realm, external_id, role FROM user WHERE user_id = ?")?;
+    Ok(stmt
+        .query_row(&[user_id.sql()], |row| {
+            Ok(User {
+                id: user_id,
+                name: row.get(0)?,
+                realm: row.get(1)?,
+                external_id: row.get(2)?,
+                role: row.get_str(3)?,
+            })
+        })
+        .optional()?)
+}
+
+fn insert_audit(tx: &Transaction, user_id: UserId, action: &AdminAction) -> Result<(), CardsError> {
+    tx.execute::<&[&dyn ToSql]>(
+        "INSERT INTO audit_log (timestamp, user_id, action) VALUES (?, ?, ?)",
+        &[&util::timestamp(), &user_id.sql(), &action.sql()],
+    )?;
+    Ok(())
+}
+
+fn load_recent_chat(tx: &Transaction) -> Result<Vec<LobbyChat>, CardsError> {
+    let mut stmt = tx.prepare_cached(
+        "SELECT timestamp, user_id, message FROM lobby_chat ORDER BY timestamp DESC LIMIT 500",
+    )?;
+    let mut rows = stmt.query(NO_PARAMS)?;
+    let mut messages = Vec::with_capacity(500);
+    while let Some(row) = rows.next()? {
+        messages.push(LobbyChat {
+            timestamp: row.get(0)?,
+            user_id: row.get_str(1)?,
+            message: row.get(2)?,
+        });
+    }
+    messages.reverse();
+    Ok(messages)
+}
+
+fn load_games(tx: &Transaction, user_id: UserId) -> Result<HashMap<GameId, LobbyGame>, CardsError> {
+    let mut games = HashMap::new();
+    let mut hidden = HashSet::new();
+    let mut stmt = tx.prepare_cached(
+        "SELECT g.game_id, g.seed, g.time_control, g.spectators, g.rule_set, g.length,
+                p.hidden, d.duplicate_id, g.created_time, g.created_by, g.last_updated_time,
+                g.last_updated_by, g.started_time
+                FROM game g
+                LEFT JOIN duplicate_game d ON g.game_id = d.game_id
+                LEFT JOIN private_game p ON g.game_id = p.game_id
+                WHERE g.completed_time IS NULL AND g.last_updated_time > ?",
+    )?;
+    let mut rows = stmt.query([util::timestamp() - 8 * 60 * 60 * 1000])?;
+    while let Some(row) = rows.next()? {
+        let game_id = row.get_str(0)?;
+        let private: Option<bool> = row.get(6)?;
+        if private == Some(true) {
+            hidden.insert(game_id);
+        }
+        games.insert(
+            game_id,
+            LobbyGame {
+                players: HashSet::new(),
+                seed: row.get_json::<Seed>(1)?.redact(),
+                time_control: row.get_opt_json(2)?,
+                spectators: row.get_opt_json(3)?,
+                rule_set: row.get_opt_json(4)?,
+                length: row.get_opt_json(5)?,
+                private: private.is_some(),
+                duplicate_id: row.get_opt_str(7)?,
+                created_time: row.get(8)?,
+                created_by: row.get_str(9)?,
+                last_updated_time: row.get(10)?,
+                last_updated_by: row.get_str(11)?,
+                started_time: row.get(12)?,
+            },
+        );
+    }
+    let mut stmt = tx.prepare_cached(
+        "SELECT gp.game_id, gp.user_id, gp.strategy, gp.rules, gp.seat
+            FROM game_player gp, game g
+            WHERE gp.game_id = g.game_id AND g.completed_time IS NULL",
+    )?;
+    let mut rows = stmt.query(NO_PARAMS)?;
+    while let Some(row) = rows.next()? {
+        let game_id = row.get_str(0)?;
+        if let Some(game) = games.get_mut(&game_id) {
+            let user_id = row.get_str(1)?;
+            let player = match row.get_opt_json::<StoredBot>(2)? {
+                Some(bot) => bot.into_player(user_id),
+                None => Player::Human { user_id },
+            };
+            game.players.insert(PlayerWithOptions {
+                player,
+                rules: row.get_json(3)?,
+                seat: row.get_opt_json(4)?,
+            });
+        }
+    }
+    // the other tables of a duplicate set start out empty but still need to be joinable
+    games.retain(|_, game| !game.players.is_empty() || game.duplicate_id.is_some());
+    if !hidden.is_empty() {
+        let mut stmt = tx.prepare_cached("SELECT game_id FROM game_invite WHERE user_id = ?")?;
+        let mut rows = stmt.query(&[user_id.sql()])?;
+        while let Some(row) = rows.next()? {
+            hidden.remove(&row.get_str::<GameId>(0)?);
+        }
+        games.retain(|game_id, game| {
+            !hidden.contains(game_id)
+                || game.created_by == user_id
+                || game.players.iter().any(|p| p.player.user_id() == user_id)
+        });
+    }
+    Ok(games)
+}
+
+fn insert_game(
+    tx: &Transaction,
+    game_id: GameId,
+    seed: &Seed,
+    options: GameOptions,
+    user_id: UserId,
+) -> Result<(), CardsError> {
+    let timestamp = util::timestamp();
+    tx.execute::<&[&dyn ToSql]>(
+        "INSERT INTO game (game_id, seed, time_control, spectators, rule_set, length,
+            created_time, created_by, last_updated_time, last_updated_by)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        &[
+            &game_id.sql(),
+            &seed.sql(),
+            &options.time_control.as_ref().map(|t| t.sql()),
+            &options.spectators.as_ref().map(|s| s.sql()),
+            &options.rule_set.as_ref().map(|r| r.sql()),
+            &options.length.as_ref().map(|l| l.sql()),
+            &timestamp,
+            &user_id.sql(),
+            &timestamp,
+            &user_id.sql(),
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_privacy(
+    tx: &Transaction,
+    game_id: GameId,
+    privacy: &Privacy,
+    join_token: Option<JoinToken>,
+) -> Result<(), CardsError> {
+    tx.execute::<&[&dyn ToSql]>(
+        "INSERT INTO private_game (game_id, hidden, join_token) VALUES (?, ?, ?)",
+        &[
+            &game_id.sql(),
+            &privacy.hidden,
+            &join_token.as_ref().map(|t| t.sql()),
+        ],
+    )?;
+    for user_id in &privacy.invited {
+        tx.execute::<&[&dyn ToSql]>(
+            "INSERT INTO game_invite (game_id, user_id) VALUES (?, ?)",
+            &[&game_id.sql(), &user_id.sql()],
+        )?;
+    }
+    Ok(())
+}
+
+fn check_access(
+    tx: &Transaction,
+    game_id: GameId,
+    user_id: UserId,
+    join_token: Option<JoinToken>,
+) -> Result<(), CardsError> {
+    let private = tx
+        .query_row(
+            "SELECT p.join_token, g.created_by FROM private_game p, game g
+                WHERE p.game_id = ? AND g.game_id = p.game_id",
+            &[game_id.sql()],
+            |row| Ok((row.get_opt_str::<JoinToken>(0)?, row.get_str::<UserId>(1)?)),
+        )
+        .optional()?;
+    let (token, created_by) = match private {
+        Some(private) => private,
+        None => return Ok(()),
+    };
+    if created_by == user_id || (join_token.is_some() && join_token == token) {
+        return Ok(());
+    }
+    let member = tx.query_row::<_, &[&dyn ToSql], _>(
+        "SELECT EXISTS (SELECT 1 FROM game_invite WHERE game_id = ? AND user_id = ?)
+            OR EXISTS (SELECT 1 FROM game_player WHERE game_id = ? AND user_id = ?)",
+        &[
+            &game_id.sql(),
+            &user_id.sql(),
+            &game_id.sql(),
+            &user_id.sql(),
+        ],
+        |row| row.get::<_, bool>(0),
+    )?;
+    match join_token {
+        _ if member => Ok(()),
+        Some(join_token) => Err(CardsError::InvalidJoinToken(join_token, game_id)),
+        None => Err(CardsError::NotInvited(user_id, game_id)),
+    }
+}
+
+fn audience(tx: &Transaction, game_id: GameId) -> Result<Audience, CardsError> {
+    let hidden = tx
+        .query_row(
+            "SELECT hidden FROM private_game WHERE game_id = ?",
+            &[game_id.sql()],
+            |row| row.get::<_, bool>(0),
+        )
+        .optional()?;
+    if hidden != Some(true) {
+        return Ok(None);
+    }
+    let mut stmt = tx.prepare_cached(
+        "SELECT created_by FROM game WHERE game_id = ?1
+            UNION SELECT user_id FROM game_player WHERE game_id = ?1
+            UNION SELECT user_id FROM game_invite WHERE game_id = ?1",
+    )?;
+    let mut rows = stmt.query(&[game_id.sql()])?;
+    let mut audience = HashSet::new();
+    while let Some(row) = rows.next()? {
+        audience.insert(row.get_str(0)?);
+    }
+    Ok(Some(audience))
+}
+
+fn validate_game_unstarted(tx: &Transaction, game_id: GameId) -> Result<(), CardsError> {
+    let started = tx
+        .query_row(
+            "SELECT started_time FROM game WHERE game_id = ?",
+            &[game_id.sql()],
+            |row| Ok(row.get::<_, Option<i64>>(0)?.is_some()),
+        )
+        .optional()?;
+    match started {
+        None => Err(CardsError::UnknownGame(game_id)),
+        Some(true) => Err(CardsError::GameHasStarted(game_id)),
+        _ => Ok(()),
+    }
+}
+
+fn insert_player(
+    tx: &Transaction,
+    game_id: GameId,
+    player: &PlayerWithOptions,
+) -> Result<bool, CardsError> {
+    let rows = tx.execute::<&[&dyn ToSql]>(
+        "INSERT OR IGNORE INTO game_player (game_id, user_id, strategy, rules, seat)
+            VALUES (?, ?, ?, ?, ?)",
+        &[
+            &game_id.sql(),
+            &player.player.user_id().sql(),
+            &StoredBot::from_player(&player.player)
+                .as_ref()
+                .map(|s| s.sql()),
+            &player.rules.sql(),
+            &player.seat.as_ref().map(|s| s.sql()),
+        ],
+    )?;
+    Ok(rows > 0)
+}
+
+fn remove_player(
+    tx: &Transaction,
+    game_id: GameId,
+    user_id: UserId,
+) -> Result<Option<Player>, CardsError> {
+    let player = tx
+        .query_row::<_, &[&dyn ToSql], _>(
+            "SELECT strategy FROM game_player WHERE game_id = ? AND user_id = ?",
+            &[&game_id.sql(), &user_id.sql()],
+            |row| {
+                Ok(match row.get_opt_json::<StoredBot>(0)? {
+                    Some(bot) => bot.into_player(user_id),
+                    None => Player::Human { user_id },
+                })
+            },
+        )
+        .optional()?;
+    tx.execute::<&[&dyn ToSql]>(
+        "DELETE FROM game_player WHERE game_id = ? AND user_id = ?",
+        &[&game_id.sql(), &user_id.sql()],
+    )?;
+    Ok(player)
+}
@@ -0,0 +1,180 @@
+use crate::{ArchiveEntry, CardsError, ExternalUser, GameOptions, User};
+use std::collections::{HashMap, HashSet};
+use turbo_hearts_api::{
+    AnalysisResponse, AuditEntry, DuplicateId, GameEvent, GameId, GameRecord, GameSnapshot,
+    JoinToken, LobbyChat, LobbyGame, Player, PlayerRating, PlayerWithOptions, Privacy, RatedPlayer,
+    RatingChange, Seed, UserId,
+};
+
+// who gets lobby events about a hidden game, everyone if it's None
+pub type Audience = Option<HashSet<UserId>>;
+
+#[derive(Clone, Debug)]
+pub struct StoredEvent {
+    pub game_id: GameId,
+    pub timestamp: i64,
+    pub event: GameEvent,
+}
+
+// each method is a single transaction
+pub trait Storage: Send + Sync {
+    fn persist_events(
+        &self,
+        game_id: GameId,
+        event_id: usize,
+        events: &[GameEvent],
+    ) -> Result<(), CardsError>;
+
+    fn persist_hand(
+        &self,
+        game_id: GameId,
+        event_id: usize,
+        events: &[GameEvent],
+        snapshot: Option<&GameSnapshot>,
+        completed: bool,
+    ) -> Result<(), CardsError>;
+
+    // the latest snapshot with the sit event and those after it, or every
+    // event if there's no snapshot or it's ahead of the events
+    fn load_game(
+        &self,
+        game_id: GameId,
+    ) -> Result<(Option<GameSnapshot>, Vec<GameEvent>), CardsError>;
+
+    fn check_access(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+        join_token: Option<JoinToken>,
+    ) -> Result<(), CardsError>;
+
+    fn load_bot_games(&self) -> Result<Vec<GameId>, CardsError>;
+
+    fn delete_stale_games(&self) -> Result<(), CardsError>;
+
+    fn load_lobby(
+        &self,
+        user_id: UserId,
+    ) -> Result<(Vec<LobbyChat>, HashMap<GameId, LobbyGame>), CardsError>;
+
+    fn insert_game(
+        &self,
+        game_id: GameId,
+        seed: &Seed,
+        options: GameOptions,
+        player: &PlayerWithOptions,
+        privacy: Option<&Privacy>,
+        join_token: Option<JoinToken>,
+    ) -> Result<Audience, CardsError>;
+
+    fn insert_duplicate(
+        &self,
+        duplicate_id: DuplicateId,
+        game_ids: &[GameId],
+        value: &str,
+        options: GameOptions,
+        player: &PlayerWithOptions,
+    ) -> Result<(), CardsError>;
+
+    // returns whether the user wasn't already seated
+    fn insert_player(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+        player: &PlayerWithOptions,
+        join_token: Option<JoinToken>,
+    ) -> Result<(bool, Audience), CardsError>;
+
+    fn remove_player(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+    ) -> Result<(Option<Player>, Audience), CardsError>;
+
+    fn start_game(
+        &self,
+        game_id: GameId,
+    ) -> Result<(Vec<PlayerWithOptions>, Seed, GameOptions, Audience), CardsError>;
+
+    fn audience(&self, game_id: GameId) -> Result<Audience, CardsError>;
+
+    fn insert_chat(&self, user_id: UserId, message: &str) -> Result<(), CardsError>;
+
+    fn load_user_for_token(&self, auth_token: &str) -> Result<Option<User>, CardsError>;
+
+    fn load_users(&self, ids: &[UserId]) -> Result<Vec<User>, CardsError>;
+
+    fn insert_user(&self, auth_token: &str, user: &ExternalUser) -> Result<User, CardsError>;
+
+    // leaves out hidden games and duplicate sets with a table still in progress
+    fn load_completed_games(
+        &self,
+        after: Option<GameId>,
+        page_size: u32,
+    ) -> Result<Vec<StoredEvent>, CardsError>;
+
+    fn load_game_events(&self, game_id: GameId) -> Result<Vec<StoredEvent>, CardsError>;
+
+    fn load_game_times(&self, game_id: GameId) -> Result<(i64, Option<i64>), CardsError>;
+
+    // a game can only be imported once, and counts as confirmed by its importer
+    fn insert_record(
+        &self,
+        user_id: UserId,
+        seed: &Seed,
+        options: GameOptions,
+        players: &[PlayerWithOptions],
+        record: &GameRecord,
+    ) -> Result<(), CardsError>;
+
+    // imported games are only rated once every human player has confirmed them
+    fn confirm_import(&self, game_id: GameId, user_id: UserId) -> Result<(), CardsError>;
+
+    fn load_duplicate_games(&self, duplicate_id: DuplicateId) -> Result<Vec<GameId>, CardsError>;
+
+    fn load_duplicate_id(&self, game_id: GameId) -> Result<Option<DuplicateId>, CardsError>;
+
+    fn update_ratings(&self) -> Result<usize, CardsError>;
+
+    fn load_ratings(&self) -> Result<Vec<PlayerRating>, CardsError>;
+
+    fn load_rating_history(&self, player: RatedPlayer) -> Result<Vec<RatingChange>, CardsError>;
+
+    fn load_analysis(&self, game_id: GameId) -> Result<Option<AnalysisResponse>, CardsError>;
+
+    fn save_analysis(&self, analysis: &AnalysisResponse) -> Result<(), CardsError>;
+
+    fn load_archivable_games(
+        &self,
+        cutoff: i64,
+        limit: u32,
+    ) -> Result<Vec<StoredEvent>, CardsError>;
+
+    // skips games with events added since they were read
+    fn mark_archived(&self, games: &[(GameId, usize, ArchiveEntry)]) -> Result<usize, CardsError>;
+
+    // admin actions are recorded in the audit log in the same transaction
+
+    fn delete_game(&self, admin_id: UserId, game_id: GameId) -> Result<Audience, CardsError>;
+
+    fn abort_game(
+        &self,
+        admin_id: UserId,
+        game_id: GameId,
+        event_id: usize,
+        events: &[GameEvent],
+    ) -> Result<(), CardsError>;
+
+    fn rename_user(&self, admin_id: UserId, user_id: UserId, name: &str) -> Result<(), CardsError>;
+
+    fn ban_user(&self, admin_id: UserId, user_id: UserId) -> Result<(), CardsError>;
+
+    fn delete_chat(
+        &self,
+        admin_id: UserId,
+        user_id: UserId,
+        timestamp: i64,
+    ) -> Result<(), CardsError>;
+
+    fn load_audit_log(&self, limit: u32) -> Result<Vec<AuditEntry>, CardsError>;
+}
@@ -1,23 +1,42 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::sync::mpsc::UnboundedSender;
-use turbo_hearts_api::GameEvent;
+use turbo_hearts_api::{GameEvent, Seat};
 
 #[derive(Debug)]
 pub struct Subscriber {
     tx: UnboundedSender<(GameEvent, usize)>,
     counter: AtomicUsize,
     last_event_id: usize,
+    follow: Option<Seat>,
 }
 
 impl Subscriber {
-    pub fn new(tx: UnboundedSender<(GameEvent, usize)>, last_event_id: Option<usize>) -> Self {
+    pub fn new(
+        tx: UnboundedSender<(GameEvent, usize)>,
+        last_event_id: Option<usize>,
+        follow: Option<Seat>,
+    ) -> Self {
         Self {
             tx,
             counter: AtomicUsize::new(1),
             last_event_id: last_event_id.unwrap_or(0),
+            follow,
         }
     }
 
+    pub fn follow(&self) -> Option<Seat> {
+        self.follow
+    }
+
+    pub fn last_event_id(&self) -> usize {
+        self.last_event_id
+    }
+
+    // events replayed after a snapshot keep the ids they were first sent with
+    pub fn skip_to(&self, event_id: usize) {
+        self.counter.fetch_max(event_id + 1, Ordering::Relaxed);
+    }
+
     pub fn send(&self, event: GameEvent) -> bool {
         if event.is_stable() {
             let event_id = self.counter.fetch_add(1, Ordering::Relaxed);
@@ -1,108 +1,147 @@
-use crate::{CardsError, Database, GetJson, GetStr, ToSqlStr};
-use rusqlite::{Rows, ToSql};
+use crate::{
+    check_boards_played, load_duplicate_summary, Analyzer, CardsError, Storage, StoredEvent,
+};
 use std::mem;
 use turbo_hearts_api::{
-    ChargingRules, Game, GameEvent, GameEventsRequest, GameId, GameState, GameSummaryEvent,
-    GameSummaryResponse, LeaderboardGame, LeaderboardHand, LeaderboardRequest, LeaderboardResponse,
-    Player, Seat, UserId,
+    ChargingRules, DuplicateId, Game, GameEvent, GameEventsRequest, GameId, GameState,
+    GameSummaryEvent, GameSummaryResponse, LeaderboardGame, LeaderboardHand, LeaderboardRequest,
+    LeaderboardResponse, Player, RatedPlayer, RatingHistoryRequest, Seat, UserId,
 };
 use warp::{Filter, Rejection, Reply};
 
-const SELECT_GAME_EVENTS: &'static str = r#"
-SELECT   game_id,
-         timestamp,
-         event
-FROM     event
-WHERE    game_id = ?
-ORDER BY event_id"#;
-
-const SELECT_FIRST_PAGE_OF_GAME_EVENTS: &'static str = r#"
-WITH ids AS
-(
-         SELECT   e.game_id
-         FROM     event e,
-                  game g
-         WHERE    e.game_id = g.game_id
-         AND      e.event_id = 0
-         AND      g.completed_time IS NOT NULL
-         AND      NOT e.event LIKE '%"type":"bot"%'
-         ORDER BY g.completed_time DESC limit ?)
-SELECT   game_id,
-         timestamp,
-         event
-FROM     event
-WHERE    game_id IN ids
-ORDER BY game_id,
-         event_id"#;
-
-const SELECT_NEXT_PAGE_OF_GAME_EVENTS: &'static str = r#"
-WITH ids AS
-(
-         SELECT   e.game_id
-         FROM     event e,
-                  game g
-         WHERE    e.game_id = g.game_id
-         AND      e.event_id = 0
-         AND      g.completed_time IS NOT NULL
-         AND      g.completed_time <
-                  (
-                         SELECT completed_time
-                         FROM   game
-                         WHERE  game_id = ?)
-         AND      NOT e.event LIKE '%"type":"bot"%'
-         ORDER BY g.completed_time DESC limit ?)
-SELECT   game_id,
-         timestamp,
-         event
-FROM     event
-WHERE    game_id IN ids
-ORDER BY game_id,
-         event_id"#;
-
-pub fn router<'a>(db: infallible!(&'a Database)) -> reply!() {
+pub fn router<'a>(
+    storage: infallible!(&'a dyn Storage),
+    analyzer: infallible!(&'static Analyzer),
+    user_id: rejection!(UserId),
+) -> reply!() {
     warp::path("summary")
-        .and(leaderboard(db.clone()).or(game(db.clone())).or(games(db)))
+        .and(
+            leaderboard(storage.clone())
+                .or(game(storage.clone(), user_id.clone()))
+                .or(games(storage.clone()))
+                .or(ratings(storage.clone()))
+                .or(rating_history(storage.clone()))
+                .or(duplicate(storage.clone()))
+                .or(analysis(storage, analyzer, user_id)),
+        )
         .boxed()
 }
 
-fn leaderboard<'a>(db: infallible!(&'a Database)) -> reply!() {
-    async fn handle(db: &Database, request: LeaderboardRequest) -> Result<impl Reply, Rejection> {
+fn leaderboard<'a>(storage: infallible!(&'a dyn Storage)) -> reply!() {
+    async fn handle(
+        storage: &dyn Storage,
+        request: LeaderboardRequest,
+    ) -> Result<impl Reply, Rejection> {
         let LeaderboardRequest { game_id, page_size } = request;
         let page_size = page_size.unwrap_or(100);
-        let games = db.run_read_only(|tx| {
-            Ok(match game_id {
-                None => {
-                    let mut stmt = tx.prepare_cached(SELECT_FIRST_PAGE_OF_GAME_EVENTS)?;
-                    let rows = stmt.query(&[page_size])?;
-                    read_leaderboard(rows)?
-                }
-                Some(game_id) => {
-                    let mut stmt = tx.prepare_cached(SELECT_NEXT_PAGE_OF_GAME_EVENTS)?;
-                    let rows = stmt.query::<&[&dyn ToSql]>(&[&game_id.sql(), &page_size])?;
-                    read_leaderboard(rows)?
-                }
-            })
-        })?;
-        Ok(warp::reply::json(&games))
+        let events = storage.load_completed_games(game_id, page_size)?;
+        Ok(warp::reply::json(&read_leaderboard(events)))
     }
 
     warp::path!("leaderboard")
-        .and(db)
+        .and(storage)
+        .and(warp::query())
+        .and_then(handle)
+}
+
+fn ratings<'a>(storage: infallible!(&'a dyn Storage)) -> reply!() {
+    async fn handle(storage: &dyn Storage) -> Result<impl Reply, Rejection> {
+        let ratings = storage.load_ratings()?;
+        Ok(warp::reply::json(&ratings))
+    }
+
+    warp::path!("ratings").and(storage).and_then(handle)
+}
+
+fn rating_history<'a>(storage: infallible!(&'a dyn Storage)) -> reply!() {
+    async fn handle(
+        storage: &dyn Storage,
+        request: RatingHistoryRequest,
+    ) -> Result<impl Reply, Rejection> {
+        let RatingHistoryRequest {
+            user_id,
+            strategy,
+            options,
+        } = request;
+        let player = match (user_id, strategy) {
+            (_, Some(strategy)) => Some(RatedPlayer::Bot { strategy, options }),
+            (Some(user_id), None) => Some(RatedPlayer::Human { user_id }),
+            (None, None) => None,
+        };
+        let history = match player {
+            Some(player) => storage.load_rating_history(player)?,
+            None => Vec::new(),
+        };
+        Ok(warp::reply::json(&history))
+    }
+
+    warp::path!("rating_history")
+        .and(storage)
         .and(warp::query())
         .and_then(handle)
 }
 
-fn read_leaderboard(mut rows: Rows<'_>) -> Result<LeaderboardResponse, rusqlite::Error> {
+fn duplicate<'a>(storage: infallible!(&'a dyn Storage)) -> reply!() {
+    async fn handle(
+        duplicate_id: DuplicateId,
+        storage: &dyn Storage,
+    ) -> Result<impl Reply, Rejection> {
+        let summary = load_duplicate_summary(storage, duplicate_id)?;
+        Ok(warp::reply::json(&summary))
+    }
+
+    warp::path!("duplicate" / DuplicateId)
+        .and(storage)
+        .and_then(handle)
+}
+
+fn analysis<'a>(
+    storage: infallible!(&'a dyn Storage),
+    analyzer: infallible!(&'static Analyzer),
+    user_id: rejection!(UserId),
+) -> reply!() {
+    async fn handle(
+        game_id: GameId,
+        storage: &dyn Storage,
+        analyzer: &'static Analyzer,
+        user_id: UserId,
+    ) -> Result<impl Reply, Rejection> {
+        storage.check_access(game_id, user_id, None)?;
+        let analysis = analyzer.analyze(game_id).await?;
+        Ok(warp::reply::json(&analysis))
+    }
+
+    warp::path!("analysis" / GameId)
+        .and(storage)
+        .and(analyzer)
+        .and(user_id)
+        .and_then(handle)
+}
+
+fn read_leaderboard(events: Vec<StoredEvent>) -> LeaderboardResponse {
     let mut games = Vec::new();
     let mut hands = Vec::with_capacity(4);
     let mut players = [Player::Human {
         user_id: UserId::null(),
     }; 4];
     let mut state = GameState::new();
-    while let Some(row) = rows.next()? {
-        let game_id = row.get_str(0)?;
-        let timestamp = row.get(1)?;
-        let event = row.get_json(2)?;
+    for StoredEvent {
+        game_id,
+        timestamp,
+        event,
+    } in events
+    {
+        // an aborted game keeps the hands that were finished before it ended
+        if let GameEvent::Abort = event {
+            games.push(LeaderboardGame {
+                game_id,
+                completed_time: timestamp,
+                players,
+                hands: mem::take(&mut hands),
+            });
+            state = GameState::new();
+            continue;
+        }
         let was_playing = state.phase.is_playing();
         state.apply(&event);
         if let GameEvent::Sit {
@@ -113,7 +152,7 @@ fn read_leaderboard(mut rows: Rows<'_>) -> Result<LeaderboardResponse, rusqlite:
             ..
         } = event
         {
-            players = [north, east, south, west];
+            players = [*north, *east, *south, *west];
         }
         let is_playing = state.phase.is_playing();
         if was_playing && !is_playing {
@@ -134,7 +173,7 @@ fn read_leaderboard(mut rows: Rows<'_>) -> Result<LeaderboardResponse, rusqlite:
                 ten_winner: players[state.won.ten_winner().unwrap().idx()].user_id(),
                 jack_winner: players[state.won.jack_winner().unwrap().idx()].user_id(),
             });
-            if hands.len() == 4 {
+            if state.phase.is_complete() {
                 let mut complete_hands = Vec::with_capacity(4);
                 mem::swap(&mut hands, &mut complete_hands);
                 games.push(LeaderboardGame {
@@ -148,55 +187,48 @@ fn read_leaderboard(mut rows: Rows<'_>) -> Result<LeaderboardResponse, rusqlite:
         }
     }
     games.sort_by_key(|game| -game.completed_time);
-    Ok(games)
+    games
 }
 
-fn game<'a>(db: infallible!(&'a Database)) -> reply!() {
-    async fn handle(game_id: GameId, db: &Database) -> Result<impl Reply, Rejection> {
-        let reply = db.run_read_only(|tx| {
-            let mut stmt = tx.prepare_cached(SELECT_GAME_EVENTS)?;
-            let rows = stmt.query(&[game_id.sql()])?;
-            let mut games = read_games(rows)?;
-            if let Some(game) = games.pop() {
-                Ok(game)
-            } else {
-                Err(CardsError::IncompleteGame(game_id))
-            }
-        })?;
-        Ok(warp::reply::json(&reply))
+fn game<'a>(storage: infallible!(&'a dyn Storage), user_id: rejection!(UserId)) -> reply!() {
+    async fn handle(
+        game_id: GameId,
+        storage: &dyn Storage,
+        user_id: UserId,
+    ) -> Result<impl Reply, Rejection> {
+        storage.check_access(game_id, user_id, None)?;
+        check_boards_played(storage, game_id)?;
+        let events = storage.load_game_events(game_id)?;
+        match read_games(events).pop() {
+            Some(game) => Ok(warp::reply::json(&game)),
+            None => Err(CardsError::IncompleteGame(game_id).into()),
+        }
     }
 
-    warp::path!("game" / GameId).and(db).and_then(handle)
+    warp::path!("game" / GameId)
+        .and(storage)
+        .and(user_id)
+        .and_then(handle)
 }
 
-fn games<'a>(db: infallible!(&'a Database)) -> reply!() {
-    async fn handle(db: &Database, request: GameEventsRequest) -> Result<impl Reply, Rejection> {
+fn games<'a>(storage: infallible!(&'a dyn Storage)) -> reply!() {
+    async fn handle(
+        storage: &dyn Storage,
+        request: GameEventsRequest,
+    ) -> Result<impl Reply, Rejection> {
         let GameEventsRequest { game_id, page_size } = request;
         let page_size = page_size.unwrap_or(100);
-        let games = db.run_read_only(|tx| {
-            Ok(match game_id {
-                None => {
-                    let mut stmt = tx.prepare_cached(SELECT_FIRST_PAGE_OF_GAME_EVENTS)?;
-                    let rows = stmt.query(&[page_size])?;
-                    read_games(rows)?
-                }
-                Some(game_id) => {
-                    let mut stmt = tx.prepare_cached(SELECT_NEXT_PAGE_OF_GAME_EVENTS)?;
-                    let rows = stmt.query::<&[&dyn ToSql]>(&[&game_id.sql(), &page_size])?;
-                    read_games(rows)?
-                }
-            })
-        })?;
-        Ok(warp::reply::json(&games))
+        let events = storage.load_completed_games(game_id, page_size)?;
+        Ok(warp::reply::json(&read_games(events)))
     }
 
     warp::path!("games")
-        .and(db)
+        .and(storage)
         .and(warp::query())
         .and_then(handle)
 }
 
-fn read_games(mut rows: Rows<'_>) -> Result<Vec<GameSummaryResponse>, CardsError> {
+fn read_games(stored: Vec<StoredEvent>) -> Vec<GameSummaryResponse> {
     let mut games = Vec::new();
     let mut hands = Vec::new();
     let mut game: Game<()> = Game::new();
@@ -205,10 +237,12 @@ fn read_games(mut rows: Rows<'_>) -> Result<Vec<GameSummaryResponse>, CardsError
         user_id: UserId::null(),
     }; 4];
     let mut rules = ChargingRules::Classic;
-    while let Some(row) = rows.next()? {
-        let game_id = row.get_str(0)?;
-        let timestamp = row.get(1)?;
-        let event = row.get_json(2)?;
+    for StoredEvent {
+        game_id,
+        timestamp,
+        event,
+    } in stored
+    {
         if let GameEvent::Sit {
             north,
             east,
@@ -220,9 +254,20 @@ fn read_games(mut rows: Rows<'_>) -> Result<Vec<GameSummaryResponse>, CardsError
         {
             hands = Vec::with_capacity(4);
             game = Game::new();
-            players = [*north, *east, *south, *west];
+            players = [**north, **east, **south, **west];
             rules = *charging_rules;
         }
+        if let GameEvent::Abort = event {
+            games.push(GameSummaryResponse {
+                game_id,
+                players,
+                rules,
+                hands: mem::take(&mut hands),
+            });
+            events = Vec::new();
+            game = Game::new();
+            continue;
+        }
         let mut synthetic_events = Vec::new();
         let was_playing = game.state.phase.is_playing();
         game.apply(&event, |_, e| {
@@ -239,7 +284,7 @@ fn read_games(mut rows: Rows<'_>) -> Result<Vec<GameSummaryResponse>, CardsError
         if was_playing && !is_playing {
             hands.push(events);
             events = Vec::new();
-            if hands.len() == 4 {
+            if game.state.phase.is_complete() {
                 games.push(GameSummaryResponse {
                     game_id,
                     players,
@@ -251,5 +296,5 @@ fn read_games(mut rows: Rows<'_>) -> Result<Vec<GameSummaryResponse>, CardsError
             }
         }
     }
-    Ok(games)
+    games
 }
@@ -1,12 +1,24 @@
-use crate::{persist_events, CardsError, Database, Games, Lobby};
+use crate::{
+    archive_games, check_snapshot, game_endpoints, handle_rejection, import_record, latest_version,
+    load_duplicate_summary, load_events, load_record, migrate, record_endpoints, render_metrics,
+    schema_version, util, Analyzer, CardsError, Database, Direction, ExternalUser, GameOptions,
+    Games, Lobby, MemoryStorage, Storage, Users,
+};
+use http::StatusCode;
 use log::LevelFilter;
 use once_cell::sync::Lazy;
+use rusqlite::{Connection, ToSql, NO_PARAMS};
+use serde_json::{json, Value};
 use std::{collections::HashMap, future::Future};
 use tempfile::TempDir;
+use tokio::time::{self, Duration};
 use turbo_hearts_api::{
-    BotStrategy, Card, ChargingRules, GameEvent, GameId, GamePhase, LobbyEvent, PassDirection,
-    Player, PlayerWithOptions, RulesError, Seat, Seed, UserId,
+    AdminAction, BotOptions, BotStrategy, Card, Cards, ChargingRules, Difficulty, DuplicateId,
+    Game, GameEvent, GameId, GameLength, GamePhase, GameRecord, JoinToken, LobbyEvent, MoonScoring,
+    PassDirection, Player, PlayerWithOptions, Privacy, RatedPlayer, RuleSet, RulesError, Seat,
+    Seed, SpectatorPolicy, TimeControl, UserId,
 };
+use warp::{Filter, Rejection};
 
 macro_rules! h {
     ($user_id:expr) => {
@@ -31,32 +43,131 @@ static TSLATCHER: Lazy<UserId> = Lazy::new(|| UserId::new());
 static CARRINO: Lazy<UserId> = Lazy::new(|| UserId::new());
 static DCERVELLI: Lazy<UserId> = Lazy::new(|| UserId::new());
 
-struct TestRunner {
-    _temp_dir: TempDir,
-    db: &'static Database,
+const NO_OPTIONS: GameOptions = GameOptions {
+    time_control: None,
+    spectators: None,
+    rule_set: None,
+    length: None,
+};
+const OMNISCIENT: SpectatorPolicy = SpectatorPolicy::Omniscient { delay_seconds: 0 };
+const NO_MOON: RuleSet = RuleSet {
+    moon: MoonScoring::Disabled,
+    ..RuleSet::STANDARD
+};
+
+struct TestRunner<S: 'static> {
+    _temp_dir: Option<TempDir>,
+    storage: &'static S,
     lobby: &'static Lobby,
     games: &'static Games,
 }
 
-impl TestRunner {
+impl TestRunner<MemoryStorage> {
     fn new() -> Self {
+        Self::with_storage(MemoryStorage::new(), None)
+    }
+}
+
+impl TestRunner<Database> {
+    fn sqlite() -> Self {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut path = temp_dir.path().to_owned();
+        path.push("test.db");
+        Self::with_storage(Database::new(path).unwrap(), Some(temp_dir))
+    }
+}
+
+pub trait TestStorage: Storage {
+    fn count_snapshots(&self, game_id: GameId) -> Result<usize, CardsError>;
+
+    fn corrupt_snapshots(&self, game_id: GameId) -> Result<(), CardsError>;
+
+    // not counting archived events
+    fn count_events(&self, game_id: GameId) -> Result<usize, CardsError>;
+
+    fn make_admin(&self, user_id: UserId) -> Result<(), CardsError>;
+
+    fn insert_duplicate_table(
+        &self,
+        duplicate_id: DuplicateId,
+        game_id: GameId,
+    ) -> Result<(), CardsError>;
+}
+
+impl TestStorage for Database {
+    fn count_snapshots(&self, game_id: GameId) -> Result<usize, CardsError> {
+        let snapshots: i64 = self.run_read_only(|tx| {
+            Ok(tx.query_row(
+                "SELECT COUNT(*) FROM snapshot WHERE game_id = ?",
+                &[game_id.to_string()],
+                |row| row.get(0),
+            )?)
+        })?;
+        Ok(snapshots as usize)
+    }
+
+    fn corrupt_snapshots(&self, game_id: GameId) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| {
+            tx.execute(
+                "UPDATE snapshot SET snapshot = json_set(snapshot, '$.state.totals[0]', 1000)
+                    WHERE game_id = ?",
+                &[game_id.to_string()],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn count_events(&self, game_id: GameId) -> Result<usize, CardsError> {
+        let rows: i64 = self.run_read_only(|tx| {
+            Ok(tx.query_row(
+                "SELECT COUNT(*) FROM event WHERE game_id = ?",
+                &[game_id.to_string()],
+                |row| row.get(0),
+            )?)
+        })?;
+        Ok(rows as usize)
+    }
+
+    fn make_admin(&self, user_id: UserId) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| {
+            tx.execute(
+                "UPDATE user SET role = 'admin' WHERE user_id = ?",
+                &[user_id.to_string()],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn insert_duplicate_table(
+        &self,
+        duplicate_id: DuplicateId,
+        game_id: GameId,
+    ) -> Result<(), CardsError> {
+        self.run_with_retry(|tx| {
+            tx.execute::<&[&dyn ToSql]>(
+                "INSERT INTO duplicate_game (duplicate_id, game_id, rotation) VALUES (?, ?, 0)",
+                &[&duplicate_id.to_string(), &game_id.to_string()],
+            )?;
+            Ok(())
+        })
+    }
+}
+
+impl<S: Storage> TestRunner<S> {
+    fn with_storage(storage: S, temp_dir: Option<TempDir>) -> Self {
         let _ = env_logger::builder()
             .filter_level(LevelFilter::Info)
             .filter_module("turbo_hearts_bot", LevelFilter::Debug)
             .is_test(true)
             .try_init();
-        let temp_dir = tempfile::tempdir().unwrap();
-        let mut path = temp_dir.path().to_owned();
-        path.push("test.db");
-        let db = Database::new(path).unwrap();
-        let db = &*Box::leak(Box::new(db));
-        let lobby = Lobby::new(db).unwrap();
+        let storage = &*Box::leak(Box::new(storage));
+        let lobby = Lobby::new(storage).unwrap();
         let lobby = &*Box::leak(Box::new(lobby));
-        let games = Games::new(db, false);
+        let games = Games::new(storage, false, true);
         let games = &*Box::leak(Box::new(games));
         Self {
             _temp_dir: temp_dir,
-            db,
+            storage,
             lobby,
             games,
         }
@@ -64,14 +175,14 @@ impl TestRunner {
 
     async fn run<F, T>(&self, task: T) -> F::Output
     where
-        T: FnOnce(&'static Database, &'static Lobby, &'static Games) -> F + Send + 'static,
+        T: FnOnce(&'static S, &'static Lobby, &'static Games) -> F + Send + 'static,
         F: Future + Send + 'static,
         F::Output: Send + 'static,
     {
-        let db = self.db;
+        let storage = self.storage;
         let lobby = self.lobby;
         let games = self.games;
-        let result = tokio::spawn(async move { task(db, lobby, games).await }).await;
+        let result = tokio::spawn(async move { task(storage, lobby, games).await }).await;
         match result {
             Ok(v) => v,
             Err(e) => std::panic::resume_unwind(e.into_panic()),
@@ -81,7 +192,7 @@ impl TestRunner {
 
 #[tokio::test(flavor = "multi_thread")]
 async fn test_lobby() -> Result<(), CardsError> {
-    async fn test(_: &Database, lobby: &Lobby, _: &Games) -> Result<(), CardsError> {
+    async fn test<S>(_: &S, lobby: &Lobby, _: &Games) -> Result<(), CardsError> {
         let mut twilson = lobby.subscribe(*TWILSON).await?;
         assert_eq!(
             twilson.recv().await,
@@ -119,16 +230,26 @@ async fn test_lobby() -> Result<(), CardsError> {
             rules: ChargingRules::Bridge,
             seat: None,
         };
-        let game_id = lobby.new_game(tslatcher, None).await?;
+        let (game_id, _) = lobby.new_game(tslatcher, None, NO_OPTIONS, None).await?;
         match twilson.recv().await {
             Some(LobbyEvent::NewGame {
                 game_id: id,
                 player,
                 seed,
+                time_control,
+                spectators,
+                rule_set,
+                length,
+                private,
             }) => {
                 assert_eq!(id, game_id);
                 assert_eq!(player, tslatcher);
                 assert_eq!(seed, Seed::Redacted);
+                assert_eq!(time_control, None);
+                assert_eq!(spectators, None);
+                assert_eq!(rule_set, None);
+                assert_eq!(length, None);
+                assert!(!private);
             }
             event => panic!("Unexpected event {:?}", event),
         }
@@ -157,7 +278,7 @@ async fn test_lobby() -> Result<(), CardsError> {
             rules: ChargingRules::Classic,
             seat: None,
         };
-        lobby.join_game(game_id, dcervelli).await?;
+        lobby.join_game(game_id, dcervelli, None).await?;
         match twilson.recv().await {
             Some(LobbyEvent::JoinGame {
                 game_id: id,
@@ -180,13 +301,14 @@ async fn test_lobby() -> Result<(), CardsError> {
         Ok(())
     }
 
-    TestRunner::new().run(test).await
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
 }
 
 #[tokio::test(flavor = "multi_thread")]
 async fn test_new_game() -> Result<(), CardsError> {
-    async fn test(_: &Database, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
-        let game_id = lobby
+    async fn test<S>(_: &S, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
+        let (game_id, _) = lobby
             .new_game(
                 PlayerWithOptions {
                     player: h![*TWILSON],
@@ -194,6 +316,11 @@ async fn test_new_game() -> Result<(), CardsError> {
                     seat: None,
                 },
                 None,
+                GameOptions {
+                    rule_set: Some(NO_MOON),
+                    ..NO_OPTIONS
+                },
+                None,
             )
             .await?;
         lobby
@@ -204,6 +331,7 @@ async fn test_new_game() -> Result<(), CardsError> {
                     rules: ChargingRules::Classic,
                     seat: None,
                 },
+                None,
             )
             .await?;
         lobby
@@ -214,6 +342,7 @@ async fn test_new_game() -> Result<(), CardsError> {
                     rules: ChargingRules::Classic,
                     seat: None,
                 },
+                None,
             )
             .await?;
         lobby
@@ -224,12 +353,13 @@ async fn test_new_game() -> Result<(), CardsError> {
                     rules: ChargingRules::Classic,
                     seat: None,
                 },
+                None,
             )
             .await?;
-        let (players, seed) = lobby.start_game(game_id).await?;
-        games.start_game(game_id, players, seed)?;
+        let (players, seed, options) = lobby.start_game(game_id).await?;
+        games.start_game(game_id, players, seed, options)?;
 
-        let mut twilson = games.subscribe(game_id, *TWILSON, None).await?;
+        let mut twilson = games.subscribe(game_id, *TWILSON, None, None, None).await?;
         match twilson.recv().await {
             Some((
                 GameEvent::Sit {
@@ -238,49 +368,292 @@ async fn test_new_game() -> Result<(), CardsError> {
                     south,
                     west,
                     rules: ChargingRules::Classic,
+                    rule_set: Some(NO_MOON),
                     ..
                 },
                 _,
             )) => assert_eq!(
-                set![north, east, south, west],
+                set![*north, *east, *south, *west],
                 set![h!(*TWILSON), h!(*TSLATCHER), h!(*DCERVELLI), h!(*CARRINO)]
             ),
             e => assert!(false, "Expected sit event, found {:?}", e),
         }
         Ok(())
     }
-    TestRunner::new().run(test).await
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_private_game() -> Result<(), CardsError> {
+    async fn test<S>(_: &S, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
+        let human = |user_id| PlayerWithOptions {
+            player: h!(user_id),
+            rules: ChargingRules::Classic,
+            seat: None,
+        };
+        let bot = PlayerWithOptions {
+            player: Player::Bot {
+                user_id: UserId::new(),
+                strategy: BotStrategy::Duck,
+                options: BotOptions::default(),
+            },
+            rules: ChargingRules::Classic,
+            seat: None,
+        };
+        let (game_id, join_token) = lobby
+            .new_game(
+                human(*TWILSON),
+                None,
+                NO_OPTIONS,
+                Some(Privacy {
+                    invited: set![*TSLATCHER],
+                    join_token: true,
+                    hidden: true,
+                }),
+            )
+            .await?;
+        let join_token = join_token.unwrap();
+        match lobby.subscribe(*CARRINO).await?.recv().await {
+            Some(LobbyEvent::LobbyState { games, .. }) => assert!(!games.contains_key(&game_id)),
+            event => panic!("Unexpected event {:?}", event),
+        }
+        match lobby.subscribe(*TSLATCHER).await?.recv().await {
+            Some(LobbyEvent::LobbyState { games, .. }) => assert!(games[&game_id].private),
+            event => panic!("Unexpected event {:?}", event),
+        }
+
+        assert!(matches!(
+            lobby.join_game(game_id, human(*CARRINO), None).await,
+            Err(CardsError::NotInvited(user_id, id)) if user_id == *CARRINO && id == game_id
+        ));
+        assert!(matches!(
+            lobby.join_game(game_id, human(*CARRINO), Some(JoinToken::new())).await,
+            Err(CardsError::InvalidJoinToken(_, id)) if id == game_id
+        ));
+        lobby
+            .join_game(game_id, human(*CARRINO), Some(join_token))
+            .await?;
+        lobby.join_game(game_id, human(*TSLATCHER), None).await?;
+        assert!(matches!(
+            lobby.add_bot(game_id, *DCERVELLI, bot).await,
+            Err(CardsError::NotInvited(user_id, _)) if user_id == *DCERVELLI
+        ));
+        assert!(matches!(
+            lobby.remove_player(game_id, *DCERVELLI, *CARRINO).await,
+            Err(CardsError::NotInvited(user_id, _)) if user_id == *DCERVELLI
+        ));
+        lobby.add_bot(game_id, *CARRINO, bot).await?;
+
+        let (players, seed, options) = lobby.start_game(game_id).await?;
+        games.start_game(game_id, players, seed, options)?;
+        assert!(matches!(
+            games.subscribe(game_id, *DCERVELLI, None, None, None).await,
+            Err(CardsError::NotInvited(user_id, _)) if user_id == *DCERVELLI
+        ));
+        games
+            .subscribe(game_id, *DCERVELLI, None, None, Some(join_token))
+            .await?;
+        games.subscribe(game_id, *CARRINO, None, None, None).await?;
+        Ok(())
+    }
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_hidden_game_summaries() -> Result<(), CardsError> {
+    async fn test<S: TestStorage>(
+        storage: &'static S,
+        lobby: &Lobby,
+        games: &Games,
+    ) -> Result<(), CardsError> {
+        let human = |user_id, seat| PlayerWithOptions {
+            player: h!(user_id),
+            rules: ChargingRules::Classic,
+            seat: Some(seat),
+        };
+        // people whose seats are taken over by bots play it out quickly
+        let options = GameOptions {
+            time_control: Some(TimeControl {
+                seconds: 1,
+                strategy: BotStrategy::Duck,
+            }),
+            spectators: Some(OMNISCIENT),
+            ..NO_OPTIONS
+        };
+        let (game_id, _) = lobby
+            .new_game(
+                human(*TWILSON, Seat::North),
+                None,
+                options,
+                Some(Privacy {
+                    invited: set![*TSLATCHER, *DCERVELLI, *CARRINO],
+                    join_token: false,
+                    hidden: true,
+                }),
+            )
+            .await?;
+        lobby
+            .join_game(game_id, human(*TSLATCHER, Seat::East), None)
+            .await?;
+        lobby
+            .join_game(game_id, human(*DCERVELLI, Seat::South), None)
+            .await?;
+        lobby
+            .join_game(game_id, human(*CARRINO, Seat::West), None)
+            .await?;
+        let (players, seed, options) = lobby.start_game(game_id).await?;
+        games.start_game(game_id, players, seed, options)?;
+        let mut rx = games.subscribe(game_id, *TWILSON, None, None, None).await?;
+        time::sleep(Duration::from_secs(1)).await;
+        games.expire_clocks().await;
+        while let Some((event, _)) = rx.recv().await {
+            if let GameEvent::GameComplete { .. } = event {
+                break;
+            }
+        }
+
+        assert!(storage
+            .load_completed_games(None, 100)?
+            .iter()
+            .all(|stored| stored.game_id != game_id));
+
+        let export = |user_id| {
+            let router = record_endpoints::router(
+                warp::any().map(move || storage as &dyn Storage),
+                warp::any().and_then(move || async move { Ok::<_, Rejection>(user_id) }),
+            )
+            .recover(handle_rejection);
+            async move {
+                warp::test::request()
+                    .path(&format!("/record/{}", game_id))
+                    .reply(&router)
+                    .await
+                    .status()
+            }
+        };
+        assert_eq!(export(UserId::new()).await, StatusCode::FORBIDDEN);
+        assert_eq!(export(*CARRINO).await, StatusCode::OK);
+        Ok(())
+    }
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_bot_options() -> Result<(), CardsError> {
+    async fn test<S>(_: &S, lobby: &Lobby, _: &Games) -> Result<(), CardsError> {
+        let human = |user_id| PlayerWithOptions {
+            player: h!(user_id),
+            rules: ChargingRules::Classic,
+            seat: None,
+        };
+        let options = BotOptions {
+            time_ms: Some(100),
+            iterations: None,
+            difficulty: Difficulty::Easy,
+            seed: Some(7),
+        };
+        let bot = |user_id, options| PlayerWithOptions {
+            player: Player::Bot {
+                user_id,
+                strategy: BotStrategy::Simulate,
+                options,
+            },
+            rules: ChargingRules::Classic,
+            seat: None,
+        };
+        let (game_id, _) = lobby
+            .new_game(human(*TWILSON), None, NO_OPTIONS, None)
+            .await?;
+        lobby.join_game(game_id, human(*TSLATCHER), None).await?;
+        lobby
+            .add_bot(game_id, *TWILSON, bot(*CARRINO, options))
+            .await?;
+        lobby
+            .add_bot(game_id, *TWILSON, bot(*DCERVELLI, BotOptions::default()))
+            .await?;
+        let (players, _, _) = lobby.start_game(game_id).await?;
+        let options_of = |user_id| {
+            players
+                .iter()
+                .find(|p| p.player.user_id() == user_id)
+                .and_then(|p| p.player.bot_options())
+        };
+        assert_eq!(options_of(*CARRINO), Some(options));
+        assert_eq!(options_of(*DCERVELLI), Some(BotOptions::default()));
+        assert_eq!(options_of(*TWILSON), None);
+
+        let (game_id, _) = lobby
+            .new_game(human(*TWILSON), None, NO_OPTIONS, None)
+            .await?;
+        for &options in &[
+            BotOptions {
+                time_ms: Some(BotOptions::MAX_TIME_MS + 1),
+                ..BotOptions::default()
+            },
+            BotOptions {
+                iterations: Some(u32::MAX),
+                ..BotOptions::default()
+            },
+            BotOptions {
+                time_ms: Some(0),
+                ..BotOptions::default()
+            },
+        ] {
+            assert!(matches!(
+                lobby.add_bot(game_id, *TWILSON, bot(*CARRINO, options)).await,
+                Err(CardsError::InvalidBotOptions(invalid)) if invalid == options
+            ));
+        }
+        let options = BotOptions {
+            time_ms: Some(BotOptions::MAX_TIME_MS),
+            iterations: Some(BotOptions::MAX_ITERATIONS),
+            ..BotOptions::default()
+        };
+        lobby
+            .add_bot(game_id, *TWILSON, bot(*CARRINO, options))
+            .await?;
+        Ok(())
+    }
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
 }
 
 #[tokio::test(flavor = "multi_thread")]
 async fn test_pass() -> Result<(), CardsError> {
-    async fn test(db: &Database, _lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
+    async fn test<S: Storage>(
+        storage: &S,
+        _lobby: &Lobby,
+        games: &Games,
+    ) -> Result<(), CardsError> {
         let game_id = GameId::new();
-        db.run_with_retry(|tx| {
-            persist_events(
-                &tx,
-                game_id,
-                0,
-                &[
-                    GameEvent::Sit {
-                        north: h!(*TWILSON),
-                        east: h!(*DCERVELLI),
-                        south: h!(*TSLATCHER),
-                        west: h!(*CARRINO),
-                        rules: ChargingRules::Classic,
-                        seed: Seed::random(),
-                    },
-                    GameEvent::Deal {
-                        north: c!(A764S A96H AJD K863C),
-                        east: c!(JT953S QT4H K93D ATC),
-                        south: c!(2S 875H T542D QJ752C),
-                        west: c!(KQ8S KJ32H Q876D 94C),
-                        pass: PassDirection::Left,
-                    },
-                ],
-            )?;
-            Ok(())
-        })?;
+        storage.persist_events(
+            game_id,
+            0,
+            &[
+                GameEvent::Sit {
+                    north: Box::new(h!(*TWILSON)),
+                    east: Box::new(h!(*DCERVELLI)),
+                    south: Box::new(h!(*TSLATCHER)),
+                    west: Box::new(h!(*CARRINO)),
+                    rules: ChargingRules::Classic,
+                    seed: Seed::random(),
+                    time_control: None,
+                    spectators: None,
+                    rule_set: None,
+                    length: None,
+                },
+                GameEvent::Deal {
+                    north: c!(A764S A96H AJD K863C),
+                    east: c!(JT953S QT4H K93D ATC),
+                    south: c!(2S 875H T542D QJ752C),
+                    west: c!(KQ8S KJ32H Q876D 94C),
+                    pass: PassDirection::Left,
+                },
+            ],
+        )?;
 
         assert!(matches!(
             games.pass_cards(game_id, *TWILSON, c!(A73S)).await,
@@ -311,65 +684,90 @@ async fn test_pass() -> Result<(), CardsError> {
 
         Ok(())
     }
-    TestRunner::new().run(test).await
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+async fn new_seeded_game(
+    lobby: &Lobby,
+    games: &Games,
+    time_control: Option<TimeControl>,
+    spectators: Option<SpectatorPolicy>,
+) -> Result<GameId, CardsError> {
+    let (game_id, _) = lobby
+        .new_game(
+            PlayerWithOptions {
+                player: h![*TWILSON],
+                rules: ChargingRules::Classic,
+                seat: Some(Seat::North),
+            },
+            Some("2a3ef864-e49e-440b-9f0a-4125c59716ee".to_string()),
+            GameOptions {
+                time_control,
+                spectators,
+                ..NO_OPTIONS
+            },
+            None,
+        )
+        .await?;
+    lobby
+        .join_game(
+            game_id,
+            PlayerWithOptions {
+                player: h![*TSLATCHER],
+                rules: ChargingRules::Classic,
+                seat: Some(Seat::East),
+            },
+            None,
+        )
+        .await?;
+    lobby
+        .join_game(
+            game_id,
+            PlayerWithOptions {
+                player: h![*DCERVELLI],
+                rules: ChargingRules::Classic,
+                seat: Some(Seat::South),
+            },
+            None,
+        )
+        .await?;
+    lobby
+        .join_game(
+            game_id,
+            PlayerWithOptions {
+                player: h![*CARRINO],
+                rules: ChargingRules::Classic,
+                seat: Some(Seat::West),
+            },
+            None,
+        )
+        .await?;
+    let (players, seed, options) = lobby.start_game(game_id).await?;
+    games.start_game(game_id, players, seed, options)?;
+    Ok(game_id)
+}
+
+async fn start_seeded_game(lobby: &Lobby, games: &Games) -> Result<GameId, CardsError> {
+    let game_id = new_seeded_game(lobby, games, None, None).await?;
+    games.pass_cards(game_id, *CARRINO, c!(87H 8C)).await?;
+    games.pass_cards(game_id, *DCERVELLI, c!(JH J2C)).await?;
+    games.pass_cards(game_id, *TWILSON, c!(Q63H)).await?;
+    games.pass_cards(game_id, *TSLATCHER, c!(AD KQC)).await?;
+    games.charge_cards(game_id, *TWILSON, c!()).await?;
+    games.charge_cards(game_id, *DCERVELLI, c!()).await?;
+    games.charge_cards(game_id, *CARRINO, c!()).await?;
+    games.charge_cards(game_id, *TSLATCHER, c!(AH)).await?;
+    games.charge_cards(game_id, *TWILSON, c!()).await?;
+    games.charge_cards(game_id, *DCERVELLI, c!()).await?;
+    games.charge_cards(game_id, *CARRINO, c!()).await?;
+    Ok(game_id)
 }
 
 #[tokio::test(flavor = "multi_thread")]
 async fn test_seeded_game() -> Result<(), CardsError> {
-    async fn test(_db: &Database, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
-        let game_id = lobby
-            .new_game(
-                PlayerWithOptions {
-                    player: h![*TWILSON],
-                    rules: ChargingRules::Classic,
-                    seat: Some(Seat::North),
-                },
-                Some("2a3ef864-e49e-440b-9f0a-4125c59716ee".to_string()),
-            )
-            .await?;
-        lobby
-            .join_game(
-                game_id,
-                PlayerWithOptions {
-                    player: h![*TSLATCHER],
-                    rules: ChargingRules::Classic,
-                    seat: Some(Seat::East),
-                },
-            )
-            .await?;
-        lobby
-            .join_game(
-                game_id,
-                PlayerWithOptions {
-                    player: h![*DCERVELLI],
-                    rules: ChargingRules::Classic,
-                    seat: Some(Seat::South),
-                },
-            )
-            .await?;
-        lobby
-            .join_game(
-                game_id,
-                PlayerWithOptions {
-                    player: h![*CARRINO],
-                    rules: ChargingRules::Classic,
-                    seat: Some(Seat::West),
-                },
-            )
-            .await?;
-        let (players, seed) = lobby.start_game(game_id).await?;
-        games.start_game(game_id, players, seed)?;
-        games.pass_cards(game_id, *CARRINO, c!(87H 8C)).await?;
-        games.pass_cards(game_id, *DCERVELLI, c!(JH J2C)).await?;
-        games.pass_cards(game_id, *TWILSON, c!(Q63H)).await?;
-        games.pass_cards(game_id, *TSLATCHER, c!(AD KQC)).await?;
-        games.charge_cards(game_id, *TWILSON, c!()).await?;
-        games.charge_cards(game_id, *DCERVELLI, c!()).await?;
-        games.charge_cards(game_id, *CARRINO, c!()).await?;
-        games.charge_cards(game_id, *TSLATCHER, c!(AH)).await?;
-        games.charge_cards(game_id, *TWILSON, c!()).await?;
-        games.charge_cards(game_id, *DCERVELLI, c!()).await?;
-        games.charge_cards(game_id, *CARRINO, c!()).await?;
+    async fn test<S>(_: &S, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
+        let game_id = start_seeded_game(lobby, games).await?;
         games.play_card(game_id, *CARRINO, c!(2C)).await?;
         games.play_card(game_id, *TWILSON, c!(8C)).await?;
         games.play_card(game_id, *TSLATCHER, c!(7C)).await?;
@@ -426,67 +824,326 @@ async fn test_seeded_game() -> Result<(), CardsError> {
         games.play_card(game_id, *CARRINO, c!(5C)).await?;
         Ok(())
     }
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_undo() -> Result<(), CardsError> {
+    async fn test(_: &MemoryStorage, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
+        let game_id = start_seeded_game(lobby, games).await?;
+        assert!(matches!(
+            games.request_undo(game_id, *CARRINO).await,
+            Err(CardsError::Rules {
+                source: RulesError::NothingToUndo
+            })
+        ));
+
+        games.play_card(game_id, *CARRINO, c!(2C)).await?;
+        games.play_card(game_id, *TWILSON, c!(8C)).await?;
+        games.request_undo(game_id, *TWILSON).await?;
+        assert!(matches!(
+            games.request_undo(game_id, *CARRINO).await,
+            Err(CardsError::Rules { source: RulesError::UndoPending(user_id) }) if user_id == *TWILSON
+        ));
+        assert!(matches!(
+            games.accept_undo(game_id, *TWILSON, Seat::North).await,
+            Err(CardsError::Rules { source: RulesError::AlreadyAcceptedUndo(acceptor, requester) })
+                if acceptor == *TWILSON && requester == *TWILSON
+        ));
+        assert!(matches!(
+            games.accept_undo(game_id, *CARRINO, Seat::East).await,
+            Err(CardsError::Rules { source: RulesError::NotRequestingUndo(user_id) }) if user_id == *TSLATCHER
+        ));
+        games.accept_undo(game_id, *CARRINO, Seat::North).await?;
+        games.reject_undo(game_id, *DCERVELLI, Seat::North).await?;
+        assert!(matches!(
+            games.accept_undo(game_id, *TSLATCHER, Seat::North).await,
+            Err(CardsError::Rules { source: RulesError::NotRequestingUndo(user_id) }) if user_id == *TWILSON
+        ));
+
+        games.request_undo(game_id, *TWILSON).await?;
+        games.accept_undo(game_id, *CARRINO, Seat::North).await?;
+        games.accept_undo(game_id, *TSLATCHER, Seat::North).await?;
+        games.accept_undo(game_id, *DCERVELLI, Seat::North).await?;
+        assert!(matches!(
+            games.play_card(game_id, *TSLATCHER, c!(7C)).await,
+            Err(CardsError::Rules { source: RulesError::NotYourTurn(user_id, _) }) if user_id == *TWILSON
+        ));
+
+        games.play_card(game_id, *TWILSON, c!(TC)).await?;
+        games.play_card(game_id, *TSLATCHER, c!(7C)).await?;
+        games.play_card(game_id, *DCERVELLI, c!(QC)).await?;
+        assert!(matches!(
+            games.play_card(game_id, *CARRINO, c!(3S)).await,
+            Err(CardsError::Rules { source: RulesError::NotYourTurn(user_id, _) }) if user_id == *DCERVELLI
+        ));
+
+        games.request_undo(game_id, *DCERVELLI).await?;
+        games.accept_undo(game_id, *CARRINO, Seat::South).await?;
+        games.accept_undo(game_id, *TWILSON, Seat::South).await?;
+        games.accept_undo(game_id, *TSLATCHER, Seat::South).await?;
+        games.play_card(game_id, *DCERVELLI, c!(3C)).await?;
+        games.play_card(game_id, *TWILSON, c!(6S)).await?;
+        Ok(())
+    }
     TestRunner::new().run(test).await
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn test_bot_game() -> Result<(), CardsError> {
-    async fn test(_: &Database, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
-        let game_id = lobby
+async fn test_time_control() -> Result<(), CardsError> {
+    async fn test(_: &MemoryStorage, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
+        let time_control = TimeControl {
+            seconds: 0,
+            strategy: BotStrategy::Duck,
+        };
+        assert!(matches!(
+            new_seeded_game(lobby, games, Some(time_control), None).await,
+            Err(CardsError::InvalidTimeControl(invalid)) if invalid == time_control
+        ));
+        let time_control = TimeControl {
+            seconds: 1,
+            strategy: BotStrategy::Duck,
+        };
+        let game_id = new_seeded_game(lobby, games, Some(time_control), None).await?;
+        let mut twilson = games.subscribe(game_id, *TWILSON, None, None, None).await?;
+        loop {
+            match twilson.recv().await {
+                Some((GameEvent::PassStatus { clock, .. }, _)) => {
+                    let clock = clock.unwrap();
+                    for &seat in &Seat::VALUES {
+                        assert!(clock.deadline(seat).is_some());
+                    }
+                    break;
+                }
+                Some(_) => {}
+                None => panic!("Expected pass status event"),
+            }
+        }
+
+        games.pass_cards(game_id, *CARRINO, c!(87H 8C)).await?;
+        games.pass_cards(game_id, *DCERVELLI, c!(JH J2C)).await?;
+        games.pass_cards(game_id, *TSLATCHER, c!(AD KQC)).await?;
+        assert!(matches!(
+            games.resume(game_id, *TWILSON).await,
+            Err(CardsError::Rules { source: RulesError::NotSubstituted(user_id) }) if user_id == *TWILSON
+        ));
+        assert!(matches!(
+            games.substitute(Seat::North).pass_cards(game_id, *TWILSON, c!(Q63H)).await,
+            Err(CardsError::Rules { source: RulesError::NotSubstituted(user_id) }) if user_id == *TWILSON
+        ));
+        time::sleep(Duration::from_secs(1)).await;
+        games.expire_clocks().await;
+        loop {
+            match twilson.recv().await {
+                Some((GameEvent::Takeover { seat, strategy }, _)) => {
+                    assert_eq!(seat, Seat::North);
+                    assert_eq!(strategy, BotStrategy::Duck);
+                    break;
+                }
+                Some(_) => {}
+                None => panic!("Expected takeover event"),
+            }
+        }
+        match twilson.recv().await {
+            Some((GameEvent::PassStatus { clock, .. }, _)) => {
+                assert_eq!(clock.unwrap().deadline(Seat::North), None);
+            }
+            event => panic!("Expected pass status event, found {:?}", event),
+        }
+        loop {
+            match twilson.recv().await {
+                Some((
+                    GameEvent::SendPass {
+                        from: Seat::North, ..
+                    },
+                    _,
+                )) => break,
+                Some(_) => {}
+                None => panic!("Expected send pass event"),
+            }
+        }
+
+        games.resume(game_id, *TWILSON).await?;
+        loop {
+            match twilson.recv().await {
+                Some((GameEvent::Resume { seat }, _)) => {
+                    assert_eq!(seat, Seat::North);
+                    break;
+                }
+                Some(_) => {}
+                None => panic!("Expected resume event"),
+            }
+        }
+        assert!(matches!(
+            games.resume(game_id, *TWILSON).await,
+            Err(CardsError::Rules { source: RulesError::NotSubstituted(user_id) }) if user_id == *TWILSON
+        ));
+
+        // bots get no clock, and are never taken over however long they think
+        let (game_id, _) = lobby
             .new_game(
                 PlayerWithOptions {
-                    player: Player::Bot {
-                        user_id: *TWILSON,
-                        strategy: BotStrategy::Random,
-                    },
-                    rules: ChargingRules::BlindChain,
-                    seat: None,
+                    player: h![*TWILSON],
+                    rules: ChargingRules::Classic,
+                    seat: Some(Seat::North),
                 },
                 None,
-            )
-            .await?;
-        lobby
-            .join_game(
-                game_id,
-                PlayerWithOptions {
-                    player: Player::Bot {
-                        user_id: *TSLATCHER,
-                        strategy: BotStrategy::Heuristic,
-                    },
-                    rules: ChargingRules::Classic,
-                    seat: None,
+                GameOptions {
+                    time_control: Some(time_control),
+                    ..NO_OPTIONS
                 },
+                None,
             )
             .await?;
+        for &(user_id, seat) in &[(*TSLATCHER, Seat::East), (*DCERVELLI, Seat::South)] {
+            lobby
+                .join_game(
+                    game_id,
+                    PlayerWithOptions {
+                        player: h![user_id],
+                        rules: ChargingRules::Classic,
+                        seat: Some(seat),
+                    },
+                    None,
+                )
+                .await?;
+        }
         lobby
             .join_game(
                 game_id,
                 PlayerWithOptions {
                     player: Player::Bot {
                         user_id: *CARRINO,
-                        strategy: BotStrategy::Duck,
+                        strategy: BotStrategy::Simulate,
+                        options: BotOptions::default(),
                     },
-                    rules: ChargingRules::Bridge,
-                    seat: None,
+                    rules: ChargingRules::Classic,
+                    seat: Some(Seat::West),
                 },
+                None,
             )
             .await?;
-        lobby
-            .join_game(
-                game_id,
-                PlayerWithOptions {
-                    player: Player::Bot {
-                        user_id: *DCERVELLI,
-                        strategy: BotStrategy::GottaTry,
+        let (players, seed, options) = lobby.start_game(game_id).await?;
+        games.start_game(game_id, players, seed, options)?;
+        let mut twilson = games.subscribe(game_id, *TWILSON, None, None, None).await?;
+        loop {
+            match twilson.recv().await {
+                Some((GameEvent::PassStatus { clock, .. }, _)) => {
+                    let clock = clock.unwrap();
+                    assert!(clock.deadline(Seat::North).is_some());
+                    assert_eq!(clock.deadline(Seat::West), None);
+                    break;
+                }
+                Some(_) => {}
+                None => panic!("Expected pass status event"),
+            }
+        }
+        time::sleep(Duration::from_secs(1)).await;
+        games.expire_clocks().await;
+        let mut takeovers = Vec::new();
+        loop {
+            match twilson.recv().await {
+                Some((GameEvent::Takeover { seat, .. }, _)) => takeovers.push(seat),
+                Some((
+                    GameEvent::HiddenSendPass {
+                        from: Seat::West, ..
                     },
-                    rules: ChargingRules::Blind,
-                    seat: None,
+                    _,
+                )) => break,
+                Some(_) => {}
+                None => panic!("Expected send pass event"),
+            }
+        }
+        assert_eq!(takeovers, vec![Seat::North, Seat::East, Seat::South]);
+        Ok(())
+    }
+    TestRunner::new().run(test).await
+}
+
+async fn start_bot_game(
+    lobby: &Lobby,
+    games: &Games,
+    spectators: SpectatorPolicy,
+    length: Option<GameLength>,
+) -> Result<GameId, CardsError> {
+    let (game_id, _) = lobby
+        .new_game(
+            PlayerWithOptions {
+                player: Player::Bot {
+                    user_id: *TWILSON,
+                    strategy: BotStrategy::Random,
+                    options: BotOptions::default(),
                 },
-            )
+                rules: ChargingRules::BlindChain,
+                seat: None,
+            },
+            None,
+            GameOptions {
+                spectators: Some(spectators),
+                length,
+                ..NO_OPTIONS
+            },
+            None,
+        )
+        .await?;
+    lobby
+        .join_game(
+            game_id,
+            PlayerWithOptions {
+                player: Player::Bot {
+                    user_id: *TSLATCHER,
+                    strategy: BotStrategy::Heuristic,
+                    options: BotOptions::default(),
+                },
+                rules: ChargingRules::Classic,
+                seat: None,
+            },
+            None,
+        )
+        .await?;
+    lobby
+        .join_game(
+            game_id,
+            PlayerWithOptions {
+                player: Player::Bot {
+                    user_id: *CARRINO,
+                    strategy: BotStrategy::Duck,
+                    options: BotOptions::default(),
+                },
+                rules: ChargingRules::Bridge,
+                seat: None,
+            },
+            None,
+        )
+        .await?;
+    lobby
+        .join_game(
+            game_id,
+            PlayerWithOptions {
+                player: Player::Bot {
+                    user_id: *DCERVELLI,
+                    strategy: BotStrategy::GottaTry,
+                    options: BotOptions::default(),
+                },
+                rules: ChargingRules::Blind,
+                seat: None,
+            },
+            None,
+        )
+        .await?;
+    let (players, seed, options) = lobby.start_game(game_id).await?;
+    games.start_game(game_id, players, seed, options)?;
+    Ok(game_id)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_bot_game() -> Result<(), CardsError> {
+    async fn test<S>(_: &S, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
+        let game_id = start_bot_game(lobby, games, OMNISCIENT, None).await?;
+        let mut rx = games
+            .subscribe(game_id, UserId::new(), None, None, None)
             .await?;
-        let (players, seed) = lobby.start_game(game_id).await?;
-        games.start_game(game_id, players, seed)?;
-        let mut rx = games.subscribe(game_id, UserId::new(), None).await?;
         let mut events = HashMap::new();
         while let Some((event, _)) = rx.recv().await {
             match event {
@@ -536,5 +1193,1415 @@ async fn test_bot_game() -> Result<(), CardsError> {
     for _ in 0..30 {
         runner.run(test).await?;
     }
+    let runner = TestRunner::sqlite();
+    for _ in 0..30 {
+        runner.run(test).await?;
+    }
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_game_length() -> Result<(), CardsError> {
+    async fn hand_totals(
+        lobby: &Lobby,
+        games: &Games,
+        length: GameLength,
+    ) -> Result<Vec<[i16; 4]>, CardsError> {
+        let game_id = start_bot_game(lobby, games, OMNISCIENT, Some(length)).await?;
+        let mut rx = games
+            .subscribe(game_id, UserId::new(), None, None, None)
+            .await?;
+        let mut totals = Vec::new();
+        let mut deals = 0;
+        while let Some((event, _)) = rx.recv().await {
+            match event {
+                GameEvent::Deal { pass, .. } => {
+                    assert_eq!(pass, PassDirection::from(deals % 4));
+                    deals += 1;
+                }
+                GameEvent::HandComplete {
+                    north_total,
+                    east_total,
+                    south_total,
+                    west_total,
+                    ..
+                } => totals.push([north_total, east_total, south_total, west_total]),
+                GameEvent::GameComplete { .. } => break,
+                _ => {}
+            }
+        }
+        assert_eq!(deals as usize, totals.len());
+        Ok(totals)
+    }
+
+    async fn test(_: &MemoryStorage, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
+        for &length in &[
+            GameLength::Rounds { rounds: 0 },
+            GameLength::Rounds { rounds: 200 },
+            GameLength::TargetScore { score: -1 },
+            GameLength::TargetScore { score: i16::MAX },
+        ] {
+            assert!(matches!(
+                start_bot_game(lobby, games, OMNISCIENT, Some(length)).await,
+                Err(CardsError::InvalidGameLength(invalid)) if invalid == length
+            ));
+        }
+
+        let totals = hand_totals(lobby, games, GameLength::Rounds { rounds: 2 }).await?;
+        assert_eq!(totals.len(), 8);
+
+        let totals = hand_totals(lobby, games, GameLength::TargetScore { score: 50 }).await?;
+        let (last, rest) = totals.split_last().unwrap();
+        assert!(last.iter().any(|&total| total >= 50));
+        for hand in rest {
+            assert!(hand.iter().all(|&total| total < 50));
+        }
+        Ok(())
+    }
+    TestRunner::new().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_spectators() -> Result<(), CardsError> {
+    async fn test(_: &MemoryStorage, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
+        let game_id = start_bot_game(lobby, games, SpectatorPolicy::HiddenHands, None).await?;
+        let mut rx = games
+            .subscribe(game_id, UserId::new(), None, None, None)
+            .await?;
+        let mut reveals = 0;
+        while let Some((event, _)) = rx.recv().await {
+            match event {
+                GameEvent::Deal {
+                    north,
+                    east,
+                    south,
+                    west,
+                    ..
+                } => assert!((north | east | south | west).is_empty()),
+                GameEvent::SendPass { .. } | GameEvent::RecvPass { .. } => {
+                    panic!("Unexpected event {:?}", event)
+                }
+                GameEvent::PlayStatus { legal_plays, .. } => assert!(legal_plays.is_empty()),
+                GameEvent::RevealHands {
+                    north,
+                    east,
+                    south,
+                    west,
+                } => {
+                    assert_eq!(north | east | south | west, Cards::ALL);
+                    reveals += 1;
+                }
+                GameEvent::GameComplete { .. } => break,
+                _ => {}
+            }
+        }
+        assert_eq!(reveals, 4);
+
+        let game_id =
+            new_seeded_game(lobby, games, None, Some(SpectatorPolicy::FollowSeat)).await?;
+        games.pass_cards(game_id, *TWILSON, c!(Q63H)).await?;
+        assert!(matches!(
+            games.subscribe(game_id, UserId::new(), None, None, None).await,
+            Err(CardsError::MissingFollow(id)) if id == game_id
+        ));
+        let mut rx = games
+            .subscribe(game_id, UserId::new(), None, Some(Seat::East), None)
+            .await?;
+        while let Some((event, _)) = rx.recv().await {
+            match event {
+                GameEvent::Deal {
+                    north,
+                    east,
+                    south,
+                    west,
+                    ..
+                } => {
+                    assert_eq!(east.len(), 13);
+                    assert!((north | south | west).is_empty());
+                }
+                GameEvent::SendPass { .. } => panic!("Unexpected event {:?}", event),
+                GameEvent::HiddenSendPass { from, count } => {
+                    assert_eq!(from, Seat::North);
+                    assert_eq!(count, 3);
+                }
+                GameEvent::EndReplay { .. } => break,
+                _ => {}
+            }
+        }
+
+        let spectators = SpectatorPolicy::Omniscient {
+            delay_seconds: SpectatorPolicy::MAX_DELAY_SECONDS + 1,
+        };
+        assert!(matches!(
+            new_seeded_game(lobby, games, None, Some(spectators)).await,
+            Err(CardsError::InvalidSpectatorPolicy(invalid)) if invalid == spectators
+        ));
+        let game_id = new_seeded_game(
+            lobby,
+            games,
+            None,
+            Some(SpectatorPolicy::Omniscient { delay_seconds: 1 }),
+        )
+        .await?;
+        let mut rx = games
+            .subscribe(game_id, UserId::new(), None, None, None)
+            .await?;
+        assert!(time::timeout(Duration::from_millis(500), rx.recv())
+            .await
+            .is_err());
+        match rx.recv().await {
+            Some((GameEvent::Sit { .. }, 1)) => {}
+            event => panic!("Unexpected event {:?}", event),
+        }
+        match rx.recv().await {
+            Some((GameEvent::Deal { north, .. }, 2)) => assert_eq!(north.len(), 13),
+            event => panic!("Unexpected event {:?}", event),
+        }
+        Ok(())
+    }
+    TestRunner::new().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_ratings() -> Result<(), CardsError> {
+    async fn test<S: TestStorage>(
+        storage: &S,
+        lobby: &Lobby,
+        games: &Games,
+    ) -> Result<(), CardsError> {
+        let game_id = start_bot_game(lobby, games, OMNISCIENT, None).await?;
+        let mut rx = games
+            .subscribe(game_id, UserId::new(), None, None, None)
+            .await?;
+        while let Some((event, _)) = rx.recv().await {
+            if let GameEvent::GameComplete { .. } = event {
+                break;
+            }
+        }
+
+        assert_eq!(storage.update_ratings()?, 1);
+        assert_eq!(storage.update_ratings()?, 0);
+        let ratings = storage.load_ratings()?;
+        assert_eq!(ratings.len(), 4);
+        for rating in &ratings {
+            assert_eq!(rating.hands, 4);
+            assert!(matches!(rating.player, RatedPlayer::Bot { .. }));
+        }
+        let total = ratings.iter().map(|rating| rating.rating).sum::<f64>();
+        assert!((total - 6000.0).abs() < 1e-6);
+        assert!(ratings[0].rating >= ratings[3].rating);
+
+        let history = storage.load_rating_history(RatedPlayer::Bot {
+            strategy: BotStrategy::Duck,
+            options: BotOptions::default(),
+        })?;
+        assert_eq!(history.len(), 4);
+        assert!(history.iter().all(|change| change.game_id == game_id));
+        assert_eq!(
+            history.iter().map(|change| change.hand).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+        Ok(())
+    }
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_duplicate() -> Result<(), CardsError> {
+    async fn test<S: TestStorage>(
+        storage: &S,
+        lobby: &Lobby,
+        games: &Games,
+    ) -> Result<(), CardsError> {
+        let bot = |user_id, strategy| PlayerWithOptions {
+            player: Player::Bot {
+                user_id,
+                strategy,
+                options: BotOptions::default(),
+            },
+            rules: ChargingRules::Classic,
+            seat: None,
+        };
+        assert!(matches!(
+            lobby
+                .new_duplicate(bot(*TWILSON, BotStrategy::Duck), 5, NO_OPTIONS)
+                .await,
+            Err(CardsError::InvalidTableCount(5))
+        ));
+        let (duplicate_id, game_ids) = lobby
+            .new_duplicate(
+                bot(*TWILSON, BotStrategy::Duck),
+                2,
+                GameOptions {
+                    time_control: Some(TimeControl {
+                        seconds: 1,
+                        strategy: BotStrategy::Duck,
+                    }),
+                    spectators: Some(OMNISCIENT),
+                    ..NO_OPTIONS
+                },
+            )
+            .await?;
+        assert_eq!(game_ids.len(), 2);
+        match lobby.subscribe(*TSLATCHER).await?.recv().await {
+            Some(LobbyEvent::LobbyState { games, .. }) => {
+                for game_id in &game_ids {
+                    assert_eq!(games[game_id].duplicate_id, Some(duplicate_id));
+                    assert_eq!(games[game_id].seed, Seed::Redacted);
+                }
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+        let person = PlayerWithOptions {
+            player: h!(*TSLATCHER),
+            rules: ChargingRules::Classic,
+            seat: None,
+        };
+        lobby.join_game(game_ids[1], person, None).await?;
+        for &game_id in &game_ids {
+            for &strategy in &[
+                BotStrategy::Random,
+                BotStrategy::Heuristic,
+                BotStrategy::GottaTry,
+            ] {
+                lobby
+                    .join_game(game_id, bot(UserId::new(), strategy), None)
+                    .await?;
+            }
+        }
+        let mut deals = Vec::new();
+        for &game_id in &game_ids {
+            let (players, seed, options) = lobby.start_game(game_id).await?;
+            games.start_game(game_id, players, seed, options)?;
+            let mut rx = games
+                .subscribe(game_id, UserId::new(), None, None, None)
+                .await?;
+            let mut dealt = false;
+            while let Some((event, _)) = rx.recv().await {
+                match event {
+                    GameEvent::Deal { .. } if !dealt => {
+                        deals.push(event);
+                        dealt = true;
+                        if game_id != game_ids[1] {
+                            continue;
+                        }
+                        // the first table's deals can't be shown while the
+                        // second is waiting on its person to pass the first
+                        assert!(matches!(
+                            load_record(storage, game_ids[0]),
+                            Err(CardsError::DuplicateInProgress(id)) if id == duplicate_id
+                        ));
+                        assert!(matches!(
+                            load_events(storage, game_ids[0]),
+                            Err(CardsError::DuplicateInProgress(id)) if id == duplicate_id
+                        ));
+                        let summary = load_duplicate_summary(storage, duplicate_id)?;
+                        assert!(summary.boards.iter().all(|board| board.results.is_empty()));
+                        // then a bot takes over for them to finish the game
+                        time::sleep(Duration::from_secs(1)).await;
+                        games.expire_clocks().await;
+                    }
+                    GameEvent::GameComplete { .. } => break,
+                    _ => {}
+                }
+            }
+        }
+        match (&deals[0], &deals[1]) {
+            (
+                GameEvent::Deal {
+                    north, east, west, ..
+                },
+                GameEvent::Deal {
+                    north: rotated_north,
+                    east: rotated_east,
+                    ..
+                },
+            ) => {
+                assert_eq!(rotated_north, west);
+                assert_eq!(rotated_east, north);
+                assert_ne!(rotated_east, east);
+            }
+            deals => panic!("Unexpected deals {:?}", deals),
+        }
+
+        let summary = load_duplicate_summary(storage, duplicate_id)?;
+        assert_eq!(
+            summary
+                .tables
+                .iter()
+                .map(|table| (table.game_id, table.rotation))
+                .collect::<Vec<_>>(),
+            vec![(game_ids[0], 0), (game_ids[1], 1)]
+        );
+        assert!(summary.tables.iter().all(|table| table.players.is_some()));
+        assert_eq!(summary.boards.len(), 4);
+        for board in &summary.boards {
+            assert_eq!(board.results.len(), 8);
+            for &game_id in &game_ids {
+                let money = board
+                    .results
+                    .iter()
+                    .filter(|result| result.game_id == game_id)
+                    .map(|result| result.money)
+                    .sum::<i16>();
+                assert_eq!(money, 0);
+            }
+            for &hand in &Seat::VALUES {
+                let results = board
+                    .results
+                    .iter()
+                    .filter(|result| result.hand == hand)
+                    .collect::<Vec<_>>();
+                assert_eq!(results.len(), 2);
+                assert_ne!(results[0].seat, results[1].seat);
+                assert!((results[0].difference + results[1].difference).abs() < 1e-6);
+            }
+        }
+        assert!(matches!(
+            load_duplicate_summary(storage, DuplicateId::new()),
+            Err(CardsError::UnknownDuplicate(_))
+        ));
+        Ok(())
+    }
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_analysis() -> Result<(), CardsError> {
+    async fn test<S: TestStorage>(
+        storage: &'static S,
+        lobby: &Lobby,
+        games: &Games,
+    ) -> Result<(), CardsError> {
+        let game_id = start_bot_game(lobby, games, OMNISCIENT, None).await?;
+        let mut rx = games
+            .subscribe(game_id, UserId::new(), None, None, None)
+            .await?;
+        while let Some((event, _)) = rx.recv().await {
+            if let GameEvent::GameComplete { .. } = event {
+                break;
+            }
+        }
+
+        // both requests wait on the one analysis, which solves nothing here
+        assert!(storage.load_analysis(game_id)?.is_none());
+        let analyzer = &*Box::leak(Box::new(Analyzer::new(storage, 1, 0)));
+        let (first, second) = tokio::join!(analyzer.analyze(game_id), analyzer.analyze(game_id));
+        for analysis in &[first?, second?] {
+            assert_eq!(analysis.game_id, game_id);
+            assert_eq!(analysis.hands.len(), 4);
+            for hand in &analysis.hands {
+                assert!(hand.plays.iter().all(|play| play.outcomes.is_none()));
+            }
+        }
+        let cached = storage.load_analysis(game_id)?.unwrap();
+        assert_eq!(cached.game_id, game_id);
+        assert_eq!(cached.solved_cards, 0);
+        assert_eq!(cached.hands.len(), 4);
+
+        // and it's redone once the server solves more of each hand, though
+        // solving every play takes minutes, so only the last six tricks are
+        let analyzer = &*Box::leak(Box::new(Analyzer::new(storage, 1, 24)));
+        let analysis = analyzer.analyze(game_id).await?;
+        assert_eq!(analysis.solved_cards, 24);
+        assert_eq!(analysis.hands.len(), 4);
+        assert!(analysis
+            .hands
+            .iter()
+            .any(|hand| hand.plays.iter().any(|play| play.outcomes.is_some())));
+        for hand in &analysis.hands {
+            assert_eq!(
+                hand.hands.iter().map(|cards| cards.len()).sum::<usize>(),
+                52
+            );
+            assert!(hand.plays.iter().any(|play| play.outcomes.is_none()));
+            for play in &hand.plays {
+                assert!(hand.hands[play.seat.idx()].contains(play.card));
+                let outcomes = match &play.outcomes {
+                    Some(outcomes) => outcomes,
+                    None => {
+                        assert_eq!((play.money, play.best_money), (None, None));
+                        continue;
+                    }
+                };
+                let (money, best_money) = (play.money.unwrap(), play.best_money.unwrap());
+                assert!(outcomes.len() > 1);
+                assert!(outcomes
+                    .iter()
+                    .any(|o| o.card == play.card && o.money == money));
+                assert!(money <= best_money);
+                assert!(outcomes.iter().all(|o| o.money <= best_money));
+            }
+        }
+
+        let game_id = new_seeded_game(lobby, games, None, None).await?;
+        assert!(matches!(
+            analyzer.analyze(game_id).await,
+            Err(CardsError::IncompleteGame(_))
+        ));
+        Ok(())
+    }
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_record() -> Result<(), CardsError> {
+    async fn test<S: TestStorage>(
+        storage: &S,
+        lobby: &Lobby,
+        games: &Games,
+    ) -> Result<(), CardsError> {
+        let game_id = start_bot_game(lobby, games, OMNISCIENT, None).await?;
+        let mut rx = games
+            .subscribe(game_id, UserId::new(), None, None, None)
+            .await?;
+        while let Some((event, _)) = rx.recv().await {
+            if let GameEvent::GameComplete { .. } = event {
+                break;
+            }
+        }
+
+        let record = load_record(storage, game_id)?;
+        assert_eq!(record.game_id, Some(game_id));
+        let mut parsed = record.to_string().parse::<GameRecord>()?;
+        assert_eq!(parsed, record);
+
+        // games are imported by someone who played in them, alongside people
+        // who have signed in, so the bots' seats are given to people
+        let mut people = Vec::new();
+        for name in &["twilson", "tslatcher", "carrino", "dcervelli"] {
+            let external = ExternalUser {
+                name: name.to_string(),
+                realm: "test".to_string(),
+                external_id: name.to_string(),
+            };
+            people.push(storage.insert_user(name, &external)?.id);
+        }
+        let importer = people[0];
+        if let GameEvent::Sit {
+            north,
+            east,
+            south,
+            west,
+            ..
+        } = &mut parsed.events[0]
+        {
+            **north = h!(people[0]);
+            **east = h!(people[1]);
+            **south = h!(people[2]);
+            **west = h!(people[3]);
+        }
+        assert!(matches!(
+            import_record(storage, *TWILSON, &parsed),
+            Err(CardsError::InvalidPlayer(id, _)) if id == *TWILSON
+        ));
+        let mut strangers = parsed.clone();
+        if let GameEvent::Sit { east, .. } = &mut strangers.events[0] {
+            **east = h!(*TSLATCHER);
+        }
+        assert!(matches!(
+            import_record(storage, importer, &strangers),
+            Err(CardsError::UnknownUser(id)) if id == *TSLATCHER
+        ));
+
+        let imported = import_record(storage, importer, &parsed)?;
+        assert_ne!(imported, game_id);
+        let imported_record = load_record(storage, imported)?;
+        assert_eq!(imported_record.events, parsed.events);
+        assert_eq!(imported_record.created_time, record.created_time);
+        assert_eq!(imported_record.completed_time, record.completed_time);
+        // imported games are listed like games played here, but only rated
+        // once everyone who played in them has confirmed them
+        assert_eq!(storage.update_ratings()?, 1);
+        let completed = storage.load_completed_games(None, 100)?;
+        assert!(!completed.is_empty());
+        assert!(completed.iter().all(|stored| stored.game_id == imported));
+        assert!(matches!(
+            storage.confirm_import(game_id, importer),
+            Err(CardsError::NotImported(id)) if id == game_id
+        ));
+        assert!(matches!(
+            storage.confirm_import(imported, *TWILSON),
+            Err(CardsError::InvalidPlayer(id, _)) if id == *TWILSON
+        ));
+        storage.confirm_import(imported, people[1])?;
+        storage.confirm_import(imported, people[2])?;
+        assert_eq!(storage.update_ratings()?, 0);
+        storage.confirm_import(imported, people[3])?;
+        assert_eq!(storage.update_ratings()?, 1);
+        assert!(storage
+            .load_ratings()?
+            .iter()
+            .any(|rating| rating.player == RatedPlayer::Human { user_id: importer }));
+        assert!(matches!(
+            import_record(storage, importer, &parsed),
+            Err(CardsError::DuplicateImport(id)) if id == imported
+        ));
+
+        let mut illegal = parsed.clone();
+        let first_play = illegal
+            .events
+            .iter()
+            .position(|event| matches!(event, GameEvent::Play { .. }))
+            .unwrap();
+        if let GameEvent::Play { seat, .. } = &mut illegal.events[first_play] {
+            *seat = seat.left();
+        }
+        match import_record(storage, importer, &illegal) {
+            Err(CardsError::IllegalEvent {
+                event_id,
+                source: RulesError::NotYourCards(_),
+                ..
+            }) => assert_eq!(event_id, first_play),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        let illegal_event = |record: &GameRecord| match import_record(storage, importer, record) {
+            Err(CardsError::IllegalEvent {
+                event_id, source, ..
+            }) => (event_id, source),
+            result => panic!("Unexpected result {:?}", result),
+        };
+
+        let mut illegal = parsed.clone();
+        illegal.events.insert(1, parsed.events[0].clone());
+        assert!(matches!(
+            illegal_event(&illegal),
+            (1, RulesError::AlreadySeated)
+        ));
+
+        let first_deal = parsed
+            .events
+            .iter()
+            .position(|event| matches!(event, GameEvent::Deal { .. }))
+            .unwrap();
+        let mut illegal = parsed.clone();
+        if let GameEvent::Deal { north, east, .. } = &mut illegal.events[first_deal] {
+            *north |= east.max();
+        }
+        let (event_id, source) = illegal_event(&illegal);
+        assert_eq!(event_id, first_deal);
+        assert!(matches!(source, RulesError::IllegalDeal));
+        let mut illegal = parsed.clone();
+        if let GameEvent::Deal { east, .. } = &mut illegal.events[first_deal] {
+            *east -= east.max();
+        }
+        let (event_id, source) = illegal_event(&illegal);
+        assert_eq!(event_id, first_deal);
+        assert!(matches!(source, RulesError::IllegalDeal));
+        let mut illegal = parsed.clone();
+        if let GameEvent::Deal { pass, .. } = &mut illegal.events[first_deal] {
+            *pass = PassDirection::Keeper;
+        }
+        let (event_id, source) = illegal_event(&illegal);
+        assert_eq!(event_id, first_deal);
+        assert!(matches!(source, RulesError::IllegalDeal));
+        let mut illegal = parsed.clone();
+        illegal
+            .events
+            .insert(first_deal + 1, parsed.events[first_deal].clone());
+        let (event_id, source) = illegal_event(&illegal);
+        assert_eq!(event_id, first_deal + 1);
+        assert!(matches!(source, RulesError::AlreadyDealt(0)));
+
+        let first_recv = parsed
+            .events
+            .iter()
+            .position(|event| matches!(event, GameEvent::RecvPass { .. }))
+            .unwrap();
+        let mut illegal = parsed.clone();
+        let other_pass = parsed.events[first_recv + 1..]
+            .iter()
+            .find_map(|event| match event {
+                GameEvent::RecvPass { cards, .. } => Some(*cards),
+                _ => None,
+            })
+            .unwrap();
+        if let GameEvent::RecvPass { cards, .. } = &mut illegal.events[first_recv] {
+            *cards = other_pass;
+        }
+        let (event_id, source) = illegal_event(&illegal);
+        assert_eq!(event_id, first_recv);
+        assert!(matches!(source, RulesError::IllegalReceive(_)));
+
+        // a game dealt at a table isn't dealt from its seed, so its keeper
+        // passes only have to share out the cards in the middle
+        let mut dealt_by_hand = parsed.clone();
+        if let GameEvent::Sit { seed, .. } = &mut dealt_by_hand.events[0] {
+            *seed = Seed::Chosen {
+                value: "dealt by hand".to_string(),
+            };
+        }
+        // though it's still the game imported above, which is only noticed
+        // once every event has been checked
+        assert!(matches!(
+            import_record(storage, importer, &dealt_by_hand),
+            Err(CardsError::DuplicateImport(id)) if id == imported
+        ));
+        let keeper_recv = dealt_by_hand
+            .events
+            .iter()
+            .rposition(
+                |event| matches!(event, GameEvent::RecvPass { cards, .. } if !cards.is_empty()),
+            )
+            .unwrap();
+        let mut illegal = dealt_by_hand.clone();
+        if let GameEvent::RecvPass { cards, .. } = &mut illegal.events[keeper_recv] {
+            *cards -= cards.max();
+        }
+        let (event_id, source) = illegal_event(&illegal);
+        assert_eq!(event_id, keeper_recv);
+        assert!(matches!(source, RulesError::IllegalReceive(_)));
+
+        let mut game: Game<()> = Game::new();
+        for event in &parsed.events[..=first_play] {
+            game.apply(event, |_, _| {});
+        }
+        let undo = game.undo_event().unwrap();
+        let requester = match parsed.events[first_play] {
+            GameEvent::Play { seat, .. } => seat,
+            _ => unreachable!(),
+        };
+        let mut undo_events = vec![GameEvent::RequestUndo { seat: requester }];
+        for &acceptor in &Seat::VALUES {
+            if acceptor != requester {
+                undo_events.push(GameEvent::AcceptUndo {
+                    requester,
+                    acceptor,
+                });
+            }
+        }
+        let mut illegal = parsed.clone();
+        illegal.events.insert(first_play + 1, undo.clone());
+        let (event_id, source) = illegal_event(&illegal);
+        assert_eq!(event_id, first_play + 1);
+        assert!(matches!(source, RulesError::UndoNotAccepted));
+        let mut illegal = parsed.clone();
+        let mut forged = undo.clone();
+        if let GameEvent::Undo { state, .. } = &mut forged {
+            state.next_actor = None;
+        }
+        let mut events = undo_events.clone();
+        events.push(forged);
+        illegal
+            .events
+            .splice(first_play + 1..first_play + 1, events);
+        let (event_id, source) = illegal_event(&illegal);
+        assert_eq!(event_id, first_play + 5);
+        assert!(matches!(source, RulesError::IllegalUndo));
+        let mut undone = parsed.clone();
+        let mut events = undo_events;
+        events.push(undo);
+        events.push(parsed.events[first_play].clone());
+        undone.events.splice(first_play + 1..first_play + 1, events);
+        assert!(matches!(
+            import_record(storage, importer, &undone),
+            Err(CardsError::DuplicateImport(id)) if id == imported
+        ));
+
+        let mut redacted = parsed.clone();
+        if let GameEvent::Sit { seed, .. } = &mut redacted.events[0] {
+            *seed = Seed::Redacted;
+        }
+        assert!(matches!(
+            import_record(storage, importer, &redacted),
+            Err(CardsError::RedactedSeed)
+        ));
+
+        let mut incomplete = parsed.clone();
+        incomplete.events.pop();
+        assert!(matches!(
+            import_record(storage, importer, &incomplete),
+            Err(CardsError::IncompleteGame(_))
+        ));
+        incomplete.events.remove(0);
+        assert!(matches!(
+            import_record(storage, importer, &incomplete),
+            Err(CardsError::MissingPlayers)
+        ));
+        Ok(())
+    }
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_snapshot() -> Result<(), CardsError> {
+    async fn test<S: TestStorage>(
+        storage: &'static S,
+        lobby: &Lobby,
+        games: &Games,
+    ) -> Result<(), CardsError> {
+        let game_id = start_bot_game(lobby, games, OMNISCIENT, None).await?;
+        let mut rx = games
+            .subscribe(game_id, UserId::new(), None, None, None)
+            .await?;
+        let mut events = Vec::new();
+        while let Some((event, event_id)) = rx.recv().await {
+            let complete = matches!(event, GameEvent::GameComplete { .. });
+            if event.is_stable() {
+                events.push((event, event_id));
+            }
+            if complete {
+                break;
+            }
+        }
+
+        assert_eq!(storage.count_snapshots(game_id)?, 3);
+        check_snapshot(storage, game_id)?;
+        let (snapshot, loaded) = storage.load_game(game_id)?;
+        let snapshot = snapshot.unwrap();
+        assert!(matches!(loaded[1], GameEvent::Deal { .. }));
+        assert_eq!(
+            loaded.len(),
+            storage.count_events(game_id)? - snapshot.event_id + 1
+        );
+
+        // a game hydrated from its snapshot replays the sit event and the
+        // scores of the hands before it, then the events after it with the
+        // ids they were first sent with
+        let deal = events
+            .iter()
+            .rposition(|(event, _)| matches!(event, GameEvent::Deal { .. }))
+            .unwrap();
+        let mut expected = events[..deal]
+            .iter()
+            .filter(|(event, _)| {
+                matches!(
+                    event,
+                    GameEvent::Sit { .. } | GameEvent::HandComplete { .. }
+                )
+            })
+            .map(|(event, _)| event.clone())
+            .collect::<Vec<_>>();
+        let summary = expected.len();
+        expected.extend(events[deal..].iter().map(|(event, _)| event.clone()));
+        let hydrated = Games::new(storage, false, false);
+        let mut rx = hydrated
+            .subscribe(game_id, UserId::new(), None, None, None)
+            .await?;
+        let mut replayed = Vec::new();
+        while replayed.len() < expected.len() {
+            let (event, event_id) = rx.recv().await.unwrap();
+            if event.is_stable() {
+                replayed.push((event, event_id));
+            }
+        }
+        assert_eq!(
+            replayed
+                .iter()
+                .map(|(event, _)| event.clone())
+                .collect::<Vec<_>>(),
+            expected
+        );
+        assert_eq!(replayed[summary..], events[deal..]);
+
+        // one that missed events from before the snapshot is sent every event
+        let last_event_id = events[deal - 1].1 - 1;
+        let mut rx = hydrated
+            .subscribe(game_id, UserId::new(), Some(last_event_id), None, None)
+            .await?;
+        let mut replayed = Vec::new();
+        while replayed.len() < events.len() - (deal - 1) {
+            let (event, event_id) = rx.recv().await.unwrap();
+            if event.is_stable() {
+                replayed.push((event, event_id));
+            }
+        }
+        assert_eq!(replayed[..], events[deal - 1..]);
+
+        storage.corrupt_snapshots(game_id)?;
+        assert!(matches!(
+            check_snapshot(storage, game_id),
+            Err(CardsError::InconsistentSnapshot(_))
+        ));
+
+        // a snapshot ahead of the events is ignored, and every event replayed
+        let behind = GameId::new();
+        let events = load_events(storage, game_id)?;
+        storage.persist_hand(
+            behind,
+            0,
+            &events[..snapshot.event_id],
+            Some(&snapshot),
+            false,
+        )?;
+        check_snapshot(storage, behind)?;
+        assert_eq!(
+            storage.load_game(behind)?,
+            (None, events[..snapshot.event_id].to_vec())
+        );
+        let hydrated = Games::new(storage, false, false);
+        let mut rx = hydrated
+            .subscribe(behind, UserId::new(), None, None, None)
+            .await?;
+        assert!(matches!(rx.recv().await, Some((GameEvent::Sit { .. }, _))));
+        Ok(())
+    }
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_archive() -> Result<(), CardsError> {
+    async fn test<S: TestStorage>(
+        storage: &'static S,
+        lobby: &Lobby,
+        games: &Games,
+    ) -> Result<(), CardsError> {
+        // games with bots are left out of the leaderboard, so it's checked
+        // with a game played by people whose seats were taken over by bots
+        let time_control = TimeControl {
+            seconds: 1,
+            strategy: BotStrategy::Duck,
+        };
+        let game_id = new_seeded_game(lobby, games, Some(time_control), Some(OMNISCIENT)).await?;
+        let mut rx = games
+            .subscribe(game_id, UserId::new(), None, None, None)
+            .await?;
+        time::sleep(Duration::from_secs(1)).await;
+        games.expire_clocks().await;
+        while let Some((event, _)) = rx.recv().await {
+            if let GameEvent::GameComplete { .. } = event {
+                break;
+            }
+        }
+        let duplicate_id = DuplicateId::new();
+        storage.insert_duplicate_table(duplicate_id, game_id)?;
+        let leaderboard = || -> Result<Vec<_>, CardsError> {
+            Ok(storage
+                .load_completed_games(None, 100)?
+                .into_iter()
+                .map(|stored| (stored.game_id, stored.timestamp, stored.event))
+                .collect())
+        };
+        let duplicate = || {
+            load_duplicate_summary(storage, duplicate_id)
+                .map(|summary| serde_json::to_value(summary).unwrap())
+        };
+        let stored_leaderboard = leaderboard()?;
+        assert!(!stored_leaderboard.is_empty());
+        assert!(stored_leaderboard.iter().all(|(id, ..)| *id == game_id));
+        let stored_duplicate = duplicate()?;
+        assert_eq!(
+            stored_duplicate["boards"][0]["results"]
+                .as_array()
+                .unwrap()
+                .len(),
+            4
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let cutoff = util::timestamp() + 1;
+
+        // only rated games are archived
+        assert_eq!(archive_games(storage, dir.path(), cutoff)?, 0);
+        assert_eq!(storage.update_ratings()?, 1);
+        let stored = storage.load_game_events(game_id)?;
+        assert_eq!(archive_games(storage, dir.path(), cutoff)?, 1);
+        assert_eq!(archive_games(storage, dir.path(), cutoff)?, 0);
+
+        assert_eq!(storage.count_events(game_id)?, 0);
+        let archived = storage.load_game_events(game_id)?;
+        assert_eq!(archived.len(), stored.len());
+        for (archived, stored) in archived.iter().zip(&stored) {
+            assert_eq!(archived.timestamp, stored.timestamp);
+            assert_eq!(archived.event, stored.event);
+        }
+        let events = load_events(storage, game_id)?;
+        assert_eq!(events.len(), stored.len());
+        assert_eq!(leaderboard()?, stored_leaderboard);
+        assert_eq!(duplicate()?, stored_duplicate);
+
+        // chat in an archived game is kept after its archived events
+        let hydrated = Games::new(storage, false, false);
+        hydrated
+            .chat(game_id, UserId::new(), "gg".to_string())
+            .await?;
+        let events = storage.load_game_events(game_id)?;
+        assert_eq!(events.len(), stored.len() + 1);
+        assert!(matches!(
+            &events.last().unwrap().event,
+            GameEvent::Chat { message, .. } if message == "gg"
+        ));
+        Ok(())
+    }
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_abort() -> Result<(), CardsError> {
+    async fn test<S: Storage>(storage: &S, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
+        let game_id = start_bot_game(lobby, games, OMNISCIENT, None).await?;
+        let mut twilson = lobby.subscribe(*TWILSON).await?;
+        assert!(matches!(
+            twilson.recv().await,
+            Some(LobbyEvent::LobbyState { .. })
+        ));
+
+        games.abort(game_id, *CARRINO).await?;
+        lobby.finish_game(game_id).await;
+        assert_eq!(
+            twilson.recv().await,
+            Some(LobbyEvent::FinishGame { game_id })
+        );
+        let mut rx = games
+            .subscribe(game_id, UserId::new(), None, None, None)
+            .await?;
+        let mut events = Vec::new();
+        while let Some((event, _)) = rx.recv().await {
+            let complete = matches!(event, GameEvent::GameComplete { .. });
+            events.push(event);
+            if complete {
+                break;
+            }
+        }
+        assert!(matches!(
+            &events[events.len() - 2..],
+            [GameEvent::Abort, GameEvent::GameComplete { .. }]
+        ));
+        assert!(matches!(
+            games.abort(game_id, *CARRINO).await,
+            Err(CardsError::Rules {
+                source: RulesError::GameComplete(_)
+            })
+        ));
+
+        let audit_log = storage.load_audit_log(10)?;
+        assert_eq!(audit_log.len(), 1);
+        assert_eq!(audit_log[0].user_id, *CARRINO);
+        assert_eq!(audit_log[0].action, AdminAction::AbortGame { game_id });
+        Ok(())
+    }
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_admin() -> Result<(), CardsError> {
+    async fn test<S: TestStorage>(
+        storage: &'static S,
+        lobby: &Lobby,
+        games: &Games,
+    ) -> Result<(), CardsError> {
+        fn external(name: &str) -> ExternalUser {
+            ExternalUser {
+                name: name.to_string(),
+                realm: "test".to_string(),
+                external_id: name.to_string(),
+            }
+        }
+
+        let users = Users::new(storage);
+        let admin = users.insert("admin".to_string(), external("admin")).await?;
+        let player = users
+            .insert("player".to_string(), external("player"))
+            .await?;
+        storage.make_admin(admin.id)?;
+        // roles are cached along with everything else about a user
+        let users = Users::new(storage);
+        assert_eq!(users.get_admin_id("admin".to_string()).await?, admin.id);
+        assert!(matches!(
+            users.get_admin_id("player".to_string()).await,
+            Err(CardsError::NotAdmin(id)) if id == player.id
+        ));
+
+        users
+            .rename(admin.id, player.id, "renamed".to_string())
+            .await?;
+        let renamed = users.get_users(vec![player.id]).await?;
+        assert_eq!(renamed.into_iter().next().unwrap().name, "renamed");
+
+        lobby.chat(player.id, "spam".to_string()).await?;
+        let timestamp = storage.load_lobby(admin.id)?.0[0].timestamp;
+        lobby.delete_chat(admin.id, player.id, timestamp).await?;
+        assert_eq!(storage.load_lobby(admin.id)?.0, vec![]);
+        assert!(matches!(
+            lobby.delete_chat(admin.id, player.id, timestamp).await,
+            Err(CardsError::UnknownChat(..))
+        ));
+
+        let mut lobby_rx = lobby.subscribe(player.id).await?;
+        let game_id = start_bot_game(lobby, games, OMNISCIENT, None).await?;
+        let mut game_rx = games
+            .subscribe(game_id, player.id, None, None, None)
+            .await?;
+        users.ban(admin.id, player.id).await?;
+        lobby.disconnect(player.id).await;
+        games.disconnect(player.id).await;
+        while lobby_rx.recv().await.is_some() {}
+        while game_rx.recv().await.is_some() {}
+        assert!(matches!(
+            users.get_user_id("player".to_string()).await,
+            Err(CardsError::UnknownAuthToken(_))
+        ));
+        assert!(matches!(
+            users.insert("again".to_string(), external("player")).await,
+            Err(CardsError::BannedUser(id)) if id == player.id
+        ));
+
+        let (game_id, _) = lobby
+            .new_game(
+                PlayerWithOptions {
+                    player: h!(admin.id),
+                    rules: ChargingRules::Classic,
+                    seat: None,
+                },
+                None,
+                NO_OPTIONS,
+                None,
+            )
+            .await?;
+        lobby.delete_game(admin.id, game_id).await?;
+        assert!(!storage.load_lobby(admin.id)?.1.contains_key(&game_id));
+        assert!(matches!(
+            lobby.delete_game(admin.id, game_id).await,
+            Err(CardsError::UnknownGame(_))
+        ));
+
+        let audit_log = storage.load_audit_log(10)?;
+        assert!(audit_log.iter().all(|entry| entry.user_id == admin.id));
+        assert_eq!(
+            audit_log
+                .into_iter()
+                .map(|entry| entry.action)
+                .collect::<Vec<_>>(),
+            vec![
+                AdminAction::DeleteGame { game_id },
+                AdminAction::BanUser { user_id: player.id },
+                AdminAction::DeleteChat {
+                    user_id: player.id,
+                    timestamp,
+                    message: "spam".to_string(),
+                },
+                AdminAction::RenameUser {
+                    user_id: player.id,
+                    old_name: "player".to_string(),
+                    new_name: "renamed".to_string(),
+                },
+            ]
+        );
+        Ok(())
+    }
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+// a game as it was stored by the first version of the schema
+const FIXTURE: &str = r#"
+INSERT INTO user (user_id, name, realm, external_id)
+    VALUES ('a8b3a5d4-0c3c-4c1c-9f0e-1b6f3e2a9c01', 'twilson', 'google', '1');
+INSERT INTO auth_token (token, user_id)
+    VALUES ('token', 'a8b3a5d4-0c3c-4c1c-9f0e-1b6f3e2a9c01');
+INSERT INTO lobby_chat (timestamp, user_id, message)
+    VALUES (1, 'a8b3a5d4-0c3c-4c1c-9f0e-1b6f3e2a9c01', 'hello');
+INSERT INTO game (game_id, seed, created_time, created_by, last_updated_time, last_updated_by,
+        started_time, completed_time)
+    VALUES ('5e0c0f52-d3a1-4b8e-8a8e-2f5f2a7c9b10', '{"type":"random","value":"seed"}', 1,
+        'a8b3a5d4-0c3c-4c1c-9f0e-1b6f3e2a9c01', 2, 'a8b3a5d4-0c3c-4c1c-9f0e-1b6f3e2a9c01', 2,
+        NULL);
+INSERT INTO game_player (game_id, user_id, strategy, rules, seat)
+    VALUES ('5e0c0f52-d3a1-4b8e-8a8e-2f5f2a7c9b10', 'a8b3a5d4-0c3c-4c1c-9f0e-1b6f3e2a9c01', NULL,
+        '"classic"', NULL);
+INSERT INTO event (game_id, event_id, timestamp, event)
+    VALUES ('5e0c0f52-d3a1-4b8e-8a8e-2f5f2a7c9b10', 0, 2, '{"type":"ping"}');
+"#;
+
+// the columns and indexes of each table, which unlike the sql kept in
+// sqlite_master don't depend on whether a table was created or altered
+fn describe_schema(conn: &Connection) -> Vec<String> {
+    let mut schema = Vec::new();
+    let mut stmt = conn
+        .prepare("SELECT type, name, tbl_name FROM sqlite_master WHERE name NOT LIKE 'sqlite_%'")
+        .unwrap();
+    let mut rows = stmt.query(NO_PARAMS).unwrap();
+    while let Some(row) = rows.next().unwrap() {
+        let (kind, name, table): (String, String, String) = (
+            row.get(0).unwrap(),
+            row.get(1).unwrap(),
+            row.get(2).unwrap(),
+        );
+        let pragma = if kind == "table" {
+            "table_info"
+        } else {
+            "index_info"
+        };
+        let mut info = conn
+            .prepare(&format!("PRAGMA {}({})", pragma, name))
+            .unwrap();
+        let mut columns = info
+            .query_map(NO_PARAMS, |row| {
+                row.get::<_, String>(if kind == "table" { 1 } else { 2 })
+            })
+            .unwrap()
+            .map(|column| column.unwrap())
+            .collect::<Vec<_>>();
+        columns.sort();
+        schema.push(format!(
+            "{} {} on {}: {}",
+            kind,
+            name,
+            table,
+            columns.join(", ")
+        ));
+    }
+    schema.sort();
+    schema
+}
+
+// every row of the fixture, once the tables it went into exist
+fn assert_fixture(conn: &Connection, version: usize) {
+    if version == 0 {
+        return;
+    }
+    for table in &[
+        "user",
+        "auth_token",
+        "lobby_chat",
+        "game",
+        "game_player",
+        "event",
+    ] {
+        let rows: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM {}", table),
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(rows, 1, "{} at version {}", table, version);
+    }
+}
+
+#[test]
+fn test_migrations() -> Result<(), CardsError> {
+    let latest = {
+        let mut conn = Connection::open_in_memory()?;
+        migrate(&mut conn, latest_version(), false)?;
+        describe_schema(&conn)
+    };
+    for version in 0..=latest_version() {
+        let mut conn = Connection::open_in_memory()?;
+        migrate(&mut conn, version.min(1), false)?;
+        if version > 0 {
+            conn.execute_batch(FIXTURE)?;
+        }
+        migrate(&mut conn, version, false)?;
+        let schema = describe_schema(&conn);
+
+        // a dry run applies every migration but leaves the database as it was
+        let steps = migrate(&mut conn, latest_version(), true)?;
+        assert_eq!(steps.len(), latest_version() - version);
+        assert_eq!(schema_version(&conn)?, version);
+        assert_eq!(describe_schema(&conn), schema);
+
+        migrate(&mut conn, latest_version(), false)?;
+        assert_eq!(schema_version(&conn)?, latest_version());
+        assert_eq!(describe_schema(&conn), latest);
+        assert_fixture(&conn, version);
+
+        let steps = migrate(&mut conn, version, false)?;
+        assert!(steps.iter().all(|step| step.direction == Direction::Down));
+        assert_eq!(schema_version(&conn)?, version);
+        assert_eq!(describe_schema(&conn), schema);
+        assert_fixture(&conn, version);
+    }
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_metrics() -> Result<(), CardsError> {
+    async fn test(_: &MemoryStorage, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
+        let game_id = new_seeded_game(lobby, games, None, None).await?;
+        assert!(games.pass_cards(game_id, *TWILSON, c!(A73S)).await.is_err());
+        games.pass_cards(game_id, *CARRINO, c!(87H 8C)).await?;
+
+        let metrics = render_metrics(lobby, games).await?;
+        assert!(metrics.contains(r#"turbo_hearts_actions_total{action="pass",outcome="ok"}"#));
+        assert!(
+            metrics.contains(r#"turbo_hearts_actions_total{action="pass",outcome="NotYourCards"}"#)
+        );
+        assert!(metrics.contains("turbo_hearts_active_games"));
+        assert!(metrics.contains(r#"turbo_hearts_subscribers{stream="lobby"}"#));
+        assert!(metrics.contains("turbo_hearts_hydration_seconds_count"));
+        Ok(())
+    }
+    TestRunner::new().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_shutdown() -> Result<(), CardsError> {
+    async fn test<S: Storage>(storage: &S, lobby: &Lobby, games: &Games) -> Result<(), CardsError> {
+        let game_id = new_seeded_game(lobby, games, None, None).await?;
+        assert!(storage.load_bot_games()?.is_empty());
+        let (_, events) = storage.load_game(game_id)?;
+        storage.persist_events(
+            game_id,
+            events.len(),
+            &[GameEvent::Takeover {
+                seat: Seat::North,
+                strategy: BotStrategy::Duck,
+            }],
+        )?;
+        assert_eq!(storage.load_bot_games()?, vec![game_id]);
+
+        // the bot passes without anyone watching the game
+        assert_eq!(games.load_bot_games().await?, 1);
+        let mut twilson = games.subscribe(game_id, *TWILSON, None, None, None).await?;
+        loop {
+            match twilson.recv().await {
+                Some((
+                    GameEvent::SendPass {
+                        from: Seat::North, ..
+                    },
+                    _,
+                )) => break,
+                Some(_) => {}
+                None => panic!("Expected send pass event"),
+            }
+        }
+        games.ping().await;
+        assert_eq!(games.counts().await.0, 1);
+
+        let mut lobby_events = lobby.subscribe(*TSLATCHER).await?;
+        lobby.shutdown().await;
+        games.shutdown().await;
+        loop {
+            match lobby_events.recv().await {
+                Some(LobbyEvent::Reconnect) => break,
+                Some(_) => {}
+                None => panic!("Expected reconnect event"),
+            }
+        }
+        assert_eq!(lobby_events.recv().await, None);
+        loop {
+            match twilson.recv().await {
+                Some((GameEvent::Reconnect, _)) => break,
+                Some(_) => {}
+                None => panic!("Expected reconnect event"),
+            }
+        }
+        assert_eq!(twilson.recv().await, None);
+        assert!(matches!(
+            games.pass_cards(game_id, *CARRINO, c!(87H 8C)).await,
+            Err(CardsError::ShuttingDown)
+        ));
+        assert!(matches!(
+            lobby.subscribe(*TSLATCHER).await,
+            Err(CardsError::ShuttingDown)
+        ));
+        Ok(())
+    }
+    TestRunner::new().run(test).await?;
+    TestRunner::sqlite().run(test).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_game_socket() -> Result<(), CardsError> {
+    async fn connect(
+        lobby: &'static Lobby,
+        games: &'static Games,
+        path: String,
+    ) -> warp::test::WsClient {
+        let user_id = *CARRINO;
+        let router = game_endpoints::router(
+            warp::any().map(move || lobby),
+            warp::any().map(move || games),
+            warp::any().and_then(move || async move { Ok::<_, Rejection>(user_id) }),
+        );
+        warp::test::ws()
+            .path(&path)
+            .handshake(router)
+            .await
+            .unwrap()
+    }
+
+    async fn recv(client: &mut warp::test::WsClient) -> Value {
+        loop {
+            let message = client.recv().await.unwrap();
+            if let Ok(text) = message.to_str() {
+                return serde_json::from_str(text).unwrap();
+            }
+        }
+    }
+
+    // reads up to the end of the replay, returning the id of the last event
+    async fn replay(client: &mut warp::test::WsClient) -> u64 {
+        let mut last_event_id = 0;
+        loop {
+            let message = recv(client).await;
+            if message["event"]["type"] == "end_replay" {
+                return last_event_id;
+            }
+            if let Some(event_id) = message["event_id"].as_u64() {
+                last_event_id = event_id;
+            }
+        }
+    }
+
+    async fn request(client: &mut warp::test::WsClient, request: Value) -> Value {
+        let request_id = request["request_id"].clone();
+        client.send_text(request.to_string()).await;
+        loop {
+            let message = recv(client).await;
+            if message["type"] != "event" && message["request_id"] == request_id {
+                return message;
+            }
+        }
+    }
+
+    async fn test(
+        _: &MemoryStorage,
+        lobby: &'static Lobby,
+        games: &'static Games,
+    ) -> Result<(), CardsError> {
+        let game_id = start_seeded_game(lobby, games).await?;
+        let path = format!("/game/socket/{}", game_id);
+        let mut client = connect(lobby, games, path.clone()).await;
+        let last_event_id = replay(&mut client).await;
+
+        let ack = request(
+            &mut client,
+            json!({"request_id": 1, "type": "play", "game_id": game_id, "card": "2C"}),
+        )
+        .await;
+        assert_eq!(ack["type"], "ack");
+        assert_eq!(ack["ack"]["type"], "play");
+
+        let error = request(
+            &mut client,
+            json!({"request_id": 2, "type": "play", "game_id": game_id, "card": "3S"}),
+        )
+        .await;
+        assert_eq!(error["type"], "error");
+        assert!(!error["rules"].is_null());
+
+        let other_game_id = new_seeded_game(lobby, games, None, None).await?;
+        let error = request(
+            &mut client,
+            json!({"request_id": 3, "type": "chat", "game_id": other_game_id, "message": "hi"}),
+        )
+        .await;
+        assert_eq!(error["type"], "error");
+        assert!(error["rules"].is_null());
+        drop(client);
+
+        let path = format!("{}?last_event_id={}", path, last_event_id);
+        let mut client = connect(lobby, games, path).await;
+        let message = loop {
+            let message = recv(&mut client).await;
+            if !message["event_id"].is_null() {
+                break message;
+            }
+        };
+        assert_eq!(message["event_id"], last_event_id + 1);
+        assert_eq!(message["event"]["type"], "play");
+        assert_eq!(message["event"]["card"], "2C");
+        Ok(())
+    }
+    TestRunner::new().run(test).await
+}
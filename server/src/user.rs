@@ -1,7 +1,11 @@
-use crate::{CardsError, Database, GetStr, ToSqlStr};
-use rusqlite::{OptionalExtension, ToSql};
+use crate::{CardsError, Storage};
+use log::info;
 use serde::Serialize;
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
 use tokio::sync::Mutex;
 use turbo_hearts_api::UserId;
 
@@ -13,6 +17,35 @@ pub struct User {
     pub realm: String,
     #[serde(skip)]
     pub external_id: String,
+    #[serde(skip)]
+    pub role: Role,
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Role {
+    Player,
+    Admin,
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Role::Player => "player",
+            Role::Admin => "admin",
+        })
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "player" => Ok(Role::Player),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!("unknown role {}", s)),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -51,17 +84,22 @@ impl Cache {
     fn insert(&mut self, user: User) {
         self.users.insert(user.id, user);
     }
+
+    fn remove(&mut self, id: UserId) {
+        self.auth_tokens.retain(|_, user_id| *user_id != id);
+        self.users.remove(&id);
+    }
 }
 
 pub struct Users {
-    db: &'static Database,
+    storage: &'static dyn Storage,
     cache: Mutex<Cache>,
 }
 
 impl Users {
-    pub fn new(db: &'static Database) -> Self {
+    pub fn new(storage: &'static dyn Storage) -> Self {
         Self {
-            db,
+            storage,
             cache: Mutex::new(Cache::new()),
         }
     }
@@ -72,23 +110,7 @@ impl Users {
             return Ok(user_id);
         }
         drop(cache);
-        match self.db.run_read_only(|tx| {
-            tx.query_row(
-                "SELECT user.user_id, user.name, user.realm, user.external_id FROM auth_token, user
-                    WHERE auth_token.token = ? AND auth_token.user_id = user.user_id",
-                &[&auth_token],
-                |row| {
-                    Ok(User {
-                        id: row.get_str(0).unwrap(),
-                        name: row.get_unwrap::<_, String>(1),
-                        realm: row.get_unwrap::<_, String>(2),
-                        external_id: row.get_unwrap::<_, String>(3),
-                    })
-                },
-            )
-            .optional()
-            .map_err(|e| e.into())
-        }) {
+        match self.storage.load_user_for_token(&auth_token) {
             Ok(user) => match user {
                 Some(user) => {
                     let user_id = user.id;
@@ -102,6 +124,15 @@ impl Users {
         }
     }
 
+    pub async fn get_admin_id(&self, auth_token: String) -> Result<UserId, CardsError> {
+        let user_id = self.get_user_id(auth_token).await?;
+        let cache = self.cache.lock().await;
+        match cache.get_user(user_id) {
+            Some(user) if user.role == Role::Admin => Ok(user_id),
+            _ => Err(CardsError::NotAdmin(user_id)),
+        }
+    }
+
     pub async fn get_users(&self, mut ids: Vec<UserId>) -> Result<HashSet<User>, CardsError> {
         let cache = self.cache.lock().await;
         let mut cached = HashSet::new();
@@ -118,27 +149,7 @@ impl Users {
             return Ok(cached);
         }
 
-        let mut uncached = HashSet::new();
-        self.db.run_read_only(|tx| {
-            let mut stmt =
-                tx.prepare_cached("SELECT name, realm, external_id FROM user WHERE user_id = ?")?;
-            for id in &ids {
-                let user = stmt
-                    .query_row(&[id.sql()], |row| {
-                        Ok(User {
-                            id: *id,
-                            name: row.get_unwrap::<_, String>(0),
-                            realm: row.get_unwrap::<_, String>(1),
-                            external_id: row.get_unwrap::<_, String>(2),
-                        })
-                    })
-                    .optional()?;
-                if let Some(user) = user {
-                    uncached.insert(user);
-                }
-            }
-            Ok(())
-        })?;
+        let uncached = self.storage.load_users(&ids)?;
         let mut cache = self.cache.lock().await;
         for user in &uncached {
             cache.insert(user.clone());
@@ -148,40 +159,48 @@ impl Users {
     }
 
     pub async fn insert(&self, auth_token: String, user: ExternalUser) -> Result<User, CardsError> {
-        let id = self.db.run_with_retry(|tx| {
-            let mut id = UserId::new();
-            let edits = tx.execute::<&[&dyn ToSql]>(
-                "INSERT INTO user (user_id, name, realm, external_id)
-                    VALUES (?, ?, ?, ?) ON CONFLICT DO NOTHING",
-                &[&id.sql(), &user.name, &user.realm, &user.external_id],
-            )?;
-            if edits == 0 {
-                id = tx.query_row_and_then(
-                    "SELECT user_id FROM user WHERE realm = ? and external_id = ?",
-                    &[&user.realm, &user.external_id],
-                    |row| row.get_str(0),
-                )?;
-            }
-            tx.execute::<&[&dyn ToSql]>(
-                "INSERT INTO auth_token (token, user_id)
-                    VALUES (?, ?) ON CONFLICT DO NOTHING",
-                &[&auth_token, &id.sql()],
-            )?;
-            Ok(id)
-        })?;
+        let user = self.storage.insert_user(&auth_token, &user)?;
         let mut cache = self.cache.lock().await;
-        let ExternalUser {
-            name,
-            realm,
-            external_id,
-        } = user;
-        let user = User {
-            id,
-            name,
-            realm,
-            external_id,
-        };
         cache.insert_with_token(auth_token, user.clone());
         Ok(user)
     }
+
+    pub async fn rename(
+        &self,
+        admin_id: UserId,
+        user_id: UserId,
+        name: String,
+    ) -> Result<(), CardsError> {
+        let result = self.storage.rename_user(admin_id, user_id, &name);
+        if result.is_ok() {
+            let mut cache = self.cache.lock().await;
+            if let Some(user) = cache.users.get_mut(&user_id) {
+                user.name = name.clone();
+            }
+        }
+        info!(
+            "rename: admin_id={}, user_id={}, name={}, error={:?}",
+            admin_id,
+            user_id,
+            name,
+            result.as_ref().err()
+        );
+        result
+    }
+
+    // they stay subscribed until the lobby and games disconnect them
+    pub async fn ban(&self, admin_id: UserId, user_id: UserId) -> Result<(), CardsError> {
+        let result = self.storage.ban_user(admin_id, user_id);
+        if result.is_ok() {
+            let mut cache = self.cache.lock().await;
+            cache.remove(user_id);
+        }
+        info!(
+            "ban: admin_id={}, user_id={}, error={:?}",
+            admin_id,
+            user_id,
+            result.as_ref().err()
+        );
+        result
+    }
 }
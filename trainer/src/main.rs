@@ -152,7 +152,7 @@ impl Trainer {
                 let mut state = self.game_state.clone();
                 state.apply(&GameEvent::Play { seat, card });
                 let won = brute_force.solve(&mut state);
-                let money = won.scores(state.charges).money(seat);
+                let money = won.scores(state.charges, state.rule_set).money(seat);
                 plays.push(money);
                 if money > best_money {
                     best_money = money;